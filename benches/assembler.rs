@@ -0,0 +1,45 @@
+//! Measures assembling and disassembling big listings.
+#![feature(test)]
+
+extern crate minivm;
+extern crate test;
+
+use test::Bencher;
+
+use minivm::backend::assembler::Assembler;
+
+// A listing of blocks with arithmetic, locals and a jump to the next block, each 54 bytes of
+// byte code, repeated the given number of times.
+fn generate(repetitions: usize) -> String {
+    (0..repetitions).map(|i| format!(concat!(
+        "// Block {i}\n",
+        "ipush {i}\n",
+        "iload 1\n",
+        "iadd       // Accumulate\n",
+        "istore 1\n",
+        "rpush 0.5\n",
+        "iload 1\n",
+        "add\n",
+        "print\n",
+        "iload 1\n",
+        "ipush 1_000\n",
+        "lt\n",
+        "jumpiffalse {next}\n",
+        "cpush 97\n",
+        "println\n"), i = i, next = (i + 1) * 54))
+        .collect()
+}
+
+#[bench]
+fn assemble_listing(bencher: &mut Bencher) {
+    let listing = generate(1000);
+    bencher.bytes = listing.len() as u64;
+    bencher.iter(|| Assembler::new().assemble(&listing));
+}
+
+#[bench]
+fn disassemble_listing(bencher: &mut Bencher) {
+    let byte_code = Assembler::new().assemble(&generate(1000));
+    bencher.bytes = byte_code.len() as u64;
+    bencher.iter(|| Assembler::new().disassemble(byte_code.clone()));
+}
@@ -0,0 +1,33 @@
+//! Programs and helpers shared by the benchmarks of the backend.
+// Each benchmark uses only some of them.
+#![allow(dead_code)]
+
+use std::io;
+use test::Bencher;
+
+use minivm::backend::VirtualMachine;
+use minivm::backend::compiler::Compiler;
+use minivm::backend::image::Image;
+use minivm::frontend::character_stream::CharacterStream;
+use minivm::frontend::lexer::Lexer;
+use minivm::frontend::parser::Parser;
+
+pub const ARITHMETIC: &str = concat!(
+    "var sum = 0\nvar i = 0\n",
+    "while i < 20000 {\n  sum = (sum + i * 3 - 1) % 1000003\n  i += 1\n}\nprintln(sum)");
+
+pub const CALLS: &str = concat!(
+    "fun fib(n) {\n  if n < 2 {\n    return n\n  }\n  return fib(n - 1) + fib(n - 2)\n}\n",
+    "println(fib(18))");
+
+pub fn compile(src: &str) -> Image {
+    let lexer = Lexer::new(CharacterStream::new(String::from(src)));
+    let statements = Parser::new(lexer).parse().unwrap();
+    Compiler::new().compile(&statements).unwrap()
+}
+
+/// Runs the program on the stack machine with the output discarded.
+pub fn run(bencher: &mut Bencher, src: &str) {
+    let image = compile(src);
+    bencher.iter(|| VirtualMachine::with_output(image.clone(), Box::new(io::sink())).run().unwrap());
+}
@@ -0,0 +1,53 @@
+//! Measures the throughput of the lexer on large generated sources.
+#![feature(test)]
+
+extern crate minivm;
+extern crate test;
+
+use test::Bencher;
+
+use minivm::frontend::character_stream::CharacterStream;
+use minivm::frontend::lexer::Lexer;
+use minivm::frontend::token::TokenType;
+
+// A source with every kind of token repeated the given number of times.
+fn generate(repetitions: usize) -> String {
+    (0..repetitions).map(|i| format!(concat!(
+        "// Iteration {i}\n",
+        "var value{i} = {i} * 3 + 0x{i:x} - {i}.25e2 / (value{i} % 7)\n",
+        "if value{i} >= {i} and not (value{i} != 1) or false {{\n",
+        "  println(\"value ${{value{i}}} is\\t\\\"big\\\"\", 'c', [1, 2, 3][0])\n",
+        "}}\n",
+        "fun f{i}(a, b) {{\n  return a.x <= b.y\n}}\n"), i = i))
+        .collect()
+}
+
+fn lex(bencher: &mut Bencher, src: String) {
+    bencher.bytes = src.len() as u64;
+    bencher.iter(|| {
+        let mut lexer = Lexer::new(CharacterStream::new(src.clone()));
+        let mut tokens = 0;
+
+        loop {
+            lexer.next_token().unwrap();
+
+            if TokenType::EOF == *lexer.current().get_token_type() {
+                break;
+            }
+
+            tokens += 1;
+        }
+
+        tokens
+    });
+}
+
+#[bench]
+fn lex_small(bencher: &mut Bencher) {
+    lex(bencher, generate(10));
+}
+
+#[bench]
+fn lex_large(bencher: &mut Bencher) {
+    lex(bencher, generate(1000));
+}
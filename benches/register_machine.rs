@@ -0,0 +1,46 @@
+//! Compares the stack machine with the register machine on the same compiled programs.
+#![feature(test)]
+
+extern crate minivm;
+extern crate test;
+
+mod common;
+
+use std::io;
+use test::Bencher;
+
+use minivm::backend::register::RegisterMachine;
+use minivm::backend::register::translator;
+
+use common::{compile, run, ARITHMETIC, CALLS};
+
+fn run_registers(bencher: &mut Bencher, src: &str) {
+    let program = translator::translate(&compile(src)).unwrap();
+    bencher.iter(|| RegisterMachine::with_output(program.clone(), Box::new(io::sink())).run().unwrap());
+}
+
+#[bench]
+fn arithmetic_on_stack_machine(bencher: &mut Bencher) {
+    run(bencher, ARITHMETIC);
+}
+
+#[bench]
+fn arithmetic_on_register_machine(bencher: &mut Bencher) {
+    run_registers(bencher, ARITHMETIC);
+}
+
+#[bench]
+fn calls_on_stack_machine(bencher: &mut Bencher) {
+    run(bencher, CALLS);
+}
+
+#[bench]
+fn calls_on_register_machine(bencher: &mut Bencher) {
+    run_registers(bencher, CALLS);
+}
+
+#[bench]
+fn translate_calls(bencher: &mut Bencher) {
+    let image = compile(CALLS);
+    bencher.iter(|| translator::translate(&image).unwrap());
+}
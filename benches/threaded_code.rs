@@ -0,0 +1,48 @@
+//! Compares the virtual machine executing the pre-decoded operations with decoding each
+//! instruction on every execution and measures the cost of decoding at load time.
+#![feature(test)]
+
+extern crate minivm;
+extern crate test;
+
+mod common;
+
+use std::io;
+use test::Bencher;
+
+use minivm::backend::VirtualMachine;
+
+use common::{compile, run, ARITHMETIC, CALLS};
+
+fn run_decoding(bencher: &mut Bencher, src: &str) {
+    let image = compile(src);
+    bencher.iter(|| VirtualMachine::with_output(image.clone(), Box::new(io::sink())).run_decoding().unwrap());
+}
+
+#[bench]
+fn arithmetic_threaded(bencher: &mut Bencher) {
+    run(bencher, ARITHMETIC);
+}
+
+#[bench]
+fn arithmetic_decoding_each_instruction(bencher: &mut Bencher) {
+    run_decoding(bencher, ARITHMETIC);
+}
+
+#[bench]
+fn calls_threaded(bencher: &mut Bencher) {
+    run(bencher, CALLS);
+}
+
+#[bench]
+fn calls_decoding_each_instruction(bencher: &mut Bencher) {
+    run_decoding(bencher, CALLS);
+}
+
+#[bench]
+fn load_image(bencher: &mut Bencher) {
+    let lines: String = (0..2000).map(|i| format!("x = {} * x + 1\nprintln(x - 2)\n", i)).collect();
+    let src = format!("var x = 1\n{}", lines);
+    let image = compile(&src);
+    bencher.iter(|| VirtualMachine::with_output(image.clone(), Box::new(io::sink())));
+}
@@ -0,0 +1,31 @@
+//! Measures the virtual machine running real arithmetic and closure calls. Integer arithmetic and
+//! recursive calls are measured by `register_machine`.
+#![feature(test)]
+
+extern crate minivm;
+extern crate test;
+
+mod common;
+
+use test::Bencher;
+
+use common::run;
+
+const REALS: &str = concat!(
+    "var x = 0.5\nvar i = 0\n",
+    "while i < 20000 {\n  x = x * 3.9 * (1.0 - x)\n  i += 1\n}\nprintln(x)");
+
+const CLOSURES: &str = concat!(
+    "fun counter() {\n  var count = 0\n  return fun(step) {\n    count += step\n    return count\n  }\n}\n",
+    "var next = counter()\nvar i = 0\n",
+    "while i < 20000 {\n  next(i % 3)\n  i += 1\n}\nprintln(next(0))");
+
+#[bench]
+fn run_real_arithmetic(bencher: &mut Bencher) {
+    run(bencher, REALS);
+}
+
+#[bench]
+fn run_closure_calls(bencher: &mut Bencher) {
+    run(bencher, CLOSURES);
+}
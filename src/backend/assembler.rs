@@ -1,6 +1,6 @@
 use std::convert::TryFrom;
 
-use backend::bytecode::Instruction;
+use backend::bytecode::{Instruction, Operand};
 use backend::byte_conversion::*;
 
 /// Generates byte code from assembly style code.
@@ -27,15 +27,12 @@ impl Assembler {
             buffer.push_str(&format!("{}", opcode));
             index += 1;
 
-            match opcode {
-                Instruction::IPush => {
-                    buffer.push(' ');
-                    let end_index = index + WORD_SIZE ;
-                    let argument = bytes_to_word(&byte_code[index..end_index]).unwrap();
-                    buffer.push_str(&format!("{}", argument));
-                    index += 8;
-                },
-                _ => (),
+            for operand in opcode.operands() {
+                let end_index = index + operand.size();
+                let bytes = &byte_code[index..end_index];
+                buffer.push(' ');
+                buffer.push_str(&disassemble_operand(*operand, bytes));
+                index = end_index;
             }
 
             buffer.push('\n');
@@ -88,40 +85,21 @@ fn translate(asm: Vec<Vec<String>>) -> Vec<u8> {
     let mut buffer: Vec<u8> = Vec::new();
 
     for line in asm {
-        if line.is_empty() {
-            continue;
-        }
-
         if let Some((mnemonic, arguments)) = line.split_first() {
-            let mnemonic = mnemonic.parse::<Instruction>().unwrap();
-
-            match mnemonic {
-                Instruction::IPush => {
-                    if arguments.len() != 1 {
-                        panic!("Expecting exactly one argument for ipush!");
-                    }
-
-                    buffer.push(u8::from(mnemonic));
-                    let int = arguments[0].replace("_", "")
-                        .parse::<i64>()
-                        .expect("Bad integer given!");
-                    buffer.append(&mut int_to_bytes(int));
-                },
-                Instruction::IAdd => {
-                    if arguments.len() != 0 {
-                        panic!("Expecting exactly zero arguments for iadd!");
-                    }
-
-                    buffer.push(u8::from(mnemonic));
-                },
-                Instruction::IPrint => {
-                    if arguments.len() != 0 {
-                        panic!("Expecting exactly zero arguments for print!");
-                    }
-
-                    buffer.push(u8::from(mnemonic));
-                },
-                _ => panic!(format!("Unrecognized mnemonic '{}'!", mnemonic)),
+            let instruction = match mnemonic.parse::<Instruction>() {
+                Ok(instruction) => instruction,
+                Err(_) => panic!("Unrecognized mnemonic '{}'!", mnemonic),
+            };
+            let operands = instruction.operands();
+
+            if arguments.len() != operands.len() {
+                panic!("Expecting exactly {} arguments for {}!", operands.len(), instruction);
+            }
+
+            buffer.push(u8::from(instruction));
+
+            for (operand, argument) in operands.iter().zip(arguments) {
+                buffer.append(&mut assemble_operand(*operand, argument));
             }
         }
     }
@@ -129,6 +107,33 @@ fn translate(asm: Vec<Vec<String>>) -> Vec<u8> {
     buffer
 }
 
+fn assemble_operand(operand: Operand, argument: &str) -> Vec<u8> {
+    let argument = argument.replace("_", "");
+
+    match operand {
+        Operand::Byte => vec![argument.parse::<u8>().expect("Bad byte given!")],
+        Operand::Short => {
+            let short = argument.parse::<u16>().expect("Bad short given!");
+            vec![(short >> 8) as u8, short as u8]
+        },
+        Operand::Integer => int_to_bytes(argument.parse::<i64>().expect("Bad integer given!")),
+        Operand::Real => float_to_bytes(argument.parse::<f64>().expect("Bad real given!")),
+        Operand::Character => int_to_bytes(i64::from(argument.parse::<u32>().expect("Bad code point given!"))),
+        Operand::Address => address_to_bytes(argument.parse::<u32>().expect("Bad address given!")),
+    }
+}
+
+fn disassemble_operand(operand: Operand, bytes: &[u8]) -> String {
+    match operand {
+        Operand::Byte => format!("{}", bytes[0]),
+        Operand::Short => format!("{}", u16::from(bytes[0]) << 8 | u16::from(bytes[1])),
+        Operand::Integer => format!("{}", bytes_to_int(bytes).unwrap()),
+        Operand::Real => format!("{:?}", bytes_to_float(bytes).unwrap()),
+        Operand::Character => format!("{}", bytes_to_word(bytes).unwrap()),
+        Operand::Address => format!("{}", bytes_to_address(bytes).unwrap()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -268,4 +273,22 @@ iprint
 "#
         )));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn assemble_and_disassemble_operands() {
+        let sut = Assembler::new();
+        let assembly = "rpush 1.5\ncpush 97\nistore 3\nload 255\nnewarray 258\nipush -1\nhalt\njump 3\n";
+
+        let byte_code = sut.assemble(assembly);
+
+        assert_that!(byte_code.len(), is(equal_to(9 + 9 + 2 + 2 + 3 + 9 + 1 + 5)));
+        assert_that!(&byte_code[22..25], is(equal_to(&[0x1d, 0x01, 0x02][..])));
+        assert_that!(&sut.disassemble(byte_code), is(equal_to(assembly)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Expecting exactly 1 arguments for iload!")]
+    fn assemble_missing_operand() {
+        Assembler::new().assemble("iload");
+    }
+}
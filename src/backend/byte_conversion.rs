@@ -62,6 +62,24 @@ pub fn word_to_float(word: u64) -> f64 {
     bytes_to_float(&buffer).unwrap()
 }
 
+/// Size of an address into the byte code in bytes.
+pub const ADDRESS_SIZE: usize = 4;
+
+pub fn bytes_to_address(bytes: &[u8]) -> Result<u32, &'static str> {
+    let mut reader = Cursor::new(bytes);
+
+    match reader.read_u32::<BigEndian>() {
+        Ok(val) => Ok(val),
+        Err(_) => Err("Bad bytes to read u32 from!"),
+    }
+}
+
+pub fn address_to_bytes(value: u32) -> Vec<u8> {
+    let mut buffer: Vec<u8> = Vec::new();
+    buffer.write_u32::<BigEndian>(value).unwrap();
+    buffer
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,4 +147,12 @@ mod tests {
 
         assert_that!(word_to_float(word), is(equal_to(an_float)));
     }
+
+    #[test]
+    fn address() {
+        let bytes = address_to_bytes(0x0102_0304);
+
+        assert_that!(bytes_to_address(&bytes), is(equal_to(Ok(0x0102_0304))));
+        assert_that!(bytes, is(equal_to(vec![0x01, 0x02, 0x03, 0x04])));
+    }
 }
@@ -67,6 +67,295 @@ pub enum Instruction {
     /// Stack (before -> after): [] -> []
     /// Other bytes (count: operand labels): -
     Halt,
+    /// Pushes eight bytes onto the stack as a real value.
+    /// Stack (before -> after): [] -> [value]
+    /// Other bytes (count: operand labels): 8: byte1, byte2, byte3, byte4, byte5, byte6, byte7, byte8
+    RPush,
+    /// Pushes eight bytes onto the stack as a character value.
+    /// Stack (before -> after): [] -> [value]
+    /// Other bytes (count: operand labels): 8: code point
+    CPush,
+    /// Pushes nil onto the stack.
+    /// Stack (before -> after): [] -> [nil]
+    /// Other bytes (count: operand labels): -
+    NPush,
+    /// Load a value of any type from a local variable #index.
+    /// Stack (before -> after): [] -> [value]
+    /// Other bytes (count: operand labels): 1: index
+    Load,
+    /// Store a value of any type into variable #index.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): 1: index
+    Store,
+    /// Add two numbers of any type.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Add,
+    /// Subtract two numbers of any type.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Sub,
+    /// Multiply two numbers of any type.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Mul,
+    /// Divide two numbers of any type.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Div,
+    /// Remainder of two numbers of any type.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Rem,
+    /// Negate a number of any type.
+    /// Stack (before -> after): [value] -> [result]
+    /// Other bytes (count: operand labels): -
+    Neg,
+    /// Print the value of any type on top of the stack.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): -
+    Print,
+    /// Print the value of any type on top of the stack followed by a new line.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): -
+    PrintLn,
+    /// Duplicate the value on top of the stack.
+    /// Stack (before -> after): [value] -> [value, value]
+    /// Other bytes (count: operand labels): -
+    Dup,
+    /// Duplicate the two values on top of the stack.
+    /// Stack (before -> after): [value1, value2] -> [value1, value2, value1, value2]
+    /// Other bytes (count: operand labels): -
+    Dup2,
+    /// Discard the value on top of the stack.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): -
+    Pop,
+    /// Create an array from the given number of values on top of the stack.
+    /// Stack (before -> after): [value1, value2, ...] -> [arrayref]
+    /// Other bytes (count: operand labels): 2: count1, count2
+    NewArray,
+    /// Load the element at the given index from an array.
+    /// Stack (before -> after): [arrayref, index] -> [value]
+    /// Other bytes (count: operand labels): -
+    GetElem,
+    /// Store a value into an array at the given index.
+    /// Stack (before -> after): [arrayref, index, value] -> []
+    /// Other bytes (count: operand labels): -
+    SetElem,
+    /// Get the number of elements of an array.
+    /// Stack (before -> after): [arrayref] -> [length]
+    /// Other bytes (count: operand labels): -
+    ArrayLength,
+    /// Pushes one byte onto the stack as a boolean value, where zero is false and everything else true.
+    /// Stack (before -> after): [] -> [value]
+    /// Other bytes (count: operand labels): 1: value
+    BPush,
+    /// Print the value on top of the stack as boolean.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): -
+    BPrint,
+    /// Negate a boolean.
+    /// Stack (before -> after): [value] -> [result]
+    /// Other bytes (count: operand labels): -
+    Not,
+    /// Continue execution at the given address.
+    /// Stack (before -> after): [no change]
+    /// Other bytes (count: operand labels): 4: address1, address2, address3, address4
+    Jump,
+    /// Continue execution at the given address if the boolean on top of the stack is false.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): 4: address1, address2, address3, address4
+    JumpIfFalse,
+    /// Continue execution at the given address if the boolean on top of the stack is true.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): 4: address1, address2, address3, address4
+    JumpIfTrue,
+    /// Compare two values of any type for equality.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Eq,
+    /// Compare two values of any type for inequality.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Ne,
+    /// Determine if the first value is less than the second.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Lt,
+    /// Determine if the first value is less than or equal to the second.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Le,
+    /// Determine if the first value is greater than the second.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Gt,
+    /// Determine if the first value is greater than or equal to the second.
+    /// Stack (before -> after): [value1, value2] -> [result]
+    /// Other bytes (count: operand labels): -
+    Ge,
+    /// Pushes a reference to the string with the given index in the string pool of the image.
+    /// Stack (before -> after): [] -> [stringref]
+    /// Other bytes (count: operand labels): 2: index1, index2
+    SPush,
+    /// Join two strings into a new string.
+    /// Stack (before -> after): [stringref1, stringref2] -> [stringref]
+    /// Other bytes (count: operand labels): -
+    Concat,
+    /// Create a new string from the printed form of the given number of values on top of the stack.
+    /// Stack (before -> after): [value1, value2, ...] -> [stringref]
+    /// Other bytes (count: operand labels): 2: count1, count2
+    BuildString,
+    /// Create a record of the type with the given index in the record types of the image. All fields are nil.
+    /// Stack (before -> after): [] -> [recordref]
+    /// Other bytes (count: operand labels): 2: index1, index2
+    NewRec,
+    /// Get the value of the field with the given index of a record.
+    /// Stack (before -> after): [recordref] -> [value]
+    /// Other bytes (count: operand labels): 1: index
+    GetField,
+    /// Set the value of the field with the given index of a record.
+    /// Stack (before -> after): [recordref, value] -> []
+    /// Other bytes (count: operand labels): 1: index
+    SetField,
+    /// Get the value of the field of a record whose name is the string with the given index in the string pool.
+    /// Used if the type of the record is not known at compile time.
+    /// Stack (before -> after): [recordref] -> [value]
+    /// Other bytes (count: operand labels): 2: index1, index2
+    GetNamedField,
+    /// Set the value of the field of a record whose name is the string with the given index in the string pool.
+    /// Used if the type of the record is not known at compile time.
+    /// Stack (before -> after): [recordref, value] -> []
+    /// Other bytes (count: operand labels): 2: index1, index2
+    SetNamedField,
+    /// Create a closure of the function with the given index in the functions of the image.
+    /// The captured upvalues are taken from the current frame as described by the function: Either the upvalue held by a local variable or an upvalue of the current closure.
+    /// Stack (before -> after): [] -> [closureref]
+    /// Other bytes (count: operand labels): 2: index1, index2
+    MkClosure,
+    /// Call a closure with the given number of arguments.
+    /// The arguments become the first local variables of the new frame. The result of the call is pushed when the callee returns.
+    /// Stack (before -> after): [closureref, arg1, ..., argN] -> [result]
+    /// Other bytes (count: operand labels): 1: count
+    CallIndirect,
+    /// Return from the current call and continue after the calling instruction.
+    /// Stack (before -> after): [result] -> [result]
+    /// Other bytes (count: operand labels): -
+    Ret,
+    /// Store a new upvalue holding nil into the local variable with the given index.
+    /// Local variables which are captured by closures live in upvalues.
+    /// Stack (before -> after): [] -> []
+    /// Other bytes (count: operand labels): 1: index
+    NewUpvalue,
+    /// Load the value of the upvalue held by the local variable with the given index.
+    /// Stack (before -> after): [] -> [value]
+    /// Other bytes (count: operand labels): 1: index
+    LoadUpvalue,
+    /// Store into the upvalue held by the local variable with the given index.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): 1: index
+    StoreUpvalue,
+    /// Get the value of the upvalue with the given index of the current closure.
+    /// Stack (before -> after): [] -> [value]
+    /// Other bytes (count: operand labels): 1: index
+    GetUpvalue,
+    /// Set the value of the upvalue with the given index of the current closure.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): 1: index
+    SetUpvalue,
+    /// Throw the value as exception.
+    /// The frames and the stack are unwound to the nearest exception handler covering the instruction,
+    /// which continues with the exception on the stack. Runtime errors are thrown as strings with
+    /// the error message.
+    /// Stack (before -> after): [value] -> (handler) [value]
+    /// Other bytes (count: operand labels): -
+    Throw,
+    /// Continue execution at the target of the integer in the jump table with the given index in the
+    /// image. Reals with an integer value select the target of the integer. Values without target,
+    /// including all which are no numbers, continue at the default address of the table.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): 2: index1, index2
+    TableSwitch,
+    /// Determine if the value is a record of the type with the given index in the record types of the
+    /// image.
+    /// Stack (before -> after): [value] -> [boolean]
+    /// Other bytes (count: operand labels): 2: index1, index2
+    IsRec,
+    /// Fail because no arm of a match matches the value. The error may be caught like an exception.
+    /// Stack (before -> after): [value] -> []
+    /// Other bytes (count: operand labels): -
+    NoMatch,
+}
+
+/// Kinds of operands following the opcode of an instruction in the byte code.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operand {
+    /// One byte unsigned number, e.g. the index of a local variable.
+    Byte,
+    /// Two bytes unsigned number, e.g. a count of values.
+    Short,
+    /// Eight bytes signed integer.
+    Integer,
+    /// Eight bytes IEEE 754 floating point number.
+    Real,
+    /// Eight bytes Unicode code point.
+    Character,
+    /// Four bytes unsigned offset into the byte code, e.g. the target of a jump.
+    Address,
+}
+
+impl Operand {
+    /// Get the number of bytes the operand occupies in the byte code.
+    pub fn size(&self) -> usize {
+        match *self {
+            Operand::Byte => 1,
+            Operand::Short => 2,
+            Operand::Address => 4,
+            Operand::Integer | Operand::Real | Operand::Character => 8,
+        }
+    }
+}
+
+impl Instruction {
+    /// Get the operands which follow the opcode in the byte code.
+    pub fn operands(&self) -> &'static [Operand] {
+        match *self {
+            Instruction::IPush => &[Operand::Integer],
+            Instruction::IStore | Instruction::ILoad => &[Operand::Byte],
+            Instruction::RPush => &[Operand::Real],
+            Instruction::CPush => &[Operand::Character],
+            Instruction::Load => &[Operand::Byte],
+            Instruction::Store => &[Operand::Byte],
+            Instruction::NewArray => &[Operand::Short],
+            Instruction::BPush => &[Operand::Byte],
+            Instruction::Jump => &[Operand::Address],
+            Instruction::JumpIfFalse => &[Operand::Address],
+            Instruction::JumpIfTrue => &[Operand::Address],
+            Instruction::SPush => &[Operand::Short],
+            Instruction::BuildString => &[Operand::Short],
+            Instruction::NewRec => &[Operand::Short],
+            Instruction::GetField => &[Operand::Byte],
+            Instruction::SetField => &[Operand::Byte],
+            Instruction::GetNamedField => &[Operand::Short],
+            Instruction::SetNamedField => &[Operand::Short],
+            Instruction::MkClosure => &[Operand::Short],
+            Instruction::CallIndirect => &[Operand::Byte],
+            Instruction::NewUpvalue => &[Operand::Byte],
+            Instruction::LoadUpvalue => &[Operand::Byte],
+            Instruction::StoreUpvalue => &[Operand::Byte],
+            Instruction::GetUpvalue => &[Operand::Byte],
+            Instruction::SetUpvalue => &[Operand::Byte],
+            Instruction::TableSwitch => &[Operand::Short],
+            Instruction::IsRec => &[Operand::Short],
+            _ => &[],
+        }
+    }
+
+    /// Get the number of bytes of the instruction including its operands.
+    pub fn size(&self) -> usize {
+        1 + self.operands().iter().map(|o| o.size()).sum::<usize>()
+    }
 }
 
 impl fmt::Display for Instruction {
@@ -84,6 +373,58 @@ impl fmt::Display for Instruction {
             Instruction::INeg => write!(f, "ineg"),
             Instruction::IPrint => write!(f, "iprint"),
             Instruction::Halt => write!(f, "halt"),
+            Instruction::RPush => write!(f, "rpush"),
+            Instruction::CPush => write!(f, "cpush"),
+            Instruction::NPush => write!(f, "npush"),
+            Instruction::Load => write!(f, "load"),
+            Instruction::Store => write!(f, "store"),
+            Instruction::Add => write!(f, "add"),
+            Instruction::Sub => write!(f, "sub"),
+            Instruction::Mul => write!(f, "mul"),
+            Instruction::Div => write!(f, "div"),
+            Instruction::Rem => write!(f, "rem"),
+            Instruction::Neg => write!(f, "neg"),
+            Instruction::Print => write!(f, "print"),
+            Instruction::PrintLn => write!(f, "println"),
+            Instruction::Dup => write!(f, "dup"),
+            Instruction::Dup2 => write!(f, "dup2"),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::NewArray => write!(f, "newarray"),
+            Instruction::GetElem => write!(f, "getelem"),
+            Instruction::SetElem => write!(f, "setelem"),
+            Instruction::ArrayLength => write!(f, "arraylength"),
+            Instruction::BPush => write!(f, "bpush"),
+            Instruction::BPrint => write!(f, "bprint"),
+            Instruction::Not => write!(f, "not"),
+            Instruction::Jump => write!(f, "jump"),
+            Instruction::JumpIfFalse => write!(f, "jumpiffalse"),
+            Instruction::JumpIfTrue => write!(f, "jumpiftrue"),
+            Instruction::Eq => write!(f, "eq"),
+            Instruction::Ne => write!(f, "ne"),
+            Instruction::Lt => write!(f, "lt"),
+            Instruction::Le => write!(f, "le"),
+            Instruction::Gt => write!(f, "gt"),
+            Instruction::Ge => write!(f, "ge"),
+            Instruction::SPush => write!(f, "spush"),
+            Instruction::Concat => write!(f, "concat"),
+            Instruction::BuildString => write!(f, "buildstring"),
+            Instruction::NewRec => write!(f, "newrec"),
+            Instruction::GetField => write!(f, "getfield"),
+            Instruction::SetField => write!(f, "setfield"),
+            Instruction::GetNamedField => write!(f, "getnamedfield"),
+            Instruction::SetNamedField => write!(f, "setnamedfield"),
+            Instruction::MkClosure => write!(f, "mkclosure"),
+            Instruction::CallIndirect => write!(f, "callindirect"),
+            Instruction::Ret => write!(f, "ret"),
+            Instruction::NewUpvalue => write!(f, "newupvalue"),
+            Instruction::LoadUpvalue => write!(f, "loadupvalue"),
+            Instruction::StoreUpvalue => write!(f, "storeupvalue"),
+            Instruction::GetUpvalue => write!(f, "getupvalue"),
+            Instruction::SetUpvalue => write!(f, "setupvalue"),
+            Instruction::Throw => write!(f, "throw"),
+            Instruction::TableSwitch => write!(f, "tableswitch"),
+            Instruction::IsRec => write!(f, "isrec"),
+            Instruction::NoMatch => write!(f, "nomatch"),
         }
     }
 }
@@ -103,6 +444,58 @@ impl From<Instruction> for u8 {
             Instruction::INeg => 0x0a,
             Instruction::IPrint => 0x0b,
             Instruction::Halt => 0x0c,
+            Instruction::RPush => 0x0d,
+            Instruction::CPush => 0x0e,
+            Instruction::NPush => 0x0f,
+            Instruction::Load => 0x10,
+            Instruction::Store => 0x11,
+            Instruction::Add => 0x12,
+            Instruction::Sub => 0x13,
+            Instruction::Mul => 0x14,
+            Instruction::Div => 0x15,
+            Instruction::Rem => 0x16,
+            Instruction::Neg => 0x17,
+            Instruction::Print => 0x18,
+            Instruction::PrintLn => 0x19,
+            Instruction::Dup => 0x1a,
+            Instruction::Dup2 => 0x1b,
+            Instruction::Pop => 0x1c,
+            Instruction::NewArray => 0x1d,
+            Instruction::GetElem => 0x1e,
+            Instruction::SetElem => 0x1f,
+            Instruction::ArrayLength => 0x20,
+            Instruction::BPush => 0x21,
+            Instruction::BPrint => 0x22,
+            Instruction::Not => 0x23,
+            Instruction::Jump => 0x24,
+            Instruction::JumpIfFalse => 0x25,
+            Instruction::JumpIfTrue => 0x26,
+            Instruction::Eq => 0x27,
+            Instruction::Ne => 0x28,
+            Instruction::Lt => 0x29,
+            Instruction::Le => 0x2a,
+            Instruction::Gt => 0x2b,
+            Instruction::Ge => 0x2c,
+            Instruction::SPush => 0x2d,
+            Instruction::Concat => 0x2e,
+            Instruction::BuildString => 0x2f,
+            Instruction::NewRec => 0x30,
+            Instruction::GetField => 0x31,
+            Instruction::SetField => 0x32,
+            Instruction::GetNamedField => 0x33,
+            Instruction::SetNamedField => 0x34,
+            Instruction::MkClosure => 0x35,
+            Instruction::CallIndirect => 0x36,
+            Instruction::Ret => 0x37,
+            Instruction::NewUpvalue => 0x38,
+            Instruction::LoadUpvalue => 0x39,
+            Instruction::StoreUpvalue => 0x3a,
+            Instruction::GetUpvalue => 0x3b,
+            Instruction::SetUpvalue => 0x3c,
+            Instruction::Throw => 0x3d,
+            Instruction::TableSwitch => 0x3e,
+            Instruction::IsRec => 0x3f,
+            Instruction::NoMatch => 0x40,
         }
     }
 }
@@ -124,6 +517,58 @@ impl TryFrom<u8> for Instruction {
             0x0a => Ok(Instruction::INeg),
             0x0b => Ok(Instruction::IPrint),
             0x0c => Ok(Instruction::Halt),
+            0x0d => Ok(Instruction::RPush),
+            0x0e => Ok(Instruction::CPush),
+            0x0f => Ok(Instruction::NPush),
+            0x10 => Ok(Instruction::Load),
+            0x11 => Ok(Instruction::Store),
+            0x12 => Ok(Instruction::Add),
+            0x13 => Ok(Instruction::Sub),
+            0x14 => Ok(Instruction::Mul),
+            0x15 => Ok(Instruction::Div),
+            0x16 => Ok(Instruction::Rem),
+            0x17 => Ok(Instruction::Neg),
+            0x18 => Ok(Instruction::Print),
+            0x19 => Ok(Instruction::PrintLn),
+            0x1a => Ok(Instruction::Dup),
+            0x1b => Ok(Instruction::Dup2),
+            0x1c => Ok(Instruction::Pop),
+            0x1d => Ok(Instruction::NewArray),
+            0x1e => Ok(Instruction::GetElem),
+            0x1f => Ok(Instruction::SetElem),
+            0x20 => Ok(Instruction::ArrayLength),
+            0x21 => Ok(Instruction::BPush),
+            0x22 => Ok(Instruction::BPrint),
+            0x23 => Ok(Instruction::Not),
+            0x24 => Ok(Instruction::Jump),
+            0x25 => Ok(Instruction::JumpIfFalse),
+            0x26 => Ok(Instruction::JumpIfTrue),
+            0x27 => Ok(Instruction::Eq),
+            0x28 => Ok(Instruction::Ne),
+            0x29 => Ok(Instruction::Lt),
+            0x2a => Ok(Instruction::Le),
+            0x2b => Ok(Instruction::Gt),
+            0x2c => Ok(Instruction::Ge),
+            0x2d => Ok(Instruction::SPush),
+            0x2e => Ok(Instruction::Concat),
+            0x2f => Ok(Instruction::BuildString),
+            0x30 => Ok(Instruction::NewRec),
+            0x31 => Ok(Instruction::GetField),
+            0x32 => Ok(Instruction::SetField),
+            0x33 => Ok(Instruction::GetNamedField),
+            0x34 => Ok(Instruction::SetNamedField),
+            0x35 => Ok(Instruction::MkClosure),
+            0x36 => Ok(Instruction::CallIndirect),
+            0x37 => Ok(Instruction::Ret),
+            0x38 => Ok(Instruction::NewUpvalue),
+            0x39 => Ok(Instruction::LoadUpvalue),
+            0x3a => Ok(Instruction::StoreUpvalue),
+            0x3b => Ok(Instruction::GetUpvalue),
+            0x3c => Ok(Instruction::SetUpvalue),
+            0x3d => Ok(Instruction::Throw),
+            0x3e => Ok(Instruction::TableSwitch),
+            0x3f => Ok(Instruction::IsRec),
+            0x40 => Ok(Instruction::NoMatch),
             n => Err(BytecodeError::UnknownInstruction(n)),
         }
     }
@@ -146,6 +591,58 @@ impl FromStr for Instruction {
             "ineg" => Ok(Instruction::INeg),
             "iprint" => Ok(Instruction::IPrint),
             "halt" => Ok(Instruction::Halt),
+            "rpush" => Ok(Instruction::RPush),
+            "cpush" => Ok(Instruction::CPush),
+            "npush" => Ok(Instruction::NPush),
+            "load" => Ok(Instruction::Load),
+            "store" => Ok(Instruction::Store),
+            "add" => Ok(Instruction::Add),
+            "sub" => Ok(Instruction::Sub),
+            "mul" => Ok(Instruction::Mul),
+            "div" => Ok(Instruction::Div),
+            "rem" => Ok(Instruction::Rem),
+            "neg" => Ok(Instruction::Neg),
+            "print" => Ok(Instruction::Print),
+            "println" => Ok(Instruction::PrintLn),
+            "dup" => Ok(Instruction::Dup),
+            "dup2" => Ok(Instruction::Dup2),
+            "pop" => Ok(Instruction::Pop),
+            "newarray" => Ok(Instruction::NewArray),
+            "getelem" => Ok(Instruction::GetElem),
+            "setelem" => Ok(Instruction::SetElem),
+            "arraylength" => Ok(Instruction::ArrayLength),
+            "bpush" => Ok(Instruction::BPush),
+            "bprint" => Ok(Instruction::BPrint),
+            "not" => Ok(Instruction::Not),
+            "jump" => Ok(Instruction::Jump),
+            "jumpiffalse" => Ok(Instruction::JumpIfFalse),
+            "jumpiftrue" => Ok(Instruction::JumpIfTrue),
+            "eq" => Ok(Instruction::Eq),
+            "ne" => Ok(Instruction::Ne),
+            "lt" => Ok(Instruction::Lt),
+            "le" => Ok(Instruction::Le),
+            "gt" => Ok(Instruction::Gt),
+            "ge" => Ok(Instruction::Ge),
+            "spush" => Ok(Instruction::SPush),
+            "concat" => Ok(Instruction::Concat),
+            "buildstring" => Ok(Instruction::BuildString),
+            "newrec" => Ok(Instruction::NewRec),
+            "getfield" => Ok(Instruction::GetField),
+            "setfield" => Ok(Instruction::SetField),
+            "getnamedfield" => Ok(Instruction::GetNamedField),
+            "setnamedfield" => Ok(Instruction::SetNamedField),
+            "mkclosure" => Ok(Instruction::MkClosure),
+            "callindirect" => Ok(Instruction::CallIndirect),
+            "ret" => Ok(Instruction::Ret),
+            "newupvalue" => Ok(Instruction::NewUpvalue),
+            "loadupvalue" => Ok(Instruction::LoadUpvalue),
+            "storeupvalue" => Ok(Instruction::StoreUpvalue),
+            "getupvalue" => Ok(Instruction::GetUpvalue),
+            "setupvalue" => Ok(Instruction::SetUpvalue),
+            "throw" => Ok(Instruction::Throw),
+            "tableswitch" => Ok(Instruction::TableSwitch),
+            "isrec" => Ok(Instruction::IsRec),
+            "nomatch" => Ok(Instruction::NoMatch),
             m => Err(BytecodeError::UnknownMnemonic(m.to_string())),
         }
     }
@@ -186,7 +683,7 @@ mod tests {
         assert_that!(Instruction::try_from(0x0a), is(equal_to(Ok(Instruction::INeg))));
         assert_that!(Instruction::try_from(0x0b), is(equal_to(Ok(Instruction::IPrint))));
         assert_that!(Instruction::try_from(0x0c), is(equal_to(Ok(Instruction::Halt))));
-        assert_that!(Instruction::try_from(0x0d), is(equal_to(Err(BytecodeError::UnknownInstruction(0x0d)))));
+        assert_that!(Instruction::try_from(0xff), is(equal_to(Err(BytecodeError::UnknownInstruction(0xff)))));
     }
 
     #[test]
@@ -206,6 +703,53 @@ mod tests {
         assert_that!(Instruction::from_str("foo"), is(equal_to(Err(BytecodeError::UnknownMnemonic(String::from("foo"))))));
     }
 
+    const ALL: [Instruction; 64] = [
+        Instruction::Nop, Instruction::IPush, Instruction::IStore, Instruction::ILoad, Instruction::IAdd,
+        Instruction::ISub, Instruction::IMul, Instruction::IDiv, Instruction::IRem, Instruction::INeg,
+        Instruction::IPrint, Instruction::Halt, Instruction::RPush, Instruction::CPush, Instruction::NPush,
+        Instruction::Load, Instruction::Store, Instruction::Add, Instruction::Sub, Instruction::Mul,
+        Instruction::Div, Instruction::Rem, Instruction::Neg, Instruction::Print, Instruction::PrintLn,
+        Instruction::Dup, Instruction::Dup2, Instruction::Pop, Instruction::NewArray, Instruction::GetElem,
+        Instruction::SetElem, Instruction::ArrayLength, Instruction::BPush, Instruction::BPrint,
+        Instruction::Not, Instruction::Jump, Instruction::JumpIfFalse, Instruction::JumpIfTrue,
+        Instruction::Eq, Instruction::Ne, Instruction::Lt, Instruction::Le, Instruction::Gt, Instruction::Ge,
+        Instruction::SPush, Instruction::Concat, Instruction::BuildString, Instruction::NewRec,
+        Instruction::GetField, Instruction::SetField, Instruction::GetNamedField, Instruction::SetNamedField,
+        Instruction::MkClosure, Instruction::CallIndirect, Instruction::Ret, Instruction::NewUpvalue,
+        Instruction::LoadUpvalue, Instruction::StoreUpvalue, Instruction::GetUpvalue, Instruction::SetUpvalue,
+        Instruction::Throw, Instruction::TableSwitch, Instruction::IsRec, Instruction::NoMatch,
+    ];
+
+    #[test]
+    fn all_instructions_round_trip() {
+        for instruction in ALL.iter() {
+            let opcode = u8::from(*instruction);
+
+            assert_that!(Instruction::try_from(opcode), is(equal_to(Ok(*instruction))));
+            assert_that!(Instruction::from_str(&instruction.to_string()), is(equal_to(Ok(*instruction))));
+        }
+    }
+
+    #[test]
+    fn opcodes_are_unique() {
+        let mut opcodes: Vec<u8> = ALL.iter().map(|i| u8::from(*i)).collect();
+        opcodes.sort();
+        opcodes.dedup();
+
+        assert_that!(opcodes.len(), is(equal_to(ALL.len())));
+    }
+
+    #[test]
+    fn instruction_size() {
+        assert_that!(Instruction::Nop.size(), is(equal_to(1)));
+        assert_that!(Instruction::IPush.size(), is(equal_to(9)));
+        assert_that!(Instruction::ILoad.size(), is(equal_to(2)));
+        assert_that!(Instruction::NewArray.size(), is(equal_to(3)));
+        assert_that!(Instruction::CPush.size(), is(equal_to(9)));
+        assert_that!(Instruction::BPush.size(), is(equal_to(2)));
+        assert_that!(Instruction::JumpIfFalse.size(), is(equal_to(5)));
+    }
+
     #[test]
     fn instruction_fmt() {
         assert_that!(&format!("{}", Instruction::Nop), is(equal_to("nop")));
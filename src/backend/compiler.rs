@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+
+use backend::bytecode::Instruction;
+use backend::byte_conversion::*;
+use backend::image::{ExceptionHandler, Function, Image, JumpTable, LineTable, RecordType};
+use backend::peephole;
+use frontend::module::{self, Module, ModuleError};
+use frontend::{Span, SyntaxError};
+use intermediate::ast::{BinaryOperator, Statement, UnaryOperator};
+use intermediate::ir::{self, Constant, Field, InstructionKind, Kind, Place, Program, TerminatorKind};
+use intermediate::linker;
+use intermediate::lowering;
+use intermediate::optimize::{self, Level};
+use intermediate::semantic::TypeChecker;
+
+/// Generates byte code from the abstract syntax tree.
+///
+/// The program is type checked first, optimized at the level of the compiler and lowered into the
+/// intermediate representation, whose blocks are emitted in their order. Jumps to the directly
+/// following block are omitted. The temporaries of the blocks live on the operand stack.
+/// Operations on integers are compiled to the integer instructions if the types are statically
+/// known, all others to the generic instructions which check the types at runtime. String literals
+/// are stored once in the string pool of the image.
+///
+/// Function bodies are emitted in place, preceded by a jump over them, and followed by the
+/// `mkclosure` which creates the function value. The blocks guarded by a try block get entries in
+/// the exception table, which exclude the bodies of functions declared in them. A switch gets a
+/// jump table.
+pub struct Compiler {
+    code: Vec<u8>,
+    strings: Vec<String>,
+    // Index of each string in the pool.
+    string_indices: HashMap<String, u16>,
+    records: Vec<RecordType>,
+    functions: Vec<Function>,
+    handlers: Vec<ExceptionHandler>,
+    jump_tables: Vec<JumpTable>,
+    lines: LineTable,
+    level: Level,
+}
+
+/// Addresses of the function being emitted, which are resolved once all blocks are emitted.
+struct Layout {
+    // Address of each block.
+    addresses: Vec<usize>,
+    // Offset of the address of each jump and the block it jumps to.
+    jumps: Vec<(usize, usize)>,
+    // Index of each jump table with its switch.
+    tables: Vec<(usize, i64, Vec<usize>, usize)>,
+    // Guarded code ranges of each try block.
+    ranges: Vec<Vec<(usize, usize)>>,
+}
+
+impl Layout {
+    fn new(function: &ir::Function) -> Layout {
+        Layout {
+            addresses: vec![0; function.blocks().len()],
+            jumps: Vec::new(),
+            tables: Vec::new(),
+            ranges: vec![Vec::new(); function.guards().len()],
+        }
+    }
+
+    // Adds the range to the try block and all enclosing ones. Adjacent ranges are joined.
+    fn guard(&mut self, function: &ir::Function, guard: Option<usize>, start: usize, end: usize) {
+        let mut current = guard;
+
+        while let Some(guard) = current {
+            let ranges = &mut self.ranges[guard];
+
+            match ranges.last_mut() {
+                _ if start == end => (),
+                Some(range) if range.1 == start => range.1 = end,
+                _ => ranges.push((start, end)),
+            }
+
+            current = function.guards()[guard].parent();
+        }
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            code: Vec::new(),
+            strings: Vec::new(),
+            string_indices: HashMap::new(),
+            records: Vec::new(),
+            functions: Vec::new(),
+            handlers: Vec::new(),
+            jump_tables: Vec::new(),
+            lines: LineTable::new(),
+            level: Level::O0,
+        }
+    }
+
+    /// Creates a compiler which optimizes the program at the given level.
+    pub fn with_level(level: Level) -> Compiler {
+        Compiler { level, ..Compiler::new() }
+    }
+
+    /// Compiles the given program into an executable image.
+    ///
+    /// Returns an error if the program is semantically wrong or uses unsupported features.
+    pub fn compile(self, statements: &[Statement]) -> Result<Image, SyntaxError> {
+        self.compile_program(statements).map(|(image, _)| image)
+    }
+
+    /// Links the given modules and compiles them into one executable image.
+    ///
+    /// The modules must be in dependency order like returned by the module loader. The code of
+    /// the last module is the entry point after all imported modules are initialized. Returns the
+    /// image together with the warnings of the semantic analysis.
+    pub fn compile_modules(self, modules: &[Module]) -> Result<(Image, Vec<ModuleError>), ModuleError> {
+        let (image, warnings) = linker::link(modules)
+            .and_then(|statements| self.compile_program(&statements))
+            .map_err(|e| module::locate(modules, e))?;
+        let warnings = warnings.into_iter().map(|warning| module::locate(modules, warning)).collect();
+        Ok((image, warnings))
+    }
+
+    /// Links the given modules and lowers them into the intermediate representation the image is
+    /// generated from, together with the warnings of the semantic analysis.
+    pub fn lower_modules(self, modules: &[Module]) -> Result<(Program, Vec<ModuleError>), ModuleError> {
+        let (program, warnings) = linker::link(modules)
+            .and_then(|statements| self.lower(&statements))
+            .map_err(|e| module::locate(modules, e))?;
+        let warnings = warnings.into_iter().map(|warning| module::locate(modules, warning)).collect();
+        Ok((program, warnings))
+    }
+
+    // Blocks which are never executed are removed by the optimizer.
+    fn lower(&self, statements: &[Statement]) -> Result<(Program, Vec<SyntaxError>), SyntaxError> {
+        let analysis = TypeChecker::new().check(statements)?;
+        let warnings = analysis.warnings().to_vec();
+
+        let program = match self.level {
+            Level::O0 => lowering::lower(statements, &analysis)?,
+            // The optimized program is checked again to get the types of the folded expressions.
+            Level::O1 => {
+                let optimized = optimize::optimize(statements, &analysis);
+                let analysis = TypeChecker::new().check(&optimized)?;
+                let mut program = lowering::lower(&optimized, &analysis)?;
+                program.remove_unreachable_blocks();
+                program
+            },
+        };
+
+        Ok((program, warnings))
+    }
+
+    fn compile_program(mut self, statements: &[Statement]) -> Result<(Image, Vec<SyntaxError>), SyntaxError> {
+        let (program, warnings) = self.lower(statements)?;
+        self.records = program.records().to_vec();
+        self.emit_function(&program, 0)?;
+        let image = Image::new(
+            self.code, self.strings, self.records, self.functions, self.handlers, self.jump_tables, self.lines);
+        let image = match self.level {
+            Level::O0 => image,
+            Level::O1 => peephole::optimize(&image),
+        };
+        Ok((image, warnings))
+    }
+
+    // Emits the blocks of the function and returns the address of its code. The handlers of a try
+    // block are added once its handler block is reached, so inner try blocks precede outer ones.
+    fn emit_function(&mut self, program: &Program, index: usize) -> Result<usize, SyntaxError> {
+        let function = &program.functions()[index];
+        let address = self.code.len();
+        let mut layout = Layout::new(function);
+
+        for (id, block) in function.blocks().iter().enumerate() {
+            let block_address = self.code.len();
+            layout.addresses[id] = block_address;
+
+            for (guard, ranges) in function.guards().iter().zip(&layout.ranges) {
+                if guard.handler() == id {
+                    let handlers = ranges.iter().map(|&(start, end)| ExceptionHandler::new(start, end, block_address));
+                    self.handlers.extend(handlers);
+                }
+            }
+
+            let mut start = self.code.len();
+
+            for instruction in block.instructions() {
+                match *instruction.kind() {
+                    InstructionKind::Closure(_, nested) => {
+                        // The body is not guarded by the try blocks around the declaration.
+                        let end_jump = self.emit_jump(Instruction::Jump, instruction.span());
+                        layout.guard(function, block.guard(), start, self.code.len());
+                        let body = self.emit_function(program, nested)?;
+                        self.patch_jump(end_jump);
+                        start = self.code.len();
+                        let nested = &program.functions()[nested];
+                        let index = self.functions.len();
+                        self.functions.push(Function::new(
+                            nested.name().to_string(), body, nested.arity(), nested.captures().to_vec()));
+                        self.emit(Instruction::MkClosure, instruction.span());
+                        self.code.push((index >> 8) as u8);
+                        self.code.push(index as u8);
+                    },
+                    ref kind => self.emit_instruction(kind, instruction.span())?,
+                }
+            }
+
+            self.emit_terminator(block.terminator(), id, &mut layout)?;
+            layout.guard(function, block.guard(), start, self.code.len());
+        }
+
+        for &(offset, block) in &layout.jumps {
+            let target = address_to_bytes(layout.addresses[block] as u32);
+            self.code[offset..offset + ADDRESS_SIZE].copy_from_slice(&target);
+        }
+
+        for &(index, low, ref targets, default) in &layout.tables {
+            let targets = targets.iter().map(|&target| layout.addresses[target]).collect();
+            self.jump_tables[index] = JumpTable::new(low, targets, layout.addresses[default]);
+        }
+
+        Ok(address)
+    }
+
+    fn emit_instruction(&mut self, kind: &InstructionKind, span: Span) -> Result<(), SyntaxError> {
+        match *kind {
+            InstructionKind::Constant(_, ref value) => self.emit_constant(value, span)?,
+            InstructionKind::Duplicate(..) => self.emit(Instruction::Dup, span),
+            InstructionKind::DuplicatePair(..) => self.emit(Instruction::Dup2, span),
+            InstructionKind::Load(_, place, kind) => {
+                let (instruction, index) = match place {
+                    Place::Local(slot) if Kind::Integer == kind => (Instruction::ILoad, slot),
+                    Place::Local(slot) => (Instruction::Load, slot),
+                    Place::Cell(slot) => (Instruction::LoadUpvalue, slot),
+                    Place::Upvalue(index) => (Instruction::GetUpvalue, index),
+                };
+                self.emit_byte(instruction, index, span);
+            },
+            InstructionKind::Store(place, _, kind) => {
+                let (instruction, index) = match place {
+                    Place::Local(slot) if Kind::Integer == kind => (Instruction::IStore, slot),
+                    Place::Local(slot) => (Instruction::Store, slot),
+                    Place::Cell(slot) => (Instruction::StoreUpvalue, slot),
+                    Place::Upvalue(index) => (Instruction::SetUpvalue, index),
+                };
+                self.emit_byte(instruction, index, span);
+            },
+            InstructionKind::NewCell(slot) => self.emit_byte(Instruction::NewUpvalue, slot, span),
+            InstructionKind::Binary(_, operator, _, _, kind) => self.emit_operator(operator, kind, span)?,
+            InstructionKind::Unary(_, UnaryOperator::Negate, _, Kind::Integer) => self.emit(Instruction::INeg, span),
+            InstructionKind::Unary(_, UnaryOperator::Negate, _, _) => self.emit(Instruction::Neg, span),
+            InstructionKind::Unary(_, UnaryOperator::Not, _, _) => self.emit(Instruction::Not, span),
+            InstructionKind::Unary(_, UnaryOperator::Plus, _, _) => (),
+            InstructionKind::Call(_, _, ref arguments) =>
+                self.emit_byte(Instruction::CallIndirect, arguments.len() as u8, span),
+            InstructionKind::Closure(..) => unreachable!("Closures are emitted with their function!"),
+            InstructionKind::Array(_, ref elements) => self.emit_short(Instruction::NewArray, elements.len(), span),
+            InstructionKind::Length(..) => self.emit(Instruction::ArrayLength, span),
+            InstructionKind::GetElement(..) => self.emit(Instruction::GetElem, span),
+            InstructionKind::SetElement(..) => self.emit(Instruction::SetElem, span),
+            InstructionKind::NewRecord(_, ref record) => {
+                let index = self.record_index(record);
+                self.emit_short(Instruction::NewRec, index, span);
+            },
+            InstructionKind::GetField(_, _, ref field) => self.emit_field_access(false, field, span)?,
+            InstructionKind::SetField(_, ref field, _) => self.emit_field_access(true, field, span)?,
+            InstructionKind::IsRecord(_, _, ref record) => {
+                let index = self.record_index(record);
+                self.emit_short(Instruction::IsRec, index, span);
+            },
+            InstructionKind::BuildString(_, ref parts) => self.emit_short(Instruction::BuildString, parts.len(), span),
+            InstructionKind::Print(_) => self.emit(Instruction::Print, span),
+            InstructionKind::PrintLn(_) => self.emit(Instruction::PrintLn, span),
+            InstructionKind::Discard(_) => self.emit(Instruction::Pop, span),
+            // The exception is already on the stack.
+            InstructionKind::Catch(_) => (),
+        }
+
+        Ok(())
+    }
+
+    // A branch jumps only to the block which does not follow. A switch gets the next free jump
+    // table, which is completed once the addresses of the blocks are known.
+    fn emit_terminator(&mut self, terminator: &ir::Terminator, id: usize, layout: &mut Layout)
+        -> Result<(), SyntaxError> {
+        let span = terminator.span();
+        let next = id + 1;
+
+        match *terminator.kind() {
+            TerminatorKind::Jump(target, _) if target == next => (),
+            TerminatorKind::Jump(target, _) => self.emit_jump_to_block(Instruction::Jump, target, span, layout),
+            TerminatorKind::Branch(_, then_block, else_block) if then_block == next =>
+                self.emit_jump_to_block(Instruction::JumpIfFalse, else_block, span, layout),
+            TerminatorKind::Branch(_, then_block, else_block) if else_block == next =>
+                self.emit_jump_to_block(Instruction::JumpIfTrue, then_block, span, layout),
+            TerminatorKind::Branch(_, then_block, else_block) => {
+                self.emit_jump_to_block(Instruction::JumpIfFalse, else_block, span, layout);
+                self.emit_jump_to_block(Instruction::Jump, then_block, span, layout);
+            },
+            TerminatorKind::Switch(_, low, ref targets, default) => {
+                if self.jump_tables.len() > usize::from(u16::MAX) {
+                    return Err(SyntaxError::new(String::from("Too many jump tables!"), span));
+                }
+
+                let index = self.jump_tables.len();
+                self.jump_tables.push(JumpTable::new(low, Vec::new(), 0));
+                layout.tables.push((index, low, targets.clone(), default));
+                self.emit_short(Instruction::TableSwitch, index, span);
+            },
+            TerminatorKind::Return(_) => self.emit(Instruction::Ret, span),
+            TerminatorKind::Throw(_) => self.emit(Instruction::Throw, span),
+            TerminatorKind::NoMatch(_) => self.emit(Instruction::NoMatch, span),
+            TerminatorKind::Halt => self.code.push(u8::from(Instruction::Halt)),
+        }
+
+        Ok(())
+    }
+
+    fn emit_constant(&mut self, value: &Constant, span: Span) -> Result<(), SyntaxError> {
+        match *value {
+            Constant::Nil => self.emit(Instruction::NPush, span),
+            Constant::Integer(value) => {
+                self.emit(Instruction::IPush, span);
+                self.code.append(&mut int_to_bytes(value));
+            },
+            Constant::Real(value) => {
+                self.emit(Instruction::RPush, span);
+                self.code.append(&mut float_to_bytes(value));
+            },
+            Constant::Character(value) => {
+                self.emit(Instruction::CPush, span);
+                self.code.append(&mut int_to_bytes(i64::from(value as u32)));
+            },
+            Constant::String(ref value) => {
+                let index = self.string_index(value, span)?;
+                self.emit_short(Instruction::SPush, usize::from(index), span);
+            },
+            Constant::Boolean(value) => self.emit_byte(Instruction::BPush, value as u8, span),
+        }
+
+        Ok(())
+    }
+
+    fn emit_operator(&mut self, operator: BinaryOperator, kind: Kind, span: Span) -> Result<(), SyntaxError> {
+        let integers = Kind::Integer == kind;
+        let instruction = match operator {
+            BinaryOperator::Add if Kind::String == kind => Instruction::Concat,
+            BinaryOperator::Add if integers => Instruction::IAdd,
+            BinaryOperator::Subtract if integers => Instruction::ISub,
+            BinaryOperator::Multiply if integers => Instruction::IMul,
+            BinaryOperator::Divide if integers => Instruction::IDiv,
+            BinaryOperator::Modulo if integers => Instruction::IRem,
+            BinaryOperator::Add => Instruction::Add,
+            BinaryOperator::Subtract => Instruction::Sub,
+            BinaryOperator::Multiply => Instruction::Mul,
+            BinaryOperator::Divide => Instruction::Div,
+            BinaryOperator::Modulo => Instruction::Rem,
+            BinaryOperator::Equal => Instruction::Eq,
+            BinaryOperator::NotEqual => Instruction::Ne,
+            BinaryOperator::LessThan => Instruction::Lt,
+            BinaryOperator::LessThanEqual => Instruction::Le,
+            BinaryOperator::GreaterThan => Instruction::Gt,
+            BinaryOperator::GreaterThanEqual => Instruction::Ge,
+            _ => return Err(SyntaxError::new(format!("Operator '{}' is not supported!", operator), span)),
+        };
+
+        self.emit(instruction, span);
+        Ok(())
+    }
+
+    // Emits the get or set of a field by index or by name.
+    fn emit_field_access(&mut self, set: bool, field: &Field, span: Span) -> Result<(), SyntaxError> {
+        match *field {
+            Field::Index(index, _) =>
+                self.emit_byte(if set { Instruction::SetField } else { Instruction::GetField }, index, span),
+            Field::Name(ref name) => {
+                let name = self.string_index(name, span)?;
+                let instruction = if set { Instruction::SetNamedField } else { Instruction::GetNamedField };
+                self.emit_short(instruction, usize::from(name), span);
+            },
+        }
+
+        Ok(())
+    }
+
+    fn record_index(&self, name: &str) -> usize {
+        self.records.iter().position(|record| record.name() == name).expect("Record types are checked!")
+    }
+
+    // Get the index of the string in the pool and adds it if it's the first use.
+    fn string_index(&mut self, value: &str, span: Span) -> Result<u16, SyntaxError> {
+        if let Some(&index) = self.string_indices.get(value) {
+            return Ok(index);
+        }
+
+        if self.strings.len() > usize::from(u16::MAX) {
+            return Err(SyntaxError::new(String::from("Too many string literals!"), span));
+        }
+
+        let index = self.strings.len() as u16;
+        self.strings.push(value.to_string());
+        self.string_indices.insert(value.to_string(), index);
+        Ok(index)
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: Span) {
+        self.lines.add(self.code.len(), span.start());
+        self.code.push(u8::from(instruction));
+    }
+
+    fn emit_byte(&mut self, instruction: Instruction, operand: u8, span: Span) {
+        self.emit(instruction, span);
+        self.code.push(operand);
+    }
+
+    fn emit_short(&mut self, instruction: Instruction, operand: usize, span: Span) {
+        self.emit(instruction, span);
+        self.code.push((operand >> 8) as u8);
+        self.code.push(operand as u8);
+    }
+
+    // Emits a jump with a placeholder address and returns the offset of the address to patch.
+    fn emit_jump(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.emit(instruction, span);
+        let offset = self.code.len();
+        self.code.append(&mut address_to_bytes(0));
+        offset
+    }
+
+    // Emits a jump whose address is resolved once the function is emitted.
+    fn emit_jump_to_block(&mut self, instruction: Instruction, block: usize, span: Span, layout: &mut Layout) {
+        let offset = self.emit_jump(instruction, span);
+        layout.jumps.push((offset, block));
+    }
+
+    // Lets the jump continue at the current end of the code.
+    fn patch_jump(&mut self, offset: usize) {
+        let target = address_to_bytes(self.code.len() as u32);
+        self.code[offset..offset + ADDRESS_SIZE].copy_from_slice(&target);
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Compiler {
+        Compiler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use backend::assembler::Assembler;
+    use backend::image::Capture;
+    use test_support::{compile, parse};
+
+    fn assembly(src: &str) -> String {
+        Assembler::new().disassemble(compile(src).unwrap().code().to_vec())
+    }
+
+    #[test]
+    fn compile_integer_arithmetic() {
+        assert_that!(&assembly("var x = 1 + 2\nprintln(-x)"), is(equal_to(
+            "ipush 1\nipush 2\niadd\nistore 0\niload 0\nineg\nprintln\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_generic_arithmetic() {
+        assert_that!(&assembly("var x\nx = 1.5 * x\nprint(x)"), is(equal_to(
+            "npush\nstore 0\nrpush 1.5\nload 0\nmul\nstore 0\nload 0\nprint\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_arrays() {
+        assert_that!(&assembly("var a = [1, 2]\na[1] = len(a)\na[0]"), is(equal_to(
+            "ipush 1\nipush 2\nnewarray 2\nstore 0\nload 0\nipush 1\nload 0\narraylength\nsetelem\nload 0\nipush 0\ngetelem\npop\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_compound_index_assignment() {
+        assert_that!(&assembly("var a = [1]\na[0] += 2"), is(equal_to(
+            "ipush 1\nnewarray 1\nstore 0\nload 0\nipush 0\ndup2\ngetelem\nipush 2\niadd\nsetelem\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_characters() {
+        assert_that!(&assembly("print('a')"), is(equal_to("cpush 97\nprint\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_comparisons() {
+        assert_that!(&assembly("1 < 2 == not true"), is(equal_to(
+            "ipush 1\nipush 2\nlt\nbpush 1\nnot\neq\npop\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_and() {
+        assert_that!(&assembly("true and false"), is(equal_to(
+            "bpush 1\njumpiffalse 21\nbpush 0\njumpiffalse 21\nbpush 1\njump 23\nbpush 0\npop\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_or() {
+        assert_that!(&assembly("true or false"), is(equal_to(
+            "bpush 1\njumpiftrue 21\nbpush 0\njumpiftrue 21\nbpush 0\njump 23\nbpush 1\npop\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_print_as_value() {
+        assert_that!(&assembly("var x = print(1)"), is(equal_to("ipush 1\nprint\nnpush\nstore 0\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_records_source_positions() {
+        let image = compile("var a = [1]\nprintln(a[1])").unwrap();
+
+        assert_that!(image.lines().position_at(0), is(equal_to(Some(::frontend::Position::new(1, 10)))));
+        // getelem is the last instruction before println and halt.
+        let getelem = image.code().len() - 3;
+        assert_that!(image.lines().position_at(getelem), is(equal_to(Some(::frontend::Position::new(2, 9)))));
+    }
+
+    #[test]
+    fn compile_semantic_error() {
+        assert_that!(compile("x + 1").unwrap_err().message(), is(equal_to("Undeclared variable 'x'!")));
+    }
+
+    #[test]
+    fn compile_strings() {
+        let image = compile("var s = \"a\" + \"b\"\ns = \"a\"\nvar x\nprintln(x + s)").unwrap();
+
+        assert_that!(&Assembler::new().disassemble(image.code().to_vec()), is(equal_to(
+            "spush 0\nspush 1\nconcat\nstore 0\nspush 0\nstore 0\nnpush\nstore 1\nload 1\nload 0\nadd\nprintln\nhalt\n")));
+        let expected = [String::from("a"), String::from("b")];
+        assert_that!(image.strings(), is(equal_to(&expected[..])));
+    }
+
+    #[test]
+    fn compile_interpolated_string() {
+        assert_that!(&assembly("var x = 1\nprint(\"x = ${x}!\")"), is(equal_to(
+            "ipush 1\nistore 0\nspush 0\niload 0\nspush 1\nbuildstring 3\nprint\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_records() {
+        assert_that!(&assembly("record P { x, y }\nvar p = P { y: 2, x: 1 }\np.x += p.y"), is(equal_to(concat!(
+            "newrec 0\ndup\nipush 2\nsetfield 1\ndup\nipush 1\nsetfield 0\nstore 0\n",
+            "load 0\ndup\ngetfield 0\nload 0\ngetfield 1\nadd\nsetfield 0\nhalt\n"))));
+    }
+
+    #[test]
+    fn compile_fields_of_unknown_records_by_name() {
+        let image = compile("var r\nr.x = r.y").unwrap();
+
+        assert_that!(&Assembler::new().disassemble(image.code().to_vec()), is(equal_to(
+            "npush\nstore 0\nload 0\nload 0\ngetnamedfield 0\nsetnamedfield 1\nhalt\n")));
+        let expected = [String::from("y"), String::from("x")];
+        assert_that!(image.strings(), is(equal_to(&expected[..])));
+    }
+
+    #[test]
+    fn compile_try_without_nested_function_bodies() {
+        let src = "try {\n    fun f() {}\n    throw 1\n} catch e {\n    println(e)\n}";
+        let image = compile(src).unwrap();
+
+        assert_that!(&assembly(src), is(equal_to(concat!(
+            "jump 7\nnpush\nret\nmkclosure 0\nstore 0\nipush 1\nthrow\n",
+            "jump 32\nstore 1\nload 1\nprintln\nhalt\n"))));
+        let expected = [ExceptionHandler::new(0, 5, 27), ExceptionHandler::new(7, 22, 27)];
+        assert_that!(image.handlers(), is(equal_to(&expected[..])));
+    }
+
+    #[test]
+    fn compile_match_tests_patterns() {
+        assert_that!(&assembly("var x\nvar y = match x { \"a\" => 1, n => n }"), is(equal_to(concat!(
+            "npush\nstore 0\nload 0\n",
+            "dup\nspush 0\neq\njumpiffalse 30\npop\nipush 1\njump 42\n",
+            "dup\nstore 1\npop\nload 1\njump 42\nnomatch\nstore 2\nhalt\n"))));
+    }
+
+    #[test]
+    fn compile_match_of_records() {
+        assert_that!(&assembly("record P { x, y }\nvar p\nmatch p { P { y: 1 } => 2 }"), is(equal_to(concat!(
+            "npush\nstore 0\nload 0\n",
+            "dup\nisrec 0\njumpiffalse 47\ndup\ngetfield 1\nipush 1\neq\njumpiffalse 47\npop\nipush 2\njump 48\n",
+            "nomatch\npop\nhalt\n"))));
+    }
+
+    #[test]
+    fn compile_match_of_dense_integers_to_table_switch() {
+        let image = compile("var x = 2\nmatch x { 1 => 10, 3 => 30, 4 => 40, _ => 0 }").unwrap();
+
+        assert_that!(&Assembler::new().disassemble(image.code().to_vec()), is(equal_to(concat!(
+            "ipush 2\nistore 0\niload 0\ndup\ntableswitch 0\n",
+            "pop\nipush 10\njump 72\npop\nipush 30\njump 72\npop\nipush 40\njump 72\n",
+            "pop\nipush 0\npop\nhalt\n"))));
+        let expected = [JumpTable::new(1, vec![17, 62, 32, 47], 62)];
+        assert_that!(image.jump_tables(), is(equal_to(&expected[..])));
+        assert_that!(compile("match 1 { 1 => 1, 2 => 2, 10 => 3 }").unwrap().jump_tables().is_empty(), is(true));
+        assert_that!(compile("match 1 { 1 => 1, 2 => 2 }").unwrap().jump_tables().is_empty(), is(true));
+    }
+
+    #[test]
+    fn compile_optimized() {
+        let statements = parse("const A = 1 + 2\nif A > 2 { println(A * 2) }").unwrap();
+        let code = Compiler::with_level(Level::O1).compile(&statements).unwrap().code().to_vec();
+
+        assert_that!(&Assembler::new().disassemble(code), is(equal_to("ipush 3\nistore 0\nipush 6\nprintln\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_optimized_jumps() {
+        let statements = parse("var b = true\nwhile b {\n  if b { continue } else { break }\n}").unwrap();
+        let code = Compiler::with_level(Level::O1).compile(&statements).unwrap().code().to_vec();
+
+        assert_that!(&Assembler::new().disassemble(code), is(equal_to(
+            "bpush 1\nstore 0\nload 0\njumpiffalse 23\nload 0\njumpiffalse 23\njump 4\nhalt\n")));
+    }
+
+    #[test]
+    fn compile_if_and_while() {
+        assert_that!(&assembly("var b = true\nwhile b {\n  if b { continue } else { break }\n}"), is(equal_to(concat!(
+            "bpush 1\nstore 0\nload 0\njumpiffalse 38\nload 0\njumpiffalse 28\njump 4\njump 33\njump 38\njump 4\n",
+            "halt\n"))));
+    }
+
+    #[test]
+    fn compile_for_range() {
+        assert_that!(&assembly("for i in 0..3 {\n  if i == 1 { continue }\n  println(i)\n}"), is(equal_to(concat!(
+            "ipush 0\nistore 0\nipush 3\nistore 1\n",
+            "iload 0\niload 1\nlt\njumpiffalse 80\niload 0\nistore 2\n",
+            "iload 2\nipush 1\neq\njumpiffalse 58\njump 61\niload 2\nprintln\n",
+            "iload 0\nipush 1\niadd\nistore 0\njump 22\nhalt\n"))));
+    }
+
+    #[test]
+    fn compile_nested_try() {
+        let image = compile("try {\n    try { throw 1 } catch a {}\n} catch b {}").unwrap();
+
+        let expected = [ExceptionHandler::new(0, 10, 15), ExceptionHandler::new(0, 17, 22)];
+        assert_that!(image.handlers(), is(equal_to(&expected[..])));
+    }
+
+    #[test]
+    fn compile_closures() {
+        let src = "var a = 1\nfun f(x) {\n    return fun() { return a + x }\n}\nf(2)()";
+        let image = compile(src).unwrap();
+
+        assert_that!(&assembly(src), is(equal_to(concat!(
+            "newupvalue 0\nipush 1\nstoreupvalue 0\n",
+            "jump 43\nload 0\nnewupvalue 0\nstoreupvalue 0\n",
+            "jump 37\ngetupvalue 0\ngetupvalue 1\nadd\nret\nnpush\nret\nmkclosure 0\n",
+            "ret\nnpush\nret\nmkclosure 1\nstore 1\n",
+            "load 1\nipush 2\ncallindirect 1\ncallindirect 0\npop\nhalt\n"))));
+        let names: Vec<_> = image.functions().iter().map(|function| function.name().to_string()).collect();
+        assert_that!(names, is(equal_to(vec![String::new(), String::from("f")])));
+        let expected = [Capture::Upvalue(0), Capture::Local(0)];
+        assert_that!(image.functions()[0].captures(), is(equal_to(&expected[..])));
+    }
+}
@@ -0,0 +1,451 @@
+use std::rc::Rc;
+
+use backend::image::{Function, RecordType};
+use backend::value::{self, Value};
+use intermediate::ast::BinaryOperator;
+
+/// An object which lives in the heap.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    /// Elements of an array.
+    Array(Vec<Value>),
+    /// Content of a string.
+    String(String),
+    /// Type and field values of a record.
+    Record(Rc<RecordType>, Vec<Value>),
+    /// Function and upvalue references of a closure.
+    Closure(Rc<Function>, Vec<Value>),
+    /// Value of a variable captured by closures.
+    Upvalue(Value),
+}
+
+/// Memory for objects which are referenced by values.
+///
+/// There is no garbage collection: Objects live until the heap is dropped.
+#[derive(Default)]
+pub struct Heap {
+    objects: Vec<Object>,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap { objects: Vec::new() }
+    }
+
+    /// Allocates a new array with the given elements and returns the reference to it.
+    pub fn new_array(&mut self, elements: Vec<Value>) -> Value {
+        self.objects.push(Object::Array(elements));
+        Value::Array(self.objects.len() - 1)
+    }
+
+    /// Get the elements of the referenced array.
+    ///
+    /// Returns an error message if the value is no array.
+    pub fn array(&self, value: Value) -> Result<&Vec<Value>, String> {
+        match value {
+            Value::Array(reference) => match self.objects[reference] {
+                Object::Array(ref elements) => Ok(elements),
+                _ => unreachable!("Array value references no array!"),
+            },
+            _ => Err(format!("Expected Array but got {}!", value.type_name())),
+        }
+    }
+
+    fn array_mut(&mut self, value: Value) -> Result<&mut Vec<Value>, String> {
+        match value {
+            Value::Array(reference) => match self.objects[reference] {
+                Object::Array(ref mut elements) => Ok(elements),
+                _ => unreachable!("Array value references no array!"),
+            },
+            _ => Err(format!("Expected Array but got {}!", value.type_name())),
+        }
+    }
+
+    /// Allocates a new string and returns the reference to it.
+    pub fn new_string(&mut self, content: String) -> Value {
+        self.objects.push(Object::String(content));
+        Value::String(self.objects.len() - 1)
+    }
+
+    /// Get the content of the referenced string.
+    ///
+    /// Returns an error message if the value is no string.
+    pub fn string(&self, value: Value) -> Result<&str, String> {
+        match value {
+            Value::String(reference) => match self.objects[reference] {
+                Object::String(ref content) => Ok(content),
+                _ => unreachable!("String value references no string!"),
+            },
+            _ => Err(format!("Expected String but got {}!", value.type_name())),
+        }
+    }
+
+    /// Allocates a new string which joins the contents of the two referenced strings.
+    ///
+    /// Returns an error message if one of the values is no string.
+    pub fn concat(&mut self, left: Value, right: Value) -> Result<Value, String> {
+        let content = String::from(self.string(left)?) + self.string(right)?;
+        Ok(self.new_string(content))
+    }
+
+    /// Applies a comparison operator to two values.
+    ///
+    /// Unlike `value::compare` strings are compared lexicographically by their content.
+    pub fn compare(&self, operator: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+        match (left, right) {
+            (Value::String(_), Value::String(_)) => {
+                let ordering = self.string(left)?.partial_cmp(self.string(right)?);
+                value::apply_ordering(operator, ordering)
+            },
+            _ => value::compare(operator, left, right),
+        }
+    }
+
+    /// Allocates a new record of the given type whose fields are nil and returns the reference to it.
+    pub fn new_record(&mut self, record_type: Rc<RecordType>) -> Value {
+        let fields = vec![Value::Nil; record_type.fields().len()];
+        self.objects.push(Object::Record(record_type, fields));
+        Value::Record(self.objects.len() - 1)
+    }
+
+    /// Get the type of the referenced record.
+    ///
+    /// Returns an error message if the value is no record.
+    pub fn record_type(&self, value: Value) -> Result<&RecordType, String> {
+        match value {
+            Value::Record(reference) => match self.objects[reference] {
+                Object::Record(ref record_type, _) => Ok(record_type),
+                _ => unreachable!("Record value references no record!"),
+            },
+            _ => Err(format!("Expected Record but got {}!", value.type_name())),
+        }
+    }
+
+    /// Get the index of the field with the given name of the referenced record.
+    ///
+    /// Returns an error message if the value is no record or has no such field.
+    pub fn field_index(&self, record: Value, name: &str) -> Result<usize, String> {
+        let record_type = self.record_type(record)?;
+        record_type.field_index(name)
+            .ok_or_else(|| format!("Record '{}' has no field '{}'!", record_type.name(), name))
+    }
+
+    /// Get the value of the field with the given index of the referenced record.
+    ///
+    /// Returns an error message if the value is no record or the index is out of bounds.
+    pub fn field(&self, record: Value, index: usize) -> Result<Value, String> {
+        match record {
+            Value::Record(reference) => match self.objects[reference] {
+                Object::Record(_, ref fields) => fields.get(index).cloned().ok_or_else(|| no_field(index)),
+                _ => unreachable!("Record value references no record!"),
+            },
+            _ => Err(format!("Expected Record but got {}!", record.type_name())),
+        }
+    }
+
+    /// Set the value of the field with the given index of the referenced record.
+    ///
+    /// Returns an error message if the value is no record or the index is out of bounds.
+    pub fn set_field(&mut self, record: Value, index: usize, value: Value) -> Result<(), String> {
+        match record {
+            Value::Record(reference) => match self.objects[reference] {
+                Object::Record(_, ref mut fields) => match fields.get_mut(index) {
+                    Some(field) => {
+                        *field = value;
+                        Ok(())
+                    },
+                    None => Err(no_field(index)),
+                },
+                _ => unreachable!("Record value references no record!"),
+            },
+            _ => Err(format!("Expected Record but got {}!", record.type_name())),
+        }
+    }
+
+    /// Allocates a new closure of the given function with the given upvalues and returns the
+    /// reference to it.
+    pub fn new_closure(&mut self, function: Rc<Function>, upvalues: Vec<Value>) -> Value {
+        self.objects.push(Object::Closure(function, upvalues));
+        Value::Closure(self.objects.len() - 1)
+    }
+
+    /// Get the function and the upvalue references of the referenced closure.
+    ///
+    /// Returns an error message if the value is no closure.
+    pub fn closure(&self, value: Value) -> Result<(&Rc<Function>, &[Value]), String> {
+        match value {
+            Value::Closure(reference) => match self.objects[reference] {
+                Object::Closure(ref function, ref upvalues) => Ok((function, upvalues)),
+                _ => unreachable!("Closure value references no closure!"),
+            },
+            _ => Err(format!("Expected Function but got {}!", value.type_name())),
+        }
+    }
+
+    /// Allocates a new upvalue holding the given value and returns the reference to it.
+    pub fn new_upvalue(&mut self, value: Value) -> Value {
+        self.objects.push(Object::Upvalue(value));
+        Value::Upvalue(self.objects.len() - 1)
+    }
+
+    /// Get the value held by the referenced upvalue.
+    ///
+    /// Returns an error message if the value is no upvalue.
+    pub fn upvalue(&self, upvalue: Value) -> Result<Value, String> {
+        match upvalue {
+            Value::Upvalue(reference) => match self.objects[reference] {
+                Object::Upvalue(value) => Ok(value),
+                _ => unreachable!("Upvalue value references no upvalue!"),
+            },
+            _ => Err(format!("Expected Upvalue but got {}!", upvalue.type_name())),
+        }
+    }
+
+    /// Set the value held by the referenced upvalue.
+    ///
+    /// Returns an error message if the value is no upvalue.
+    pub fn set_upvalue(&mut self, upvalue: Value, value: Value) -> Result<(), String> {
+        match upvalue {
+            Value::Upvalue(reference) => match self.objects[reference] {
+                Object::Upvalue(ref mut held) => {
+                    *held = value;
+                    Ok(())
+                },
+                _ => unreachable!("Upvalue value references no upvalue!"),
+            },
+            _ => Err(format!("Expected Upvalue but got {}!", upvalue.type_name())),
+        }
+    }
+
+    /// Get the element at the given index of the referenced array.
+    ///
+    /// Returns an error message if the value is no array or the index is out of bounds.
+    pub fn element(&self, array: Value, index: Value) -> Result<Value, String> {
+        let elements = self.array(array)?;
+        let index = check_index(index, elements.len())?;
+        Ok(elements[index])
+    }
+
+    /// Set the element at the given index of the referenced array.
+    ///
+    /// Returns an error message if the value is no array or the index is out of bounds.
+    pub fn set_element(&mut self, array: Value, index: Value, value: Value) -> Result<(), String> {
+        let elements = self.array_mut(array)?;
+        let index = check_index(index, elements.len())?;
+        elements[index] = value;
+        Ok(())
+    }
+
+    /// Renders the value as human readable string.
+    pub fn display(&self, value: Value) -> String {
+        let mut buffer = String::new();
+        self.display_into(value, &mut Vec::new(), &mut buffer);
+        buffer
+    }
+
+    // The visited arrays and records are tracked to render self containing ones as `[...]` and
+    // `Name {...}`.
+    fn display_into(&self, value: Value, visited: &mut Vec<usize>, buffer: &mut String) {
+        match value {
+            Value::Nil => buffer.push_str("nil"),
+            Value::Integer(value) => buffer.push_str(&value.to_string()),
+            Value::Real(value) => buffer.push_str(&format!("{:?}", value)),
+            Value::Character(value) => buffer.push(value),
+            Value::Boolean(value) => buffer.push_str(&value.to_string()),
+            Value::String(_) => buffer.push_str(self.string(value).unwrap()),
+            Value::Array(reference) => {
+                if visited.contains(&reference) {
+                    buffer.push_str("[...]");
+                    return;
+                }
+
+                visited.push(reference);
+                buffer.push('[');
+
+                let elements = self.array(value).unwrap();
+
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        buffer.push_str(", ");
+                    }
+
+                    self.display_into(*element, visited, buffer);
+                }
+
+                buffer.push(']');
+                visited.pop();
+            },
+            Value::Record(reference) => {
+                let (record_type, fields) = match self.objects[reference] {
+                    Object::Record(ref record_type, ref fields) => (record_type, fields),
+                    _ => unreachable!("Record value references no record!"),
+                };
+
+                buffer.push_str(record_type.name());
+
+                if visited.contains(&reference) {
+                    buffer.push_str(" {...}");
+                    return;
+                }
+
+                visited.push(reference);
+                buffer.push_str(" {");
+
+                for (index, (name, field)) in record_type.fields().iter().zip(fields).enumerate() {
+                    buffer.push_str(if index > 0 { ", " } else { " " });
+                    buffer.push_str(name);
+                    buffer.push_str(": ");
+                    self.display_into(*field, visited, buffer);
+                }
+
+                buffer.push_str(" }");
+                visited.pop();
+            },
+            Value::Closure(_) => {
+                let (function, _) = self.closure(value).unwrap();
+
+                if function.name().is_empty() {
+                    buffer.push_str("<fun>");
+                } else {
+                    buffer.push_str(&format!("<fun {}>", function.name()));
+                }
+            },
+            Value::Upvalue(_) => self.display_into(self.upvalue(value).unwrap(), visited, buffer),
+        }
+    }
+}
+
+fn no_field(index: usize) -> String {
+    format!("Record has no field with index {}!", index)
+}
+
+fn check_index(index: Value, length: usize) -> Result<usize, String> {
+    match index {
+        Value::Integer(index) if index >= 0 && (index as u64) < length as u64 => Ok(index as usize),
+        Value::Integer(index) => Err(format!("Array index {} out of bounds for length {}!", index, length)),
+        _ => Err(format!("Array index must be Integer but is {}!", index.type_name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    #[test]
+    fn array_element() {
+        let mut sut = Heap::new();
+        let array = sut.new_array(vec![Value::Integer(1), Value::Integer(2)]);
+
+        assert_that!(sut.element(array, Value::Integer(1)), is(equal_to(Ok(Value::Integer(2)))));
+        assert_that!(sut.set_element(array, Value::Integer(0), Value::Real(0.5)), is(equal_to(Ok(()))));
+        assert_that!(sut.element(array, Value::Integer(0)), is(equal_to(Ok(Value::Real(0.5)))));
+    }
+
+    #[test]
+    fn array_element_out_of_bounds() {
+        let mut sut = Heap::new();
+        let array = sut.new_array(vec![Value::Integer(1)]);
+
+        assert_that!(sut.element(array, Value::Integer(1)),
+            is(equal_to(Err(String::from("Array index 1 out of bounds for length 1!")))));
+        assert_that!(sut.set_element(array, Value::Integer(-1), Value::Nil),
+            is(equal_to(Err(String::from("Array index -1 out of bounds for length 1!")))));
+        assert_that!(sut.element(array, Value::Real(0.0)),
+            is(equal_to(Err(String::from("Array index must be Integer but is Real!")))));
+        assert_that!(sut.element(Value::Integer(1), Value::Integer(0)),
+            is(equal_to(Err(String::from("Expected Array but got Integer!")))));
+    }
+
+    #[test]
+    fn display() {
+        let mut sut = Heap::new();
+        let inner = sut.new_array(vec![Value::Character('a'), Value::Nil, Value::Boolean(true)]);
+        let outer = sut.new_array(vec![Value::Integer(1), Value::Real(2.0), inner]);
+
+        assert_that!(&sut.display(outer), is(equal_to("[1, 2.0, [a, nil, true]]")));
+        assert_that!(&sut.display(Value::Real(0.1)), is(equal_to("0.1")));
+    }
+
+    #[test]
+    fn display_self_containing_array() {
+        let mut sut = Heap::new();
+        let array = sut.new_array(vec![Value::Integer(1)]);
+        sut.set_element(array, Value::Integer(0), array).unwrap();
+
+        assert_that!(&sut.display(array), is(equal_to("[[...]]")));
+    }
+
+    #[test]
+    fn string_operations() {
+        let mut sut = Heap::new();
+        let left = sut.new_string(String::from("ab"));
+        let right = sut.new_string(String::from("c"));
+
+        let joined = sut.concat(left, right).unwrap();
+
+        assert_that!(sut.string(joined), is(equal_to(Ok("abc"))));
+        assert_that!(sut.compare(BinaryOperator::LessThan, joined, right), is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(sut.compare(BinaryOperator::Equal, joined, joined), is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(sut.concat(left, Value::Integer(1)),
+            is(equal_to(Err(String::from("Expected String but got Integer!")))));
+        let array = sut.new_array(vec![joined]);
+        assert_that!(&sut.display(array), is(equal_to("[abc]")));
+    }
+
+    #[test]
+    fn compare_strings_by_content() {
+        let mut sut = Heap::new();
+        let first = sut.new_string(String::from("a"));
+        let second = sut.new_string(String::from("a"));
+
+        assert_that!(sut.compare(BinaryOperator::Equal, first, second), is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(sut.compare(BinaryOperator::GreaterThanEqual, first, second),
+            is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(sut.compare(BinaryOperator::NotEqual, first, Value::Character('a')),
+            is(equal_to(Ok(Value::Boolean(true)))));
+    }
+
+    #[test]
+    fn record_fields() {
+        let mut sut = Heap::new();
+        let record_type = Rc::new(RecordType::new(String::from("P"), vec![String::from("x"), String::from("y")]));
+        let record = sut.new_record(record_type);
+
+        assert_that!(sut.set_field(record, 1, Value::Integer(2)), is(equal_to(Ok(()))));
+        assert_that!(sut.field(record, 0), is(equal_to(Ok(Value::Nil))));
+        assert_that!(sut.field(record, 1), is(equal_to(Ok(Value::Integer(2)))));
+        assert_that!(sut.field_index(record, "y"), is(equal_to(Ok(1))));
+        assert_that!(sut.field_index(record, "z"), is(equal_to(Err(String::from("Record 'P' has no field 'z'!")))));
+        assert_that!(sut.field(record, 2), is(equal_to(Err(String::from("Record has no field with index 2!")))));
+        assert_that!(sut.field(Value::Nil, 0), is(equal_to(Err(String::from("Expected Record but got Nil!")))));
+        assert_that!(&sut.display(record), is(equal_to("P { x: nil, y: 2 }")));
+    }
+
+    #[test]
+    fn closures_and_upvalues() {
+        let mut sut = Heap::new();
+        let upvalue = sut.new_upvalue(Value::Nil);
+        sut.set_upvalue(upvalue, Value::Integer(42)).unwrap();
+        let function = Rc::new(Function::new(String::from("f"), 0, 1, vec![]));
+        let closure = sut.new_closure(function, vec![upvalue]);
+        let anonymous = sut.new_closure(Rc::new(Function::new(String::new(), 0, 0, vec![])), vec![]);
+
+        assert_that!(sut.upvalue(upvalue), is(equal_to(Ok(Value::Integer(42)))));
+        let expected = [upvalue];
+        assert_that!(sut.closure(closure).unwrap().1, is(equal_to(&expected[..])));
+        assert_that!(sut.closure(upvalue).unwrap_err(), is(equal_to(String::from("Expected Function but got Upvalue!"))));
+        assert_that!(sut.upvalue(closure).unwrap_err(), is(equal_to(String::from("Expected Upvalue but got Function!"))));
+        assert_that!(&sut.display(closure), is(equal_to("<fun f>")));
+        assert_that!(&sut.display(anonymous), is(equal_to("<fun>")));
+    }
+
+    #[test]
+    fn display_self_containing_record() {
+        let mut sut = Heap::new();
+        let record = sut.new_record(Rc::new(RecordType::new(String::from("Node"), vec![String::from("next")])));
+        sut.set_field(record, 0, record).unwrap();
+
+        assert_that!(&sut.display(record), is(equal_to("Node { next: Node {...} }")));
+    }
+}
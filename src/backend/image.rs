@@ -0,0 +1,684 @@
+use std::io::{Cursor, Read};
+use byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
+
+use frontend::Position;
+
+/// Leading bytes of a serialized image.
+///
+/// The first byte is no valid opcode, so an image is distinguishable from raw byte code as
+/// generated by the assembler.
+const MAGIC: [u8; 4] = [0xff, b'M', b'V', b'M'];
+/// Version of the image format.
+const VERSION: u8 = 1;
+/// Section with the byte code.
+const CODE_SECTION: u8 = 0x01;
+/// Section with the debug line table.
+const LINES_SECTION: u8 = 0x02;
+/// Section with the constant string pool.
+const STRINGS_SECTION: u8 = 0x03;
+/// Section with the declared record types.
+const RECORDS_SECTION: u8 = 0x04;
+/// Section with the compiled functions.
+const FUNCTIONS_SECTION: u8 = 0x05;
+/// Section with the exception table.
+const HANDLERS_SECTION: u8 = 0x06;
+/// Section with the jump tables of `tableswitch`.
+const JUMP_TABLES_SECTION: u8 = 0x07;
+
+/// Executable program for the virtual machine.
+///
+/// The image holds the byte code together with the constant strings, record types, functions and
+/// jump tables it refers to by index, the exception table and meta data. It is serialized as the magic bytes,
+/// the version and a sequence of sections. Each section starts with one byte tag, followed by
+/// four bytes length of its content and the content itself:
+///
+/// ```text
+/// +-------+---------+-----+--------+---------+-----+--------+---------+-----
+/// | magic | version | tag | length | content | tag | length | content | ...
+/// +-------+---------+-----+--------+---------+-----+--------+---------+-----
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    code: Vec<u8>,
+    strings: Vec<String>,
+    records: Vec<RecordType>,
+    functions: Vec<Function>,
+    handlers: Vec<ExceptionHandler>,
+    jump_tables: Vec<JumpTable>,
+    lines: LineTable,
+}
+
+impl Image {
+    /// Creates a new image.
+    pub fn new(code: Vec<u8>, strings: Vec<String>, records: Vec<RecordType>, functions: Vec<Function>,
+               handlers: Vec<ExceptionHandler>, jump_tables: Vec<JumpTable>, lines: LineTable) -> Image {
+        Image { code, strings, records, functions, handlers, jump_tables, lines }
+    }
+
+    /// Creates an image from raw byte code without any meta data.
+    pub fn from_code(code: Vec<u8>) -> Image {
+        Image::new(code, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), LineTable::new())
+    }
+
+    /// Get the byte code.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Get the constant strings referenced by `spush`.
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    /// Get the record types referenced by `newrec`.
+    pub fn records(&self) -> &[RecordType] {
+        &self.records
+    }
+
+    /// Get the functions referenced by `mkclosure`.
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Get the exception handlers, inner handlers before the outer ones.
+    pub fn handlers(&self) -> &[ExceptionHandler] {
+        &self.handlers
+    }
+
+    /// Get the jump tables.
+    pub fn jump_tables(&self) -> &[JumpTable] {
+        &self.jump_tables
+    }
+
+    /// Get the table to map byte code to source positions.
+    pub fn lines(&self) -> &LineTable {
+        &self.lines
+    }
+
+    /// Serializes the image.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = MAGIC.to_vec();
+        buffer.push(VERSION);
+        write_section(&mut buffer, CODE_SECTION, &self.code);
+        write_section(&mut buffer, STRINGS_SECTION, &strings_to_bytes(&self.strings));
+        write_section(&mut buffer, RECORDS_SECTION, &records_to_bytes(&self.records));
+        write_section(&mut buffer, FUNCTIONS_SECTION, &functions_to_bytes(&self.functions));
+        write_section(&mut buffer, HANDLERS_SECTION, &handlers_to_bytes(&self.handlers));
+        write_section(&mut buffer, JUMP_TABLES_SECTION, &jump_tables_to_bytes(&self.jump_tables));
+        write_section(&mut buffer, LINES_SECTION, &self.lines.to_bytes());
+        buffer
+    }
+
+    /// Deserializes an image.
+    ///
+    /// Bytes without the leading magic bytes are treated as raw byte code. Returns an error
+    /// if the image is malformed.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Image, &'static str> {
+        if !bytes.starts_with(&MAGIC) {
+            return Ok(Image::from_code(bytes));
+        }
+
+        let mut reader = Cursor::new(&bytes[MAGIC.len()..]);
+
+        if VERSION != reader.read_u8().map_err(|_| "Truncated image!")? {
+            return Err("Unsupported image version!");
+        }
+
+        let mut image = Image::from_code(Vec::new());
+
+        while (reader.position() as usize) < reader.get_ref().len() {
+            let (tag, content) = read_section(&mut reader)?;
+
+            match tag {
+                CODE_SECTION => image.code = content,
+                STRINGS_SECTION => image.strings = strings_from_bytes(&content)?,
+                RECORDS_SECTION => image.records = records_from_bytes(&content)?,
+                FUNCTIONS_SECTION => image.functions = functions_from_bytes(&content)?,
+                HANDLERS_SECTION => image.handlers = handlers_from_bytes(&content)?,
+                JUMP_TABLES_SECTION => image.jump_tables = jump_tables_from_bytes(&content)?,
+                LINES_SECTION => image.lines = LineTable::from_bytes(&content)?,
+                _ => return Err("Unknown image section!"),
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+fn write_section(buffer: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    buffer.push(tag);
+    buffer.write_u32::<BigEndian>(content.len() as u32).unwrap();
+    buffer.extend_from_slice(content);
+}
+
+fn read_section(reader: &mut Cursor<&[u8]>) -> Result<(u8, Vec<u8>), &'static str> {
+    let tag = reader.read_u8().map_err(|_| "Truncated image!")?;
+    let length = reader.read_u32::<BigEndian>().map_err(|_| "Truncated image!")?;
+    let mut content = vec![0; length as usize];
+    reader.read_exact(&mut content).map_err(|_| "Truncated image!")?;
+    Ok((tag, content))
+}
+
+// Each string is written as four bytes length followed by its UTF-8 bytes.
+fn write_string(buffer: &mut Vec<u8>, string: &str) {
+    buffer.write_u32::<BigEndian>(string.len() as u32).unwrap();
+    buffer.extend_from_slice(string.as_bytes());
+}
+
+fn read_string(reader: &mut Cursor<&[u8]>, truncated: &'static str) -> Result<String, &'static str> {
+    let length = reader.read_u32::<BigEndian>().map_err(|_| truncated)?;
+    let mut content = vec![0; length as usize];
+    reader.read_exact(&mut content).map_err(|_| truncated)?;
+    String::from_utf8(content).map_err(|_| "Invalid UTF-8 in image!")
+}
+
+fn strings_to_bytes(strings: &[String]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for string in strings {
+        write_string(&mut buffer, string);
+    }
+
+    buffer
+}
+
+fn strings_from_bytes(bytes: &[u8]) -> Result<Vec<String>, &'static str> {
+    let mut reader = Cursor::new(bytes);
+    let mut strings = Vec::new();
+
+    while (reader.position() as usize) < bytes.len() {
+        strings.push(read_string(&mut reader, "Truncated string pool!")?);
+    }
+
+    Ok(strings)
+}
+
+// Each record type is written as its name, two bytes number of fields and the field names.
+fn records_to_bytes(records: &[RecordType]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for record in records {
+        write_string(&mut buffer, record.name());
+        buffer.write_u16::<BigEndian>(record.fields().len() as u16).unwrap();
+
+        for field in record.fields() {
+            write_string(&mut buffer, field);
+        }
+    }
+
+    buffer
+}
+
+fn records_from_bytes(bytes: &[u8]) -> Result<Vec<RecordType>, &'static str> {
+    let mut reader = Cursor::new(bytes);
+    let mut records = Vec::new();
+
+    while (reader.position() as usize) < bytes.len() {
+        let name = read_string(&mut reader, "Truncated record types!")?;
+        let count = reader.read_u16::<BigEndian>().map_err(|_| "Truncated record types!")?;
+        let mut fields = Vec::with_capacity(usize::from(count));
+
+        for _ in 0..count {
+            fields.push(read_string(&mut reader, "Truncated record types!")?);
+        }
+
+        records.push(RecordType::new(name, fields));
+    }
+
+    Ok(records)
+}
+
+// Each function is written as its name, four bytes address, one byte arity, one byte number of
+// captures and each capture as one byte kind (0 = local, 1 = upvalue) and one byte index.
+fn functions_to_bytes(functions: &[Function]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for function in functions {
+        write_string(&mut buffer, function.name());
+        buffer.write_u32::<BigEndian>(function.address() as u32).unwrap();
+        buffer.push(function.arity());
+        buffer.push(function.captures().len() as u8);
+
+        for capture in function.captures() {
+            match *capture {
+                Capture::Local(index) => buffer.extend_from_slice(&[0, index]),
+                Capture::Upvalue(index) => buffer.extend_from_slice(&[1, index]),
+            }
+        }
+    }
+
+    buffer
+}
+
+fn functions_from_bytes(bytes: &[u8]) -> Result<Vec<Function>, &'static str> {
+    let mut reader = Cursor::new(bytes);
+    let mut functions = Vec::new();
+
+    while (reader.position() as usize) < bytes.len() {
+        let name = read_string(&mut reader, "Truncated functions!")?;
+        let address = reader.read_u32::<BigEndian>().map_err(|_| "Truncated functions!")?;
+        let arity = reader.read_u8().map_err(|_| "Truncated functions!")?;
+        let count = reader.read_u8().map_err(|_| "Truncated functions!")?;
+        let mut captures = Vec::with_capacity(usize::from(count));
+
+        for _ in 0..count {
+            let kind = reader.read_u8().map_err(|_| "Truncated functions!")?;
+            let index = reader.read_u8().map_err(|_| "Truncated functions!")?;
+            captures.push(match kind {
+                0 => Capture::Local(index),
+                1 => Capture::Upvalue(index),
+                _ => return Err("Unknown capture kind!"),
+            });
+        }
+
+        functions.push(Function::new(name, address as usize, arity, captures));
+    }
+
+    Ok(functions)
+}
+
+// Each handler is written as four bytes start, end and handler address.
+fn handlers_to_bytes(handlers: &[ExceptionHandler]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for handler in handlers {
+        buffer.write_u32::<BigEndian>(handler.start() as u32).unwrap();
+        buffer.write_u32::<BigEndian>(handler.end() as u32).unwrap();
+        buffer.write_u32::<BigEndian>(handler.address() as u32).unwrap();
+    }
+
+    buffer
+}
+
+fn handlers_from_bytes(bytes: &[u8]) -> Result<Vec<ExceptionHandler>, &'static str> {
+    let mut reader = Cursor::new(bytes);
+    let mut handlers = Vec::new();
+
+    while (reader.position() as usize) < bytes.len() {
+        let start = reader.read_u32::<BigEndian>().map_err(|_| "Truncated exception table!")?;
+        let end = reader.read_u32::<BigEndian>().map_err(|_| "Truncated exception table!")?;
+        let address = reader.read_u32::<BigEndian>().map_err(|_| "Truncated exception table!")?;
+        handlers.push(ExceptionHandler::new(start as usize, end as usize, address as usize));
+    }
+
+    Ok(handlers)
+}
+
+// Each table is written as eight bytes lowest value, four bytes default address, two bytes count
+// and four bytes per target address.
+fn jump_tables_to_bytes(tables: &[JumpTable]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for table in tables {
+        buffer.write_i64::<BigEndian>(table.low()).unwrap();
+        buffer.write_u32::<BigEndian>(table.default() as u32).unwrap();
+        buffer.write_u16::<BigEndian>(table.targets().len() as u16).unwrap();
+
+        for &target in table.targets() {
+            buffer.write_u32::<BigEndian>(target as u32).unwrap();
+        }
+    }
+
+    buffer
+}
+
+fn jump_tables_from_bytes(bytes: &[u8]) -> Result<Vec<JumpTable>, &'static str> {
+    let mut reader = Cursor::new(bytes);
+    let mut tables = Vec::new();
+
+    while (reader.position() as usize) < bytes.len() {
+        let low = reader.read_i64::<BigEndian>().map_err(|_| "Truncated jump tables!")?;
+        let default = reader.read_u32::<BigEndian>().map_err(|_| "Truncated jump tables!")?;
+        let count = reader.read_u16::<BigEndian>().map_err(|_| "Truncated jump tables!")?;
+        let mut targets = Vec::with_capacity(usize::from(count));
+
+        for _ in 0..count {
+            targets.push(reader.read_u32::<BigEndian>().map_err(|_| "Truncated jump tables!")? as usize);
+        }
+
+        tables.push(JumpTable::new(low, targets, default as usize));
+    }
+
+    Ok(tables)
+}
+
+/// Declaration of a record with the names of its fields.
+///
+/// The fields of a record value are addressed by their index in this declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordType {
+    name: String,
+    fields: Vec<String>,
+}
+
+impl RecordType {
+    pub fn new(name: String, fields: Vec<String>) -> RecordType {
+        RecordType { name, fields }
+    }
+
+    /// Get the name of the record.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the field names in declaration order.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Get the index of the field with the given name.
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field == name)
+    }
+}
+
+/// A compiled function whose closures are created by `mkclosure`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    name: String,
+    address: usize,
+    arity: u8,
+    captures: Vec<Capture>,
+}
+
+impl Function {
+    pub fn new(name: String, address: usize, arity: u8, captures: Vec<Capture>) -> Function {
+        Function { name, address, arity, captures }
+    }
+
+    /// Get the name of the function, which is empty for anonymous functions.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the address of the first instruction of the function body.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Get the number of parameters.
+    pub fn arity(&self) -> u8 {
+        self.arity
+    }
+
+    /// Get where the upvalues of a new closure come from, in the order of their indices.
+    pub fn captures(&self) -> &[Capture] {
+        &self.captures
+    }
+
+    /// Checks that the function is called with as many arguments as it has parameters.
+    ///
+    /// Returns an error message otherwise.
+    pub fn check_arity(&self, count: usize) -> Result<(), String> {
+        if usize::from(self.arity) == count {
+            return Ok(());
+        }
+
+        let name = if self.name.is_empty() { String::new() } else { format!(" '{}'", self.name) };
+        let plural = if 1 == self.arity { "" } else { "s" };
+        Err(format!("Function{} expects {} argument{} but got {}!", name, self.arity, plural, count))
+    }
+}
+
+/// Source of an upvalue captured by a new closure.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Capture {
+    /// The upvalue held by the local variable with the given index of the creating frame.
+    Local(u8),
+    /// The upvalue with the given index of the closure executing in the creating frame.
+    Upvalue(u8),
+}
+
+/// Entry of the exception table: Exceptions thrown by the instructions in the range from start
+/// (inclusive) to end (exclusive) of one function continue at the handler address.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExceptionHandler {
+    start: usize,
+    end: usize,
+    address: usize,
+}
+
+impl ExceptionHandler {
+    pub fn new(start: usize, end: usize, address: usize) -> ExceptionHandler {
+        ExceptionHandler { start, end, address }
+    }
+
+    /// Get the address of the first guarded instruction.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Get the address after the last guarded instruction.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Get the address of the handler.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Determines if the instruction at the given address is guarded by the handler.
+    pub fn covers(&self, address: usize) -> bool {
+        self.start <= address && address < self.end
+    }
+}
+
+/// Jump targets of a `tableswitch` for consecutive integers starting at the lowest value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpTable {
+    low: i64,
+    targets: Vec<usize>,
+    default: usize,
+}
+
+impl JumpTable {
+    pub fn new(low: i64, targets: Vec<usize>, default: usize) -> JumpTable {
+        JumpTable { low, targets, default }
+    }
+
+    /// Get the value of the first target.
+    pub fn low(&self) -> i64 {
+        self.low
+    }
+
+    /// Get the addresses for the consecutive values.
+    pub fn targets(&self) -> &[usize] {
+        &self.targets
+    }
+
+    /// Get the address for all values without target.
+    pub fn default(&self) -> usize {
+        self.default
+    }
+
+    /// Get the address to continue at for the given value.
+    pub fn target(&self, value: i64) -> usize {
+        value.checked_sub(self.low)
+            .filter(|&offset| 0 <= offset && offset < self.targets.len() as i64)
+            .map_or(self.default, |offset| self.targets[offset as usize])
+    }
+}
+
+/// Maps byte code offsets to the source position the code was generated from.
+///
+/// An entry applies to all instructions from its offset up to the offset of the next entry.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LineTable {
+    entries: Vec<(usize, Position)>,
+}
+
+impl LineTable {
+    pub fn new() -> LineTable {
+        LineTable { entries: Vec::new() }
+    }
+
+    /// Adds the source position of the code starting at the given offset.
+    ///
+    /// Offsets must be added in ascending order. Nothing is added if the position does not change.
+    pub fn add(&mut self, offset: usize, position: Position) {
+        if let Some(&(last_offset, last_position)) = self.entries.last() {
+            if last_position == position {
+                return;
+            }
+
+            if last_offset == offset {
+                self.entries.pop();
+            }
+        }
+
+        self.entries.push((offset, position));
+    }
+
+    /// Get the source position of the code at the given offset.
+    pub fn position_at(&self, offset: usize) -> Option<Position> {
+        self.entries.iter()
+            .take_while(|&&(start, _)| start <= offset)
+            .last()
+            .map(|&(_, position)| position)
+    }
+
+    /// Get all entries as pairs of offset and position.
+    pub fn entries(&self) -> &[(usize, Position)] {
+        &self.entries
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        for &(offset, position) in &self.entries {
+            buffer.write_u32::<BigEndian>(offset as u32).unwrap();
+            buffer.write_u32::<BigEndian>(position.line() as u32).unwrap();
+            buffer.write_u32::<BigEndian>(position.column() as u32).unwrap();
+        }
+
+        buffer
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<LineTable, &'static str> {
+        let mut reader = Cursor::new(bytes);
+        let mut table = LineTable::new();
+
+        while (reader.position() as usize) < bytes.len() {
+            let offset = reader.read_u32::<BigEndian>().map_err(|_| "Truncated line table!")?;
+            let line = reader.read_u32::<BigEndian>().map_err(|_| "Truncated line table!")?;
+            let column = reader.read_u32::<BigEndian>().map_err(|_| "Truncated line table!")?;
+            table.entries.push((offset as usize, Position::new(u64::from(line), u64::from(column))));
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    fn lines() -> LineTable {
+        let mut lines = LineTable::new();
+        lines.add(0, Position::new(1, 1));
+        lines.add(9, Position::new(1, 1));
+        lines.add(10, Position::new(2, 5));
+        lines
+    }
+
+    #[test]
+    fn line_table_position_at() {
+        let sut = lines();
+
+        assert_that!(sut.entries().len(), is(equal_to(2)));
+        assert_that!(sut.position_at(0), is(equal_to(Some(Position::new(1, 1)))));
+        assert_that!(sut.position_at(9), is(equal_to(Some(Position::new(1, 1)))));
+        assert_that!(sut.position_at(10), is(equal_to(Some(Position::new(2, 5)))));
+        assert_that!(sut.position_at(42), is(equal_to(Some(Position::new(2, 5)))));
+        assert_that!(LineTable::new().position_at(0), is(equal_to(None)));
+    }
+
+    #[test]
+    fn line_table_replaces_entry_at_same_offset() {
+        let mut sut = LineTable::new();
+        sut.add(0, Position::new(1, 1));
+        sut.add(0, Position::new(1, 5));
+
+        let expected = [(0, Position::new(1, 5))];
+        assert_that!(sut.entries(), is(equal_to(&expected[..])));
+    }
+
+    #[test]
+    fn exception_handler_covers_range() {
+        let sut = ExceptionHandler::new(3, 7, 12);
+
+        assert_that!(sut.covers(2), is(false));
+        assert_that!(sut.covers(3), is(true));
+        assert_that!(sut.covers(6), is(true));
+        assert_that!(sut.covers(7), is(false));
+    }
+
+    #[test]
+    fn jump_table_target() {
+        let sut = JumpTable::new(-1, vec![10, 20, 30], 40);
+
+        assert_that!(sut.target(-2), is(equal_to(40)));
+        assert_that!(sut.target(-1), is(equal_to(10)));
+        assert_that!(sut.target(1), is(equal_to(30)));
+        assert_that!(sut.target(2), is(equal_to(40)));
+        assert_that!(JumpTable::new(i64::MIN, vec![10], 40).target(i64::MAX), is(equal_to(40)));
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let records = vec![RecordType::new(String::from("P"), vec![String::from("x"), String::from("y")])];
+        let functions = vec![
+            Function::new(String::from("f"), 3, 2, vec![Capture::Local(1), Capture::Upvalue(0)]),
+            Function::new(String::new(), 7, 0, vec![])];
+        let handlers = vec![ExceptionHandler::new(0, 2, 3)];
+        let jump_tables = vec![JumpTable::new(-2, vec![1, 2], 3), JumpTable::new(0, vec![], 0)];
+        let image = Image::new(
+            vec![0x02, 0x00, 0x0c], vec![String::from("äb"), String::new()], records, functions, handlers,
+            jump_tables, lines());
+
+        let bytes = image.to_bytes();
+
+        assert_that!(&bytes[0..5], is(equal_to(&[0xff, b'M', b'V', b'M', 0x01][..])));
+        assert_that!(Image::from_bytes(bytes), is(equal_to(Ok(image))));
+    }
+
+    #[test]
+    fn image_from_raw_code() {
+        assert_that!(Image::from_bytes(vec![0x05, 0x0b]), is(equal_to(Ok(Image::from_code(vec![0x05, 0x0b])))));
+    }
+
+    #[test]
+    fn image_from_malformed_bytes() {
+        assert_that!(Image::from_bytes(vec![0xff, b'M', b'V', b'M']), is(equal_to(Err("Truncated image!"))));
+        assert_that!(Image::from_bytes(vec![0xff, b'M', b'V', b'M', 0x02]), is(equal_to(Err("Unsupported image version!"))));
+        assert_that!(Image::from_bytes(vec![0xff, b'M', b'V', b'M', 0x01, 0x01, 0x00, 0x00, 0x00, 0x02, 0x0c]),
+            is(equal_to(Err("Truncated image!"))));
+        assert_that!(Image::from_bytes(vec![0xff, b'M', b'V', b'M', 0x01, 0x42, 0x00, 0x00, 0x00, 0x00]),
+            is(equal_to(Err("Unknown image section!"))));
+    }
+
+    #[test]
+    fn image_with_malformed_pools() {
+        assert_that!(Image::from_bytes(vec![0xff, b'M', b'V', b'M', 0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00]),
+            is(equal_to(Err("Truncated string pool!"))));
+        assert_that!(Image::from_bytes(vec![
+            0xff, b'M', b'V', b'M', 0x01, 0x03, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0xff]),
+            is(equal_to(Err("Invalid UTF-8 in image!"))));
+        assert_that!(Image::from_bytes(vec![
+            0xff, b'M', b'V', b'M', 0x01, 0x04, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x01, b'P', 0x00]),
+            is(equal_to(Err("Truncated record types!"))));
+        assert_that!(Image::from_bytes(vec![
+            0xff, b'M', b'V', b'M', 0x01, 0x05, 0x00, 0x00, 0x00, 0x0c,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x02, 0x00]),
+            is(equal_to(Err("Unknown capture kind!"))));
+        assert_that!(Image::from_bytes(vec![
+            0xff, b'M', b'V', b'M', 0x01, 0x06, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00]),
+            is(equal_to(Err("Truncated exception table!"))));
+        assert_that!(Image::from_bytes(vec![
+            0xff, b'M', b'V', b'M', 0x01, 0x07, 0x00, 0x00, 0x00, 0x0e,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]),
+            is(equal_to(Err("Truncated jump tables!"))));
+    }
+}
@@ -41,22 +41,80 @@ impl CodeMemory {
             Err("Index out of bounds!")
         }
     }
+
+    /// Fetches two bytes from the code memory beginning from the given index as unsigned integer.
+    ///
+    /// Returns an error result if the given index is beyond the number of available bytes.
+    pub fn fetch_short(&self, index: usize) -> Result<u16, &'static str> {
+        let high = self.fetch(index)?;
+        let low = self.fetch(index + 1)?;
+        Ok(u16::from(high) << 8 | u16::from(low))
+    }
+
+    /// Fetches an address from the code memory beginning from the given index.
+    ///
+    /// Returns an error result if the given index is beyond the number of available bytes.
+    pub fn fetch_address(&self, index: usize) -> Result<usize, &'static str> {
+        let end_index = index + ADDRESS_SIZE;
+
+        if end_index <= self.byte_code.len() {
+            bytes_to_address(&self.byte_code[index..end_index]).map(|address| address as usize)
+        } else {
+            Err("Index out of bounds!")
+        }
+    }
+
+    /// Get the number of bytes in the code memory.
+    pub fn len(&self) -> usize {
+        self.byte_code.len()
+    }
+
+    /// Determines if there is no code at all.
+    pub fn is_empty(&self) -> bool {
+        self.byte_code.is_empty()
+    }
 }
 
-pub struct Stack {
-    mem: Vec<u64>,
+pub struct Stack<T> {
+    mem: Vec<T>,
 }
 
-impl Stack {
-    pub fn new() -> Stack { Stack { mem: Vec::new() } }
+impl<T> Stack<T> {
+    pub fn new() -> Stack<T> { Stack { mem: Vec::new() } }
 
-    pub fn push(&mut self, value: u64) {
+    pub fn push(&mut self, value: T) {
         self.mem.push(value);
     }
 
-    pub fn pop(&mut self) -> u64 {
+    pub fn pop(&mut self) -> T {
         self.mem.pop().unwrap()
     }
+
+    /// Pops the top most value or returns `None` if the stack is empty.
+    pub fn try_pop(&mut self) -> Option<T> {
+        self.mem.pop()
+    }
+
+    /// Drops the values above the given number of values.
+    pub fn truncate(&mut self, len: usize) {
+        self.mem.truncate(len);
+    }
+
+    /// Get the number of values on the stack.
+    pub fn len(&self) -> usize {
+        self.mem.len()
+    }
+
+    /// Determines if there are no values on the stack.
+    pub fn is_empty(&self) -> bool {
+        self.mem.is_empty()
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Stack<T> {
+        Stack::new()
+    }
 }
 
 #[cfg(test)]
@@ -94,6 +152,22 @@ mod tests {
         assert_that!(sut.fetch_word(3), is(equal_to(Err("Index out of bounds!"))));
     }
 
+    #[test]
+    fn code_memory_fetch_short() {
+        let sut = CodeMemory::new(vec![0x01, 0x02, 0x03]);
+
+        assert_that!(sut.fetch_short(1), is(equal_to(Ok(0x0203))));
+        assert_that!(sut.fetch_short(2), is(equal_to(Err("Index out of bounds!"))));
+    }
+
+    #[test]
+    fn code_memory_fetch_address() {
+        let sut = CodeMemory::new(vec![0x00, 0x00, 0x01, 0x02]);
+
+        assert_that!(sut.fetch_address(0), is(equal_to(Ok(0x0102))));
+        assert_that!(sut.fetch_address(1), is(equal_to(Err("Index out of bounds!"))));
+    }
+
     #[test]
     fn stack_push_nd_pop() {
         let mut sut = Stack::new();
@@ -102,5 +176,6 @@ mod tests {
 
         assert_that!(sut.pop(), is(equal_to(23)));
         assert_that!(sut.pop(), is(equal_to(42)));
+        assert_that!(sut.try_pop(), is(equal_to(None)));
     }
 }
\ No newline at end of file
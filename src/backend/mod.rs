@@ -2,116 +2,1091 @@
 pub mod bytecode;
 pub mod byte_conversion;
 pub mod assembler;
+pub mod compiler;
+pub mod heap;
+pub mod image;
 pub mod memory;
+pub mod peephole;
+pub mod register;
+pub mod threaded;
+pub mod value;
 
-use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
 
-use backend::bytecode::{Instruction, BytecodeError};
-use backend::byte_conversion::*;
-use backend::memory::{CodeMemory, Stack};
+use backend::heap::Heap;
+use backend::image::{Capture, ExceptionHandler, Function, Image, JumpTable, LineTable, RecordType};
+use backend::memory::Stack;
+use backend::threaded::{Operation, ThreadedCode};
+use backend::value::Value;
+use frontend::Position;
+use intermediate::ast::BinaryOperator;
 
-/// Trait for a virtual machine.
+/// Describes an error which occurred while executing byte code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmError {
+    message: String,
+    position: Option<Position>,
+    stack_trace: Vec<StackTraceElement>,
+}
+
+impl VmError {
+    /// Creates a new error.
+    ///
+    /// The position is the location in the source code the failing instruction was compiled from,
+    /// if known.
+    pub fn new(message: String, position: Option<Position>) -> VmError {
+        VmError { message, position, stack_trace: Vec::new() }
+    }
+
+    /// Adds the calls which were active when the error occurred, the innermost first.
+    pub fn with_stack_trace(mut self, stack_trace: Vec<StackTraceElement>) -> VmError {
+        self.stack_trace = stack_trace;
+        self
+    }
+
+    /// Get the human readable error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the source position of the failing instruction.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// Get the calls which were active when the error occurred, the innermost first. It's empty
+    /// for errors at top level. The displayed error shows consecutive equal calls once.
+    pub fn stack_trace(&self) -> &[StackTraceElement] {
+        &self.stack_trace
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{} at {}", self.message, position)?,
+            None => write!(f, "{}", self.message)?,
+        }
+
+        // Repeated calls like those of an endless recursion are shown once.
+        let mut index = 0;
+
+        while index < self.stack_trace.len() {
+            let element = &self.stack_trace[index];
+            let repeated = self.stack_trace[index + 1..].iter().take_while(|&other| other == element).count();
+            write!(f, "\n    {}", element)?;
+
+            if repeated > 0 {
+                write!(f, "\n    ... repeated {} more times", repeated)?;
+            }
+
+            index += repeated + 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// A call which was active when an error occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackTraceElement {
+    function: String,
+    position: Option<Position>,
+}
+
+impl StackTraceElement {
+    pub fn new(function: String, position: Option<Position>) -> StackTraceElement {
+        StackTraceElement { function, position }
+    }
+
+    /// Get the name of the called function, which is empty for anonymous functions.
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    /// Get the source position of the call.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+}
+
+impl fmt::Display for StackTraceElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.function.is_empty() {
+            write!(f, "in <fun>")?;
+        } else {
+            write!(f, "in <fun {}>", self.function)?;
+        }
+
+        match self.position {
+            Some(position) => write!(f, " called at {}", position),
+            None => Ok(()),
+        }
+    }
+}
+
+// How the virtual machine gets the operations it executes. Only the threaded code is used by
+// normal runs, the others are for measurements.
+#[derive(Copy, Clone)]
+enum Dispatch {
+    Threaded,
+    // Counts the executed instructions.
+    Counted,
+    // Decodes each instruction again.
+    Decoding,
+}
+
+/// Maximum number of nested calls.
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// State of a calling function which is restored when the called function returns.
+struct Frame {
+    locals: Vec<Value>,
+    closure: Option<Value>,
+    stack_base: usize,
+    return_address: usize,
+}
+
+/// Stack based virtual machine which executes an image.
+///
+/// The byte code is decoded once when the machine is created into operations with their operands
+/// inline, so executing an instruction needs no decoding. The instruction pointer is the index of
+/// the next operation, the byte code offsets are only looked up for errors.
+///
+/// The local variables are addressed by index and are `nil` until a value is stored into them.
+/// Each call has its own local variables, the operand stack is shared by all calls. The execution
+/// stops at the `halt` instruction or at the end of the byte code.
+///
+/// Errors of the executed instructions are thrown as string exceptions like values thrown by
+/// `throw`: The frames of the calls are unwound up to the nearest exception handler of the image
+/// covering the failing instruction or the active call, and the operand stack is reset to the
+/// height at the call. Errors without handler abort the execution.
 pub struct VirtualMachine {
-    code: CodeMemory,
-    stack: Stack,
+    code: ThreadedCode,
+    lines: LineTable,
+    stack: Stack<Value>,
+    locals: Vec<Value>,
+    frames: Vec<Frame>,
+    closure: Option<Value>,
+    stack_base: usize,
+    exception: Option<Value>,
+    heap: Heap,
+    records: Vec<Rc<RecordType>>,
+    functions: Vec<Rc<Function>>,
+    handlers: Vec<(ExceptionHandler, usize)>,
+    jump_tables: Vec<JumpTable>,
+    output: Box<dyn Write>,
     instruction_pointer: usize,
+    instruction_start: usize,
+    executed: u64,
 }
 
 impl VirtualMachine {
-    pub fn new(byte_code: Vec<u8>) -> VirtualMachine {
+    /// Creates a virtual machine which prints to STDOUT.
+    pub fn new(image: Image) -> VirtualMachine {
+        VirtualMachine::with_output(image, Box::new(io::stdout()))
+    }
+
+    /// Creates a virtual machine which prints to the given output.
+    ///
+    /// The strings of the image are allocated in the heap up front and shared by all `spush`. The
+    /// addresses of the functions, handlers and jump tables are translated to operation indices.
+    pub fn with_output(image: Image, output: Box<dyn Write>) -> VirtualMachine {
+        let mut heap = Heap::new();
+        let strings = image.strings().iter()
+            .map(|string| heap.new_string(string.clone()))
+            .collect();
+        let mut code = ThreadedCode::new(image.code().to_vec(), strings);
+        let records = image.records().iter().cloned().map(Rc::new).collect();
+        let functions = image.functions().iter()
+            .map(|function| Rc::new(Function::new(
+                function.name().to_string(), code.index(function.address()), function.arity(),
+                function.captures().to_vec())))
+            .collect();
+        let handlers = image.handlers().iter()
+            .map(|&handler| (handler, code.index(handler.address())))
+            .collect();
+        let jump_tables = image.jump_tables().iter()
+            .map(|table| JumpTable::new(
+                table.low(),
+                table.targets().iter().map(|&target| code.index(target)).collect(),
+                code.index(table.default())))
+            .collect();
+
         VirtualMachine {
-            code: CodeMemory::new(byte_code),
+            code,
+            lines: image.lines().clone(),
             stack: Stack::new(),
+            locals: Vec::new(),
+            frames: Vec::new(),
+            closure: None,
+            stack_base: 0,
+            exception: None,
+            heap,
+            records,
+            functions,
+            handlers,
+            jump_tables,
+            output,
             instruction_pointer: 0,
+            instruction_start: 0,
+            executed: 0,
         }
     }
 
+    /// Get the number of instructions executed by `run_counted`, e.g. to measure the instructions
+    /// per second.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
     /// Run a given program.
-    pub fn run(&mut self) -> Result<(), &'static str> {
-        loop {
-            let opcode = self.fetch();
+    pub fn run(&mut self) -> Result<(), VmError> {
+        self.run_with(Dispatch::Threaded)
+    }
 
-            if opcode.is_err() {
-                return Err(opcode.unwrap_err());
-            }
+    /// Run a given program like `run` and count the executed instructions.
+    pub fn run_counted(&mut self) -> Result<(), VmError> {
+        self.run_with(Dispatch::Counted)
+    }
 
-            let instruction = self.decode(opcode.unwrap());
+    /// Run a given program like `run`, but decode each instruction from the byte code again
+    /// before executing it like a virtual machine without threaded code.
+    ///
+    /// This is only meant to measure how much faster the threaded code is.
+    pub fn run_decoding(&mut self) -> Result<(), VmError> {
+        self.run_with(Dispatch::Decoding)
+    }
 
-            if instruction.is_err() {
-                return Err("Bad opcode!");
-            }
+    fn run_with(&mut self, dispatch: Dispatch) -> Result<(), VmError> {
+        let result = self.run_until_halt(dispatch);
+        let _ = self.output.flush();
+        result
+    }
 
-            let instruction = instruction.unwrap();
+    fn run_until_halt(&mut self, dispatch: Dispatch) -> Result<(), VmError> {
+        while let Some(&operation) = self.code.operations().get(self.instruction_pointer) {
+            let operation = match dispatch {
+                Dispatch::Threaded => operation,
+                // The jumps added by the decoder are no instructions.
+                Dispatch::Counted => {
+                    if !matches!(operation, Operation::Continue(_)) {
+                        self.executed += 1;
+                    }
 
-            if instruction == Instruction::Halt {
-                return Ok(());
+                    operation
+                },
+                Dispatch::Decoding => self.code.decode_again(self.instruction_pointer),
+            };
+            self.instruction_start = self.instruction_pointer;
+            self.instruction_pointer += 1;
+
+            if Operation::Halt == operation {
+                break;
             }
 
-            self.execute(instruction);
+            if let Err(error) = self.execute(operation) {
+                self.throw(error)?;
+            }
         }
+
+        Ok(())
     }
 
-    fn fetch(&self) -> Result<u8, &'static str> {
-        self.code.fetch(self.instruction_pointer)
+    // Continues at the nearest handler with the exception on the stack. Returns the error with
+    // the stack trace if there is no handler.
+    fn throw(&mut self, error: VmError) -> Result<(), VmError> {
+        let exception = match self.exception.take() {
+            Some(exception) => exception,
+            None => self.heap.new_string(error.message().to_string()),
+        };
+        let mut address = self.code.offset(self.instruction_start);
+        let mut depth = self.frames.len();
+
+        loop {
+            if let Some(&(_, index)) = self.handlers.iter().find(|&&(handler, _)| handler.covers(address)) {
+                self.instruction_pointer = index;
+                break;
+            }
+
+            if 0 == depth {
+                return Err(error.with_stack_trace(self.stack_trace()));
+            }
+
+            depth -= 1;
+            // The calling operation is right before the return address.
+            address = self.code.offset(self.frames[depth].return_address - 1);
+        }
+
+        if depth < self.frames.len() {
+            self.frames.truncate(depth + 1);
+            let frame = self.frames.pop().expect("Frame of the handler!");
+            self.locals = frame.locals;
+            self.closure = frame.closure;
+            self.stack_base = frame.stack_base;
+        }
+
+        self.stack.truncate(self.stack_base);
+        self.stack.push(exception);
+        Ok(())
     }
 
-    fn decode(&self, opcode: u8) -> Result<Instruction, BytecodeError> {
-        Instruction::try_from(opcode)
+    fn stack_trace(&self) -> Vec<StackTraceElement> {
+        let mut stack_trace = Vec::with_capacity(self.frames.len());
+        let mut closure = self.closure;
+
+        for frame in self.frames.iter().rev() {
+            let name = closure
+                .and_then(|closure| self.heap.closure(closure).ok())
+                .map_or(String::new(), |(function, _)| function.name().to_string());
+            let position = self.lines.position_at(self.code.offset(frame.return_address - 1));
+            stack_trace.push(StackTraceElement::new(name, position));
+            closure = frame.closure;
+        }
+
+        stack_trace
     }
 
-    fn execute(&mut self, instruction: Instruction) {
-        self.inc_instruction_pointer();
+    fn execute(&mut self, operation: Operation) -> Result<(), VmError> {
+        match operation {
+            Operation::Nop => (),
+            Operation::Push(value) => self.stack.push(value),
+            Operation::IStore(index) => {
+                let value = self.pop_integer()?;
+                self.store(index, Value::Integer(value));
+            },
+            Operation::Store(index) => {
+                let value = self.pop()?;
+                self.store(index, value);
+            },
+            Operation::Load(index) => {
+                let value = self.locals.get(index).cloned().unwrap_or(Value::Nil);
+                self.stack.push(value);
+            },
+            Operation::IAdd => self.integer_arithmetic(BinaryOperator::Add)?,
+            Operation::ISub => self.integer_arithmetic(BinaryOperator::Subtract)?,
+            Operation::IMul => self.integer_arithmetic(BinaryOperator::Multiply)?,
+            Operation::IDiv => self.integer_arithmetic(BinaryOperator::Divide)?,
+            Operation::IRem => self.integer_arithmetic(BinaryOperator::Modulo)?,
+            Operation::INeg => {
+                let operand = self.pop_integer()?;
+                self.stack.push(Value::Integer(operand.wrapping_neg()));
+            },
+            Operation::Add => self.arithmetic(BinaryOperator::Add)?,
+            Operation::Sub => self.arithmetic(BinaryOperator::Subtract)?,
+            Operation::Mul => self.arithmetic(BinaryOperator::Multiply)?,
+            Operation::Div => self.arithmetic(BinaryOperator::Divide)?,
+            Operation::Rem => self.arithmetic(BinaryOperator::Modulo)?,
+            Operation::Neg => {
+                let operand = self.pop()?;
+                let result = value::negate(operand).map_err(|e| self.error(e))?;
+                self.stack.push(result);
+            },
+            Operation::IPrint => {
+                let operand = self.pop_integer()?;
+                self.print(&operand.to_string())?;
+            },
+            Operation::BPrint => {
+                let operand = self.pop_boolean()?;
+                self.print(&operand.to_string())?;
+            },
+            Operation::Print => {
+                let operand = self.pop()?;
+                let text = self.heap.display(operand);
+                self.print(&text)?;
+            },
+            Operation::PrintLn => {
+                let operand = self.pop()?;
+                let text = self.heap.display(operand) + "\n";
+                self.print(&text)?;
+            },
+            Operation::Dup => {
+                let value = self.pop()?;
+                self.stack.push(value);
+                self.stack.push(value);
+            },
+            Operation::Dup2 => {
+                let second = self.pop()?;
+                let first = self.pop()?;
+                self.stack.push(first);
+                self.stack.push(second);
+                self.stack.push(first);
+                self.stack.push(second);
+            },
+            Operation::Pop => {
+                self.pop()?;
+            },
+            Operation::NewArray(count) => {
+                if count > self.stack.len() {
+                    return Err(self.error("Stack underflow!"));
+                }
 
-        match instruction {
-            Instruction::Nop => (),
-            Instruction::IPush => {
-                let result = self.code.fetch_word(self.instruction_pointer);
+                let mut elements = Vec::with_capacity(count);
 
-                match result {
-                    Ok(value) => self.stack.push(value),
-                    Err(msg) => panic!("Error: {}", msg),
+                for _ in 0..count {
+                    elements.push(self.stack.pop());
                 }
 
-                self.inc_instruction_pointer_word();
+                elements.reverse();
+                let array = self.heap.new_array(elements);
+                self.stack.push(array);
+            },
+            Operation::GetElem => {
+                let index = self.pop()?;
+                let array = self.pop()?;
+                let element = self.heap.element(array, index).map_err(|e| self.error(e))?;
+                self.stack.push(element);
+            },
+            Operation::SetElem => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                let array = self.pop()?;
+                self.heap.set_element(array, index, value).map_err(|e| self.error(e))?;
+            },
+            Operation::ArrayLength => {
+                let array = self.pop()?;
+                let length = self.heap.array(array).map_err(|e| self.error(e))?.len();
+                self.stack.push(Value::Integer(length as i64));
+            },
+            Operation::Not => {
+                let operand = self.pop_boolean()?;
+                self.stack.push(Value::Boolean(!operand));
+            },
+            Operation::Jump(index) | Operation::Continue(index) => self.instruction_pointer = index,
+            Operation::JumpIfFalse(index) => {
+                if !self.pop_boolean()? {
+                    self.instruction_pointer = index;
+                }
             },
-            Instruction::IStore => unimplemented!(),
-            Instruction::ILoad => unimplemented!(),
-            Instruction::IAdd => {
-                let first_operand = self.stack.pop();
-                let first_operand = word_to_int(first_operand);
+            Operation::JumpIfTrue(index) => {
+                if self.pop_boolean()? {
+                    self.instruction_pointer = index;
+                }
+            },
+            Operation::Eq => self.compare(BinaryOperator::Equal)?,
+            Operation::Ne => self.compare(BinaryOperator::NotEqual)?,
+            Operation::Lt => self.compare(BinaryOperator::LessThan)?,
+            Operation::Le => self.compare(BinaryOperator::LessThanEqual)?,
+            Operation::Gt => self.compare(BinaryOperator::GreaterThan)?,
+            Operation::Ge => self.compare(BinaryOperator::GreaterThanEqual)?,
+            Operation::Concat => {
+                let right = self.pop()?;
+                let left = self.pop()?;
+                let result = self.heap.concat(left, right).map_err(|e| self.error(e))?;
+                self.stack.push(result);
+            },
+            Operation::BuildString(count) => {
+
+                if count > self.stack.len() {
+                    return Err(self.error("Stack underflow!"));
+                }
+
+                let mut parts = Vec::with_capacity(count);
+
+                for _ in 0..count {
+                    parts.push(self.stack.pop());
+                }
+
+                let content: String = parts.iter().rev().map(|&part| self.heap.display(part)).collect();
+                let string = self.heap.new_string(content);
+                self.stack.push(string);
+            },
+            Operation::NewRec(index) => {
+                let record_type = match self.records.get(index) {
+                    Some(record_type) => Rc::clone(record_type),
+                    None => return Err(self.error(format!("Unknown record type {}!", index))),
+                };
+                let record = self.heap.new_record(record_type);
+                self.stack.push(record);
+            },
+            Operation::GetField(index) => {
+                let record = self.pop()?;
+                let value = self.heap.field(record, index).map_err(|e| self.error(e))?;
+                self.stack.push(value);
+            },
+            Operation::SetField(index) => {
+                let value = self.pop()?;
+                let record = self.pop()?;
+                self.heap.set_field(record, index, value).map_err(|e| self.error(e))?;
+            },
+            Operation::GetNamedField(name) => {
+                let record = self.pop()?;
+                let index = self.named_field_index(record, name)?;
+                let value = self.heap.field(record, index).map_err(|e| self.error(e))?;
+                self.stack.push(value);
+            },
+            Operation::SetNamedField(name) => {
+                let value = self.pop()?;
+                let record = self.pop()?;
+                let index = self.named_field_index(record, name)?;
+                self.heap.set_field(record, index, value).map_err(|e| self.error(e))?;
+            },
+            Operation::MkClosure(index) => {
+                let function = match self.functions.get(index) {
+                    Some(function) => Rc::clone(function),
+                    None => return Err(self.error(format!("Unknown function {}!", index))),
+                };
+                let mut upvalues = Vec::with_capacity(function.captures().len());
+
+                for capture in function.captures() {
+                    upvalues.push(match *capture {
+                        Capture::Local(index) => self.locals.get(usize::from(index)).cloned().unwrap_or(Value::Nil),
+                        Capture::Upvalue(index) => self.closure_upvalue(usize::from(index))?,
+                    });
+                }
+
+                let closure = self.heap.new_closure(function, upvalues);
+                self.stack.push(closure);
+            },
+            Operation::CallIndirect(count) => {
+
+                if count >= self.stack.len() {
+                    return Err(self.error("Stack underflow!"));
+                }
 
-                let second_operand = self.stack.pop();
-                let second_operand = word_to_int(second_operand);
+                let mut arguments = Vec::with_capacity(count);
 
-                let result = first_operand + second_operand;
-                let result = int_to_word(result);
+                for _ in 0..count {
+                    arguments.push(self.stack.pop());
+                }
+
+                arguments.reverse();
+                let callee = self.stack.pop();
+                self.call(callee, arguments)?;
+            },
+            Operation::Ret => {
+                let result = self.pop()?;
+                let frame = match self.frames.pop() {
+                    Some(frame) => frame,
+                    None => return Err(self.error("Return outside of function!")),
+                };
+                self.locals = frame.locals;
+                self.closure = frame.closure;
+                self.stack_base = frame.stack_base;
+                self.instruction_pointer = frame.return_address;
                 self.stack.push(result);
             },
-            Instruction::ISub => unimplemented!(),
-            Instruction::IMul => unimplemented!(),
-            Instruction::IDiv => unimplemented!(),
-            Instruction::IRem => unimplemented!(),
-            Instruction::INeg => unimplemented!(),
-            Instruction::IPrint => {
-                let operand = self.stack.pop();
-                let operand = word_to_int(operand);
-                print!("{}", operand);
+            Operation::NewUpvalue(index) => {
+                let upvalue = self.heap.new_upvalue(Value::Nil);
+                self.store(index, upvalue);
+            },
+            Operation::LoadUpvalue(index) => {
+                let upvalue = self.locals.get(index).cloned().unwrap_or(Value::Nil);
+                let value = self.heap.upvalue(upvalue).map_err(|e| self.error(e))?;
+                self.stack.push(value);
+            },
+            Operation::StoreUpvalue(index) => {
+                let value = self.pop()?;
+                let upvalue = self.locals.get(index).cloned().unwrap_or(Value::Nil);
+                self.heap.set_upvalue(upvalue, value).map_err(|e| self.error(e))?;
+            },
+            Operation::GetUpvalue(index) => {
+                let upvalue = self.closure_upvalue(index)?;
+                let value = self.heap.upvalue(upvalue).map_err(|e| self.error(e))?;
+                self.stack.push(value);
+            },
+            Operation::SetUpvalue(index) => {
+                let value = self.pop()?;
+                let upvalue = self.closure_upvalue(index)?;
+                self.heap.set_upvalue(upvalue, value).map_err(|e| self.error(e))?;
+            },
+            Operation::Throw => {
+                let exception = self.pop()?;
+                let message = format!("Uncaught exception '{}'!", self.heap.display(exception));
+                self.exception = Some(exception);
+                return Err(self.error(message));
             },
-            Instruction::Halt => panic!("The opcode 'halt' should exit the loop before execute!"),
+            // Reals are equal to the integers with the same value like for `eq`.
+            Operation::TableSwitch(index) => {
+                let value = self.pop()?;
+                let table = match self.jump_tables.get(index) {
+                    Some(table) => table,
+                    None => return Err(self.error(format!("Unknown jump table {}!", index))),
+                };
+                self.instruction_pointer = match value {
+                    Value::Integer(value) => table.target(value),
+                    Value::Real(value) if value == (value as i64) as f64 => table.target(value as i64),
+                    _ => table.default(),
+                };
+            },
+            Operation::IsRec(index) => {
+                let value = self.pop()?;
+                let record_type = match self.records.get(index) {
+                    Some(record_type) => record_type,
+                    None => return Err(self.error(format!("Unknown record type {}!", index))),
+                };
+                let result = self.heap.record_type(value).is_ok_and(|actual| actual.name() == record_type.name());
+                self.stack.push(Value::Boolean(result));
+            },
+            Operation::NoMatch => {
+                let value = self.pop()?;
+                let message = format!("No match for value '{}'!", self.heap.display(value));
+                return Err(self.error(message));
+            },
+            Operation::Fault(fault) => return Err(self.error(fault.to_string())),
+            Operation::Halt => unreachable!("The operation 'halt' should exit the loop before execute!"),
+        }
+
+        Ok(())
+    }
+
+    // Saves the current frame and continues at the function of the closure whose first local
+    // variables are the arguments.
+    fn call(&mut self, callee: Value, arguments: Vec<Value>) -> Result<(), VmError> {
+        let function = Rc::clone(self.heap.closure(callee).map_err(|e| self.error(e))?.0);
+
+        function.check_arity(arguments.len()).map_err(|e| self.error(e))?;
+
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(self.error("Call stack overflow!"));
         }
+
+        self.frames.push(Frame {
+            locals: mem::replace(&mut self.locals, arguments),
+            closure: self.closure.replace(callee),
+            stack_base: mem::replace(&mut self.stack_base, self.stack.len()),
+            return_address: self.instruction_pointer,
+        });
+        self.instruction_pointer = function.address();
+        Ok(())
+    }
+
+    // Get the reference of the upvalue with the given index of the executing closure.
+    fn closure_upvalue(&self, index: usize) -> Result<Value, VmError> {
+        let upvalue = match self.closure {
+            Some(closure) => self.heap.closure(closure).map_err(|e| self.error(e))?.1.get(index).cloned(),
+            None => None,
+        };
+        upvalue.ok_or_else(|| self.error(format!("Unknown upvalue {}!", index)))
     }
 
-    fn inc_instruction_pointer(&mut self) {
-        self.instruction_pointer += 1;
+    fn named_field_index(&self, record: Value, name: Value) -> Result<usize, VmError> {
+        let name = self.heap.string(name).map_err(|e| self.error(e))?;
+        self.heap.field_index(record, name).map_err(|e| self.error(e))
+    }
+
+    fn store(&mut self, index: usize, value: Value) {
+        if index >= self.locals.len() {
+            self.locals.resize(index + 1, Value::Nil);
+        }
+
+        self.locals[index] = value;
+    }
+
+    fn integer_arithmetic(&mut self, operator: BinaryOperator) -> Result<(), VmError> {
+        let right = self.pop_integer()?;
+        let left = self.pop_integer()?;
+        let result = value::integer_arithmetic(operator, left, right).map_err(|e| self.error(e))?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    // Adding two strings joins them, everything else is a numeric operation.
+    fn arithmetic(&mut self, operator: BinaryOperator) -> Result<(), VmError> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let result = match (operator, left, right) {
+            (BinaryOperator::Add, Value::String(_), Value::String(_)) => self.heap.concat(left, right),
+            _ => value::arithmetic(operator, left, right),
+        };
+        let result = result.map_err(|e| self.error(e))?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn compare(&mut self, operator: BinaryOperator) -> Result<(), VmError> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let result = self.heap.compare(operator, left, right).map_err(|e| self.error(e))?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, VmError> {
+        match self.stack.try_pop() {
+            Some(value) => Ok(value),
+            None => Err(self.error("Stack underflow!")),
+        }
+    }
+
+    fn pop_integer(&mut self) -> Result<i64, VmError> {
+        match self.pop()? {
+            Value::Integer(value) => Ok(value),
+            value => Err(self.error(format!("Expected Integer but got {}!", value.type_name()))),
+        }
+    }
+
+    // Only booleans are allowed as conditions, there is no implicit truthiness of other values.
+    fn pop_boolean(&mut self) -> Result<bool, VmError> {
+        match self.pop()? {
+            Value::Boolean(value) => Ok(value),
+            value => Err(self.error(format!("Expected Boolean but got {}!", value.type_name()))),
+        }
     }
 
-    fn inc_instruction_pointer_word(&mut self) {
-        self.instruction_pointer += WORD_SIZE;
+    fn print(&mut self, text: &str) -> Result<(), VmError> {
+        self.output.write_all(text.as_bytes())
+            .map_err(|_| VmError::new(String::from("Can't write output!"), None))
+    }
+
+    // The error is located at the source position of the currently executed instruction.
+    fn error<S: Into<String>>(&self, message: S) -> VmError {
+        VmError::new(message.into(), self.lines.position_at(self.code.offset(self.instruction_start)))
     }
 }
 
 #[cfg(test)]
 mod tests {
-//    use super::*;
-//    use hamcrest::prelude::*;
-}
\ No newline at end of file
+    use super::*;
+    use hamcrest::prelude::*;
+    use std::path::Path;
+    use backend::assembler::Assembler;
+    use backend::compiler::Compiler;
+    use frontend::module::ModuleLoader;
+    use test_support::{compile, SharedOutput};
+
+    fn run(image: Image) -> (Result<(), VmError>, String) {
+        let output = SharedOutput::new();
+        let result = VirtualMachine::with_output(image, Box::new(output.clone())).run();
+        (result, output.text())
+    }
+
+    fn run_assembly(assembly: &str) -> (Result<(), VmError>, String) {
+        run(Image::from_code(Assembler::new().assemble(assembly)))
+    }
+
+    fn run_source(src: &str) -> (Result<(), VmError>, String) {
+        run(compile(src).unwrap())
+    }
+
+    #[test]
+    fn run_integer_arithmetic() {
+        let (result, output) = run_assembly("ipush 7\nipush 2\nisub\nipush 3\nimul\nineg\niprint\nhalt\nipush 1\niprint");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("-15")));
+    }
+
+    #[test]
+    fn run_decoding_each_instruction() {
+        for name in &["arithmetic", "control", "exceptions", "functions", "match", "records"] {
+            let path = format!("test/programs/{}.mvm", name);
+            let modules = ModuleLoader::new().load(Path::new(&path)).unwrap();
+            let image = Compiler::new().compile_modules(&modules).unwrap().0;
+            let output = SharedOutput::new();
+            let result = VirtualMachine::with_output(image.clone(), Box::new(output.clone())).run_decoding();
+
+            assert_that!((result, output.text()), is(equal_to(run(image))));
+        }
+    }
+
+    #[test]
+    fn count_executed_instructions() {
+        let mut vm = VirtualMachine::with_output(
+            Image::from_code(Assembler::new().assemble("ipush 7\nipush 2\nisub\niprint\nhalt\nipush 1")),
+            Box::new(io::sink()));
+
+        assert_that!(vm.executed(), is(equal_to(0)));
+        assert_that!(vm.run_counted(), is(equal_to(Ok(()))));
+        assert_that!(vm.executed(), is(equal_to(5)));
+    }
+
+    #[test]
+    fn count_no_jumps_added_by_decoding() {
+        // The decoder appends a jump to the end of the code.
+        let image = Image::from_code(Assembler::new().assemble("ipush 7
+iprint"));
+        let mut vm = VirtualMachine::with_output(image.clone(), Box::new(io::sink()));
+        assert_that!(vm.run_counted(), is(equal_to(Ok(()))));
+        assert_that!(vm.executed(), is(equal_to(2)));
+
+        let mut vm = VirtualMachine::with_output(image, Box::new(io::sink()));
+        assert_that!(vm.run(), is(equal_to(Ok(()))));
+        assert_that!(vm.executed(), is(equal_to(0)));
+    }
+
+    #[test]
+    fn run_locals() {
+        let (result, output) = run_assembly("ipush 42\nistore 3\niload 3\niload 0\nprintln\nprintln");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("nil\n42\n")));
+    }
+
+    #[test]
+    fn run_generic_arithmetic() {
+        let (result, output) = run_assembly("ipush 1\nrpush 0.5\nadd\ncpush 97\nprint\nprintln");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("a1.5\n")));
+    }
+
+    #[test]
+    fn run_arrays() {
+        let (result, output) = run_assembly(r#"
+ipush 1
+ipush 2
+newarray 2
+dup
+ipush 0
+dup2
+getelem
+ipush 10
+iadd
+setelem
+dup
+arraylength
+println
+println
+"#);
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("2\n[11, 2]\n")));
+    }
+
+    #[test]
+    fn run_booleans() {
+        let (result, output) = run_assembly("bpush 1\nnot\nbprint\nipush 1\nrpush 1.0\neq\nprint\ncpush 98\ncpush 97\nlt\nprintln");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("falsetruefalse\n")));
+    }
+
+    #[test]
+    fn run_strings() {
+        let (result, output) = run_source(
+            "var s = \"ab\"\ns += \"c\"\nprintln(s)\nprintln(s < \"b\")\nprintln(s == \"a\" + \"bc\")\nvar x\nx = s\nprintln(x + \"!\")");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("abc\ntrue\ntrue\nabc!\n")));
+    }
+
+    #[test]
+    fn run_interpolated_string() {
+        let (result, output) = run_source("var a = [1, 2]\nvar c = 'x'\nprintln(\"${c} = ${a[0] + a[1]} of ${a}, \\${raw}\")");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("x = 3 of [1, 2], ${raw}\n")));
+    }
+
+    #[test]
+    fn run_string_errors() {
+        let (result, _) = run_source("var x\nx = 1\nprintln(\"a\" + x)");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Operator '+' can not be applied to String and Integer!")));
+
+        let (result, _) = run_assembly("spush 0");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Unknown string constant 0!")));
+    }
+
+    #[test]
+    fn run_records() {
+        let (result, output) = run_source(concat!(
+            "record Point { x, y }\nrecord Line { start, end }\n",
+            "var l = Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 } }\n",
+            "l.end.x += l.start.y\nvar p = l.start\np.y = \"b\"\nprintln(l)\nprintln(l.end.x)"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("Line { start: Point { x: 1, y: b }, end: Point { x: 5, y: 4 } }\n5\n")));
+    }
+
+    #[test]
+    fn run_compound_assignment_evaluates_target_once() {
+        let (result, output) = run_source(concat!(
+            "record P { x, y }\nvar calls = 0\nvar l = [P { x: 1, y: 2 }]\n",
+            "fun target() {\n  calls += 1\n  return l\n}\n",
+            "target()[calls - 1].x += 10\ntarget()[0].y *= 3\nprintln(l)\nprintln(calls)"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("[P { x: 11, y: 6 }]\n2\n")));
+    }
+
+    #[test]
+    fn run_record_errors() {
+        let (result, _) = run_source("record P { x }\nvar a = [P { x: 1 }, 1]\nprintln(a[0].y)");
+        assert_that!(result.unwrap_err().to_string(), is(equal_to(String::from("Record 'P' has no field 'y'! at [3, 9]"))));
+
+        let (result, _) = run_source("var r\nr = 1\nr.x = 2");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Expected Record but got Integer!")));
+
+        let (result, _) = run_assembly("newrec 0");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Unknown record type 0!")));
+    }
+
+    #[test]
+    fn run_modules() {
+        let modules = ModuleLoader::new().load(Path::new("test/modules/main.mvm")).unwrap();
+        let (result, output) = run(Compiler::new().compile_modules(&modules).unwrap().0);
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("geometry.Point { x: 1, y: 2 } 42 0\n")));
+    }
+
+    #[test]
+    fn run_closures() {
+        let (result, output) = run_source(concat!(
+            "fun counter(start) {\n  var count = start\n  return fun() {\n    count += 1\n    return count\n  }\n}\n",
+            "var next = counter(10)\nvar other = counter(0)\nprintln(\"${next()} ${next()} ${other()} ${next()}\")\n",
+            "fun adder(a) {\n  return fun(b) { return fun(c) { return a + b + c } }\n}\n",
+            "println(adder(1)(2)(3))\nprintln(counter)\nprintln(fun() {})"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("11 12 1 13\n6\n<fun counter>\n<fun>\n")));
+    }
+
+    #[test]
+    fn run_function_errors() {
+        let (result, _) = run_source("fun f(a) {}\nf()");
+        assert_that!(result.unwrap_err().to_string(),
+            is(equal_to(String::from("Function 'f' expects 1 argument but got 0! at [2, 1]"))));
+
+        let (result, _) = run_source("var x\nx = 1\nx()");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Expected Function but got Integer!")));
+
+        let (result, _) = run_source("fun f() {\n  return f()\n}\nf()");
+        let error = result.unwrap_err();
+        assert_that!(error.to_string(), is(equal_to(String::from(concat!(
+            "Call stack overflow! at [2, 10]\n",
+            "    in <fun f> called at [2, 10]\n",
+            "    ... repeated 1022 more times\n",
+            "    in <fun f> called at [4, 1]")))));
+        assert_that!(error.stack_trace().len(), is(equal_to(MAX_CALL_DEPTH)));
+
+        let (result, _) = run_assembly("npush\nret");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Return outside of function!")));
+        let (result, _) = run_assembly("mkclosure 0");
+        assert_that!(result.unwrap_err().message(), is(equal_to("Unknown function 0!")));
+    }
+
+    #[test]
+    fn run_exceptions() {
+        let (result, output) = run_source(concat!(
+            "fun check(x) {\n  throw \"bad ${x}\"\n}\n",
+            "try {\n  println([1, check(2)])\n} catch e {\n  println(e)\n}\n",
+            "try {\n  try { println(1 / 0) } catch e { throw [e] }\n} catch e {\n  println(e[0])\n}\n",
+            "var a = []\ntry { a[0] } catch e { println(e) }\nprintln(len(a) + 1)"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("bad 2\nDivision by zero!\nArray index 0 out of bounds for length 0!\n1\n")));
+    }
+
+    #[test]
+    fn run_match() {
+        let (result, output) = run_source(concat!(
+            "record P { x, y }\n",
+            "fun f(v) {\n  return match v {\n    1 => \"one\", 2 => \"two\", 3 => \"three\"\n",
+            "    \"a\" => \"a\"\n    P { x: 0, y } => \"y ${y}\"\n    other => \"other ${other}\"\n  }\n}\n",
+            "println([f(2), f(3.0), f(4), f(\"a\"), f(P { x: 0, y: 1 }), f(P { x: 1, y: 0 }), f([])])\n",
+            "try { match 0 { 1 => 1 } } catch e { println(e) }"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to(concat!(
+            "[two, three, other 4, a, y 1, other P { x: 1, y: 0 }, other []]\n",
+            "No match for value '0'!\n"))));
+    }
+
+    #[test]
+    fn run_loops() {
+        let (result, output) = run_source(concat!(
+            "var z = fun() { return 0 }\nvar fs = [z, z, z]\n",
+            "for i in 0..3 {\n  fs[i] = fun() { return i * 10 }\n}\n",
+            "for f in fs { print(f()) }\nprintln(\"\")\n",
+            "var n = 0\nwhile n < 3 {\n  n += 1\n",
+            "  for x in [1, 2, 3, 4] {\n    if x == n { continue } else if x > 3 { break }\n    print(x)\n  }\n}\n",
+            "println(\"\")\nfor i in 5..2 { println(i) }"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("01020\n231312\n")));
+    }
+
+    #[test]
+    fn run_uncaught_exception_has_stack_trace() {
+        let (result, _) = run_source(concat!(
+            "fun boom(x) {\n  return fun() { throw x }\n}\n",
+            "fun outer() {\n  return boom(1)()\n}\n",
+            "fun f() { outer() }\nf()"));
+        let error = result.unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Uncaught exception '1'!")));
+        assert_that!(error.to_string(), is(equal_to(String::from(concat!(
+            "Uncaught exception '1'! at [2, 18]\n",
+            "    in <fun> called at [5, 10]\n",
+            "    in <fun outer> called at [7, 11]\n",
+            "    in <fun f> called at [8, 1]")))));
+        assert_that!(error.stack_trace().len(), is(equal_to(3)));
+    }
+
+    #[test]
+    fn run_jumps() {
+        // Prints 3, 2, 1 by counting down until the counter is zero.
+        let (result, output) = run_assembly(r#"
+ipush 3         // 0
+istore 0        // 9
+iload 0         // 11
+ipush 0         // 13
+gt              // 22
+jumpiffalse 50  // 23
+iload 0         // 28
+iprint          // 30
+iload 0         // 31
+ipush 1         // 33
+isub            // 42
+istore 0        // 43
+jump 11         // 45
+halt            // 50
+"#);
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("321")));
+    }
+
+    #[test]
+    fn run_short_circuit() {
+        let (result, output) = run_source(
+            "var a = [1]\nprintln(false and a[5] == 1)\nprintln(true or a[5] == 1)\nprintln(true and not (a[0] > 1))");
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("false\ntrue\ntrue\n")));
+    }
+
+    #[test]
+    fn run_condition_must_be_boolean() {
+        let (result, _) = run_source("var x\nx = 1\nprintln(x or true)");
+
+        assert_that!(result, is(equal_to(Err(VmError::new(
+            String::from("Expected Boolean but got Integer!"), Some(Position::new(3, 9)))))));
+    }
+
+    #[test]
+    fn run_errors() {
+        assert_that!(run_assembly("ipush 1\nipush 0\nidiv").0,
+            is(equal_to(Err(VmError::new(String::from("Division by zero!"), None)))));
+        assert_that!(run_assembly("rpush 1.0\nistore 0").0,
+            is(equal_to(Err(VmError::new(String::from("Expected Integer but got Real!"), None)))));
+        assert_that!(run_assembly("ipush 1\njumpiftrue 0").0,
+            is(equal_to(Err(VmError::new(String::from("Expected Boolean but got Integer!"), None)))));
+        assert_that!(run_assembly("bpush 1\nipush 1\nlt").0,
+            is(equal_to(Err(VmError::new(String::from("Operator '<' can not be applied to Boolean and Integer!"), None)))));
+        assert_that!(run_assembly("iadd").0,
+            is(equal_to(Err(VmError::new(String::from("Stack underflow!"), None)))));
+        assert_that!(run(Image::from_code(vec![0xfe])).0,
+            is(equal_to(Err(VmError::new(String::from("Bad opcode 0xfe!"), None)))));
+    }
+
+    #[test]
+    fn run_error_has_source_position() {
+        let mut lines = LineTable::new();
+        lines.add(0, Position::new(1, 1));
+        lines.add(3, Position::new(2, 7));
+        let code = Assembler::new().assemble("newarray 0\nipush 0\ngetelem");
+
+        let (result, _) = run(Image::new(code, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), lines));
+        let error = result.unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Array index 0 out of bounds for length 0!")));
+        assert_that!(error.position(), is(equal_to(Some(Position::new(2, 7)))));
+        assert_that!(&error.to_string(), is(equal_to("Array index 0 out of bounds for length 0! at [2, 7]")));
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use backend::bytecode::Instruction;
+use backend::byte_conversion::*;
+use backend::image::{ExceptionHandler, Function, Image, JumpTable, LineTable};
+
+/// Rewrites short sequences of instructions in the byte code of the image into cheaper ones:
+///
+/// - `nop` is removed.
+/// - `ipush 0; iadd` is removed, the value below is an integer as `iadd` expects.
+/// - `ipush a; ipush b; iadd` becomes `ipush a+b`.
+/// - `istore n; iload n` becomes `dup; istore n`.
+/// - Jumps and jump tables targeting a `jump` continue at its target instead.
+///
+/// A sequence is only rewritten if no jump, jump table, function or exception handler refers to
+/// an address inside of it. All these addresses and the debug line table are moved along with the
+/// code. Byte code which can not be decoded is returned unchanged, so the pass works on raw byte
+/// code of the assembler as well as on images of the compiler.
+pub fn optimize(image: &Image) -> Image {
+    let mut codes = match decode(image.code()) {
+        Some(codes) => codes,
+        None => return image.clone(),
+    };
+
+    let indices: HashMap<usize, usize> = codes.iter().enumerate().map(|(index, code)| (code.origin, index)).collect();
+    let targets: Vec<Option<usize>> = codes.iter()
+        .map(|code| code.target().map(|target| thread(target, &codes, &indices)))
+        .collect();
+
+    for (code, target) in codes.iter_mut().zip(targets) {
+        if let Some(target) = target {
+            code.operands = address_to_bytes(target as u32);
+        }
+    }
+
+    let jump_tables: Vec<JumpTable> = image.jump_tables().iter()
+        .map(|table| JumpTable::new(
+            table.low(),
+            table.targets().iter().map(|&target| thread(target, &codes, &indices)).collect(),
+            thread(table.default(), &codes, &indices)))
+        .collect();
+
+    let mut leaders: HashSet<usize> = codes.iter().filter_map(|code| code.target()).collect();
+    leaders.extend(jump_tables.iter().flat_map(|table| table.targets().iter().cloned().chain(Some(table.default()))));
+    leaders.extend(image.functions().iter().map(|function| function.address()));
+    leaders.extend(image.handlers().iter().flat_map(|handler| vec![handler.start(), handler.end(), handler.address()]));
+
+    let mut rewritten = Vec::with_capacity(codes.len());
+
+    for code in codes {
+        if Instruction::Nop != code.instruction {
+            rewritten.push(code);
+            reduce(&mut rewritten, &leaders);
+        }
+    }
+
+    let relocation = Relocation::new(&rewritten);
+    let mut code = Vec::with_capacity(image.code().len());
+
+    for mut rewritten in rewritten {
+        if let Some(target) = rewritten.target() {
+            rewritten.operands = address_to_bytes(relocation.address(target) as u32);
+        }
+
+        code.push(u8::from(rewritten.instruction));
+        code.extend_from_slice(&rewritten.operands);
+    }
+
+    let functions = image.functions().iter()
+        .map(|function| Function::new(
+            function.name().to_string(), relocation.address(function.address()), function.arity(),
+            function.captures().to_vec()))
+        .collect();
+    let handlers = image.handlers().iter()
+        .map(|handler| ExceptionHandler::new(
+            relocation.address(handler.start()), relocation.address(handler.end()),
+            relocation.address(handler.address())))
+        .collect();
+    let jump_tables = jump_tables.iter()
+        .map(|table| JumpTable::new(
+            table.low(),
+            table.targets().iter().map(|&target| relocation.address(target)).collect(),
+            relocation.address(table.default())))
+        .collect();
+    let mut lines = LineTable::new();
+
+    for &(offset, position) in image.lines().entries() {
+        lines.add(relocation.address(offset), position);
+    }
+
+    Image::new(code, image.strings().to_vec(), image.records().to_vec(), functions, handlers, jump_tables, lines)
+}
+
+/// Instruction with its operand bytes.
+#[derive(Debug, Clone, PartialEq)]
+struct Code {
+    // Address of the first original instruction the code replaces.
+    origin: usize,
+    instruction: Instruction,
+    operands: Vec<u8>,
+}
+
+impl Code {
+    fn new(origin: usize, instruction: Instruction, operands: Vec<u8>) -> Code {
+        Code { origin, instruction, operands }
+    }
+
+    fn is(&self, instruction: Instruction) -> bool {
+        self.instruction == instruction
+    }
+
+    // Get the address the instruction jumps to.
+    fn target(&self) -> Option<usize> {
+        match self.instruction {
+            Instruction::Jump | Instruction::JumpIfFalse | Instruction::JumpIfTrue =>
+                bytes_to_address(&self.operands).ok().map(|address| address as usize),
+            _ => None,
+        }
+    }
+
+    // Get the value pushed by `ipush`.
+    fn integer(&self) -> Option<i64> {
+        match self.instruction {
+            Instruction::IPush => bytes_to_int(&self.operands).ok(),
+            _ => None,
+        }
+    }
+}
+
+fn decode(code: &[u8]) -> Option<Vec<Code>> {
+    let mut codes = Vec::new();
+    let mut address = 0;
+
+    while address < code.len() {
+        let instruction = Instruction::try_from(code[address]).ok()?;
+        let operands = code.get(address + 1..address + instruction.size())?;
+        codes.push(Code::new(address, instruction, operands.to_vec()));
+        address += instruction.size();
+    }
+
+    Some(codes)
+}
+
+// Follows the chain of jumps starting at the target. Endless loops of jumps are kept as they are.
+fn thread(target: usize, codes: &[Code], indices: &HashMap<usize, usize>) -> usize {
+    let mut current = target;
+
+    for _ in 0..codes.len() {
+        match indices.get(&current).map(|&index| &codes[index]) {
+            Some(code) if code.is(Instruction::Jump) => current = code.target().unwrap_or(current),
+            _ => return current,
+        }
+    }
+
+    target
+}
+
+// Rewrites the sequences at the end of the code as long as any rule applies, so the result of
+// one rule may become part of the next one.
+fn reduce(codes: &mut Vec<Code>, leaders: &HashSet<usize>) {
+    loop {
+        let length = codes.len();
+        // Only the first instruction of a sequence may be entered from elsewhere.
+        let joinable = |count: usize| count <= length
+            && codes[length + 1 - count..].iter().all(|code| !leaders.contains(&code.origin));
+
+        let (count, replacement) = match codes[..] {
+            [.., ref zero, ref add] if joinable(2) && Some(0) == zero.integer() && add.is(Instruction::IAdd) =>
+                (2, vec![]),
+            [.., ref left, ref right, ref add] if joinable(3) && add.is(Instruction::IAdd) => {
+                match (left.integer(), right.integer()) {
+                    (Some(left_value), Some(right_value)) => {
+                        let sum = left_value.wrapping_add(right_value);
+                        (3, vec![Code::new(left.origin, Instruction::IPush, int_to_bytes(sum))])
+                    },
+                    _ => return,
+                }
+            },
+            [.., ref store, ref load] if joinable(2) && store.is(Instruction::IStore) && load.is(Instruction::ILoad)
+                && store.operands == load.operands =>
+                (2, vec![
+                    Code::new(store.origin, Instruction::Dup, Vec::new()),
+                    Code::new(load.origin, Instruction::IStore, store.operands.clone())]),
+            _ => return,
+        };
+
+        codes.truncate(length - count);
+        codes.extend(replacement);
+    }
+}
+
+/// Maps the addresses of the original byte code to the rewritten one.
+struct Relocation {
+    // Origin and new address of each rewritten instruction.
+    addresses: Vec<(usize, usize)>,
+    end: usize,
+}
+
+impl Relocation {
+    fn new(codes: &[Code]) -> Relocation {
+        let mut addresses = Vec::with_capacity(codes.len());
+        let mut end = 0;
+
+        for code in codes {
+            addresses.push((code.origin, end));
+            end += code.instruction.size();
+        }
+
+        Relocation { addresses, end }
+    }
+
+    // Removed instructions continue at the next remaining one.
+    fn address(&self, original: usize) -> usize {
+        let index = self.addresses.partition_point(|&(origin, _)| origin < original);
+        self.addresses.get(index).map_or(self.end, |&(_, address)| address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use backend::assembler::Assembler;
+    use frontend::Position;
+
+    fn optimize_assembly(assembly: &str) -> String {
+        let sut = Assembler::new();
+        let image = optimize(&Image::from_code(sut.assemble(assembly)));
+        sut.disassemble(image.code().to_vec())
+    }
+
+    #[test]
+    fn remove_nop_and_adding_zero() {
+        assert_that!(&optimize_assembly("nop\nipush 5\nnop\nipush 0\niadd\niprint\nhalt"),
+            is(equal_to("ipush 5\niprint\nhalt\n")));
+    }
+
+    #[test]
+    fn fold_integer_additions() {
+        assert_that!(&optimize_assembly("ipush 1\nipush 2\niadd\nipush 3\niadd\niprint"),
+            is(equal_to("ipush 6\niprint\n")));
+        assert_that!(&optimize_assembly("ipush 9_223_372_036_854_775_807\nipush 1\niadd\niprint"),
+            is(equal_to("ipush -9223372036854775808\niprint\n")));
+        assert_that!(&optimize_assembly("iload 0\nipush 2\niadd\niprint"), is(equal_to("iload 0\nipush 2\niadd\niprint\n")));
+    }
+
+    #[test]
+    fn store_and_load_with_dup() {
+        assert_that!(&optimize_assembly("ipush 1\nistore 0\niload 0\niprint\nistore 1\niload 2"),
+            is(equal_to("ipush 1\ndup\nistore 0\niprint\nistore 1\niload 2\n")));
+    }
+
+    #[test]
+    fn thread_jumps() {
+        // 0: jump 10, 5: nop, 6: halt, 7: nop, 8: nop, 9: nop, 10: jump 6, 15: jump 20, 20: jump 25, 25: jump 20
+        assert_that!(&optimize_assembly("jump 10\nnop\nhalt\nnop\nnop\nnop\njump 6\njump 20\njump 25\njump 20"),
+            is(equal_to("jump 5\nhalt\njump 5\njump 16\njump 21\njump 16\n")));
+    }
+
+    #[test]
+    fn keep_sequences_entered_by_jumps() {
+        assert_that!(&optimize_assembly("ipush 1\nipush 2\niadd\niprint\njump 9"),
+            is(equal_to("ipush 1\nipush 2\niadd\niprint\njump 9\n")));
+        assert_that!(&optimize_assembly("ipush 1\nnop\nipush 2\niadd\niprint\njump 10"),
+            is(equal_to("ipush 1\nipush 2\niadd\niprint\njump 9\n")));
+    }
+
+    #[test]
+    fn move_addresses_of_image() {
+        // 0: nop, 1: ipush 7, 10: nop, 11: iprint, 12: halt
+        let code = Assembler::new().assemble("nop\nipush 7\nnop\niprint\nhalt");
+        let mut lines = LineTable::new();
+        lines.add(0, Position::new(1, 1));
+        lines.add(10, Position::new(2, 1));
+        lines.add(11, Position::new(3, 1));
+        let image = Image::new(
+            code, vec![String::from("s")], vec![], vec![Function::new(String::from("f"), 1, 0, vec![])],
+            vec![ExceptionHandler::new(1, 11, 12)], vec![JumpTable::new(0, vec![10], 13)], lines);
+
+        let optimized = optimize(&image);
+
+        let mut expected_lines = LineTable::new();
+        expected_lines.add(0, Position::new(1, 1));
+        expected_lines.add(9, Position::new(3, 1));
+        let expected = Image::new(
+            Assembler::new().assemble("ipush 7\niprint\nhalt"), vec![String::from("s")], vec![],
+            vec![Function::new(String::from("f"), 0, 0, vec![])], vec![ExceptionHandler::new(0, 9, 10)],
+            vec![JumpTable::new(0, vec![9], 11)], expected_lines);
+        assert_that!(optimized, is(equal_to(expected)));
+    }
+
+    #[test]
+    fn keep_undecodable_code() {
+        let image = Image::from_code(vec![0x02, 0x00]);
+
+        assert_that!(optimize(&image), is(equal_to(image.clone())));
+    }
+}
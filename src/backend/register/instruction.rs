@@ -0,0 +1,429 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use backend::bytecode::BytecodeError;
+
+const SIZE_OP: u32 = 6;
+const SIZE_A: u32 = 8;
+const SIZE_B: u32 = 9;
+const SIZE_C: u32 = 9;
+const SIZE_BX: u32 = SIZE_B + SIZE_C;
+const POSITION_A: u32 = SIZE_OP;
+const POSITION_C: u32 = POSITION_A + SIZE_A;
+const POSITION_B: u32 = POSITION_C + SIZE_C;
+const POSITION_BX: u32 = POSITION_C;
+
+/// Highest register index, which is also the maximum of the A operand.
+pub const MAX_A: usize = (1 << SIZE_A) - 1;
+/// Highest value of the B and C operands.
+pub const MAX_B: usize = (1 << SIZE_B) - 1;
+/// Highest value of the unsigned Bx operand.
+pub const MAX_BX: usize = (1 << SIZE_BX) - 1;
+/// Highest absolute value of the signed sBx operand.
+pub const MAX_SBX: i32 = (MAX_BX >> 1) as i32;
+/// B and C operands marked as RK with this bit set address a constant instead of a register.
+pub const BIT_RK: usize = 1 << (SIZE_B - 1);
+/// Highest index of a constant which fits into an RK operand.
+pub const MAX_RK_CONSTANT: usize = BIT_RK - 1;
+
+/// Defines the operations of the register machine.
+///
+/// The encoding follows the [Lua 5.1 VM Instructions](http://underpop.free.fr/l/lua/docs/a-no-frills-introduction-to-lua-5.1-vm-instructions.pdf):
+/// Each instruction is one 32 bit word holding the operation in its six lowest bits followed by
+/// the operands A (8 bits), C (9 bits) and B (9 bits). Instructions with a bigger operand use the
+/// combined B and C bits as unsigned Bx or signed sBx operand. R(x) denotes register x of the
+/// executing function, K(x) constant x and RK(x) a constant if x has `BIT_RK` set, else a register.
+/// The first registers of a function hold its local variables, starting with the arguments.
+///
+/// Errors are the same as those of the byte code instructions the operations are translated from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OpCode {
+    /// R(A) := R(B)
+    Move,
+    /// R(A) := R(B), which must be an integer.
+    IMove,
+    /// R(A) := K(Bx)
+    LoadK,
+    /// R(A) := B != 0; if C != 0 then pc++
+    LoadBool,
+    /// R(A) := nil
+    LoadNil,
+    /// R(A) := value of upvalue B of the closure
+    GetUpval,
+    /// value of upvalue A of the closure := RK(B)
+    SetUpval,
+    /// R(A) := new upvalue holding nil
+    NewCell,
+    /// R(A) := value of the upvalue in R(B)
+    GetCell,
+    /// value of the upvalue in R(A) := RK(B)
+    SetCell,
+    /// R(A) := [R(B), ..., R(B + C - 1)]
+    NewArray,
+    /// R(A) := RK(B)[RK(C)]
+    GetElem,
+    /// R(A)[RK(B)] := RK(C)
+    SetElem,
+    /// R(A) := length of the array RK(B)
+    Len,
+    /// R(A) := new record of record type Bx
+    NewRec,
+    /// R(A) := field C of the record R(B)
+    GetField,
+    /// field B of the record R(A) := RK(C)
+    SetField,
+    /// R(A) := field named K(Bx) of the record R(A)
+    GetNamed,
+    /// field named K(Bx) of the record R(A) := R(A + 1)
+    SetNamed,
+    /// R(A) := R(A) is a record of record type Bx
+    IsRec,
+    /// R(A) := RK(B) + RK(C), which joins two strings.
+    Add,
+    /// R(A) := RK(B) - RK(C)
+    Sub,
+    /// R(A) := RK(B) * RK(C)
+    Mul,
+    /// R(A) := RK(B) / RK(C)
+    Div,
+    /// R(A) := RK(B) % RK(C)
+    Mod,
+    /// R(A) := RK(B) + RK(C) of two integers
+    IAdd,
+    /// R(A) := RK(B) - RK(C) of two integers
+    ISub,
+    /// R(A) := RK(B) * RK(C) of two integers
+    IMul,
+    /// R(A) := RK(B) / RK(C) of two integers
+    IDiv,
+    /// R(A) := RK(B) % RK(C) of two integers
+    IMod,
+    /// R(A) := RK(B) .. RK(C)
+    Concat,
+    /// R(A) := -RK(B)
+    Neg,
+    /// R(A) := -RK(B) of an integer
+    INeg,
+    /// R(A) := not RK(B) of a boolean
+    Not,
+    /// R(A) := R(B) .. ... .. R(B + C - 1) of the displayed values
+    BuildString,
+    /// pc += sBx
+    Jmp,
+    /// if (RK(B) == RK(C)) != A then pc++
+    Eq,
+    /// if (RK(B) != RK(C)) != A then pc++
+    Ne,
+    /// if (RK(B) < RK(C)) != A then pc++
+    Lt,
+    /// if (RK(B) <= RK(C)) != A then pc++
+    Le,
+    /// if (RK(B) > RK(C)) != A then pc++
+    Gt,
+    /// if (RK(B) >= RK(C)) != A then pc++
+    Ge,
+    /// if R(A) != C then pc++, R(A) must be a boolean
+    Test,
+    /// R(A) := R(A)(R(A + 1), ..., R(A + B))
+    Call,
+    /// return R(A)
+    Return,
+    /// R(A) := closure of function Bx
+    Closure,
+    /// throw R(A)
+    Throw,
+    /// fail because no pattern matches R(A)
+    NoMatch,
+    /// pc := target of jump table Bx for R(A)
+    Switch,
+    /// print R(A)
+    Print,
+    /// print R(A) followed by a new line
+    PrintLn,
+    /// print R(A), which must be an integer
+    IPrint,
+    /// print R(A), which must be a boolean
+    BPrint,
+    /// stop the execution
+    Halt,
+}
+
+/// Kinds of operands an operation uses, which defines how an instruction is displayed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    /// No operands.
+    None,
+    /// Register A.
+    A,
+    /// Register A and register or value B.
+    AB,
+    /// Register A and registers or values B and C.
+    ABC,
+    /// Register A and RK(B).
+    ARk,
+    /// Register A, RK(B) and RK(C).
+    ARkRk,
+    /// Register A, register or value B and RK(C).
+    ABRk,
+    /// Register A and index Bx.
+    ABx,
+    /// Register A and constant K(Bx).
+    AKx,
+    /// Signed offset sBx.
+    SBx,
+}
+
+impl OpCode {
+    /// Get the operands used by the operation.
+    pub fn mode(&self) -> Mode {
+        match *self {
+            OpCode::Halt => Mode::None,
+            OpCode::LoadNil | OpCode::NewCell | OpCode::Return | OpCode::Throw | OpCode::NoMatch
+            | OpCode::Print | OpCode::PrintLn | OpCode::IPrint | OpCode::BPrint => Mode::A,
+            OpCode::Move | OpCode::IMove | OpCode::GetUpval | OpCode::GetCell | OpCode::Call => Mode::AB,
+            OpCode::LoadBool | OpCode::NewArray | OpCode::GetField | OpCode::BuildString | OpCode::Test => Mode::ABC,
+            OpCode::SetUpval | OpCode::SetCell | OpCode::Len | OpCode::Neg | OpCode::INeg | OpCode::Not => Mode::ARk,
+            OpCode::GetElem | OpCode::SetElem | OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div
+            | OpCode::Mod | OpCode::IAdd | OpCode::ISub | OpCode::IMul | OpCode::IDiv | OpCode::IMod
+            | OpCode::Concat | OpCode::Eq | OpCode::Ne | OpCode::Lt | OpCode::Le | OpCode::Gt
+            | OpCode::Ge => Mode::ARkRk,
+            OpCode::SetField => Mode::ABRk,
+            OpCode::NewRec | OpCode::IsRec | OpCode::Closure | OpCode::Switch => Mode::ABx,
+            OpCode::LoadK | OpCode::GetNamed | OpCode::SetNamed => Mode::AKx,
+            OpCode::Jmp => Mode::SBx,
+        }
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OpCode::Move => write!(f, "move"),
+            OpCode::IMove => write!(f, "imove"),
+            OpCode::LoadK => write!(f, "loadk"),
+            OpCode::LoadBool => write!(f, "loadbool"),
+            OpCode::LoadNil => write!(f, "loadnil"),
+            OpCode::GetUpval => write!(f, "getupval"),
+            OpCode::SetUpval => write!(f, "setupval"),
+            OpCode::NewCell => write!(f, "newcell"),
+            OpCode::GetCell => write!(f, "getcell"),
+            OpCode::SetCell => write!(f, "setcell"),
+            OpCode::NewArray => write!(f, "newarray"),
+            OpCode::GetElem => write!(f, "getelem"),
+            OpCode::SetElem => write!(f, "setelem"),
+            OpCode::Len => write!(f, "len"),
+            OpCode::NewRec => write!(f, "newrec"),
+            OpCode::GetField => write!(f, "getfield"),
+            OpCode::SetField => write!(f, "setfield"),
+            OpCode::GetNamed => write!(f, "getnamed"),
+            OpCode::SetNamed => write!(f, "setnamed"),
+            OpCode::IsRec => write!(f, "isrec"),
+            OpCode::Add => write!(f, "add"),
+            OpCode::Sub => write!(f, "sub"),
+            OpCode::Mul => write!(f, "mul"),
+            OpCode::Div => write!(f, "div"),
+            OpCode::Mod => write!(f, "mod"),
+            OpCode::IAdd => write!(f, "iadd"),
+            OpCode::ISub => write!(f, "isub"),
+            OpCode::IMul => write!(f, "imul"),
+            OpCode::IDiv => write!(f, "idiv"),
+            OpCode::IMod => write!(f, "imod"),
+            OpCode::Concat => write!(f, "concat"),
+            OpCode::Neg => write!(f, "neg"),
+            OpCode::INeg => write!(f, "ineg"),
+            OpCode::Not => write!(f, "not"),
+            OpCode::BuildString => write!(f, "buildstring"),
+            OpCode::Jmp => write!(f, "jmp"),
+            OpCode::Eq => write!(f, "eq"),
+            OpCode::Ne => write!(f, "ne"),
+            OpCode::Lt => write!(f, "lt"),
+            OpCode::Le => write!(f, "le"),
+            OpCode::Gt => write!(f, "gt"),
+            OpCode::Ge => write!(f, "ge"),
+            OpCode::Test => write!(f, "test"),
+            OpCode::Call => write!(f, "call"),
+            OpCode::Return => write!(f, "return"),
+            OpCode::Closure => write!(f, "closure"),
+            OpCode::Throw => write!(f, "throw"),
+            OpCode::NoMatch => write!(f, "nomatch"),
+            OpCode::Switch => write!(f, "switch"),
+            OpCode::Print => write!(f, "print"),
+            OpCode::PrintLn => write!(f, "println"),
+            OpCode::IPrint => write!(f, "iprint"),
+            OpCode::BPrint => write!(f, "bprint"),
+            OpCode::Halt => write!(f, "halt"),
+        }
+    }
+}
+
+// The operations in the order of their codes.
+const OPCODES: [OpCode; 54] = [
+    OpCode::Move, OpCode::IMove, OpCode::LoadK, OpCode::LoadBool, OpCode::LoadNil, OpCode::GetUpval,
+    OpCode::SetUpval, OpCode::NewCell, OpCode::GetCell, OpCode::SetCell, OpCode::NewArray,
+    OpCode::GetElem, OpCode::SetElem, OpCode::Len, OpCode::NewRec, OpCode::GetField, OpCode::SetField,
+    OpCode::GetNamed, OpCode::SetNamed, OpCode::IsRec, OpCode::Add, OpCode::Sub, OpCode::Mul,
+    OpCode::Div, OpCode::Mod, OpCode::IAdd, OpCode::ISub, OpCode::IMul, OpCode::IDiv, OpCode::IMod,
+    OpCode::Concat, OpCode::Neg, OpCode::INeg, OpCode::Not, OpCode::BuildString, OpCode::Jmp,
+    OpCode::Eq, OpCode::Ne, OpCode::Lt, OpCode::Le, OpCode::Gt, OpCode::Ge, OpCode::Test,
+    OpCode::Call, OpCode::Return, OpCode::Closure, OpCode::Throw, OpCode::NoMatch, OpCode::Switch,
+    OpCode::Print, OpCode::PrintLn, OpCode::IPrint, OpCode::BPrint, OpCode::Halt,
+];
+
+impl From<OpCode> for u8 {
+    fn from(original: OpCode) -> u8 {
+        OPCODES.iter().position(|&opcode| opcode == original).expect("Every operation has a code!") as u8
+    }
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = BytecodeError;
+
+    fn try_from(original: u8) -> Result<Self, Self::Error> {
+        OPCODES.get(usize::from(original)).cloned().ok_or(BytecodeError::UnknownInstruction(original))
+    }
+}
+
+/// One encoded instruction of the register machine.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Instruction(u32);
+
+impl Instruction {
+    /// Encodes an instruction with the operands A, B and C.
+    pub fn abc(opcode: OpCode, a: usize, b: usize, c: usize) -> Instruction {
+        debug_assert!(a <= MAX_A && b <= MAX_B && c <= MAX_B, "Operands out of range!");
+        Instruction(u32::from(u8::from(opcode))
+            | (a as u32) << POSITION_A
+            | (b as u32) << POSITION_B
+            | (c as u32) << POSITION_C)
+    }
+
+    /// Encodes an instruction with the operands A and Bx.
+    pub fn abx(opcode: OpCode, a: usize, bx: usize) -> Instruction {
+        debug_assert!(a <= MAX_A && bx <= MAX_BX, "Operands out of range!");
+        Instruction(u32::from(u8::from(opcode)) | (a as u32) << POSITION_A | (bx as u32) << POSITION_BX)
+    }
+
+    /// Encodes an instruction with the operands A and sBx.
+    pub fn asbx(opcode: OpCode, a: usize, sbx: i32) -> Instruction {
+        debug_assert!((-MAX_SBX..=MAX_SBX).contains(&sbx), "Offset out of range!");
+        Instruction::abx(opcode, a, (sbx + MAX_SBX) as usize)
+    }
+
+    /// Decodes the operation.
+    pub fn opcode(&self) -> Result<OpCode, BytecodeError> {
+        OpCode::try_from((self.0 & ((1 << SIZE_OP) - 1)) as u8)
+    }
+
+    pub fn a(&self) -> usize {
+        (self.0 >> POSITION_A) as usize & MAX_A
+    }
+
+    pub fn b(&self) -> usize {
+        (self.0 >> POSITION_B) as usize & MAX_B
+    }
+
+    pub fn c(&self) -> usize {
+        (self.0 >> POSITION_C) as usize & MAX_B
+    }
+
+    pub fn bx(&self) -> usize {
+        (self.0 >> POSITION_BX) as usize & MAX_BX
+    }
+
+    pub fn sbx(&self) -> i32 {
+        self.bx() as i32 - MAX_SBX
+    }
+
+    /// Replaces the operand A.
+    pub fn set_a(&mut self, a: usize) {
+        debug_assert!(a <= MAX_A, "Operand out of range!");
+        self.0 = self.0 & !((MAX_A as u32) << POSITION_A) | (a as u32) << POSITION_A;
+    }
+
+    /// Replaces the operand sBx.
+    pub fn set_sbx(&mut self, sbx: i32) {
+        debug_assert!((-MAX_SBX..=MAX_SBX).contains(&sbx), "Offset out of range!");
+        self.0 = self.0 & !((MAX_BX as u32) << POSITION_BX) | ((sbx + MAX_SBX) as u32) << POSITION_BX;
+    }
+}
+
+/// Displays an RK operand as register or constant index prefixed by `k`.
+fn rk(operand: usize) -> String {
+    if operand & BIT_RK != 0 {
+        format!("k{}", operand & MAX_RK_CONSTANT)
+    } else {
+        operand.to_string()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opcode = match self.opcode() {
+            Ok(opcode) => opcode,
+            Err(_) => return write!(f, "0x{:08x}", self.0),
+        };
+
+        match opcode.mode() {
+            Mode::None => write!(f, "{}", opcode),
+            Mode::A => write!(f, "{} {}", opcode, self.a()),
+            Mode::AB => write!(f, "{} {} {}", opcode, self.a(), self.b()),
+            Mode::ABC => write!(f, "{} {} {} {}", opcode, self.a(), self.b(), self.c()),
+            Mode::ARk => write!(f, "{} {} {}", opcode, self.a(), rk(self.b())),
+            Mode::ARkRk => write!(f, "{} {} {} {}", opcode, self.a(), rk(self.b()), rk(self.c())),
+            Mode::ABRk => write!(f, "{} {} {} {}", opcode, self.a(), self.b(), rk(self.c())),
+            Mode::ABx => write!(f, "{} {} {}", opcode, self.a(), self.bx()),
+            Mode::AKx => write!(f, "{} {} k{}", opcode, self.a(), self.bx()),
+            Mode::SBx => write!(f, "{} {:+}", opcode, self.sbx()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    #[test]
+    fn encode_and_decode_operands() {
+        let sut = Instruction::abc(OpCode::IAdd, 255, BIT_RK | 3, 511);
+
+        assert_that!(sut.opcode(), is(equal_to(Ok(OpCode::IAdd))));
+        assert_that!(sut.a(), is(equal_to(255)));
+        assert_that!(sut.b(), is(equal_to(BIT_RK | 3)));
+        assert_that!(sut.c(), is(equal_to(511)));
+        assert_that!(Instruction::abx(OpCode::LoadK, 1, MAX_BX).bx(), is(equal_to(MAX_BX)));
+        assert_that!(Instruction::asbx(OpCode::Jmp, 0, -MAX_SBX).sbx(), is(equal_to(-MAX_SBX)));
+        assert_that!(Instruction::asbx(OpCode::Jmp, 0, 7).sbx(), is(equal_to(7)));
+    }
+
+    #[test]
+    fn replace_operands() {
+        let mut sut = Instruction::abc(OpCode::Add, 4, 1, 2);
+        sut.set_a(9);
+
+        assert_that!(sut, is(equal_to(Instruction::abc(OpCode::Add, 9, 1, 2))));
+
+        let mut sut = Instruction::asbx(OpCode::Jmp, 0, 0);
+        sut.set_sbx(-3);
+
+        assert_that!(sut.sbx(), is(equal_to(-3)));
+    }
+
+    #[test]
+    fn opcode_round_trip() {
+        for &opcode in OPCODES.iter() {
+            assert_that!(OpCode::try_from(u8::from(opcode)), is(equal_to(Ok(opcode))));
+        }
+
+        assert_that!(OpCode::try_from(54), is(equal_to(Err(BytecodeError::UnknownInstruction(54)))));
+    }
+
+    #[test]
+    fn display_instructions() {
+        assert_that!(Instruction::abc(OpCode::IAdd, 2, 0, BIT_RK | 1).to_string(), is(equal_to("iadd 2 0 k1".to_string())));
+        assert_that!(Instruction::abx(OpCode::LoadK, 1, 3).to_string(), is(equal_to("loadk 1 k3".to_string())));
+        assert_that!(Instruction::asbx(OpCode::Jmp, 0, -2).to_string(), is(equal_to("jmp -2".to_string())));
+        assert_that!(Instruction::asbx(OpCode::Jmp, 0, 2).to_string(), is(equal_to("jmp +2".to_string())));
+        assert_that!(Instruction::abc(OpCode::Halt, 0, 0, 0).to_string(), is(equal_to("halt".to_string())));
+    }
+}
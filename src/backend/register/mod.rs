@@ -0,0 +1,587 @@
+//! Register based alternative to the stack based virtual machine.
+pub mod instruction;
+pub mod translator;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+
+use backend::{MAX_CALL_DEPTH, StackTraceElement, VmError};
+use backend::heap::Heap;
+use backend::image::{Capture, Function, JumpTable, LineTable, RecordType};
+use backend::register::instruction::{BIT_RK, Instruction, MAX_RK_CONSTANT, OpCode};
+use backend::register::translator::{Constant, Handler, Program};
+use backend::value::{self, Value};
+use intermediate::ast::BinaryOperator;
+
+// State of a calling function which is restored when the called function returns.
+struct Frame {
+    registers: Vec<Value>,
+    closure: Option<Value>,
+    return_address: usize,
+    result: usize,
+}
+
+/// Register based virtual machine which executes a program translated from an image.
+///
+/// Each call has its own registers, the first of them hold the arguments. Values are moved
+/// between registers and operations name their operands directly instead of going through an
+/// operand stack, so the same program needs less instructions to execute. The output and the
+/// errors are the same as those of the stack based `VirtualMachine` for the image.
+pub struct RegisterMachine {
+    code: Vec<Instruction>,
+    lines: LineTable,
+    registers: Vec<Value>,
+    frames: Vec<Frame>,
+    closure: Option<Value>,
+    exception: Option<Value>,
+    heap: Heap,
+    constants: Vec<Value>,
+    records: Vec<Rc<RecordType>>,
+    functions: Vec<Rc<Function>>,
+    frame_sizes: HashMap<usize, usize>,
+    handlers: Vec<Handler>,
+    jump_tables: Vec<JumpTable>,
+    output: Box<dyn Write>,
+    program_counter: usize,
+    instruction_start: usize,
+}
+
+impl RegisterMachine {
+    /// Creates a register machine which prints to STDOUT.
+    pub fn new(program: Program) -> RegisterMachine {
+        RegisterMachine::with_output(program, Box::new(io::stdout()))
+    }
+
+    /// Creates a register machine which prints to the given output.
+    ///
+    /// The string constants are allocated in the heap up front.
+    pub fn with_output(program: Program, output: Box<dyn Write>) -> RegisterMachine {
+        let mut heap = Heap::new();
+        let strings: Vec<Value> = program.strings().iter()
+            .map(|string| heap.new_string(string.clone()))
+            .collect();
+        let constants = program.constants().iter()
+            .map(|&constant| match constant {
+                Constant::Nil => Value::Nil,
+                Constant::Integer(value) => Value::Integer(value),
+                Constant::Real(value) => Value::Real(value),
+                Constant::Character(value) => Value::Character(value),
+                Constant::Boolean(value) => Value::Boolean(value),
+                Constant::String(index) => strings[index],
+            })
+            .collect();
+        let frame_sizes = program.functions().iter()
+            .map(Function::address)
+            .zip(program.frame_sizes().iter().cloned())
+            .collect();
+
+        RegisterMachine {
+            code: program.code().to_vec(),
+            lines: program.lines().clone(),
+            registers: vec![Value::Nil; program.registers()],
+            frames: Vec::new(),
+            closure: None,
+            exception: None,
+            heap,
+            constants,
+            records: program.records().iter().cloned().map(Rc::new).collect(),
+            functions: program.functions().iter().cloned().map(Rc::new).collect(),
+            frame_sizes,
+            handlers: program.handlers().to_vec(),
+            jump_tables: program.jump_tables().to_vec(),
+            output,
+            program_counter: 0,
+            instruction_start: 0,
+        }
+    }
+
+    /// Run the program.
+    pub fn run(&mut self) -> Result<(), VmError> {
+        let result = self.run_until_halt();
+        let _ = self.output.flush();
+        result
+    }
+
+    fn run_until_halt(&mut self) -> Result<(), VmError> {
+        while self.program_counter < self.code.len() {
+            self.instruction_start = self.program_counter;
+            let instruction = self.code[self.program_counter];
+            self.program_counter += 1;
+            let opcode = instruction.opcode()
+                .map_err(|_| self.error(format!("Bad instruction {}!", instruction)))?;
+
+            if OpCode::Halt == opcode {
+                break;
+            }
+
+            if let Err(error) = self.execute(opcode, instruction) {
+                self.throw(error)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Continues at the nearest handler with the exception in its register. Returns the error
+    // with the stack trace if there is no handler.
+    fn throw(&mut self, error: VmError) -> Result<(), VmError> {
+        let exception = match self.exception.take() {
+            Some(exception) => exception,
+            None => self.heap.new_string(error.message().to_string()),
+        };
+        let mut address = self.instruction_start;
+        let mut depth = self.frames.len();
+
+        let register = loop {
+            if let Some(handler) = self.handlers.iter().find(|handler| handler.range().covers(address)) {
+                self.program_counter = handler.range().address();
+                break handler.register();
+            }
+
+            if 0 == depth {
+                return Err(error.with_stack_trace(self.stack_trace()));
+            }
+
+            depth -= 1;
+            // The calling instruction is right before the return address.
+            address = self.frames[depth].return_address - 1;
+        };
+
+        if depth < self.frames.len() {
+            self.frames.truncate(depth + 1);
+            let frame = self.frames.pop().expect("Frame of the handler!");
+            self.registers = frame.registers;
+            self.closure = frame.closure;
+        }
+
+        self.registers[register] = exception;
+        Ok(())
+    }
+
+    fn stack_trace(&self) -> Vec<StackTraceElement> {
+        let mut stack_trace = Vec::with_capacity(self.frames.len());
+        let mut closure = self.closure;
+
+        for frame in self.frames.iter().rev() {
+            let name = closure
+                .and_then(|closure| self.heap.closure(closure).ok())
+                .map_or(String::new(), |(function, _)| function.name().to_string());
+            stack_trace.push(StackTraceElement::new(name, self.lines.position_at(frame.return_address - 1)));
+            closure = frame.closure;
+        }
+
+        stack_trace
+    }
+
+    fn execute(&mut self, opcode: OpCode, instruction: Instruction) -> Result<(), VmError> {
+        let a = instruction.a();
+
+        match opcode {
+            OpCode::Move => self.registers[a] = self.registers[instruction.b()],
+            OpCode::IMove => {
+                let value = self.integer(self.registers[instruction.b()])?;
+                self.registers[a] = Value::Integer(value);
+            },
+            OpCode::LoadK => self.registers[a] = self.constants[instruction.bx()],
+            OpCode::LoadBool => {
+                self.registers[a] = Value::Boolean(0 != instruction.b());
+
+                if 0 != instruction.c() {
+                    self.program_counter += 1;
+                }
+            },
+            OpCode::LoadNil => self.registers[a] = Value::Nil,
+            OpCode::GetUpval => {
+                let upvalue = self.closure_upvalue(instruction.b())?;
+                self.registers[a] = self.heap.upvalue(upvalue).map_err(|e| self.error(e))?;
+            },
+            OpCode::SetUpval => {
+                let value = self.rk(instruction.b());
+                let upvalue = self.closure_upvalue(a)?;
+                self.heap.set_upvalue(upvalue, value).map_err(|e| self.error(e))?;
+            },
+            OpCode::NewCell => self.registers[a] = self.heap.new_upvalue(Value::Nil),
+            OpCode::GetCell => {
+                let upvalue = self.registers[instruction.b()];
+                self.registers[a] = self.heap.upvalue(upvalue).map_err(|e| self.error(e))?;
+            },
+            OpCode::SetCell => {
+                let value = self.rk(instruction.b());
+                self.heap.set_upvalue(self.registers[a], value).map_err(|e| self.error(e))?;
+            },
+            OpCode::NewArray => {
+                let start = instruction.b();
+                let elements = self.registers[start..start + instruction.c()].to_vec();
+                self.registers[a] = self.heap.new_array(elements);
+            },
+            OpCode::GetElem => {
+                let (array, index) = (self.rk(instruction.b()), self.rk(instruction.c()));
+                self.registers[a] = self.heap.element(array, index).map_err(|e| self.error(e))?;
+            },
+            OpCode::SetElem => {
+                let (index, value) = (self.rk(instruction.b()), self.rk(instruction.c()));
+                self.heap.set_element(self.registers[a], index, value).map_err(|e| self.error(e))?;
+            },
+            OpCode::Len => {
+                let length = self.heap.array(self.rk(instruction.b())).map_err(|e| self.error(e))?.len();
+                self.registers[a] = Value::Integer(length as i64);
+            },
+            OpCode::NewRec => {
+                let index = instruction.bx();
+                let record_type = match self.records.get(index) {
+                    Some(record_type) => Rc::clone(record_type),
+                    None => return Err(self.error(format!("Unknown record type {}!", index))),
+                };
+                self.registers[a] = self.heap.new_record(record_type);
+            },
+            OpCode::GetField => {
+                let record = self.registers[instruction.b()];
+                self.registers[a] = self.heap.field(record, instruction.c()).map_err(|e| self.error(e))?;
+            },
+            OpCode::SetField => {
+                let value = self.rk(instruction.c());
+                self.heap.set_field(self.registers[a], instruction.b(), value).map_err(|e| self.error(e))?;
+            },
+            OpCode::GetNamed => {
+                let record = self.registers[a];
+                let index = self.named_field_index(record, self.constants[instruction.bx()])?;
+                self.registers[a] = self.heap.field(record, index).map_err(|e| self.error(e))?;
+            },
+            OpCode::SetNamed => {
+                let (record, value) = (self.registers[a], self.registers[a + 1]);
+                let index = self.named_field_index(record, self.constants[instruction.bx()])?;
+                self.heap.set_field(record, index, value).map_err(|e| self.error(e))?;
+            },
+            OpCode::IsRec => {
+                let index = instruction.bx();
+                let record_type = match self.records.get(index) {
+                    Some(record_type) => record_type,
+                    None => return Err(self.error(format!("Unknown record type {}!", index))),
+                };
+                let result = self.heap.record_type(self.registers[a])
+                    .is_ok_and(|actual| actual.name() == record_type.name());
+                self.registers[a] = Value::Boolean(result);
+            },
+            OpCode::Add => self.arithmetic(BinaryOperator::Add, instruction)?,
+            OpCode::Sub => self.arithmetic(BinaryOperator::Subtract, instruction)?,
+            OpCode::Mul => self.arithmetic(BinaryOperator::Multiply, instruction)?,
+            OpCode::Div => self.arithmetic(BinaryOperator::Divide, instruction)?,
+            OpCode::Mod => self.arithmetic(BinaryOperator::Modulo, instruction)?,
+            OpCode::IAdd => self.integer_arithmetic(BinaryOperator::Add, instruction)?,
+            OpCode::ISub => self.integer_arithmetic(BinaryOperator::Subtract, instruction)?,
+            OpCode::IMul => self.integer_arithmetic(BinaryOperator::Multiply, instruction)?,
+            OpCode::IDiv => self.integer_arithmetic(BinaryOperator::Divide, instruction)?,
+            OpCode::IMod => self.integer_arithmetic(BinaryOperator::Modulo, instruction)?,
+            OpCode::Concat => {
+                let (left, right) = (self.rk(instruction.b()), self.rk(instruction.c()));
+                self.registers[a] = self.heap.concat(left, right).map_err(|e| self.error(e))?;
+            },
+            OpCode::Neg => {
+                self.registers[a] = value::negate(self.rk(instruction.b())).map_err(|e| self.error(e))?;
+            },
+            OpCode::INeg => {
+                let operand = self.integer(self.rk(instruction.b()))?;
+                self.registers[a] = Value::Integer(operand.wrapping_neg());
+            },
+            OpCode::Not => {
+                let operand = self.boolean(self.rk(instruction.b()))?;
+                self.registers[a] = Value::Boolean(!operand);
+            },
+            OpCode::BuildString => {
+                let start = instruction.b();
+                let content: String = self.registers[start..start + instruction.c()].iter()
+                    .map(|&part| self.heap.display(part))
+                    .collect();
+                self.registers[a] = self.heap.new_string(content);
+            },
+            OpCode::Jmp => self.jump(instruction.sbx()),
+            OpCode::Eq => self.compare(BinaryOperator::Equal, instruction)?,
+            OpCode::Ne => self.compare(BinaryOperator::NotEqual, instruction)?,
+            OpCode::Lt => self.compare(BinaryOperator::LessThan, instruction)?,
+            OpCode::Le => self.compare(BinaryOperator::LessThanEqual, instruction)?,
+            OpCode::Gt => self.compare(BinaryOperator::GreaterThan, instruction)?,
+            OpCode::Ge => self.compare(BinaryOperator::GreaterThanEqual, instruction)?,
+            OpCode::Test => {
+                if self.boolean(self.registers[a])? != (0 != instruction.c()) {
+                    self.program_counter += 1;
+                }
+            },
+            OpCode::Call => self.call(a, instruction.b())?,
+            OpCode::Return => {
+                let result = self.registers[a];
+                let frame = match self.frames.pop() {
+                    Some(frame) => frame,
+                    None => return Err(self.error("Return outside of function!")),
+                };
+                self.registers = frame.registers;
+                self.closure = frame.closure;
+                self.program_counter = frame.return_address;
+                self.registers[frame.result] = result;
+            },
+            OpCode::Closure => {
+                let index = instruction.bx();
+                let function = match self.functions.get(index) {
+                    Some(function) => Rc::clone(function),
+                    None => return Err(self.error(format!("Unknown function {}!", index))),
+                };
+                let mut upvalues = Vec::with_capacity(function.captures().len());
+
+                for capture in function.captures() {
+                    upvalues.push(match *capture {
+                        Capture::Local(index) => self.registers[usize::from(index)],
+                        Capture::Upvalue(index) => self.closure_upvalue(usize::from(index))?,
+                    });
+                }
+
+                self.registers[a] = self.heap.new_closure(function, upvalues);
+            },
+            OpCode::Throw => {
+                let exception = self.registers[a];
+                let message = format!("Uncaught exception '{}'!", self.heap.display(exception));
+                self.exception = Some(exception);
+                return Err(self.error(message));
+            },
+            OpCode::NoMatch => {
+                let message = format!("No match for value '{}'!", self.heap.display(self.registers[a]));
+                return Err(self.error(message));
+            },
+            // Reals are equal to the integers with the same value like for `eq`.
+            OpCode::Switch => {
+                let index = instruction.bx();
+                let table = match self.jump_tables.get(index) {
+                    Some(table) => table,
+                    None => return Err(self.error(format!("Unknown jump table {}!", index))),
+                };
+                self.program_counter = match self.registers[a] {
+                    Value::Integer(value) => table.target(value),
+                    Value::Real(value) if value == (value as i64) as f64 => table.target(value as i64),
+                    _ => table.default(),
+                };
+            },
+            OpCode::Print => {
+                let text = self.heap.display(self.registers[a]);
+                self.print(&text)?;
+            },
+            OpCode::PrintLn => {
+                let text = self.heap.display(self.registers[a]) + "\n";
+                self.print(&text)?;
+            },
+            OpCode::IPrint => {
+                let operand = self.integer(self.registers[a])?;
+                self.print(&operand.to_string())?;
+            },
+            OpCode::BPrint => {
+                let operand = self.boolean(self.registers[a])?;
+                self.print(&operand.to_string())?;
+            },
+            OpCode::Halt => unreachable!("The operation 'halt' should exit the loop before execute!"),
+        }
+
+        Ok(())
+    }
+
+    // Saves the registers of the caller and continues at the function of the closure in
+    // register `callee` with the following registers as arguments.
+    fn call(&mut self, callee: usize, count: usize) -> Result<(), VmError> {
+        let closure = self.registers[callee];
+        let function = Rc::clone(self.heap.closure(closure).map_err(|e| self.error(e))?.0);
+
+        function.check_arity(count).map_err(|e| self.error(e))?;
+
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(self.error("Call stack overflow!"));
+        }
+
+        let size = self.frame_sizes.get(&function.address()).cloned().unwrap_or(count);
+        let mut registers = vec![Value::Nil; size.max(count)];
+        registers[..count].copy_from_slice(&self.registers[callee + 1..callee + 1 + count]);
+
+        self.frames.push(Frame {
+            registers: mem::replace(&mut self.registers, registers),
+            closure: self.closure.replace(closure),
+            return_address: self.program_counter,
+            result: callee,
+        });
+        self.program_counter = function.address();
+        Ok(())
+    }
+
+    fn jump(&mut self, offset: i32) {
+        self.program_counter = (self.program_counter as i64 + i64::from(offset)) as usize;
+    }
+
+    // Get the value of a register or constant operand.
+    fn rk(&self, operand: usize) -> Value {
+        if operand & BIT_RK != 0 {
+            self.constants[operand & MAX_RK_CONSTANT]
+        } else {
+            self.registers[operand]
+        }
+    }
+
+    // Get the reference of the upvalue with the given index of the executing closure.
+    fn closure_upvalue(&self, index: usize) -> Result<Value, VmError> {
+        let upvalue = match self.closure {
+            Some(closure) => self.heap.closure(closure).map_err(|e| self.error(e))?.1.get(index).cloned(),
+            None => None,
+        };
+        upvalue.ok_or_else(|| self.error(format!("Unknown upvalue {}!", index)))
+    }
+
+    fn named_field_index(&self, record: Value, name: Value) -> Result<usize, VmError> {
+        let name = self.heap.string(name).map_err(|e| self.error(e))?;
+        self.heap.field_index(record, name).map_err(|e| self.error(e))
+    }
+
+    // The right operand is checked first like the stack machine pops it first.
+    fn integer_arithmetic(&mut self, operator: BinaryOperator, instruction: Instruction) -> Result<(), VmError> {
+        let right = self.integer(self.rk(instruction.c()))?;
+        let left = self.integer(self.rk(instruction.b()))?;
+        self.registers[instruction.a()] = value::integer_arithmetic(operator, left, right).map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    // Adding two strings joins them, everything else is a numeric operation.
+    fn arithmetic(&mut self, operator: BinaryOperator, instruction: Instruction) -> Result<(), VmError> {
+        let (left, right) = (self.rk(instruction.b()), self.rk(instruction.c()));
+        let result = match (operator, left, right) {
+            (BinaryOperator::Add, Value::String(_), Value::String(_)) => self.heap.concat(left, right),
+            _ => value::arithmetic(operator, left, right),
+        };
+        self.registers[instruction.a()] = result.map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    // Skips the next instruction, which is a jump, unless the result is the expected one in A.
+    fn compare(&mut self, operator: BinaryOperator, instruction: Instruction) -> Result<(), VmError> {
+        let (left, right) = (self.rk(instruction.b()), self.rk(instruction.c()));
+        let result = self.heap.compare(operator, left, right).map_err(|e| self.error(e))?;
+
+        if result != Value::Boolean(0 != instruction.a()) {
+            self.program_counter += 1;
+        }
+
+        Ok(())
+    }
+
+    fn integer(&self, value: Value) -> Result<i64, VmError> {
+        match value {
+            Value::Integer(value) => Ok(value),
+            value => Err(self.error(format!("Expected Integer but got {}!", value.type_name()))),
+        }
+    }
+
+    // Only booleans are allowed as conditions, there is no implicit truthiness of other values.
+    fn boolean(&self, value: Value) -> Result<bool, VmError> {
+        match value {
+            Value::Boolean(value) => Ok(value),
+            value => Err(self.error(format!("Expected Boolean but got {}!", value.type_name()))),
+        }
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), VmError> {
+        self.output.write_all(text.as_bytes())
+            .map_err(|_| VmError::new(String::from("Can't write output!"), None))
+    }
+
+    // The error is located at the source position of the currently executed instruction.
+    fn error<S: Into<String>>(&self, message: S) -> VmError {
+        VmError::new(message.into(), self.lines.position_at(self.instruction_start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use std::fs;
+    use std::path::Path;
+    use backend::VirtualMachine;
+    use backend::assembler::Assembler;
+    use backend::compiler::Compiler;
+    use backend::image::Image;
+    use backend::register::translator::translate;
+    use frontend::module::ModuleLoader;
+    use intermediate::optimize::Level;
+    use test_support::{compile, SharedOutput};
+
+    fn run(image: &Image) -> (Result<(), VmError>, String) {
+        let output = SharedOutput::new();
+        let result = RegisterMachine::with_output(translate(image).unwrap(), Box::new(output.clone())).run();
+        (result, output.text())
+    }
+
+    fn run_stack(image: &Image) -> (Result<(), VmError>, String) {
+        let output = SharedOutput::new();
+        let result = VirtualMachine::with_output(image.clone(), Box::new(output.clone())).run();
+        (result, output.text())
+    }
+
+    #[test]
+    fn run_arithmetic_and_locals() {
+        let (result, output) = run(&Image::from_code(Assembler::new().assemble(
+            "ipush 7\nistore 0\niload 0\nipush 2\nisub\nipush 3\nimul\nineg\niprint\nrpush 1.5\nipush 2\nmul\nprint\nhalt\nipush 1\niprint")));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("-153.0")));
+    }
+
+    #[test]
+    fn run_closures_and_records() {
+        let (result, output) = run(&compile(concat!(
+            "record P { x, y }\nvar p = P { x: 1, y: [2, 3] }\n",
+            "fun counter() {\n  var n = 0\n  return fun() { n += 1\n return n }\n}\n",
+            "var c = counter()\nc()\np.x = c()\nprintln(p)\nprintln(\"${p.y[1]}!\")")).unwrap());
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("P { x: 2, y: [2, 3] }\n3!\n")));
+    }
+
+    #[test]
+    fn run_errors_like_the_stack_machine() {
+        let sources = [
+            "var x = 1\nx = x / 0",
+            "var a = [1]\nprintln(a[2])",
+            "fun f(n) { return f(n + 1) }\nf(0)",
+            "fun boom(x) {\n  return fun() { throw x }\n}\nfun outer() {\n  return boom(1)()\n}\nouter()",
+        ];
+        let mut images: Vec<_> = sources.iter().map(|src| compile(src).unwrap()).collect();
+        images.push(Image::from_code(Assembler::new().assemble("ipush 1\nbpush 1\niadd\niprint")));
+        images.push(Image::from_code(Assembler::new().assemble("ipush 1\njumpiffalse 0")));
+
+        for image in images {
+            assert_that!(run(&image), is(equal_to(run_stack(&image))));
+        }
+    }
+
+    #[test]
+    fn handle_errors_in_called_functions() {
+        let (result, output) = run(&compile(concat!(
+            "fun f(a) { return a[3] }\n",
+            "try {\n  println(f([1]))\n} catch e {\n  println(\"caught ${e}\")\n}\nprintln(\"done\")")).unwrap());
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("caught Array index 3 out of bounds for length 1!\ndone\n")));
+    }
+
+    #[test]
+    fn register_machine_and_virtual_machine_agree() {
+        let mut paths: Vec<_> = fs::read_dir("test/programs").unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        paths.push(Path::new("test/modules/main.mvm").to_path_buf());
+
+        for path in paths {
+            let modules = ModuleLoader::new().load(&path).unwrap();
+
+            for &level in &[Level::O0, Level::O1] {
+                let image = Compiler::with_level(level).compile_modules(&modules).unwrap().0;
+
+                assert_that!(run(&image), is(equal_to(run_stack(&image))));
+            }
+        }
+    }
+}
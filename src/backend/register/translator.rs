@@ -0,0 +1,928 @@
+use std::char;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+
+use backend::bytecode::Instruction as StackInstruction;
+use backend::byte_conversion::*;
+use backend::image::{Capture, ExceptionHandler, Function, Image, JumpTable, LineTable, RecordType};
+use backend::memory::CodeMemory;
+use backend::register::instruction::*;
+
+/// A constant of the register code.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Constant {
+    Nil,
+    Integer(i64),
+    Real(f64),
+    Character(char),
+    Boolean(bool),
+    /// Index of the string in the string pool.
+    String(usize),
+}
+
+impl Constant {
+    // Identifies equal constants, reals by their bits to tell apart e.g. 0.0 and -0.0.
+    fn key(&self) -> (u8, u64) {
+        match *self {
+            Constant::Nil => (0, 0),
+            Constant::Integer(value) => (1, value as u64),
+            Constant::Real(value) => (2, value.to_bits()),
+            Constant::Character(value) => (3, u64::from(value as u32)),
+            Constant::Boolean(value) => (4, value as u64),
+            Constant::String(index) => (5, index as u64),
+        }
+    }
+}
+
+/// An exception handler whose handling code expects the exception in a register.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Handler {
+    range: ExceptionHandler,
+    register: usize,
+}
+
+impl Handler {
+    pub fn new(range: ExceptionHandler, register: usize) -> Handler {
+        Handler { range, register }
+    }
+
+    /// Get the covered instructions and the address of the handling code.
+    pub fn range(&self) -> &ExceptionHandler {
+        &self.range
+    }
+
+    /// Get the register the exception is stored into.
+    pub fn register(&self) -> usize {
+        self.register
+    }
+}
+
+/// A program of the register machine translated from an image.
+///
+/// Addresses of functions, handlers, jump tables and the line table are instruction indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    code: Vec<Instruction>,
+    constants: Vec<Constant>,
+    strings: Vec<String>,
+    records: Vec<RecordType>,
+    functions: Vec<Function>,
+    frame_sizes: Vec<usize>,
+    registers: usize,
+    handlers: Vec<Handler>,
+    jump_tables: Vec<JumpTable>,
+    lines: LineTable,
+}
+
+impl Program {
+    pub fn code(&self) -> &[Instruction] {
+        &self.code
+    }
+
+    pub fn constants(&self) -> &[Constant] {
+        &self.constants
+    }
+
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    pub fn records(&self) -> &[RecordType] {
+        &self.records
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Get the number of registers of each function in the order of the functions.
+    pub fn frame_sizes(&self) -> &[usize] {
+        &self.frame_sizes
+    }
+
+    /// Get the number of registers of the top level code.
+    pub fn registers(&self) -> usize {
+        self.registers
+    }
+
+    pub fn handlers(&self) -> &[Handler] {
+        &self.handlers
+    }
+
+    pub fn jump_tables(&self) -> &[JumpTable] {
+        &self.jump_tables
+    }
+
+    pub fn lines(&self) -> &LineTable {
+        &self.lines
+    }
+}
+
+/// Translates the stack byte code of an image into register code.
+///
+/// The height of the operand stack must be the same each time an instruction is reached, as
+/// the Java verifier demands. This way every stack slot becomes a fixed register following the
+/// local variables of the function. Local variables and constants are used as operands directly
+/// instead of being pushed, comparisons followed by a conditional jump become one test and
+/// values stored into a local variable are computed into its register right away. Code which is
+/// never reached is dropped.
+///
+/// Returns an error message for code which can not be translated, e.g. because the stack height
+/// differs between paths or too many registers are needed.
+pub fn translate(image: &Image) -> Result<Program, String> {
+    let flow = Flow::analyze(image)?;
+    let mut translator = Translator::new(image, &flow);
+    translator.translate()?;
+    translator.finish()
+}
+
+// An instruction reached by the control flow.
+struct Node {
+    instruction: StackInstruction,
+    operand: u64,
+    owner: usize,
+    depth: usize,
+    next: usize,
+}
+
+impl Node {
+    fn falls_through(&self) -> bool {
+        !matches!(self.instruction, StackInstruction::Jump | StackInstruction::Ret | StackInstruction::Throw
+            | StackInstruction::NoMatch | StackInstruction::TableSwitch | StackInstruction::Halt)
+    }
+}
+
+// The stack heights of the reached instructions and the register layout of the functions. The
+// top level code is owner 0, function `i` of the image is owner `i + 1`.
+struct Flow {
+    nodes: BTreeMap<usize, Node>,
+    leaders: HashSet<usize>,
+    end_reached: bool,
+    locals: Vec<usize>,
+    registers: Vec<usize>,
+}
+
+impl Flow {
+    fn analyze(image: &Image) -> Result<Flow, String> {
+        let mut flow = Flow {
+            nodes: BTreeMap::new(),
+            leaders: HashSet::new(),
+            end_reached: false,
+            locals: vec![0; image.functions().len() + 1],
+            registers: vec![0; image.functions().len() + 1],
+        };
+        let memory = CodeMemory::new(image.code().to_vec());
+        flow.explore(image, &memory, 0, 0)?;
+
+        for (index, function) in image.functions().iter().enumerate() {
+            flow.locals[index + 1] = usize::from(function.arity());
+            flow.explore(image, &memory, function.address(), index + 1)?;
+        }
+
+        flow.layout(image)?;
+        Ok(flow)
+    }
+
+    fn explore(&mut self, image: &Image, memory: &CodeMemory, entry: usize, owner: usize) -> Result<(), String> {
+        self.leaders.insert(entry);
+        let mut pending = vec![(entry, 0)];
+
+        while let Some((address, depth)) = pending.pop() {
+            if address >= memory.len() {
+                self.end_reached = true;
+                continue;
+            }
+
+            if let Some(node) = self.nodes.get(&address) {
+                if node.owner != owner {
+                    return Err(format!("Code at {} belongs to more than one function!", address));
+                }
+
+                if node.depth != depth {
+                    return Err(format!("Stack height at {} differs between paths!", address));
+                }
+
+                continue;
+            }
+
+            let node = Flow::decode(memory, address, owner, depth)?;
+            let (pops, pushes) = effect(node.instruction, node.operand);
+
+            if depth < pops {
+                return Err(format!("Stack underflow at {}!", address));
+            }
+
+            let after = depth - pops + pushes;
+            let mut targets = Vec::new();
+
+            match node.instruction {
+                StackInstruction::Jump | StackInstruction::JumpIfFalse | StackInstruction::JumpIfTrue =>
+                    targets.push(node.operand as usize),
+                StackInstruction::TableSwitch => {
+                    let table = image.jump_tables().get(node.operand as usize)
+                        .ok_or_else(|| format!("Unknown jump table {}!", node.operand))?;
+                    targets.extend(table.targets());
+                    targets.push(table.default());
+                },
+                _ => (),
+            }
+
+            for &target in &targets {
+                self.leaders.insert(target);
+                pending.push((target, after));
+            }
+
+            if node.falls_through() {
+                pending.push((node.next, after));
+            }
+
+            if let Some(handler) = image.handlers().iter().find(|handler| handler.covers(address)) {
+                self.leaders.insert(handler.address());
+                pending.push((handler.address(), 1));
+            }
+
+            self.nodes.insert(address, node);
+        }
+
+        Ok(())
+    }
+
+    fn decode(memory: &CodeMemory, address: usize, owner: usize, depth: usize) -> Result<Node, String> {
+        let opcode = memory.fetch(address)?;
+        let instruction = StackInstruction::try_from(opcode)
+            .map_err(|_| format!("Bad opcode 0x{:02x}!", opcode))?;
+        let operand = match instruction.operands().first() {
+            Some(operand) => {
+                let start = address + 1;
+
+                match operand.size() {
+                    1 => u64::from(memory.fetch(start)?),
+                    2 => u64::from(memory.fetch_short(start)?),
+                    ADDRESS_SIZE => memory.fetch_address(start)? as u64,
+                    _ => memory.fetch_word(start)?,
+                }
+            },
+            None => 0,
+        };
+
+        Ok(Node { instruction, operand, owner, depth, next: address + instruction.size() })
+    }
+
+    // The registers of a function are its local variables followed by the stack slots.
+    fn layout(&mut self, image: &Image) -> Result<(), String> {
+        let mut depths = vec![0; self.locals.len()];
+
+        for node in self.nodes.values() {
+            let locals = &mut self.locals[node.owner];
+
+            match node.instruction {
+                StackInstruction::IStore | StackInstruction::Store | StackInstruction::ILoad
+                | StackInstruction::Load | StackInstruction::NewUpvalue | StackInstruction::LoadUpvalue
+                | StackInstruction::StoreUpvalue => *locals = (*locals).max(node.operand as usize + 1),
+                StackInstruction::MkClosure => {
+                    if let Some(function) = image.functions().get(node.operand as usize) {
+                        for capture in function.captures() {
+                            if let Capture::Local(index) = *capture {
+                                *locals = (*locals).max(usize::from(index) + 1);
+                            }
+                        }
+                    }
+                },
+                _ => (),
+            }
+
+            let (pops, pushes) = effect(node.instruction, node.operand);
+            let depth = &mut depths[node.owner];
+            *depth = (*depth).max(node.depth).max(node.depth - pops + pushes);
+        }
+
+        for (owner, depth) in depths.into_iter().enumerate() {
+            self.registers[owner] = self.locals[owner] + depth;
+
+            if self.registers[owner] > MAX_A + 1 {
+                return Err(String::from("Too many registers!"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The number of values an instruction pops from and pushes onto the stack.
+fn effect(instruction: StackInstruction, operand: u64) -> (usize, usize) {
+    let count = operand as usize;
+
+    match instruction {
+        StackInstruction::Nop | StackInstruction::Jump | StackInstruction::Halt
+        | StackInstruction::NewUpvalue => (0, 0),
+        StackInstruction::IPush | StackInstruction::RPush | StackInstruction::CPush
+        | StackInstruction::NPush | StackInstruction::SPush | StackInstruction::BPush
+        | StackInstruction::ILoad | StackInstruction::Load | StackInstruction::NewRec
+        | StackInstruction::MkClosure | StackInstruction::LoadUpvalue
+        | StackInstruction::GetUpvalue => (0, 1),
+        StackInstruction::IStore | StackInstruction::Store | StackInstruction::IPrint
+        | StackInstruction::BPrint | StackInstruction::Print | StackInstruction::PrintLn
+        | StackInstruction::Pop | StackInstruction::JumpIfFalse | StackInstruction::JumpIfTrue
+        | StackInstruction::StoreUpvalue | StackInstruction::SetUpvalue | StackInstruction::Ret
+        | StackInstruction::Throw | StackInstruction::TableSwitch | StackInstruction::NoMatch => (1, 0),
+        StackInstruction::INeg | StackInstruction::Neg | StackInstruction::Not
+        | StackInstruction::ArrayLength | StackInstruction::GetField | StackInstruction::GetNamedField
+        | StackInstruction::IsRec => (1, 1),
+        StackInstruction::IAdd | StackInstruction::ISub | StackInstruction::IMul | StackInstruction::IDiv
+        | StackInstruction::IRem | StackInstruction::Add | StackInstruction::Sub | StackInstruction::Mul
+        | StackInstruction::Div | StackInstruction::Rem | StackInstruction::Eq | StackInstruction::Ne
+        | StackInstruction::Lt | StackInstruction::Le | StackInstruction::Gt | StackInstruction::Ge
+        | StackInstruction::Concat | StackInstruction::GetElem => (2, 1),
+        StackInstruction::SetField | StackInstruction::SetNamedField => (2, 0),
+        StackInstruction::SetElem => (3, 0),
+        StackInstruction::Dup => (1, 2),
+        StackInstruction::Dup2 => (2, 4),
+        StackInstruction::NewArray | StackInstruction::BuildString => (count, 1),
+        StackInstruction::CallIndirect => (count + 1, 1),
+    }
+}
+
+// A value on the stack which is not yet stored in the register of its slot.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operand {
+    Register(usize),
+    Constant(usize),
+}
+
+// The last emitted instruction, whose result register may be replaced by a local variable.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Producer {
+    index: usize,
+    integer: bool,
+}
+
+struct Translator<'a> {
+    image: &'a Image,
+    flow: &'a Flow,
+    code: Vec<Instruction>,
+    origins: Vec<usize>,
+    constants: Vec<Constant>,
+    constant_indices: HashMap<(u8, u64), usize>,
+    addresses: Vec<(usize, usize)>,
+    jumps: Vec<(usize, usize)>,
+    stack: Vec<Operand>,
+    base: usize,
+    producer: Option<Producer>,
+    origin: usize,
+    fused: Option<usize>,
+}
+
+impl<'a> Translator<'a> {
+    fn new(image: &'a Image, flow: &'a Flow) -> Translator<'a> {
+        Translator {
+            image,
+            flow,
+            code: Vec::new(),
+            origins: Vec::new(),
+            constants: Vec::new(),
+            constant_indices: HashMap::new(),
+            addresses: Vec::new(),
+            jumps: Vec::new(),
+            stack: Vec::new(),
+            base: 0,
+            producer: None,
+            origin: 0,
+            fused: None,
+        }
+    }
+
+    // Translates the reached instructions in the order of their addresses. At the start of a
+    // basic block all stack slots are in their registers.
+    fn translate(&mut self) -> Result<(), String> {
+        let flow = self.flow;
+        let mut previous: Option<&Node> = None;
+
+        for (&address, node) in &flow.nodes {
+            let falls_into = previous.is_some_and(|previous| previous.falls_through() && previous.next == address);
+            previous = Some(node);
+            self.origin = address;
+
+            if self.fused == Some(address) {
+                self.fused = None;
+                self.addresses.push((address, self.code.len() - 1));
+                continue;
+            }
+
+            if flow.leaders.contains(&address) || !falls_into {
+                if falls_into {
+                    self.materialize_all()?;
+                }
+
+                self.base = flow.locals[node.owner];
+                self.stack = (0..node.depth).map(|slot| Operand::Register(self.base + slot)).collect();
+                self.producer = None;
+            }
+
+            self.addresses.push((address, self.code.len()));
+            self.translate_instruction(node)?;
+        }
+
+        if flow.end_reached {
+            self.origin = self.image.code().len();
+            self.addresses.push((self.origin, self.code.len()));
+            self.emit(Instruction::abc(OpCode::Halt, 0, 0, 0));
+        }
+
+        Ok(())
+    }
+
+    fn translate_instruction(&mut self, node: &Node) -> Result<(), String> {
+        let depth = self.stack.len();
+        let operand = node.operand as usize;
+
+        match node.instruction {
+            StackInstruction::Nop => (),
+            StackInstruction::IPush => self.push_constant(Constant::Integer(word_to_int(node.operand)))?,
+            StackInstruction::RPush => self.push_constant(Constant::Real(word_to_float(node.operand)))?,
+            StackInstruction::CPush => match char::from_u32(node.operand as u32) {
+                Some(value) if node.operand <= u64::from(u32::MAX) => self.push_constant(Constant::Character(value))?,
+                _ => return Err(format!("Bad character code point {}!", node.operand)),
+            },
+            StackInstruction::NPush => self.push_constant(Constant::Nil)?,
+            StackInstruction::BPush => self.push_constant(Constant::Boolean(0 != operand))?,
+            StackInstruction::SPush => {
+                let index = self.string_constant(operand)?;
+                self.stack.push(Operand::Constant(index));
+            },
+            StackInstruction::ILoad | StackInstruction::Load => self.stack.push(Operand::Register(operand)),
+            StackInstruction::IStore => self.store(operand, true)?,
+            StackInstruction::Store => self.store(operand, false)?,
+            StackInstruction::IAdd => self.binary(OpCode::IAdd, true)?,
+            StackInstruction::ISub => self.binary(OpCode::ISub, true)?,
+            StackInstruction::IMul => self.binary(OpCode::IMul, true)?,
+            StackInstruction::IDiv => self.binary(OpCode::IDiv, true)?,
+            StackInstruction::IRem => self.binary(OpCode::IMod, true)?,
+            StackInstruction::Add => self.binary(OpCode::Add, false)?,
+            StackInstruction::Sub => self.binary(OpCode::Sub, false)?,
+            StackInstruction::Mul => self.binary(OpCode::Mul, false)?,
+            StackInstruction::Div => self.binary(OpCode::Div, false)?,
+            StackInstruction::Rem => self.binary(OpCode::Mod, false)?,
+            StackInstruction::Concat => self.binary(OpCode::Concat, false)?,
+            StackInstruction::GetElem => self.binary(OpCode::GetElem, false)?,
+            StackInstruction::INeg => self.unary(OpCode::INeg, true)?,
+            StackInstruction::Neg => self.unary(OpCode::Neg, false)?,
+            StackInstruction::Not => self.unary(OpCode::Not, false)?,
+            StackInstruction::ArrayLength => self.unary(OpCode::Len, true)?,
+            StackInstruction::IPrint => self.consume(OpCode::IPrint)?,
+            StackInstruction::BPrint => self.consume(OpCode::BPrint)?,
+            StackInstruction::Print => self.consume(OpCode::Print)?,
+            StackInstruction::PrintLn => self.consume(OpCode::PrintLn)?,
+            StackInstruction::Ret => self.consume(OpCode::Return)?,
+            StackInstruction::Throw => self.consume(OpCode::Throw)?,
+            StackInstruction::NoMatch => self.consume(OpCode::NoMatch)?,
+            StackInstruction::Halt => self.emit(Instruction::abc(OpCode::Halt, 0, 0, 0)),
+            StackInstruction::Dup => {
+                let top = self.stack[depth - 1];
+                self.stack.push(top);
+            },
+            StackInstruction::Dup2 => {
+                let first = self.stack[depth - 2];
+                let second = self.stack[depth - 1];
+                self.stack.push(first);
+                self.stack.push(second);
+            },
+            StackInstruction::Pop => {
+                self.stack.pop();
+            },
+            StackInstruction::NewArray => self.sequence(OpCode::NewArray, operand, operand)?,
+            StackInstruction::BuildString => self.sequence(OpCode::BuildString, operand, operand)?,
+            StackInstruction::CallIndirect => self.sequence(OpCode::Call, operand + 1, operand)?,
+            StackInstruction::SetElem => {
+                let c = self.rk(depth - 1)?;
+                let b = self.rk(depth - 2)?;
+                let a = self.register(depth - 3)?;
+                self.stack.truncate(depth - 3);
+                self.emit(Instruction::abc(OpCode::SetElem, a, b, c));
+            },
+            StackInstruction::NewRec => {
+                let a = self.base + depth;
+                self.produce(Instruction::abx(OpCode::NewRec, a, operand), false);
+            },
+            StackInstruction::GetField => {
+                let b = self.register(depth - 1)?;
+                self.stack.pop();
+                let a = self.base + depth - 1;
+                self.produce(Instruction::abc(OpCode::GetField, a, b, operand), false);
+            },
+            StackInstruction::SetField => {
+                let c = self.rk(depth - 1)?;
+                let a = self.register(depth - 2)?;
+                self.stack.truncate(depth - 2);
+                self.emit(Instruction::abc(OpCode::SetField, a, operand, c));
+            },
+            // The named field operations work in the registers of their stack slots.
+            StackInstruction::GetNamedField | StackInstruction::IsRec => {
+                let bx = match node.instruction {
+                    StackInstruction::GetNamedField => self.string_constant(operand)?,
+                    _ => operand,
+                };
+                let opcode = match node.instruction {
+                    StackInstruction::GetNamedField => OpCode::GetNamed,
+                    _ => OpCode::IsRec,
+                };
+                self.materialize(depth - 1)?;
+                self.emit(Instruction::abx(opcode, self.base + depth - 1, bx));
+            },
+            StackInstruction::SetNamedField => {
+                let bx = self.string_constant(operand)?;
+                self.materialize(depth - 2)?;
+                self.materialize(depth - 1)?;
+                self.stack.truncate(depth - 2);
+                self.emit(Instruction::abx(OpCode::SetNamed, self.base + depth - 2, bx));
+            },
+            StackInstruction::MkClosure => {
+                let a = self.base + depth;
+                self.produce(Instruction::abx(OpCode::Closure, a, operand), false);
+            },
+            StackInstruction::NewUpvalue => {
+                self.protect_local(operand, depth)?;
+                self.emit(Instruction::abc(OpCode::NewCell, operand, 0, 0));
+            },
+            StackInstruction::LoadUpvalue => {
+                let a = self.base + depth;
+                self.produce(Instruction::abc(OpCode::GetCell, a, operand, 0), false);
+            },
+            StackInstruction::StoreUpvalue => {
+                let b = self.rk(depth - 1)?;
+                self.stack.pop();
+                self.emit(Instruction::abc(OpCode::SetCell, operand, b, 0));
+            },
+            StackInstruction::GetUpvalue => {
+                let a = self.base + depth;
+                self.produce(Instruction::abc(OpCode::GetUpval, a, operand, 0), false);
+            },
+            StackInstruction::SetUpvalue => {
+                let b = self.rk(depth - 1)?;
+                self.stack.pop();
+                self.emit(Instruction::abc(OpCode::SetUpval, operand, b, 0));
+            },
+            StackInstruction::Jump => {
+                self.materialize_all()?;
+                self.jump(operand);
+            },
+            StackInstruction::JumpIfFalse | StackInstruction::JumpIfTrue => {
+                let a = self.register(depth - 1)?;
+                self.stack.pop();
+                self.materialize_all()?;
+                let expected = (StackInstruction::JumpIfTrue == node.instruction) as usize;
+                self.emit(Instruction::abc(OpCode::Test, a, 0, expected));
+                self.jump(operand);
+            },
+            StackInstruction::Eq => self.compare(OpCode::Eq, node)?,
+            StackInstruction::Ne => self.compare(OpCode::Ne, node)?,
+            StackInstruction::Lt => self.compare(OpCode::Lt, node)?,
+            StackInstruction::Le => self.compare(OpCode::Le, node)?,
+            StackInstruction::Gt => self.compare(OpCode::Gt, node)?,
+            StackInstruction::Ge => self.compare(OpCode::Ge, node)?,
+            StackInstruction::TableSwitch => {
+                let a = self.register(depth - 1)?;
+                self.stack.pop();
+                self.materialize_all()?;
+                self.emit(Instruction::abx(OpCode::Switch, a, operand));
+            },
+        }
+
+        Ok(())
+    }
+
+    // Stores the top of the stack into a local variable. Integer stores check the type.
+    fn store(&mut self, local: usize, integer: bool) -> Result<(), String> {
+        let slot = self.stack.len() - 1;
+        self.protect_local(local, slot)?;
+        let top = self.base + slot;
+
+        match self.stack[slot] {
+            Operand::Constant(index) if !integer || self.is_integer_constant(index) =>
+                self.load_constant(local, index)?,
+            Operand::Register(register) if register == top && self.producer.is_some_and(|producer|
+                producer.index + 1 == self.code.len() && self.code[producer.index].a() == top
+                    && (!integer || producer.integer)) => {
+                let index = self.code.len() - 1;
+                self.code[index].set_a(local);
+            },
+            Operand::Register(register) if register == local && !integer => (),
+            _ => {
+                let register = self.register(slot)?;
+                let opcode = if integer { OpCode::IMove } else { OpCode::Move };
+                self.emit(Instruction::abc(opcode, local, register, 0));
+            },
+        }
+
+        self.stack.pop();
+        self.producer = None;
+        Ok(())
+    }
+
+    fn binary(&mut self, opcode: OpCode, integer: bool) -> Result<(), String> {
+        let depth = self.stack.len();
+        let c = self.rk(depth - 1)?;
+        let b = self.rk(depth - 2)?;
+        self.stack.truncate(depth - 2);
+        let a = self.base + depth - 2;
+        self.produce(Instruction::abc(opcode, a, b, c), integer);
+        Ok(())
+    }
+
+    fn unary(&mut self, opcode: OpCode, integer: bool) -> Result<(), String> {
+        let depth = self.stack.len();
+        let b = self.rk(depth - 1)?;
+        self.stack.pop();
+        let a = self.base + depth - 1;
+        self.produce(Instruction::abc(opcode, a, b, 0), integer);
+        Ok(())
+    }
+
+    // Emits an instruction using the value on top of the stack.
+    fn consume(&mut self, opcode: OpCode) -> Result<(), String> {
+        let depth = self.stack.len();
+        let a = self.register(depth - 1)?;
+        self.stack.pop();
+        self.emit(Instruction::abc(opcode, a, 0, 0));
+        Ok(())
+    }
+
+    // Emits an instruction using the given number of values on top of the stack, which must be
+    // in consecutive registers.
+    fn sequence(&mut self, opcode: OpCode, count: usize, operand: usize) -> Result<(), String> {
+        let depth = self.stack.len();
+
+        for slot in depth - count..depth {
+            self.materialize(slot)?;
+        }
+
+        self.stack.truncate(depth - count);
+        let a = self.base + depth - count;
+
+        match opcode {
+            OpCode::Call => {
+                self.emit(Instruction::abc(opcode, a, operand, 0));
+                self.stack.push(Operand::Register(a));
+            },
+            _ => self.produce(Instruction::abc(opcode, a, a, operand), false),
+        }
+
+        Ok(())
+    }
+
+    // A comparison followed by a conditional jump becomes a test of the comparison, else the
+    // boolean result is loaded into the register.
+    fn compare(&mut self, opcode: OpCode, node: &Node) -> Result<(), String> {
+        let depth = self.stack.len();
+        let c = self.rk(depth - 1)?;
+        let b = self.rk(depth - 2)?;
+        self.stack.truncate(depth - 2);
+
+        if let Some(jump) = self.flow.nodes.get(&node.next).filter(|jump| !self.flow.leaders.contains(&node.next)
+            && (StackInstruction::JumpIfFalse == jump.instruction || StackInstruction::JumpIfTrue == jump.instruction)) {
+            self.materialize_all()?;
+            let expected = (StackInstruction::JumpIfTrue == jump.instruction) as usize;
+            self.emit(Instruction::abc(opcode, expected, b, c));
+            self.origin = node.next;
+            self.jump(jump.operand as usize);
+            self.fused = Some(node.next);
+            return Ok(());
+        }
+
+        let a = self.base + depth - 2;
+        self.emit(Instruction::abc(opcode, 1, b, c));
+        self.emit(Instruction::asbx(OpCode::Jmp, 0, 1));
+        self.emit(Instruction::abc(OpCode::LoadBool, a, 0, 1));
+        self.emit(Instruction::abc(OpCode::LoadBool, a, 1, 0));
+        self.stack.push(Operand::Register(a));
+        Ok(())
+    }
+
+    fn jump(&mut self, target: usize) {
+        self.jumps.push((self.code.len(), target));
+        self.emit(Instruction::asbx(OpCode::Jmp, 0, 0));
+    }
+
+    fn push_constant(&mut self, constant: Constant) -> Result<(), String> {
+        let index = self.constant(constant)?;
+        self.stack.push(Operand::Constant(index));
+        Ok(())
+    }
+
+    fn constant(&mut self, constant: Constant) -> Result<usize, String> {
+        if let Some(&index) = self.constant_indices.get(&constant.key()) {
+            return Ok(index);
+        }
+
+        if self.constants.len() > MAX_BX {
+            return Err(String::from("Too many constants!"));
+        }
+
+        self.constants.push(constant);
+        self.constant_indices.insert(constant.key(), self.constants.len() - 1);
+        Ok(self.constants.len() - 1)
+    }
+
+    fn string_constant(&mut self, index: usize) -> Result<usize, String> {
+        if index >= self.image.strings().len() {
+            return Err(format!("Unknown string constant {}!", index));
+        }
+
+        self.constant(Constant::String(index))
+    }
+
+    fn is_integer_constant(&self, index: usize) -> bool {
+        matches!(self.constants[index], Constant::Integer(_))
+    }
+
+    // Get the operand of a stack slot as register or constant.
+    fn rk(&mut self, slot: usize) -> Result<usize, String> {
+        match self.stack[slot] {
+            Operand::Constant(index) if index <= MAX_RK_CONSTANT => Ok(BIT_RK | index),
+            _ => self.register(slot),
+        }
+    }
+
+    // Get the register holding the value of a stack slot.
+    fn register(&mut self, slot: usize) -> Result<usize, String> {
+        match self.stack[slot] {
+            Operand::Register(register) => Ok(register),
+            Operand::Constant(_) => {
+                self.materialize(slot)?;
+                Ok(self.base + slot)
+            },
+        }
+    }
+
+    // Moves the value of a stack slot into its register.
+    fn materialize(&mut self, slot: usize) -> Result<(), String> {
+        let register = self.base + slot;
+
+        match self.stack[slot] {
+            Operand::Register(source) if source == register => return Ok(()),
+            Operand::Register(source) => self.emit(Instruction::abc(OpCode::Move, register, source, 0)),
+            Operand::Constant(index) => self.load_constant(register, index)?,
+        }
+
+        self.stack[slot] = Operand::Register(register);
+        Ok(())
+    }
+
+    fn materialize_all(&mut self) -> Result<(), String> {
+        for slot in 0..self.stack.len() {
+            self.materialize(slot)?;
+        }
+
+        Ok(())
+    }
+
+    // Moves the slots below the given one which still refer to a local variable before it's
+    // overwritten.
+    fn protect_local(&mut self, local: usize, end: usize) -> Result<(), String> {
+        for slot in 0..end {
+            if Operand::Register(local) == self.stack[slot] {
+                self.materialize(slot)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_constant(&mut self, register: usize, index: usize) -> Result<(), String> {
+        let instruction = match self.constants[index] {
+            Constant::Nil => Instruction::abc(OpCode::LoadNil, register, 0, 0),
+            Constant::Boolean(value) => Instruction::abc(OpCode::LoadBool, register, value as usize, 0),
+            _ => Instruction::abx(OpCode::LoadK, register, index),
+        };
+        self.emit(instruction);
+        Ok(())
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.code.push(instruction);
+        self.origins.push(self.origin);
+        self.producer = None;
+    }
+
+    // Emits an instruction whose result is the new top of the stack.
+    fn produce(&mut self, instruction: Instruction, integer: bool) {
+        let register = instruction.a();
+        self.emit(instruction);
+        self.producer = Some(Producer { index: self.code.len() - 1, integer });
+        self.stack.push(Operand::Register(register));
+    }
+
+    // Get the instruction index of a stack code address.
+    fn relocate(&self, address: usize) -> usize {
+        let position = self.addresses.partition_point(|&(origin, _)| origin < address);
+        self.addresses.get(position).map_or(self.code.len(), |&(_, index)| index)
+    }
+
+    fn finish(mut self) -> Result<Program, String> {
+        for &(index, target) in &self.jumps {
+            let target = self.relocate(target.min(self.image.code().len()));
+            let offset = target as i64 - index as i64 - 1;
+
+            if offset.abs() > i64::from(MAX_SBX) {
+                return Err(String::from("Jump too far!"));
+            }
+
+            self.code[index].set_sbx(offset as i32);
+        }
+
+        let handlers = self.image.handlers().iter()
+            .filter_map(|handler| self.flow.nodes.get(&handler.address()).map(|node| Handler::new(
+                ExceptionHandler::new(
+                    self.relocate(handler.start()), self.relocate(handler.end()), self.relocate(handler.address())),
+                self.flow.locals[node.owner])))
+            .collect();
+        let jump_tables = self.image.jump_tables().iter()
+            .map(|table| JumpTable::new(
+                table.low(),
+                table.targets().iter().map(|&target| self.relocate(target)).collect(),
+                self.relocate(table.default())))
+            .collect();
+        let functions = self.image.functions().iter()
+            .map(|function| Function::new(
+                function.name().to_string(), self.relocate(function.address()), function.arity(),
+                function.captures().to_vec()))
+            .collect();
+        let mut lines = LineTable::new();
+
+        for (index, &origin) in self.origins.iter().enumerate() {
+            if let Some(position) = self.image.lines().position_at(origin) {
+                lines.add(index, position);
+            }
+        }
+
+        Ok(Program {
+            code: self.code,
+            constants: self.constants,
+            strings: self.image.strings().to_vec(),
+            records: self.image.records().to_vec(),
+            functions,
+            frame_sizes: self.flow.registers[1..].to_vec(),
+            registers: self.flow.registers[0],
+            handlers,
+            jump_tables,
+            lines,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use backend::assembler::Assembler;
+
+    fn translate_assembly(assembly: &str) -> Result<Program, String> {
+        translate(&Image::from_code(Assembler::new().assemble(assembly)))
+    }
+
+    fn listing(program: &Program) -> String {
+        program.code().iter().map(|instruction| format!("{}\n", instruction)).collect()
+    }
+
+    #[test]
+    fn translate_loop_to_register_operands() {
+        let program = translate_assembly(concat!(
+            "ipush 0\nistore 0\niload 0\nipush 10\nlt\njumpiffalse 47\n",
+            "iload 0\nipush 1\niadd\nistore 0\njump 11\nhalt")).unwrap();
+
+        assert_that!(listing(&program), is(equal_to(String::from(
+            "loadk 0 k0\nlt 0 0 k1\njmp +2\niadd 0 0 k2\njmp -4\nhalt\n"))));
+        assert_that!(program.constants(), is(equal_to(&[
+            Constant::Integer(0), Constant::Integer(10), Constant::Integer(1)][..])));
+        assert_that!(program.registers(), is(equal_to(3)));
+    }
+
+    #[test]
+    fn translate_comparison_to_boolean() {
+        let program = translate_assembly("load 0\nload 1\neq\nbprint").unwrap();
+
+        assert_that!(listing(&program), is(equal_to(String::from(
+            "eq 1 0 1\njmp +1\nloadbool 2 0 1\nloadbool 2 1 0\nbprint 2\nhalt\n"))));
+    }
+
+    #[test]
+    fn translate_call_into_consecutive_registers() {
+        let program = translate_assembly("load 0\nipush 2\nload 1\ncallindirect 2\nstore 1\nhalt").unwrap();
+
+        assert_that!(listing(&program), is(equal_to(String::from(
+            "move 2 0\nloadk 3 k0\nmove 4 1\ncall 2 2\nmove 1 2\nhalt\n"))));
+    }
+
+    #[test]
+    fn translate_keeps_pending_local_before_store() {
+        let program = translate_assembly("load 0\nipush 1\nstore 0\nprint").unwrap();
+
+        assert_that!(listing(&program), is(equal_to(String::from("move 1 0\nloadk 0 k0\nprint 1\nhalt\n"))));
+    }
+
+    #[test]
+    fn reject_untranslatable_code() {
+        assert_that!(translate_assembly("bpush 1\njumpiftrue 16\nipush 1\nnop\nhalt"),
+            is(equal_to(Err(String::from("Stack height at 16 differs between paths!")))));
+        assert_that!(translate_assembly("iadd"), is(equal_to(Err(String::from("Stack underflow at 0!")))));
+    }
+}
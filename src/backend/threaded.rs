@@ -0,0 +1,390 @@
+use std::char;
+use std::convert::TryFrom;
+use std::fmt;
+
+use backend::bytecode::Instruction;
+use backend::byte_conversion::*;
+use backend::memory::CodeMemory;
+use backend::value::Value;
+
+/// Index which stops the execution because it's beyond all operations.
+pub const END: usize = usize::MAX;
+
+/// A byte code instruction decoded at load time with its operands inline.
+///
+/// Addresses of jumps are indices of operations instead of byte code offsets. All pushes of
+/// constants become `Push` of the value, string constants are the preallocated strings.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operation {
+    Nop,
+    Push(Value),
+    IStore(usize),
+    Store(usize),
+    /// Both `iload` and `load`.
+    Load(usize),
+    IAdd,
+    ISub,
+    IMul,
+    IDiv,
+    IRem,
+    INeg,
+    IPrint,
+    Halt,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Neg,
+    Print,
+    PrintLn,
+    Dup,
+    Dup2,
+    Pop,
+    NewArray(usize),
+    GetElem,
+    SetElem,
+    ArrayLength,
+    BPrint,
+    Not,
+    Jump(usize),
+    JumpIfFalse(usize),
+    JumpIfTrue(usize),
+    /// Jump appended by the decoder to continue in the already decoded code, which is no
+    /// instruction of the byte code.
+    Continue(usize),
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Concat,
+    BuildString(usize),
+    NewRec(usize),
+    GetField(usize),
+    SetField(usize),
+    /// The name of the field as preallocated string.
+    GetNamedField(Value),
+    /// The name of the field as preallocated string.
+    SetNamedField(Value),
+    MkClosure(usize),
+    CallIndirect(usize),
+    Ret,
+    NewUpvalue(usize),
+    LoadUpvalue(usize),
+    StoreUpvalue(usize),
+    GetUpvalue(usize),
+    SetUpvalue(usize),
+    Throw,
+    TableSwitch(usize),
+    IsRec(usize),
+    NoMatch,
+    /// Byte code which can not be decoded fails when it's executed.
+    Fault(Fault),
+}
+
+/// Describes why byte code could not be decoded.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Fault {
+    BadOpcode(u8),
+    BadCharacter(u64),
+    UnknownString(usize),
+    /// The operands end beyond the byte code.
+    Truncated,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fault::BadOpcode(opcode) => write!(f, "Bad opcode 0x{:02x}!", opcode),
+            Fault::BadCharacter(code_point) => write!(f, "Bad character code point {}!", code_point),
+            Fault::UnknownString(index) => write!(f, "Unknown string constant {}!", index),
+            Fault::Truncated => write!(f, "Index out of bounds!"),
+        }
+    }
+}
+
+/// Byte code translated into a sequence of operations the virtual machine runs without decoding.
+///
+/// The code is decoded from the first byte on in one go. Code only reachable by jumping into the
+/// middle of an instruction or behind undecodable bytes is decoded on demand when an index of its
+/// address is requested and appended with a jump back to the already decoded code. Each operation
+/// remembers the offset of its instruction to look up source positions and exception handlers.
+pub struct ThreadedCode {
+    code: CodeMemory,
+    strings: Vec<Value>,
+    operations: Vec<Operation>,
+    offsets: Vec<usize>,
+    // Index of the operation decoded at each offset, `END` if none.
+    indices: Vec<usize>,
+}
+
+impl ThreadedCode {
+    /// Decodes the byte code using the given preallocated strings for `spush` and named fields.
+    pub fn new(byte_code: Vec<u8>, strings: Vec<Value>) -> ThreadedCode {
+        let mut threaded = ThreadedCode {
+            indices: vec![END; byte_code.len()],
+            code: CodeMemory::new(byte_code),
+            strings,
+            operations: Vec::new(),
+            offsets: Vec::new(),
+        };
+        threaded.index(0);
+        threaded
+    }
+
+    /// Get the index of the operation decoded from the given byte code offset, decoding it if
+    /// necessary. Offsets beyond the byte code are `END`.
+    pub fn index(&mut self, offset: usize) -> usize {
+        if offset >= self.code.len() {
+            return END;
+        }
+
+        if END == self.indices[offset] {
+            let start = self.operations.len();
+            self.decode_from(offset);
+
+            for index in start..self.operations.len() {
+                self.operations[index] = match self.operations[index] {
+                    Operation::Jump(target) => Operation::Jump(self.resolved(target)),
+                    Operation::JumpIfFalse(target) => Operation::JumpIfFalse(self.resolved(target)),
+                    Operation::JumpIfTrue(target) => Operation::JumpIfTrue(self.resolved(target)),
+                    Operation::Continue(target) => Operation::Continue(self.resolved(target)),
+                    operation => operation,
+                };
+            }
+        }
+
+        self.resolved(offset)
+    }
+
+    /// Decodes the operation at the given index from the byte code again, which is only meant to
+    /// measure the cost of decoding. The jumps added by the decoder are returned unchanged.
+    pub fn decode_again(&self, index: usize) -> Operation {
+        match self.operations[index] {
+            Operation::Continue(target) => Operation::Continue(target),
+            _ => match self.decode(self.offsets[index]).0 {
+                Operation::Jump(target) => Operation::Jump(self.resolved(target)),
+                Operation::JumpIfFalse(target) => Operation::JumpIfFalse(self.resolved(target)),
+                Operation::JumpIfTrue(target) => Operation::JumpIfTrue(self.resolved(target)),
+                operation => operation,
+            },
+        }
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Get the byte code offset of the instruction the operation was decoded from.
+    pub fn offset(&self, index: usize) -> usize {
+        self.offsets.get(index).cloned().unwrap_or_else(|| self.code.len())
+    }
+
+    // Decodes the instructions from the offset and all jump targets not decoded yet. The jumps
+    // keep their target offsets until all targets are decoded.
+    fn decode_from(&mut self, offset: usize) {
+        let mut pending = vec![offset];
+
+        while let Some(start) = pending.pop() {
+            if start >= self.code.len() || END != self.indices[start] {
+                continue;
+            }
+
+            let mut offset = start;
+
+            loop {
+                // Continues in the already decoded code or stops at the end.
+                if offset >= self.code.len() || END != self.indices[offset] {
+                    self.operations.push(Operation::Continue(offset));
+                    self.offsets.push(offset);
+                    break;
+                }
+
+                self.indices[offset] = self.operations.len();
+                let (operation, size) = self.decode(offset);
+                self.operations.push(operation);
+                self.offsets.push(offset);
+
+                match operation {
+                    Operation::Jump(target) | Operation::JumpIfFalse(target) | Operation::JumpIfTrue(target) =>
+                        pending.push(target),
+                    Operation::Fault(_) => break,
+                    _ => (),
+                }
+
+                offset += size;
+            }
+        }
+    }
+
+    fn resolved(&self, offset: usize) -> usize {
+        self.indices.get(offset).cloned().unwrap_or(END)
+    }
+
+    // Get the operation and the size of its instruction.
+    fn decode(&self, offset: usize) -> (Operation, usize) {
+        let opcode = match self.code.fetch(offset) {
+            Ok(opcode) => opcode,
+            Err(_) => return (Operation::Fault(Fault::Truncated), 1),
+        };
+        let instruction = match Instruction::try_from(opcode) {
+            Ok(instruction) => instruction,
+            Err(_) => return (Operation::Fault(Fault::BadOpcode(opcode)), 1),
+        };
+        let operand = match self.operand(instruction, offset + 1) {
+            Ok(operand) => operand,
+            Err(_) => return (Operation::Fault(Fault::Truncated), instruction.size()),
+        };
+        let index = operand as usize;
+        let operation = match instruction {
+            Instruction::Nop => Operation::Nop,
+            Instruction::IPush => Operation::Push(Value::Integer(operand as i64)),
+            Instruction::RPush => Operation::Push(Value::Real(f64::from_bits(operand))),
+            Instruction::CPush => match char::from_u32(operand as u32) {
+                Some(value) if operand <= u64::from(u32::MAX) => Operation::Push(Value::Character(value)),
+                _ => Operation::Fault(Fault::BadCharacter(operand)),
+            },
+            Instruction::NPush => Operation::Push(Value::Nil),
+            Instruction::SPush => match self.strings.get(index) {
+                Some(&value) => Operation::Push(value),
+                None => Operation::Fault(Fault::UnknownString(index)),
+            },
+            Instruction::BPush => Operation::Push(Value::Boolean(0 != operand)),
+            Instruction::IStore => Operation::IStore(index),
+            Instruction::Store => Operation::Store(index),
+            Instruction::ILoad | Instruction::Load => Operation::Load(index),
+            Instruction::IAdd => Operation::IAdd,
+            Instruction::ISub => Operation::ISub,
+            Instruction::IMul => Operation::IMul,
+            Instruction::IDiv => Operation::IDiv,
+            Instruction::IRem => Operation::IRem,
+            Instruction::INeg => Operation::INeg,
+            Instruction::IPrint => Operation::IPrint,
+            Instruction::Halt => Operation::Halt,
+            Instruction::Add => Operation::Add,
+            Instruction::Sub => Operation::Sub,
+            Instruction::Mul => Operation::Mul,
+            Instruction::Div => Operation::Div,
+            Instruction::Rem => Operation::Rem,
+            Instruction::Neg => Operation::Neg,
+            Instruction::Print => Operation::Print,
+            Instruction::PrintLn => Operation::PrintLn,
+            Instruction::Dup => Operation::Dup,
+            Instruction::Dup2 => Operation::Dup2,
+            Instruction::Pop => Operation::Pop,
+            Instruction::NewArray => Operation::NewArray(index),
+            Instruction::GetElem => Operation::GetElem,
+            Instruction::SetElem => Operation::SetElem,
+            Instruction::ArrayLength => Operation::ArrayLength,
+            Instruction::BPrint => Operation::BPrint,
+            Instruction::Not => Operation::Not,
+            Instruction::Jump => Operation::Jump(index),
+            Instruction::JumpIfFalse => Operation::JumpIfFalse(index),
+            Instruction::JumpIfTrue => Operation::JumpIfTrue(index),
+            Instruction::Eq => Operation::Eq,
+            Instruction::Ne => Operation::Ne,
+            Instruction::Lt => Operation::Lt,
+            Instruction::Le => Operation::Le,
+            Instruction::Gt => Operation::Gt,
+            Instruction::Ge => Operation::Ge,
+            Instruction::Concat => Operation::Concat,
+            Instruction::BuildString => Operation::BuildString(index),
+            Instruction::NewRec => Operation::NewRec(index),
+            Instruction::GetField => Operation::GetField(index),
+            Instruction::SetField => Operation::SetField(index),
+            Instruction::GetNamedField | Instruction::SetNamedField => match self.strings.get(index) {
+                Some(&name) if Instruction::GetNamedField == instruction => Operation::GetNamedField(name),
+                Some(&name) => Operation::SetNamedField(name),
+                None => Operation::Fault(Fault::UnknownString(index)),
+            },
+            Instruction::MkClosure => Operation::MkClosure(index),
+            Instruction::CallIndirect => Operation::CallIndirect(index),
+            Instruction::Ret => Operation::Ret,
+            Instruction::NewUpvalue => Operation::NewUpvalue(index),
+            Instruction::LoadUpvalue => Operation::LoadUpvalue(index),
+            Instruction::StoreUpvalue => Operation::StoreUpvalue(index),
+            Instruction::GetUpvalue => Operation::GetUpvalue(index),
+            Instruction::SetUpvalue => Operation::SetUpvalue(index),
+            Instruction::Throw => Operation::Throw,
+            Instruction::TableSwitch => Operation::TableSwitch(index),
+            Instruction::IsRec => Operation::IsRec(index),
+            Instruction::NoMatch => Operation::NoMatch,
+        };
+
+        (operation, instruction.size())
+    }
+
+    fn operand(&self, instruction: Instruction, offset: usize) -> Result<u64, &'static str> {
+        match instruction.operands().first() {
+            Some(operand) => match operand.size() {
+                1 => self.code.fetch(offset).map(u64::from),
+                2 => self.code.fetch_short(offset).map(u64::from),
+                ADDRESS_SIZE => self.code.fetch_address(offset).map(|address| address as u64),
+                _ => self.code.fetch_word(offset),
+            },
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use backend::assembler::Assembler;
+
+    fn threaded(assembly: &str) -> ThreadedCode {
+        ThreadedCode::new(Assembler::new().assemble(assembly), vec![Value::String(0)])
+    }
+
+    #[test]
+    fn decode_operands_inline() {
+        let sut = threaded("ipush 3\nspush 0\njumpiffalse 20\ngetnamedfield 0\nhalt");
+
+        assert_that!(sut.operations(), is(equal_to(&[
+            Operation::Push(Value::Integer(3)),
+            Operation::Push(Value::String(0)),
+            Operation::JumpIfFalse(4),
+            Operation::GetNamedField(Value::String(0)),
+            Operation::Halt,
+            Operation::Continue(END),
+        ][..])));
+        assert_that!(sut.offset(3), is(equal_to(17)));
+    }
+
+    #[test]
+    fn decode_jump_into_instruction() {
+        // The operand of bpush is the opcode of nop.
+        let mut sut = threaded("bpush 1\njump 1\nhalt");
+
+        assert_that!(sut.operations(), is(equal_to(&[
+            Operation::Push(Value::Boolean(true)),
+            Operation::Jump(4),
+            Operation::Halt,
+            Operation::Continue(END),
+            Operation::Nop,
+            Operation::Continue(1),
+        ][..])));
+        assert_that!(sut.index(1), is(equal_to(4)));
+        assert_that!(sut.index(3), is(equal_to(6)));
+        assert_that!(sut.operations()[6], is(equal_to(Operation::Fault(Fault::BadOpcode(0x00)))));
+        assert_that!(sut.index(100), is(equal_to(END)));
+
+        for index in 0..sut.operations().len() {
+            assert_that!(sut.decode_again(index), is(equal_to(sut.operations()[index])));
+        }
+    }
+
+    #[test]
+    fn decode_faults() {
+        let sut = ThreadedCode::new(vec![0x0f, 0xfe, 0x0f], Vec::new());
+
+        assert_that!(sut.operations(), is(equal_to(&[
+            Operation::Push(Value::Nil), Operation::Fault(Fault::BadOpcode(0xfe))][..])));
+        assert_that!(threaded("spush 1").operations()[0], is(equal_to(Operation::Fault(Fault::UnknownString(1)))));
+        assert_that!(ThreadedCode::new(vec![0x02, 0x00], Vec::new()).operations()[0],
+            is(equal_to(Operation::Fault(Fault::Truncated))));
+    }
+}
@@ -0,0 +1,236 @@
+use std::cmp::Ordering;
+
+use intermediate::ast::{BinaryOperator, UnaryOperator};
+
+/// A value the virtual machine operates on.
+///
+/// Values are small and copied around. Everything bigger lives in the heap and the value only
+/// holds the reference (index) to the heap object.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Value {
+    /// Absence of a value, e.g. of a declared but not initialized variable.
+    Nil,
+    /// Signed 64 bit integer.
+    Integer(i64),
+    /// 64 bit floating point number.
+    Real(f64),
+    /// Unicode character.
+    Character(char),
+    /// Boolean, the only type allowed in conditions.
+    Boolean(bool),
+    /// Reference to an immutable string in the heap.
+    String(usize),
+    /// Reference to an array in the heap.
+    Array(usize),
+    /// Reference to a record in the heap.
+    Record(usize),
+    /// Reference to a closure in the heap.
+    Closure(usize),
+    /// Reference to an upvalue in the heap, which holds a variable captured by closures.
+    Upvalue(usize),
+}
+
+impl Value {
+    /// Get the name of the type of the value as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Nil => "Nil",
+            Value::Integer(_) => "Integer",
+            Value::Real(_) => "Real",
+            Value::Character(_) => "Character",
+            Value::Boolean(_) => "Boolean",
+            Value::String(_) => "String",
+            Value::Array(_) => "Array",
+            Value::Record(_) => "Record",
+            Value::Closure(_) => "Function",
+            Value::Upvalue(_) => "Upvalue",
+        }
+    }
+}
+
+/// Applies an arithmetic operator to two values.
+///
+/// Integers wrap around on overflow. If one operand is a real number the other integer operand
+/// is converted to a real number. Returns an error message for division by zero and operands
+/// which are no numbers.
+pub fn arithmetic(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+    match (left, right) {
+        (Value::Integer(left), Value::Integer(right)) => integer_arithmetic(operator, left, right),
+        (Value::Integer(left), Value::Real(right)) => real_arithmetic(operator, left as f64, right),
+        (Value::Real(left), Value::Integer(right)) => real_arithmetic(operator, left, right as f64),
+        (Value::Real(left), Value::Real(right)) => real_arithmetic(operator, left, right),
+        _ => Err(format!(
+            "Operator '{}' can not be applied to {} and {}!", operator, left.type_name(), right.type_name())),
+    }
+}
+
+/// Applies an arithmetic operator to two integers.
+pub fn integer_arithmetic(operator: BinaryOperator, left: i64, right: i64) -> Result<Value, String> {
+    let result = match operator {
+        BinaryOperator::Add => left.wrapping_add(right),
+        BinaryOperator::Subtract => left.wrapping_sub(right),
+        BinaryOperator::Multiply => left.wrapping_mul(right),
+        BinaryOperator::Divide | BinaryOperator::Modulo if 0 == right =>
+            return Err(String::from("Division by zero!")),
+        BinaryOperator::Divide => left.wrapping_div(right),
+        BinaryOperator::Modulo => left.wrapping_rem(right),
+        _ => return Err(format!("Operator '{}' is not arithmetic!", operator)),
+    };
+
+    Ok(Value::Integer(result))
+}
+
+fn real_arithmetic(operator: BinaryOperator, left: f64, right: f64) -> Result<Value, String> {
+    let result = match operator {
+        BinaryOperator::Add => left + right,
+        BinaryOperator::Subtract => left - right,
+        BinaryOperator::Multiply => left * right,
+        BinaryOperator::Divide => left / right,
+        BinaryOperator::Modulo => left % right,
+        _ => return Err(format!("Operator '{}' is not arithmetic!", operator)),
+    };
+
+    Ok(Value::Real(result))
+}
+
+/// Negates a number.
+pub fn negate(value: Value) -> Result<Value, String> {
+    match value {
+        Value::Integer(value) => Ok(Value::Integer(value.wrapping_neg())),
+        Value::Real(value) => Ok(Value::Real(-value)),
+        _ => Err(format!("Operator '{}' can not be applied to {}!", UnaryOperator::Negate, value.type_name())),
+    }
+}
+
+/// Applies a comparison operator to two values.
+///
+/// Numbers are compared by their numeric value regardless if integer or real, and characters by
+/// their code point. All values may be compared for (in)equality, where values of different types
+/// are never equal. Arrays and records are only equal if they are the same object. Returns an error message if
+/// the values can not be ordered.
+///
+/// Strings are compared by reference here, see `Heap::compare` to compare them by content.
+pub fn compare(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+    let ordering = match (left, right) {
+        (Value::Integer(left), Value::Integer(right)) => left.partial_cmp(&right),
+        (Value::Integer(left), Value::Real(right)) => (left as f64).partial_cmp(&right),
+        (Value::Real(left), Value::Integer(right)) => left.partial_cmp(&(right as f64)),
+        (Value::Real(left), Value::Real(right)) => left.partial_cmp(&right),
+        (Value::Character(left), Value::Character(right)) => left.partial_cmp(&right),
+        _ => match operator {
+            BinaryOperator::Equal => return Ok(Value::Boolean(left == right)),
+            BinaryOperator::NotEqual => return Ok(Value::Boolean(left != right)),
+            _ => return Err(format!(
+                "Operator '{}' can not be applied to {} and {}!", operator, left.type_name(), right.type_name())),
+        },
+    };
+
+    apply_ordering(operator, ordering)
+}
+
+/// Evaluates a comparison operator for the ordering of two operands.
+///
+/// An ordering of `None` denotes incomparable operands like NaN, which are only unequal.
+pub fn apply_ordering(operator: BinaryOperator, ordering: Option<Ordering>) -> Result<Value, String> {
+    let result = match operator {
+        BinaryOperator::Equal => ordering == Some(Ordering::Equal),
+        BinaryOperator::NotEqual => ordering != Some(Ordering::Equal),
+        BinaryOperator::LessThan => ordering == Some(Ordering::Less),
+        BinaryOperator::LessThanEqual => ordering == Some(Ordering::Less) || ordering == Some(Ordering::Equal),
+        BinaryOperator::GreaterThan => ordering == Some(Ordering::Greater),
+        BinaryOperator::GreaterThanEqual =>
+            ordering == Some(Ordering::Greater) || ordering == Some(Ordering::Equal),
+        _ => return Err(format!("Operator '{}' is no comparison!", operator)),
+    };
+
+    Ok(Value::Boolean(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    #[test]
+    fn arithmetic_with_integers() {
+        assert_that!(arithmetic(BinaryOperator::Add, Value::Integer(2), Value::Integer(3)),
+            is(equal_to(Ok(Value::Integer(5)))));
+        assert_that!(arithmetic(BinaryOperator::Subtract, Value::Integer(2), Value::Integer(3)),
+            is(equal_to(Ok(Value::Integer(-1)))));
+        assert_that!(arithmetic(BinaryOperator::Multiply, Value::Integer(2), Value::Integer(3)),
+            is(equal_to(Ok(Value::Integer(6)))));
+        assert_that!(arithmetic(BinaryOperator::Divide, Value::Integer(7), Value::Integer(2)),
+            is(equal_to(Ok(Value::Integer(3)))));
+        assert_that!(arithmetic(BinaryOperator::Modulo, Value::Integer(-7), Value::Integer(2)),
+            is(equal_to(Ok(Value::Integer(-1)))));
+    }
+
+    #[test]
+    fn arithmetic_wraps_around() {
+        assert_that!(arithmetic(BinaryOperator::Add, Value::Integer(i64::MAX), Value::Integer(1)),
+            is(equal_to(Ok(Value::Integer(i64::MIN)))));
+        assert_that!(arithmetic(BinaryOperator::Divide, Value::Integer(i64::MIN), Value::Integer(-1)),
+            is(equal_to(Ok(Value::Integer(i64::MIN)))));
+    }
+
+    #[test]
+    fn arithmetic_division_by_zero() {
+        assert_that!(arithmetic(BinaryOperator::Divide, Value::Integer(1), Value::Integer(0)),
+            is(equal_to(Err(String::from("Division by zero!")))));
+        assert_that!(arithmetic(BinaryOperator::Modulo, Value::Integer(1), Value::Integer(0)),
+            is(equal_to(Err(String::from("Division by zero!")))));
+    }
+
+    #[test]
+    fn arithmetic_with_reals() {
+        assert_that!(arithmetic(BinaryOperator::Add, Value::Integer(1), Value::Real(0.5)),
+            is(equal_to(Ok(Value::Real(1.5)))));
+        assert_that!(arithmetic(BinaryOperator::Divide, Value::Real(1.0), Value::Integer(4)),
+            is(equal_to(Ok(Value::Real(0.25)))));
+    }
+
+    #[test]
+    fn arithmetic_with_wrong_types() {
+        assert_that!(arithmetic(BinaryOperator::Add, Value::Character('a'), Value::Integer(1)),
+            is(equal_to(Err(String::from("Operator '+' can not be applied to Character and Integer!")))));
+        assert_that!(arithmetic(BinaryOperator::Multiply, Value::Nil, Value::Array(0)),
+            is(equal_to(Err(String::from("Operator '*' can not be applied to Nil and Array!")))));
+    }
+
+    #[test]
+    fn negate_numbers() {
+        assert_that!(negate(Value::Integer(1)), is(equal_to(Ok(Value::Integer(-1)))));
+        assert_that!(negate(Value::Real(1.5)), is(equal_to(Ok(Value::Real(-1.5)))));
+        assert_that!(negate(Value::Nil), is(equal_to(Err(String::from("Operator '-' can not be applied to Nil!")))));
+    }
+
+    #[test]
+    fn compare_numbers() {
+        assert_that!(compare(BinaryOperator::LessThan, Value::Integer(1), Value::Integer(2)),
+            is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(compare(BinaryOperator::Equal, Value::Integer(1), Value::Real(1.0)),
+            is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(compare(BinaryOperator::GreaterThanEqual, Value::Real(0.5), Value::Integer(1)),
+            is(equal_to(Ok(Value::Boolean(false)))));
+        assert_that!(compare(BinaryOperator::NotEqual, Value::Real(f64::NAN), Value::Real(f64::NAN)),
+            is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(compare(BinaryOperator::LessThanEqual, Value::Character('a'), Value::Character('b')),
+            is(equal_to(Ok(Value::Boolean(true)))));
+    }
+
+    #[test]
+    fn compare_for_equality() {
+        assert_that!(compare(BinaryOperator::Equal, Value::Boolean(true), Value::Boolean(true)),
+            is(equal_to(Ok(Value::Boolean(true)))));
+        assert_that!(compare(BinaryOperator::Equal, Value::Nil, Value::Integer(0)),
+            is(equal_to(Ok(Value::Boolean(false)))));
+        assert_that!(compare(BinaryOperator::NotEqual, Value::Array(0), Value::Array(1)),
+            is(equal_to(Ok(Value::Boolean(true)))));
+    }
+
+    #[test]
+    fn compare_unordered_values() {
+        assert_that!(compare(BinaryOperator::LessThan, Value::Boolean(false), Value::Boolean(true)),
+            is(equal_to(Err(String::from("Operator '<' can not be applied to Boolean and Boolean!")))));
+    }
+}
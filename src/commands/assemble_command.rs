@@ -4,15 +4,19 @@ use std::path::Path;
 
 use commands::Command;
 use backend::assembler::Assembler;
+use backend::image::Image;
+use backend::peephole;
+use intermediate::optimize::Level;
 
 /// Command to translate assembly style code to byte code.
 pub struct AssembleCommand {
     file: String,
+    level: Level,
 }
 
 impl AssembleCommand {
-    pub fn new(file: String) -> AssembleCommand {
-        AssembleCommand { file}
+    pub fn new(file: String, level: Level) -> AssembleCommand {
+        AssembleCommand { file, level }
     }
 }
 
@@ -32,6 +36,10 @@ impl Command for AssembleCommand {
 
         let assembler = Assembler::new();
         let byte_code = assembler.assemble(&assembler_code);
+        let byte_code = match self.level {
+            Level::O0 => byte_code,
+            Level::O1 => peephole::optimize(&Image::from_code(byte_code)).code().to_vec(),
+        };
 
         let mut output = File::create(target_file)
             .expect("Can't create target file!");
@@ -0,0 +1,92 @@
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+use commands::Command;
+use commands::read_file_as_bytes;
+
+use backend::VirtualMachine;
+use backend::image::Image;
+use error;
+
+/// Command to measure the speed of the virtual machine running byte code.
+pub struct BenchCommand {
+    file: String,
+    runs: usize,
+}
+
+impl BenchCommand {
+    /// Creates the command which runs the byte code the given number of times.
+    pub fn new(file: String, runs: usize) -> BenchCommand {
+        BenchCommand { file, runs }
+    }
+}
+
+impl Command for BenchCommand {
+    fn execute(&self) {
+        let byte_code = read_file_as_bytes(Path::new(&self.file));
+        let image = match Image::from_bytes(byte_code) {
+            Ok(image) => image,
+            Err(e) => {
+                error(e);
+                process::exit(1);
+            }
+        };
+
+        let mut executed = 0;
+        let mut samples = Vec::with_capacity(self.runs);
+
+        for _ in 0..self.runs {
+            // The output of the program is discarded and loading the image is not measured.
+            let mut vm = VirtualMachine::with_output(image.clone(), Box::new(io::sink()));
+            let start = Instant::now();
+            let result = vm.run_counted();
+            let elapsed = start.elapsed();
+
+            if let Err(e) = result {
+                error(&format!("{}", e));
+                process::exit(1);
+            }
+
+            let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+            executed = vm.executed();
+            samples.push(executed as f64 / seconds);
+        }
+
+        let (mean, stddev) = mean_and_stddev(&samples);
+        println!("{}: {} runs, {} instructions per run", self.file, self.runs, executed);
+        println!("mean:   {:.0} instructions/s", mean);
+        println!("stddev: {:.0} instructions/s", stddev);
+    }
+}
+
+// Sample mean and standard deviation, the deviation of a single sample is zero.
+fn mean_and_stddev(samples: &[f64]) -> (f64, f64) {
+    let count = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / count;
+
+    if samples.len() < 2 {
+        return (mean, 0.0);
+    }
+
+    let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (count - 1.0);
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    #[test]
+    fn mean_and_stddev_of_samples() {
+        assert_that!(mean_and_stddev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]),
+            is(equal_to((5.0, (32.0f64 / 7.0).sqrt()))));
+    }
+
+    #[test]
+    fn mean_and_stddev_of_single_sample() {
+        assert_that!(mean_and_stddev(&[3.0]), is(equal_to((3.0, 0.0))));
+    }
+}
@@ -1,18 +1,79 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+use backend::compiler::Compiler;
 use commands::Command;
+use frontend::module::ModuleLoader;
+use intermediate::optimize::Level;
+use {error, warning};
 
 /// Command to compile to byte code.
 pub struct CompileCommand {
     file: String,
+    level: Level,
+    emit_ir: bool,
 }
 
 impl CompileCommand {
-    pub fn new(file: String) -> CompileCommand {
-        CompileCommand { file }
+    pub fn new(file: String, level: Level, emit_ir: bool) -> CompileCommand {
+        CompileCommand { file, level, emit_ir }
+    }
+
+    // Prints the intermediate representation the byte code would be generated from.
+    fn print_ir(&self, source_file: &Path) {
+        let program = ModuleLoader::new().load(source_file)
+            .and_then(|modules| Compiler::with_level(self.level).lower_modules(&modules));
+
+        match program {
+            Ok((program, warnings)) => {
+                for message in warnings {
+                    warning(&format!("{}", message));
+                }
+
+                print!("{}", program);
+            },
+            Err(e) => {
+                error(&format!("{}", e));
+                process::exit(1);
+            }
+        }
     }
 }
 
 impl Command for CompileCommand {
     fn execute(&self) {
-        println!("Compile file {} ...", self.file);
+        let source_file = Path::new(&self.file);
+
+        if self.emit_ir {
+            return self.print_ir(source_file);
+        }
+
+        let base_file_name = source_file.file_stem().unwrap();
+        let target_file = &format!("{}.mcode", base_file_name.to_str().unwrap());
+        let target_file = Path::new(target_file);
+        println!("Compile file {:?} to {:?} ...", &source_file, &target_file);
+
+        let image = ModuleLoader::new().load(source_file)
+            .and_then(|modules| Compiler::with_level(self.level).compile_modules(&modules));
+        let image = match image {
+            Ok((image, warnings)) => {
+                for message in warnings {
+                    warning(&format!("{}", message));
+                }
+
+                image
+            },
+            Err(e) => {
+                error(&format!("{}", e));
+                process::exit(1);
+            }
+        };
+
+        let mut output = File::create(target_file)
+            .expect("Can't create target file!");
+        output.write_all(&image.to_bytes())
+            .expect("Can't write target file!");
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,11 @@
 use std::path::Path;
+use std::process;
 
 use commands::Command;
 use backend::assembler::Assembler;
+use backend::image::Image;
 use commands::read_file_as_bytes;
+use error;
 
 /// Command to translate byte code to assembly style code.
 pub struct DisassembleCommand{
@@ -19,10 +22,31 @@ impl Command for DisassembleCommand {
     fn execute(&self) {
         println!("{}:", &self.file);
         let byte_code :Vec<u8> = read_file_as_bytes(Path::new(&self.file));
+        let image = match Image::from_bytes(byte_code) {
+            Ok(image) => image,
+            Err(e) => {
+                error(e);
+                process::exit(1);
+            }
+        };
 
         let assembler = Assembler::new();
-        let asm = assembler.disassemble(byte_code);
+        let asm = assembler.disassemble(image.code().to_vec());
         print!("{}", asm);
+
+        // The string pool, record types and jump tables are listed as comment, so the output stays valid assembly.
+        for (index, string) in image.strings().iter().enumerate() {
+            println!("// string {}: {:?}", index, string);
+        }
+
+        for (index, record) in image.records().iter().enumerate() {
+            println!("// record {}: {} {{ {} }}", index, record.name(), record.fields().join(", "));
+        }
+
+        for (index, table) in image.jump_tables().iter().enumerate() {
+            let targets: Vec<String> = table.targets().iter().map(|target| target.to_string()).collect();
+            println!("// jump table {}: low {} targets [{}] default {}", index, table.low(), targets.join(", "), table.default());
+        }
     }
 }
 
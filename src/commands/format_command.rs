@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+
+use commands::Command;
+use frontend::formatter::Formatter;
+use error;
+
+/// Command to format source code in the canonical style.
+pub struct FormatCommand {
+    file: String,
+    check: bool,
+}
+
+impl FormatCommand {
+    pub fn new(file: String, check: bool) -> FormatCommand {
+        FormatCommand { file, check }
+    }
+}
+
+impl Command for FormatCommand {
+    fn execute(&self) {
+        let mut source = String::new();
+
+        if File::open(&self.file).and_then(|mut f| f.read_to_string(&mut source)).is_err() {
+            error("Failed to read file!");
+            process::exit(1);
+        }
+
+        let formatted = match Formatter::new().format(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                error(&format!("{}", e));
+                process::exit(1);
+            }
+        };
+
+        if formatted == source {
+            return;
+        }
+
+        if self.check {
+            println!("File {} is not formatted.", self.file);
+            process::exit(1);
+        }
+
+        println!("Format file {} ...", self.file);
+        let mut output = File::create(&self.file)
+            .expect("Can't write source file!");
+        output.write_all(formatted.as_bytes())
+            .expect("Can't write source file!");
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::Path;
+use std::process;
+use std::thread;
+
+use commands::Command;
+use frontend::module::{self, ModuleLoader};
+use intermediate::interpreter::{self, Interpreter};
+use intermediate::linker;
+use intermediate::semantic::TypeChecker;
+use {error, warning};
+
+/// Command to execute source code without compiling it to byte code.
+pub struct InterpretCommand {
+    file: String,
+}
+
+impl InterpretCommand {
+    pub fn new(file: String) -> InterpretCommand {
+        InterpretCommand { file }
+    }
+}
+
+impl Command for InterpretCommand {
+    fn execute(&self) {
+        let modules = match ModuleLoader::new().load(Path::new(&self.file)) {
+            Ok(modules) => modules,
+            Err(e) => {
+                error(&format!("{}", e));
+                process::exit(1);
+            }
+        };
+        let program = linker::link(&modules)
+            .and_then(|statements| TypeChecker::new().check(&statements).map(|analysis| (statements, analysis)));
+        let (statements, analysis) = match program {
+            Ok(program) => program,
+            Err(e) => {
+                error(&format!("{}", module::locate(&modules, e)));
+                process::exit(1);
+            }
+        };
+
+        for message in analysis.warnings() {
+            warning(&format!("{}", module::locate(&modules, message.clone())));
+        }
+
+        let result = thread::Builder::new()
+            .stack_size(interpreter::STACK_SIZE)
+            .spawn(move || Interpreter::new(analysis).run(&statements))
+            .expect("Can't start interpreter thread!")
+            .join()
+            .expect("Interpreter thread panicked!");
+
+        if let Err(e) = result {
+            error(&format!("{}", e));
+            process::exit(1);
+        }
+    }
+}
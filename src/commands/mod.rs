@@ -1,8 +1,11 @@
 ///! This module provides the various CLI command implementations.
 
 mod assemble_command;
+mod bench_command;
 mod compile_command;
 mod disassemble_command;
+mod format_command;
+mod interpret_command;
 mod parse_command;
 mod run_command;
 
@@ -11,8 +14,11 @@ use std::fs::File;
 use std::io::prelude::*;
 
 pub use self::assemble_command::AssembleCommand;
+pub use self::bench_command::BenchCommand;
 pub use self::compile_command::CompileCommand;
 pub use self::disassemble_command::DisassembleCommand;
+pub use self::format_command::FormatCommand;
+pub use self::interpret_command::InterpretCommand;
 pub use self::parse_command::ParserCommand;
 pub use self::run_command::RunCommand;
 
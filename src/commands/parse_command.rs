@@ -4,6 +4,7 @@ use frontend::character_stream::CharacterStream;
 use frontend::lexer::Lexer;
 use frontend::parser::Parser;
 use frontend::token::TokenType;
+use intermediate::printer::{print, Format};
 use commands::Command;
 use error;
 
@@ -12,17 +13,19 @@ pub struct ParserCommand {
     file: String,
     print_tokens: bool,
     print_ast: bool,
+    ast_format: Format,
 }
 
 impl ParserCommand {
-    pub fn new(file: String, print_tokens: bool, print_ast: bool) -> ParserCommand {
-        ParserCommand { file, print_tokens, print_ast }
+    pub fn new(file: String, print_tokens: bool, print_ast: bool, ast_format: Format) -> ParserCommand {
+        ParserCommand { file, print_tokens, print_ast, ast_format }
     }
 }
 
 impl Command for ParserCommand {
     fn execute(&self) {
-        println!("Parsing file {} ...", self.file);
+        // The banners go to STDERR, so STDOUT only holds the requested output, e.g. valid JSON.
+        eprintln!("Parsing file {} ...", self.file);
 
         let mut f = match File::open(&self.file) {
             Ok(f) => f,
@@ -45,14 +48,16 @@ impl Command for ParserCommand {
         debug!("{}", content);
         debug!("--- source end ---");
 
-        let input_stream = CharacterStream::new(content);
-        let mut lexer = Lexer::new(input_stream);
-
         if self.print_tokens {
-            println!("Recognized tokens:");
+            let mut lexer = Lexer::new(CharacterStream::new(content.clone()));
+            eprintln!("Recognized tokens:");
 
             loop {
-                lexer.next();
+                if let Err(e) = lexer.next_token() {
+                    error(&format!("{}", e));
+                    return;
+                }
+
                 let token = lexer.current();
                 println!("{}", token);
 
@@ -63,11 +68,20 @@ impl Command for ParserCommand {
             }
         }
 
-        let parser = Parser::new(lexer);
-        parser.parse();
+        let lexer = Lexer::new(CharacterStream::new(content));
+        let mut parser = Parser::new(lexer);
+
+        let statements = match parser.parse() {
+            Ok(statements) => statements,
+            Err(e) => {
+                error(&format!("{}", e));
+                return;
+            }
+        };
 
         if self.print_ast {
-            println!("Parsed AST:")
+            eprintln!("Parsed AST:");
+            print!("{}", print(&statements, self.ast_format));
         }
     }
 }
@@ -1,25 +1,55 @@
+use std::process;
+
 use commands::Command;
 use commands::read_file_as_bytes;
 use std::path::Path;
 
 use backend::VirtualMachine;
+use backend::image::Image;
+use backend::register::RegisterMachine;
+use backend::register::translator;
+use error;
 
 /// Command to run byte code.
 pub struct RunCommand {
     file: String,
+    registers: bool,
 }
 
 impl RunCommand {
-    pub fn new(file: String) -> RunCommand {
-        RunCommand { file }
+    /// Creates the command which executes the byte code on the register machine if `registers`
+    /// is true, else on the stack machine.
+    pub fn new(file: String, registers: bool) -> RunCommand {
+        RunCommand { file, registers }
     }
 }
 
 impl Command for RunCommand {
     fn execute(&self) {
         let byte_code = read_file_as_bytes(Path::new(&self.file));
-        let mut vm = VirtualMachine::new(byte_code);
-        let _ = vm.run();
+        let image = match Image::from_bytes(byte_code) {
+            Ok(image) => image,
+            Err(e) => {
+                error(e);
+                process::exit(1);
+            }
+        };
+
+        let result = if self.registers {
+            match translator::translate(&image) {
+                Ok(program) => RegisterMachine::new(program).run(),
+                Err(e) => {
+                    error(&e);
+                    process::exit(1);
+                }
+            }
+        } else {
+            VirtualMachine::new(image).run()
+        };
+
+        if let Err(e) = result {
+            error(&format!("{}", e));
+            process::exit(1);
+        }
     }
 }
-
@@ -1,3 +1,5 @@
+use frontend::unicode_tables::{self, XID_CONTINUE, XID_START};
+
 /// Provides several helper functions to recognize characters.
 pub struct CharacterHelper {}
 
@@ -12,11 +14,28 @@ impl CharacterHelper {
         ch.is_numeric()
     }
 
+    /// Determines if a given character is an ASCII digit which starts a number literal.
+    pub fn is_digit(ch: char) -> bool {
+        ch.is_ascii_digit()
+    }
+
     /// Determines if a given character is alphabetic or numeric.
     pub fn is_alphanumeric(ch: char) -> bool {
         (CharacterHelper::is_alphabetic(ch) || CharacterHelper::is_numeric(ch))
     }
 
+    /// Determines if a given character may start an identifier: The underscore or a character
+    /// with the Unicode property XID_Start.
+    pub fn is_identifier_start(ch: char) -> bool {
+        ch == '_' || unicode_tables::contains(XID_START, ch)
+    }
+
+    /// Determines if a given character may continue an identifier: A character with the Unicode
+    /// property XID_Continue, which includes the underscore and the decimal digits of all scripts.
+    pub fn is_identifier_part(ch: char) -> bool {
+        unicode_tables::contains(XID_CONTINUE, ch)
+    }
+
     /// Determines if a given character is a double quote.
     pub fn is_double_quote(ch: char) -> bool {
         ch == '"'
@@ -39,6 +58,8 @@ impl CharacterHelper {
             ch == '<' ||
             ch == '>' ||
             ch == ',' ||
+            ch == '.' ||
+            ch == ':' ||
             ch == '(' ||
             ch == ')' ||
             ch == '{' ||
@@ -47,9 +68,14 @@ impl CharacterHelper {
             ch == ']'
     }
 
+    /// Determines if a given character and the character after it start a comment.
+    pub fn is_comment_start(ch: char, next: char) -> bool {
+        ch == '#' || (ch == '/' && (next == '/' || next == '*'))
+    }
+
     /// Determines if a given character is a white space.
     pub fn is_white_space(ch: char) -> bool {
-        ch == ' ' || ch == '\t'
+        ch == ' ' || ch == '\t' || ch == '\r'
     }
 
     /// Determines if a given character is a new line.
@@ -127,6 +153,49 @@ mod tests {
         assert_that!(CharacterHelper::is_alphanumeric('_'), is(false));
     }
 
+    #[test]
+    fn is_digit() {
+        assert_that!(CharacterHelper::is_digit('0'), is(true));
+        assert_that!(CharacterHelper::is_digit('9'), is(true));
+
+        assert_that!(CharacterHelper::is_digit('a'), is(false));
+        assert_that!(CharacterHelper::is_digit('\u{663}'), is(false));
+        assert_that!(CharacterHelper::is_digit('½'), is(false));
+    }
+
+    #[test]
+    fn is_identifier_start() {
+        assert_that!(CharacterHelper::is_identifier_start('a'), is(true));
+        assert_that!(CharacterHelper::is_identifier_start('Z'), is(true));
+        assert_that!(CharacterHelper::is_identifier_start('_'), is(true));
+        assert_that!(CharacterHelper::is_identifier_start('ä'), is(true));
+        assert_that!(CharacterHelper::is_identifier_start('λ'), is(true));
+        assert_that!(CharacterHelper::is_identifier_start('変'), is(true));
+
+        assert_that!(CharacterHelper::is_identifier_start('3'), is(false));
+        assert_that!(CharacterHelper::is_identifier_start('\u{0301}'), is(false));
+        assert_that!(CharacterHelper::is_identifier_start('$'), is(false));
+        assert_that!(CharacterHelper::is_identifier_start('€'), is(false));
+    }
+
+    #[test]
+    fn is_identifier_part() {
+        assert_that!(CharacterHelper::is_identifier_part('a'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('_'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('3'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('ß'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('\u{0301}'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('\u{663}'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('\u{94D}'), is(true));
+        assert_that!(CharacterHelper::is_identifier_part('·'), is(true));
+
+        assert_that!(CharacterHelper::is_identifier_part('²'), is(false));
+        assert_that!(CharacterHelper::is_identifier_part('½'), is(false));
+        assert_that!(CharacterHelper::is_identifier_part('-'), is(false));
+        assert_that!(CharacterHelper::is_identifier_part(' '), is(false));
+        assert_that!(CharacterHelper::is_identifier_part('😀'), is(false));
+    }
+
     #[test]
     fn is_double_quote() {
         assert_that!(CharacterHelper::is_double_quote('"'), is(true));
@@ -163,6 +232,8 @@ mod tests {
         assert_that!(CharacterHelper::is_operator('<'), is(true));
         assert_that!(CharacterHelper::is_operator('>'), is(true));
         assert_that!(CharacterHelper::is_operator(','), is(true));
+        assert_that!(CharacterHelper::is_operator('.'), is(true));
+        assert_that!(CharacterHelper::is_operator(':'), is(true));
         assert_that!(CharacterHelper::is_operator('('), is(true));
         assert_that!(CharacterHelper::is_operator(')'), is(true));
         assert_that!(CharacterHelper::is_operator('{'), is(true));
@@ -178,10 +249,22 @@ mod tests {
         assert_that!(CharacterHelper::is_operator('\n'), is(false));
     }
 
+    #[test]
+    fn is_comment_start() {
+        assert_that!(CharacterHelper::is_comment_start('#', ' '), is(true));
+        assert_that!(CharacterHelper::is_comment_start('/', '/'), is(true));
+        assert_that!(CharacterHelper::is_comment_start('/', '*'), is(true));
+
+        assert_that!(CharacterHelper::is_comment_start('/', ' '), is(false));
+        assert_that!(CharacterHelper::is_comment_start('*', '/'), is(false));
+        assert_that!(CharacterHelper::is_comment_start('a', '/'), is(false));
+    }
+
     #[test]
     fn is_white_space() {
         assert_that!(CharacterHelper::is_white_space(' '), is(true));
         assert_that!(CharacterHelper::is_white_space('\t'), is(true));
+        assert_that!(CharacterHelper::is_white_space('\r'), is(true));
 
         assert_that!(CharacterHelper::is_white_space('3'), is(false));
         assert_that!(CharacterHelper::is_white_space('a'), is(false));
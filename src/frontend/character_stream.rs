@@ -1,10 +1,16 @@
 use frontend::Position;
+use frontend::Span;
+use frontend::token::END_OF_FILE;
 
 /// Represents a string as stream of characters.
+///
+/// The stream works on Unicode scalar values, so multi byte UTF-8 characters are one character.
+/// Positions count characters while offsets count bytes of the original string.
 #[derive(Debug)]
 pub struct CharacterStream {
-    input: String,
+    input: Vec<char>,
     index: usize,
+    offset: usize,
     line: u64,
     column: u64,
     new_line_seen: bool
@@ -13,9 +19,17 @@ pub struct CharacterStream {
 impl CharacterStream {
     /// Creates a new stream.
     pub fn new(input: String) -> CharacterStream {
+        CharacterStream::with_offset(input, 0)
+    }
+
+    /// Creates a new stream whose offsets start at the given value instead of zero.
+    ///
+    /// This keeps the spans of several source files distinct if they are analyzed together.
+    pub fn with_offset(input: String, offset: usize) -> CharacterStream {
         CharacterStream {
-            input,
+            input: input.chars().collect(),
             index: 0,
+            offset,
             line: 1,
             column: 1,
             new_line_seen: false,
@@ -33,6 +47,7 @@ impl CharacterStream {
             self.column = 1;
         }
 
+        self.offset += self.current().len_utf8();
         self.index += 1;
 
         if self.new_line_seen {
@@ -54,26 +69,49 @@ impl CharacterStream {
     }
 
     /// Get the character at the streams current position.
-    pub fn current(&mut self) -> char {
-        match self.input.chars().nth(self.index) {
-            Some(ch) => ch,
-            None => 0 as char
-        }
+    pub fn current(&self) -> char {
+        self.char_at(self.index)
     }
 
     /// Get the next character without moving to it.
-    pub fn peek(&mut self) -> char {
-        let index_backup = self.index;
-        self.next();
-        let peeked = self.current();
-        self.index = index_backup;
-        peeked
+    pub fn peek(&self) -> char {
+        self.char_at(self.index + 1)
     }
 
     /// Get the position of the current character.
     pub fn position(&self) -> Position {
         Position { line: self.line, column: self.column }
     }
+
+    /// Get the byte offset of the current character in the input string.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Get the source range from the given start up to the current character.
+    pub fn span_from(&self, start: Position, start_offset: usize) -> Span {
+        Span::new(start, self.position(), start_offset, self.offset)
+    }
+
+    /// Get the source text from the given byte offset up to, but excluding, the current character.
+    pub fn text_from(&self, start_offset: usize) -> String {
+        let mut length = self.offset - start_offset;
+        let mut start = self.index;
+
+        while length > 0 {
+            start -= 1;
+            length -= self.input[start].len_utf8();
+        }
+
+        self.input[start..self.index].iter().collect()
+    }
+
+    fn char_at(&self, index: usize) -> char {
+        match self.input.get(index) {
+            Some(ch) => *ch,
+            None => END_OF_FILE,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -103,7 +141,7 @@ mod tests {
 
     #[test]
     fn current_for_empty_string() {
-        let mut sut = crete_sut("");
+        let sut = crete_sut("");
 
         assert_that!(sut.current(), is(equal_to(0 as char)));
     }
@@ -177,4 +215,58 @@ mod tests {
         assert_that!(sut.current(), is(equal_to('o')));
         assert_that!(sut.peek(), is(equal_to(0 as char)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn peek_does_not_move_position() {
+        let mut sut = crete_sut("a\nb");
+        sut.next();
+
+        assert_that!(sut.peek(), is(equal_to('b')));
+        assert_that!(sut.position(), is(equal_to(Position::new(1, 2))));
+        assert_that!(sut.offset(), is(equal_to(1)));
+        sut.next();
+        assert_that!(sut.position(), is(equal_to(Position::new(2, 1))));
+        assert_that!(sut.offset(), is(equal_to(2)));
+    }
+
+    #[test]
+    fn offset_counts_bytes() {
+        let mut sut = crete_sut("äb");
+
+        assert_that!(sut.offset(), is(equal_to(0)));
+        sut.next();
+        assert_that!(sut.offset(), is(equal_to(2)));
+    }
+
+    #[test]
+    fn iterate_through_multi_byte_characters() {
+        let mut sut = crete_sut("ä€😀");
+
+        assert_that!(sut.current(), is(equal_to('ä')));
+        assert_that!(sut.peek(), is(equal_to('€')));
+        sut.next();
+        assert_that!(sut.current(), is(equal_to('€')));
+        assert_that!(sut.offset(), is(equal_to(2)));
+        sut.next();
+        assert_that!(sut.current(), is(equal_to('😀')));
+        assert_that!(sut.offset(), is(equal_to(5)));
+        assert_that!(sut.position(), is(equal_to(Position::new(1, 3))));
+        assert_that!(sut.has_next(), is(true));
+        sut.next();
+        assert_that!(sut.offset(), is(equal_to(9)));
+        assert_that!(sut.has_next(), is(false));
+        assert_that!(sut.current(), is(equal_to(0 as char)));
+    }
+
+    #[test]
+    fn text_from() {
+        let mut sut = crete_sut("aä€b");
+        sut.next();
+        let start = sut.offset();
+        sut.next();
+        sut.next();
+
+        assert_that!(&sut.text_from(start), is(equal_to("ä€")));
+        assert_that!(&sut.text_from(sut.offset()), is(equal_to("")));
+    }
+}
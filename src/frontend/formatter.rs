@@ -0,0 +1,412 @@
+use std::mem;
+
+use frontend::character_stream::CharacterStream;
+use frontend::lexer::Lexer;
+use frontend::parser::Parser;
+use frontend::token::{TokenType, Keyword, Operator};
+use frontend::SyntaxError;
+
+/// Number of spaces used for one level of indentation.
+const INDENTATION: usize = 4;
+
+/// Formats source code in the canonical style.
+///
+/// The source is parsed first to reject syntactically wrong code. Then the code is transformed
+/// into a concrete syntax tree which, unlike the abstract syntax tree, keeps the literal text of
+/// every token, the comments and the line structure, with the lines inside braces nested in blocks.
+/// This tree is written back with canonical spacing and indentation:
+///
+/// * one space around binary operators and after commas,
+/// * no space inside parenthesis and brackets and between a function name (or `fun`) and its
+///   arguments or parameters,
+/// * four spaces indentation per open brace,
+/// * at most one blank line between statements and exactly one new line at the end of the file.
+#[derive(Default)]
+pub struct Formatter;
+
+impl Formatter {
+    pub fn new() -> Formatter {
+        Formatter
+    }
+
+    /// Formats the given source code.
+    pub fn format(&self, source: &str) -> Result<String, SyntaxError> {
+        Parser::new(Lexer::new(CharacterStream::new(source.to_string()))).parse()?;
+        let tree = ConcreteSyntaxTree::read(source)?;
+        Ok(tree.write())
+    }
+
+    /// Determines if the given source code is already formatted.
+    pub fn is_formatted(&self, source: &str) -> Result<bool, SyntaxError> {
+        Ok(self.format(source)? == source)
+    }
+}
+
+/// A token together with its literal text as found in the source.
+#[derive(Debug, PartialEq)]
+struct Lexeme {
+    token_type: TokenType,
+    text: String,
+}
+
+/// A node of the concrete syntax tree.
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// One line of source code.
+    Line(Vec<Lexeme>),
+    /// A line without any token.
+    Blank,
+    /// The lines after an opening brace up to the line with the closing brace.
+    Block(Vec<Node>),
+}
+
+/// Lossless representation of the source code as lines nested in the blocks opened by braces.
+#[derive(Debug, PartialEq)]
+struct ConcreteSyntaxTree {
+    nodes: Vec<Node>,
+}
+
+impl ConcreteSyntaxTree {
+    fn read(source: &str) -> Result<ConcreteSyntaxTree, SyntaxError> {
+        let mut lexer = Lexer::with_comments(CharacterStream::new(source.to_string()));
+        let mut builder = TreeBuilder { blocks: vec![Vec::new()] };
+        let mut line = Vec::new();
+
+        loop {
+            lexer.next_token()?;
+            let span = lexer.span();
+
+            match *lexer.current().get_token_type() {
+                TokenType::EOF => break,
+                TokenType::EOL => builder.add(mem::take(&mut line)),
+                ref token_type => line.push(Lexeme {
+                    token_type: token_type.clone(),
+                    text: source[span.start_offset()..span.end_offset()].to_string(),
+                }),
+            }
+        }
+
+        if !line.is_empty() {
+            builder.add(line);
+        }
+
+        Ok(ConcreteSyntaxTree { nodes: builder.finish() })
+    }
+
+    fn write(&self) -> String {
+        let mut writer = TreeWriter { buffer: String::new(), pending_blank: false };
+        writer.write(&self.nodes, 0);
+        writer.buffer
+    }
+}
+
+// Nests the lines into blocks while reading: Each brace opened and not closed on a line starts a
+// block, a line starting with a closing brace belongs to the enclosing block again.
+struct TreeBuilder {
+    blocks: Vec<Vec<Node>>,
+}
+
+impl TreeBuilder {
+    fn add(&mut self, line: Vec<Lexeme>) {
+        if line.is_empty() {
+            self.current().push(Node::Blank);
+            return;
+        }
+
+        let mut skip = 0;
+
+        if TokenType::RightBrace == line[0].token_type {
+            self.close();
+            skip = 1;
+        }
+
+        let opened = line[skip..].iter().fold(0isize, |opened, lexeme| match lexeme.token_type {
+            TokenType::LeftBrace => opened + 1,
+            TokenType::RightBrace => opened - 1,
+            _ => opened,
+        });
+        self.current().push(Node::Line(line));
+
+        for _ in 0..opened {
+            self.blocks.push(Vec::new());
+        }
+
+        for _ in opened..0 {
+            self.close();
+        }
+    }
+
+    fn current(&mut self) -> &mut Vec<Node> {
+        self.blocks.last_mut().expect("The outermost block is never closed!")
+    }
+
+    fn close(&mut self) {
+        if self.blocks.len() > 1 {
+            let block = self.blocks.pop().unwrap();
+            self.current().push(Node::Block(block));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Node> {
+        while self.blocks.len() > 1 {
+            self.close();
+        }
+
+        self.blocks.pop().unwrap()
+    }
+}
+
+// Writes the lines indented by their depth. Blank lines are collapsed to one and dropped at the
+// start and end of the file.
+struct TreeWriter {
+    buffer: String,
+    pending_blank: bool,
+}
+
+impl TreeWriter {
+    fn write(&mut self, nodes: &[Node], depth: usize) {
+        for node in nodes {
+            match *node {
+                Node::Blank => self.pending_blank = !self.buffer.is_empty(),
+                Node::Block(ref nodes) => self.write(nodes, depth + 1),
+                Node::Line(ref lexemes) => {
+                    if self.pending_blank {
+                        self.buffer.push('\n');
+                        self.pending_blank = false;
+                    }
+
+                    self.buffer.push_str(&" ".repeat(depth * INDENTATION));
+                    self.buffer.push_str(&write_line(lexemes));
+                    self.buffer.push('\n');
+                },
+            }
+        }
+    }
+}
+
+fn write_line(lexemes: &[Lexeme]) -> String {
+    let mut buffer = String::new();
+
+    for (index, lexeme) in lexemes.iter().enumerate() {
+        if index > 0 {
+            let before = if index > 1 { Some(&lexemes[index - 2].token_type) } else { None };
+
+            if needs_space(before, &lexemes[index - 1].token_type, &lexeme.token_type) {
+                buffer.push(' ');
+            }
+        }
+
+        buffer.push_str(&lexeme.text);
+    }
+
+    buffer
+}
+
+fn needs_space(before: Option<&TokenType>, previous: &TokenType, next: &TokenType) -> bool {
+    match *next {
+        TokenType::RightParen | TokenType::RightBracket | TokenType::Comma
+            | TokenType::Dot | TokenType::DotDot | TokenType::Colon => return false,
+        TokenType::LeftParen | TokenType::LeftBracket => match *previous {
+            TokenType::Identifier(_) | TokenType::RightParen | TokenType::RightBracket => return false,
+            TokenType::Keyword(Keyword::Fun) if TokenType::LeftParen == *next => return false,
+            _ => (),
+        },
+        _ => (),
+    }
+
+    match *previous {
+        TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot | TokenType::DotDot => false,
+        TokenType::Operator(Operator::Plus) | TokenType::Operator(Operator::Minus) =>
+            !is_unary_position(before),
+        _ => true,
+    }
+}
+
+// A sign is unary if it is the first token or follows something which can not end an operand.
+fn is_unary_position(before: Option<&TokenType>) -> bool {
+    match before {
+        None => true,
+        Some(TokenType::Operator(_)) | Some(TokenType::LeftParen) | Some(TokenType::LeftBracket)
+            | Some(TokenType::LeftBrace) | Some(TokenType::Comma) | Some(TokenType::Colon)
+            | Some(TokenType::Arrow) => true,
+        Some(TokenType::Keyword(keyword)) => *keyword != Keyword::Var && *keyword != Keyword::Const,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    fn format(source: &str) -> String {
+        Formatter::new().format(source).unwrap()
+    }
+
+    #[test]
+    fn format_spacing() {
+        assert_that!(&format("var   x=1+2*  3\n"), is(equal_to("var x = 1 + 2 * 3\n")));
+        assert_that!(&format("println( x ,y )"), is(equal_to("println(x, y)\n")));
+        assert_that!(&format("x=(1+2)%3"), is(equal_to("x = (1 + 2) % 3\n")));
+        assert_that!(&format("z=not a  or b>=c"), is(equal_to("z = not a or b >= c\n")));
+        assert_that!(&format("x+=-1\ny%=  2"), is(equal_to("x += -1\ny %= 2\n")));
+        assert_that!(&format("p = Point{x:-1 ,y : p . x}"), is(equal_to("p = Point { x: -1, y: p.x }\n")));
+    }
+
+    #[test]
+    fn format_functions() {
+        assert_that!(&format("fun add(a,b){\nreturn a+b\n}\nvar f=fun( x ){ return -x }\nf (1)( 2 )"),
+            is(equal_to("fun add(a, b) {\n    return a + b\n}\nvar f = fun(x) { return -x }\nf(1)(2)\n")));
+    }
+
+    #[test]
+    fn format_exceptions() {
+        assert_that!(&format("try{\nthrow  \"x\"\n}catch e{ println(e) }"),
+            is(equal_to("try {\n    throw \"x\"\n} catch e { println(e) }\n")));
+    }
+
+    #[test]
+    fn format_loops() {
+        assert_that!(&format("for i in 0 .. n{\nif i>1{ break }else{continue}\n}"),
+            is(equal_to("for i in 0..n {\n    if i > 1 { break } else { continue }\n}\n")));
+    }
+
+    #[test]
+    fn format_keeps_literal_text() {
+        assert_that!(&format("var s=\"a  b\"\nvar r=7.0E+2"), is(equal_to("var s = \"a  b\"\nvar r = 7.0E+2\n")));
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        assert_that!(&format("// header\nvar x=1   // one\n\n\n#  two\ny = /* inline */x\n/* multi\n  line */\n"),
+            is(equal_to("// header\nvar x = 1 // one\n\n#  two\ny = /* inline */ x\n/* multi\n  line */\n")));
+    }
+
+    #[test]
+    fn format_blank_lines() {
+        assert_that!(&format("\n\nvar x = 1\n\n\n\nvar y = 2\n\n\n"), is(equal_to("var x = 1\n\nvar y = 2\n")));
+    }
+
+    #[test]
+    fn format_keeps_escape_sequences() {
+        assert_that!(&format("var s=\"\\u{e4}\\n\"\nvar c='\\t'"), is(equal_to("var s = \"\\u{e4}\\n\"\nvar c = '\\t'\n")));
+    }
+
+    #[test]
+    fn format_empty() {
+        assert_that!(&format(""), is(equal_to("")));
+        assert_that!(&format("\n\n"), is(equal_to("")));
+    }
+
+    #[test]
+    fn format_is_idempotent() {
+        let formatted = format("var s = \"Hello, World!\"\nprintln( s )\n\n\nvar x=1\nz = x+y\n");
+
+        assert_that!(&format(&formatted), is(equal_to(&formatted)));
+    }
+
+    #[test]
+    fn format_syntax_error() {
+        assert_that!(Formatter::new().format("var = 1").is_err(), is(true));
+    }
+
+    #[test]
+    fn is_formatted() {
+        let sut = Formatter::new();
+
+        assert_that!(sut.is_formatted("var x = 1\n"), is(equal_to(Ok(true))));
+        assert_that!(sut.is_formatted("var x=1\n"), is(equal_to(Ok(false))));
+        assert_that!(sut.is_formatted("var x = 1"), is(equal_to(Ok(false))));
+    }
+
+    #[test]
+    fn format_commented_program() {
+        let source = concat!(
+            "// Counts down.\n",
+            "fun count(n) { // n >= 0\n",
+            "/* Nested blocks\n   are indented. */\n",
+            "while n>0{\n",
+            "if n%2==0 {\n",
+            "  # even\n",
+            "println(n)\n",
+            "} else {\n",
+            "    // odd\n",
+            "}\n",
+            "n-=1\n",
+            "}\n",
+            "}\n");
+
+        assert_that!(&format(source), is(equal_to(concat!(
+            "// Counts down.\n",
+            "fun count(n) { // n >= 0\n",
+            "    /* Nested blocks\n   are indented. */\n",
+            "    while n > 0 {\n",
+            "        if n % 2 == 0 {\n",
+            "            # even\n",
+            "            println(n)\n",
+            "        } else {\n",
+            "            // odd\n",
+            "        }\n",
+            "        n -= 1\n",
+            "    }\n",
+            "}\n"))));
+    }
+
+    #[test]
+    fn read_nests_blocks() {
+        let tree = ConcreteSyntaxTree::read("while true {\nx\n\n} // done\n").unwrap();
+
+        assert_that!(tree, is(equal_to(ConcreteSyntaxTree {
+            nodes: vec![
+                Node::Line(vec![
+                    Lexeme { token_type: TokenType::Keyword(Keyword::While), text: String::from("while") },
+                    Lexeme { token_type: TokenType::Bool(true), text: String::from("true") },
+                    Lexeme { token_type: TokenType::LeftBrace, text: String::from("{") },
+                ]),
+                Node::Block(vec![
+                    Node::Line(vec![
+                        Lexeme { token_type: TokenType::Identifier(String::from("x")), text: String::from("x") },
+                    ]),
+                    Node::Blank,
+                ]),
+                Node::Line(vec![
+                    Lexeme { token_type: TokenType::RightBrace, text: String::from("}") },
+                    Lexeme { token_type: TokenType::Comment(String::from("// done")), text: String::from("// done") },
+                ]),
+            ],
+        })));
+    }
+
+    #[test]
+    fn write_indents_blocks() {
+        let tree = ConcreteSyntaxTree {
+            nodes: vec![
+                Node::Line(vec![
+                    Lexeme { token_type: TokenType::Keyword(Keyword::While), text: String::from("while") },
+                    Lexeme { token_type: TokenType::Bool(true), text: String::from("true") },
+                    Lexeme { token_type: TokenType::LeftBrace, text: String::from("{") },
+                ]),
+                Node::Block(vec![
+                    Node::Line(vec![
+                        Lexeme { token_type: TokenType::Identifier(String::from("x")), text: String::from("x") },
+                    ]),
+                ]),
+                Node::Line(vec![
+                    Lexeme { token_type: TokenType::RightBrace, text: String::from("}") },
+                ]),
+            ],
+        };
+
+        assert_that!(&tree.write(), is(equal_to("while true {\n    x\n}\n")));
+    }
+
+    #[test]
+    fn needs_space_for_unary_sign() {
+        let minus = TokenType::Operator(Operator::Minus);
+        let one = TokenType::Integer(1);
+
+        assert_that!(needs_space(None, &minus, &one), is(false));
+        assert_that!(needs_space(Some(&TokenType::Comma), &minus, &one), is(false));
+        assert_that!(needs_space(Some(&TokenType::Identifier(String::from("x"))), &minus, &one), is(true));
+    }
+}
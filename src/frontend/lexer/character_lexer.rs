@@ -1,11 +1,16 @@
 use frontend::lexer::SubLexer;
+use frontend::lexer::escape::scan_escape_sequence;
 use frontend::token::*;
 use frontend::character_stream::CharacterStream;
 use frontend::character_helper::CharacterHelper;
+use frontend::SyntaxError;
 #[cfg(test)]
-use frontend::Position;
+use frontend::{Position, Span};
 
 /// Recognizes a character literal (`'c'`) token.
+///
+/// The character may be given as escape sequence (see
+/// [scan_escape_sequence](../escape/fn.scan_escape_sequence.html)).
 pub struct CharacterLexer {}
 
 impl CharacterLexer {
@@ -15,36 +20,40 @@ impl CharacterLexer {
 }
 
 impl SubLexer for CharacterLexer {
-    fn scan(&self, input: &mut CharacterStream) -> Token {
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError> {
         let position = input.position();
+        let offset = input.offset();
         debug!("Start character lexing at position {}.", position);
 
-        if !input.has_next() {
-            panic!("Unterminated character literal");
-        }
-
         input.next(); // consume '
 
         if CharacterHelper::is_single_quote(input.current()) {
-            return Token::new(
+            input.next(); // consume '
+            return Ok(Token::new(
                 position,
                 TokenType::Character(0 as char),
-                String::from(""));
-        }
-
-        let ch = input.current();
-
-        if !input.has_next() {
-            panic!("Unterminated character literal");
+                String::from("")));
         }
 
-        input.next(); // consume character
+        let (ch, literal) = match input.current() {
+            END_OF_FILE => return Err(SyntaxError::new(
+                String::from("Unterminated character literal!"),
+                input.span_from(position, offset))),
+            '\\' => scan_escape_sequence(input)?,
+            ch => {
+                input.next(); // consume character
+                (ch, ch.to_string())
+            },
+        };
 
         if !CharacterHelper::is_single_quote(input.current()) {
-            panic!("Unterminated character literal!");
+            return Err(SyntaxError::new(
+                String::from("Unterminated character literal!"),
+                input.span_from(position, offset)));
         }
 
-        Token::new(position, TokenType::Character(ch), format!("'{}'", ch))
+        input.next(); // consume '
+        Ok(Token::new(position, TokenType::Character(ch), format!("'{}'", literal)))
     }
 }
 
@@ -58,13 +67,14 @@ mod tests {
         let mut src = CharacterStream::new(String::from("'c'"));
         let sut = CharacterLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
             TokenType::Character('c'),
             String::from("'c'"))
         )));
+        assert_that!(src.has_next(), is(false));
     }
 
     #[test]
@@ -72,7 +82,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("''"));
         let sut = CharacterLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -82,12 +92,75 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // Panics as expected, but test fails in IDE, not on CLI
-    #[should_panic]
     fn scan_unterminated_char() {
         let mut src = CharacterStream::new(String::from("'c"));
         let sut = CharacterLexer::new();
 
         let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated character literal!"),
+            Span::new(Position::new(1, 1), Position::new(1, 3), 0, 2))))));
+    }
+
+    #[test]
+    fn scan_too_long_char() {
+        let mut src = CharacterStream::new(String::from("'ab'"));
+        let sut = CharacterLexer::new();
+
+        let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated character literal!"),
+            Span::new(Position::new(1, 1), Position::new(1, 3), 0, 2))))));
+    }
+
+    #[test]
+    fn scan_escaped_char() {
+        let mut src = CharacterStream::new(String::from("'\\''"));
+        let sut = CharacterLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Character('\''),
+            String::from("'\\''"))
+        )));
+    }
+
+    #[test]
+    fn scan_unicode_char() {
+        let mut src = CharacterStream::new(String::from("'😀' '\\u{20AC}'"));
+        let sut = CharacterLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Character('😀'),
+            String::from("'😀'"))
+        )));
+
+        src.next(); // skip space
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 5),
+            TokenType::Character('€'),
+            String::from("'\\u{20AC}'"))
+        )));
+    }
+
+    #[test]
+    fn scan_char_with_bad_escape_sequence() {
+        let mut src = CharacterStream::new(String::from("'\\k'"));
+        let sut = CharacterLexer::new();
+
+        let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unknown escape sequence '\\k'!"),
+            Span::new(Position::new(1, 2), Position::new(1, 4), 1, 3))))));
     }
-}
\ No newline at end of file
+}
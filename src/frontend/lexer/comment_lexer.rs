@@ -0,0 +1,159 @@
+use frontend::lexer::SubLexer;
+use frontend::token::*;
+use frontend::character_stream::CharacterStream;
+use frontend::character_helper::CharacterHelper;
+use frontend::SyntaxError;
+#[cfg(test)]
+use frontend::{Position, Span};
+
+/// Recognizes a line comment (`// ...` or `# ...`) or a block comment (`/* ... */`) token.
+///
+/// Block comments may be nested. The literal of the token is the whole comment including the
+/// comment delimiters. Line comments end before the new line character.
+pub struct CommentLexer {}
+
+impl CommentLexer {
+    pub fn new() -> CommentLexer {
+        CommentLexer {}
+    }
+
+    fn scan_line_comment(&self, input: &mut CharacterStream) -> String {
+        let mut literal = String::new();
+
+        while END_OF_FILE != input.current() && !CharacterHelper::is_new_line(input.current()) {
+            literal.push(input.current());
+            input.next(); // consume character
+        }
+
+        literal
+    }
+
+    fn scan_block_comment(&self, input: &mut CharacterStream) -> Result<String, SyntaxError> {
+        let position = input.position();
+        let offset = input.offset();
+        let mut literal = String::new();
+        let mut depth = 0;
+
+        loop {
+            if END_OF_FILE == input.current() {
+                return Err(SyntaxError::new(
+                    String::from("Unterminated block comment!"),
+                    input.span_from(position, offset)));
+            }
+
+            if '/' == input.current() && '*' == input.peek() {
+                depth += 1;
+                literal.push_str("/*");
+                input.next(); // consume /
+                input.next(); // consume *
+            } else if '*' == input.current() && '/' == input.peek() {
+                depth -= 1;
+                literal.push_str("*/");
+                input.next(); // consume *
+                input.next(); // consume /
+
+                if depth == 0 {
+                    break;
+                }
+            } else {
+                literal.push(input.current());
+                input.next(); // consume character
+            }
+        }
+
+        Ok(literal)
+    }
+}
+
+impl SubLexer for CommentLexer {
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError> {
+        let position = input.position();
+        debug!("Start comment lexing at position {}.", position);
+
+        let literal = if '/' == input.current() && '*' == input.peek() {
+            self.scan_block_comment(input)?
+        } else {
+            self.scan_line_comment(input)
+        };
+
+        Ok(Token::new(position, TokenType::Comment(literal.clone()), literal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    #[test]
+    fn scan_line_comment() {
+        let mut src = CharacterStream::new(String::from("// foo bar\nx"));
+        let sut = CommentLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Comment(String::from("// foo bar")),
+            String::from("// foo bar"))
+        )));
+        assert_that!(src.current(), is(equal_to('\n')));
+    }
+
+    #[test]
+    fn scan_hash_comment_at_eof() {
+        let mut src = CharacterStream::new(String::from("# foo"));
+        let sut = CommentLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Comment(String::from("# foo")),
+            String::from("# foo"))
+        )));
+        assert_that!(src.has_next(), is(false));
+    }
+
+    #[test]
+    fn scan_block_comment() {
+        let mut src = CharacterStream::new(String::from("/* foo\nbar */ x"));
+        let sut = CommentLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Comment(String::from("/* foo\nbar */")),
+            String::from("/* foo\nbar */"))
+        )));
+        assert_that!(src.current(), is(equal_to(' ')));
+    }
+
+    #[test]
+    fn scan_nested_block_comment() {
+        let mut src = CharacterStream::new(String::from("/* a /* b */ c */d"));
+        let sut = CommentLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Comment(String::from("/* a /* b */ c */")),
+            String::from("/* a /* b */ c */"))
+        )));
+        assert_that!(src.current(), is(equal_to('d')));
+    }
+
+    #[test]
+    fn scan_unterminated_block_comment() {
+        let mut src = CharacterStream::new(String::from("/* a /* b */"));
+        let sut = CommentLexer::new();
+
+        let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated block comment!"),
+            Span::new(Position::new(1, 1), Position::new(1, 13), 0, 12))))));
+    }
+}
@@ -0,0 +1,146 @@
+use frontend::character_stream::CharacterStream;
+use frontend::token::END_OF_FILE;
+use frontend::Position;
+use frontend::SyntaxError;
+
+/// Maximum number of hex digits in a Unicode escape sequence (`\u{10FFFF}`).
+const MAX_UNICODE_DIGITS: usize = 6;
+
+/// Scans an escape sequence beginning with the backslash at the current position.
+///
+/// Returns the denoted character together with the literal escape sequence as written in the
+/// source. Recognized sequences are `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'`, `\$` and `\u{...}`
+/// with one to six hex digits denoting a Unicode scalar value.
+pub fn scan_escape_sequence(input: &mut CharacterStream) -> Result<(char, String), SyntaxError> {
+    let position = input.position();
+    let offset = input.offset();
+    input.next(); // consume \
+    let escaped = input.current();
+
+    let ch = match escaped {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        '\'' => '\'',
+        '$' => '$',
+        'u' => return scan_unicode_escape_sequence(input, position, offset),
+        END_OF_FILE => return Err(SyntaxError::new(
+            String::from("Unterminated escape sequence!"),
+            input.span_from(position, offset))),
+        _ => {
+            input.next(); // consume bad character
+            return Err(SyntaxError::new(
+                format!("Unknown escape sequence '\\{}'!", escaped),
+                input.span_from(position, offset)));
+        },
+    };
+
+    input.next(); // consume escaped character
+    Ok((ch, format!("\\{}", escaped)))
+}
+
+// The stream is at the 'u' right after the backslash which is at the given position.
+fn scan_unicode_escape_sequence(input: &mut CharacterStream, position: Position, offset: usize)
+    -> Result<(char, String), SyntaxError> {
+    let mut literal = String::from("\\u");
+    input.next(); // consume u
+
+    if '{' != input.current() {
+        return Err(SyntaxError::new(
+            String::from("Expected '{' in Unicode escape sequence!"),
+            input.span_from(position, offset)));
+    }
+
+    literal.push('{');
+    input.next(); // consume {
+    let mut digits = String::new();
+
+    while input.current().is_ascii_hexdigit() {
+        digits.push(input.current());
+        input.next(); // consume digit
+    }
+
+    if '}' != input.current() {
+        return Err(SyntaxError::new(
+            String::from("Expected '}' in Unicode escape sequence!"),
+            input.span_from(position, offset)));
+    }
+
+    literal.push_str(&digits);
+    literal.push('}');
+    input.next(); // consume }
+
+    if digits.is_empty() || digits.len() > MAX_UNICODE_DIGITS {
+        return Err(SyntaxError::new(
+            String::from("Unicode escape sequence must have one to six hex digits!"),
+            input.span_from(position, offset)));
+    }
+
+    let value = u32::from_str_radix(&digits, 16).unwrap();
+
+    match ::std::char::from_u32(value) {
+        Some(ch) => Ok((ch, literal)),
+        None => Err(SyntaxError::new(
+            format!("Invalid Unicode character '{}' in escape sequence!", literal),
+            input.span_from(position, offset))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use frontend::Span;
+
+    fn scan(src: &str) -> Result<(char, String), SyntaxError> {
+        let mut input = CharacterStream::new(String::from(src));
+        scan_escape_sequence(&mut input)
+    }
+
+    #[test]
+    fn scan_simple_escape_sequences() {
+        assert_that!(scan("\\n"), is(equal_to(Ok(('\n', String::from("\\n"))))));
+        assert_that!(scan("\\t"), is(equal_to(Ok(('\t', String::from("\\t"))))));
+        assert_that!(scan("\\r"), is(equal_to(Ok(('\r', String::from("\\r"))))));
+        assert_that!(scan("\\0"), is(equal_to(Ok(('\0', String::from("\\0"))))));
+        assert_that!(scan("\\\\"), is(equal_to(Ok(('\\', String::from("\\\\"))))));
+        assert_that!(scan("\\\""), is(equal_to(Ok(('"', String::from("\\\""))))));
+        assert_that!(scan("\\'"), is(equal_to(Ok(('\'', String::from("\\'"))))));
+        assert_that!(scan("\\$"), is(equal_to(Ok(('$', String::from("\\$"))))));
+    }
+
+    #[test]
+    fn scan_unicode_escape_sequence() {
+        assert_that!(scan("\\u{41}"), is(equal_to(Ok(('A', String::from("\\u{41}"))))));
+        assert_that!(scan("\\u{e4}"), is(equal_to(Ok(('ä', String::from("\\u{e4}"))))));
+        assert_that!(scan("\\u{1F600}"), is(equal_to(Ok(('😀', String::from("\\u{1F600}"))))));
+    }
+
+    #[test]
+    fn scan_unknown_escape_sequence() {
+        assert_that!(scan("\\q"), is(equal_to(Err(SyntaxError::new(
+            String::from("Unknown escape sequence '\\q'!"),
+            Span::new(Position::new(1, 1), Position::new(1, 3), 0, 2))))));
+    }
+
+    #[test]
+    fn scan_bad_unicode_escape_sequence() {
+        assert_that!(scan("\\u41").is_err(), is(true));
+        assert_that!(scan("\\u{41").is_err(), is(true));
+        assert_that!(scan("\\u{}").is_err(), is(true));
+        assert_that!(scan("\\u{1234567}").is_err(), is(true));
+        assert_that!(scan("\\u{D800}"), is(equal_to(Err(SyntaxError::new(
+            String::from("Invalid Unicode character '\\u{D800}' in escape sequence!"),
+            Span::new(Position::new(1, 1), Position::new(1, 9), 0, 8))))));
+    }
+
+    #[test]
+    fn scan_unterminated_escape_sequence() {
+        assert_that!(scan("\\"), is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated escape sequence!"),
+            Span::new(Position::new(1, 1), Position::new(1, 2), 0, 1))))));
+    }
+}
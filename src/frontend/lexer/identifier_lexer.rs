@@ -2,10 +2,14 @@ use frontend::lexer::SubLexer;
 use frontend::token::*;
 use frontend::character_stream::CharacterStream;
 use frontend::character_helper::CharacterHelper;
+use frontend::SyntaxError;
 #[cfg(test)]
 use frontend::Position;
 
 /// Recognizes a identifier token.
+///
+/// Identifiers start with a letter or underscore followed by letters, digits, underscores and
+/// combining marks. Keywords and boolean literals are recognized as such.
 pub struct IdentifierLexer {}
 
 impl IdentifierLexer {
@@ -13,7 +17,7 @@ impl IdentifierLexer {
         IdentifierLexer {}
     }
 
-    fn collect_alpha_numeric_characters(&self, input: &mut CharacterStream) -> String {
+    fn collect_identifier_characters(&self, input: &mut CharacterStream) -> String {
         let mut consumed = String::new();
 
         loop {
@@ -22,8 +26,8 @@ impl IdentifierLexer {
                 break;
             }
 
-            if !CharacterHelper::is_alphanumeric(input.current()) {
-                // no more alphanumeric characters
+            if !CharacterHelper::is_identifier_part(input.current()) {
+                // no more identifier characters
                 break;
             }
 
@@ -37,17 +41,19 @@ impl IdentifierLexer {
 }
 
 impl SubLexer for IdentifierLexer {
-    fn scan(&self, input: &mut CharacterStream) -> Token {
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError> {
         let position = input.position();
         debug!("Start identifier lexing at position {}.", position);
-        let literal = self.collect_alpha_numeric_characters(input);
+        let literal = self.collect_identifier_characters(input);
 
-        match literal.as_str() {
+        Ok(match literal.as_str() {
             "true" | "false" => {
                 let value = literal.parse::<bool>().unwrap();
                 Token::new(position, TokenType::Bool(value), literal.clone())
             },
-            "and" | "or" | "not" | "if" | "else" | "while" | "const" | "var" => {
+            "and" | "or" | "not" | "if" | "else" | "while" | "const" | "var" | "record" | "import"
+                | "fun" | "return" | "try" | "catch" | "throw" | "match" | "for" | "in" | "break"
+                | "continue" => {
                 let keyword = Keyword::for_literal(&literal);
                 Token::new(position, TokenType::Keyword(keyword), literal.clone())
             },
@@ -63,7 +69,7 @@ impl SubLexer for IdentifierLexer {
                     TokenType::Identifier(literal.clone()),
                     literal.clone())
             }
-        }
+        })
     }
 }
 
@@ -73,41 +79,41 @@ mod tests {
     use hamcrest::prelude::*;
 
     #[test]
-    fn collect_alpha_numeric_characters_empty() {
+    fn collect_identifier_characters_empty() {
         let mut src = CharacterStream::new(String::from(""));
         let sut = IdentifierLexer::new();
 
-        let token = sut.collect_alpha_numeric_characters(&mut src);
+        let token = sut.collect_identifier_characters(&mut src);
 
         assert_that!(token, is(equal_to(String::from(""))));
     }
 
     #[test]
-    fn collect_alpha_numeric_characters() {
+    fn collect_identifier_characters() {
         let mut src = CharacterStream::new(String::from("h3ll0"));
         let sut = IdentifierLexer::new();
 
-        let token = sut.collect_alpha_numeric_characters(&mut src);
+        let token = sut.collect_identifier_characters(&mut src);
 
         assert_that!(token, is(equal_to(String::from("h3ll0"))));
     }
 
     #[test]
-    fn collect_alpha_numeric_characters_trailing_whitespace() {
+    fn collect_identifier_characters_trailing_whitespace() {
         let mut src = CharacterStream::new(String::from("h3ll0 "));
         let sut = IdentifierLexer::new();
 
-        let token = sut.collect_alpha_numeric_characters(&mut src);
+        let token = sut.collect_identifier_characters(&mut src);
 
         assert_that!(token, is(equal_to(String::from("h3ll0"))));
     }
 
     #[test]
-    fn collect_alpha_numeric_characters_trailing_minus() {
+    fn collect_identifier_characters_trailing_minus() {
         let mut src = CharacterStream::new(String::from("h3ll0-"));
         let sut = IdentifierLexer::new();
 
-        let token = sut.collect_alpha_numeric_characters(&mut src);
+        let token = sut.collect_identifier_characters(&mut src);
 
         assert_that!(token, is(equal_to(String::from("h3ll0"))));
     }
@@ -117,7 +123,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("true"));
         let sut = IdentifierLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1), TokenType::Bool(true), String::from("true"))
@@ -129,7 +135,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("false"));
         let sut = IdentifierLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1), TokenType::Bool(false), String::from("false"))
@@ -141,7 +147,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("var"));
         let sut = IdentifierLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1), TokenType::Keyword(Keyword::Var), String::from("var"))
@@ -153,7 +159,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("snafu"));
         let sut = IdentifierLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -167,7 +173,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from(""));
         let sut = IdentifierLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -175,4 +181,39 @@ mod tests {
             String::from(""))
         )));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn collect_identifier_characters_with_underscores() {
+        let mut src = CharacterStream::new(String::from("_my_var1 "));
+        let sut = IdentifierLexer::new();
+
+        let token = sut.collect_identifier_characters(&mut src);
+
+        assert_that!(token, is(equal_to(String::from("_my_var1"))));
+    }
+
+    #[test]
+    fn scan_unicode_identifier() {
+        let mut src = CharacterStream::new(String::from("größe_λ+1"));
+        let sut = IdentifierLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Identifier(String::from("größe_λ")),
+            String::from("größe_λ"))
+        )));
+    }
+
+    #[test]
+    fn scan_identifier_with_combining_mark() {
+        let mut src = CharacterStream::new(String::from("e\u{0301}x"));
+        let sut = IdentifierLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        let expected = TokenType::Identifier(String::from("e\u{0301}x"));
+        assert_that!(token.get_token_type(), is(equal_to(&expected)));
+    }
+}
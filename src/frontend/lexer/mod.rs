@@ -1,17 +1,24 @@
 ///! This module provides various lexers.
 
+use std::collections::VecDeque;
+
 use frontend::character_stream::CharacterStream;
 use frontend::character_helper::CharacterHelper;
 use frontend::token::Token;
 use frontend::token::TokenType;
 use frontend::Position;
+use frontend::Span;
+use frontend::SyntaxError;
 use frontend::lexer::character_lexer::CharacterLexer;
+use frontend::lexer::comment_lexer::CommentLexer;
 use frontend::lexer::identifier_lexer::IdentifierLexer;
 use frontend::lexer::number_lexer::NumberLexer;
 use frontend::lexer::operator_lexer::OperatorLexer;
 use frontend::lexer::string_lexer::StringLexer;
 
 mod character_lexer;
+mod comment_lexer;
+mod escape;
 mod identifier_lexer;
 mod number_lexer;
 mod operator_lexer;
@@ -21,20 +28,55 @@ mod string_lexer;
 pub struct Lexer {
     input: CharacterStream,
     current: Token,
+    current_span: Span,
+    peeked: Option<Result<(Token, Span), SyntaxError>>,
+    keep_comments: bool,
+    replay: Option<VecDeque<(Token, Span)>>,
+    replay_end: Span,
 }
 
 impl Lexer {
-    /// Creates a new lexer.
+    /// Creates a new lexer which skips comments.
     pub fn new(input: CharacterStream) -> Lexer {
         Lexer {
             input,
             current: Token::new(
                 Position::null(),
                 TokenType::EOF,
-                String::from(""))
+                String::from("")),
+            current_span: Span::null(),
+            peeked: None,
+            keep_comments: false,
+            replay: None,
+            replay_end: Span::null(),
         }
     }
 
+    /// Creates a new lexer which emits comments as [Comment](../token/enum.TokenType.html#variant.Comment)
+    /// tokens.
+    ///
+    /// This is useful for tooling like the formatter which must not lose comments.
+    pub fn with_comments(input: CharacterStream) -> Lexer {
+        let mut lexer = Lexer::new(input);
+        lexer.keep_comments = true;
+        lexer
+    }
+
+    /// Creates a lexer which replays already recognized tokens, e.g. the tokens of an expression
+    /// embedded in an interpolated string.
+    ///
+    /// After the last token [EOF](../token/enum.TokenType.html#variant.EOF) is emitted at the end
+    /// of the last token.
+    pub fn from_tokens(tokens: Vec<(Token, Span)>) -> Lexer {
+        let end = tokens.last()
+            .map(|&(_, span)| Span::new(span.end(), span.end(), span.end_offset(), span.end_offset()))
+            .unwrap_or_else(Span::null);
+        let mut lexer = Lexer::new(CharacterStream::new(String::new()));
+        lexer.replay = Some(tokens.into_iter().collect());
+        lexer.replay_end = end;
+        lexer
+    }
+
     /// Get the input character stream.
     pub fn input(self) -> CharacterStream {
         self.input
@@ -47,47 +89,84 @@ impl Lexer {
         &self.current
     }
 
+    /// Get the source range of the current recognized token.
+    pub fn span(&self) -> Span {
+        self.current_span
+    }
+
     /// Recognizes the next token.
-    pub fn next(&mut self) {
-        if !self.input.has_next() {
-            debug!("No more input to lex.");
-            self.current = Token::new(
-                self.input.position(),
-                TokenType::EOF,
-                String::from(""));
-            return;
+    ///
+    /// Returns an error if the source at the current position is not a valid token.
+    pub fn next_token(&mut self) -> Result<(), SyntaxError> {
+        let (token, span) = match self.peeked.take() {
+            Some(peeked) => peeked?,
+            None => self.scan()?,
+        };
+        self.current = token;
+        self.current_span = span;
+        Ok(())
+    }
+
+    /// Get the token after the current one without moving to it.
+    ///
+    /// Returns an error if the source after the current token is not a valid token.
+    pub fn peek(&mut self) -> Result<&Token, SyntaxError> {
+        if self.peeked.is_none() {
+            let scanned = self.scan();
+            self.peeked = Some(scanned);
+        }
+
+        match self.peeked {
+            Some(Ok((ref token, _))) => Ok(token),
+            Some(Err(ref e)) => Err(e.clone()),
+            None => unreachable!(),
+        }
+    }
+
+    fn scan(&mut self) -> Result<(Token, Span), SyntaxError> {
+        if let Some(ref mut tokens) = self.replay {
+            return Ok(match tokens.pop_front() {
+                Some(token) => token,
+                None => (Token::new(self.replay_end.start(), TokenType::EOF, String::from("")), self.replay_end),
+            });
         }
 
         while self.input.has_next() {
             debug!("Lexer loop iteration start.");
             let position = self.input.position();
+            let offset = self.input.offset();
             let current = self.input.current();
 
-            if CharacterHelper::is_alphabetic(current) {
+            let token = if CharacterHelper::is_comment_start(current, self.input.peek()) {
+                // scan for line or block comment
+                debug!("Current char is '{}' at {}. Use comment lexer.", current, position);
+                let comment = CommentLexer::new().scan(&mut self.input)?;
+
+                if !self.keep_comments {
+                    continue;
+                }
+
+                comment
+            } else if CharacterHelper::is_identifier_start(current) {
                 // scan for boolean/keyword/identifier
                 debug!("Current char is '{}' at {}. Use identifier lexer.", current, position);
-                self.current = IdentifierLexer::new().scan(&mut self.input);
-                break;
-            } else if CharacterHelper::is_numeric(current) {
+                IdentifierLexer::new().scan(&mut self.input)?
+            } else if CharacterHelper::is_digit(current) {
                 // scan for integer and real number
                 debug!("Current char is '{}' at {}. Use number lexer.", current, position);
-                self.current = NumberLexer::new().scan(&mut self.input);
-                break;
+                NumberLexer::new().scan(&mut self.input)?
             } else if CharacterHelper::is_double_quote(current) {
                 // scan for string literal
                 debug!("Current char is '{}' at {}. Use string lexer.", current, position);
-                self.current = StringLexer::new().scan(&mut self.input);
-                break;
+                StringLexer::new().scan(&mut self.input)?
             } else if CharacterHelper::is_single_quote(current) {
                 // scan for single character literal
                 debug!("Current char is '{}' at {}. Use character lexer.", current, position);
-                self.current = CharacterLexer::new().scan(&mut self.input);
-                break;
+                CharacterLexer::new().scan(&mut self.input)?
             } else if CharacterHelper::is_operator(current) {
                 // scan for operator or delimiter
                 debug!("Current char is '{}' at {}. Use operator lexer.", current, position);
-                self.current = OperatorLexer::new().scan(&mut self.input);
-                break;
+                OperatorLexer::new().scan(&mut self.input)?
             } else if CharacterHelper::is_white_space(current) {
                 // ignore white spaces
                 debug!("Current char is '{}' at {}. Ignoring whitespace.", current, position);
@@ -95,23 +174,33 @@ impl Lexer {
                 continue;
             } else if CharacterHelper::is_new_line(current) {
                 debug!("Current char is '{}' at {}. Detected EOL.", current, position);
-                self.current = Token::new(
-                    self.input.position(),
-                    TokenType::EOL,
-                    String::from("\\n"));
                 self.input.next(); // consume \n
-                break;
-            }
+                Token::new(
+                    position,
+                    TokenType::EOL,
+                    String::from("\\n"))
+            } else {
+                debug!("Current char is '{}' at {}. No lexer recognizes it.", current, position);
+                self.input.next(); // consume unrecognized character
+                return Err(SyntaxError::new(
+                    format!("Unrecognized character '{}'!", current.escape_default()),
+                    self.input.span_from(position, offset)));
+            };
+
+            return Ok((token, self.input.span_from(position, offset)));
         }
-    }
 
-    pub fn peek(&self) -> &Token {
-        unimplemented!();
+        debug!("No more input to lex.");
+        let position = self.input.position();
+        let offset = self.input.offset();
+        Ok((Token::new(position, TokenType::EOF, String::from("")),
+            Span::new(position, position, offset, offset)))
     }
 }
 
 trait SubLexer {
-    fn scan(&self, input: &mut CharacterStream) -> Token;
+    /// Scans one token beginning at the current character of the given input.
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError>;
 }
 
 #[cfg(test)]
@@ -119,6 +208,7 @@ mod tests {
     use super::*;
     use hamcrest::prelude::*;
     use frontend::token::Keyword;
+    use frontend::token::Operator;
 
     #[test]
     fn lex_source() {
@@ -129,18 +219,274 @@ z = x + y\n
 println(z)\n"));
         let mut sut = Lexer::new(src);
 
-        sut.next();
+        sut.next_token().unwrap();
         let mut expected = Token::new(
             Position::new(1, 1),
             TokenType::Keyword(Keyword::Var),
             String::from("var"));
         assert_that!(sut.current(), is(equal_to(&expected)));
 
-        sut.next();
+        sut.next_token().unwrap();
         expected = Token::new(
             Position::new(1, 5),
             TokenType::Identifier(String::from("s")),
             String::from("s"));
         assert_that!(sut.current(), is(equal_to(&expected)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lex_trailing_white_space() {
+        let src = CharacterStream::new(String::from("x  "));
+        let mut sut = Lexer::new(src);
+
+        sut.next_token().unwrap();
+        let identifier = TokenType::Identifier(String::from("x"));
+        assert_that!(sut.current().get_token_type(), is(equal_to(&identifier)));
+        sut.next_token().unwrap();
+        assert_that!(sut.current().get_token_type(), is(equal_to(&TokenType::EOF)));
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 4), Position::new(1, 4), 3, 3))));
+    }
+
+    #[test]
+    fn span() {
+        let src = CharacterStream::new(String::from("var foo = 42\n"));
+        let mut sut = Lexer::new(src);
+
+        sut.next_token().unwrap();
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 1), Position::new(1, 4), 0, 3))));
+        sut.next_token().unwrap();
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 5), Position::new(1, 8), 4, 7))));
+        sut.next_token().unwrap();
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 9), Position::new(1, 10), 8, 9))));
+        sut.next_token().unwrap();
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 11), Position::new(1, 13), 10, 12))));
+        sut.next_token().unwrap();
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 13), Position::new(2, 1), 12, 13))));
+    }
+
+    #[test]
+    fn peek() {
+        let src = CharacterStream::new(String::from("x = 1"));
+        let mut sut = Lexer::new(src);
+
+        sut.next_token().unwrap();
+        assert_that!(sut.peek().unwrap().get_token_type(), is(equal_to(&TokenType::Operator(Operator::Assign))));
+        let identifier = TokenType::Identifier(String::from("x"));
+        assert_that!(sut.current().get_token_type(), is(equal_to(&identifier)));
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 1), Position::new(1, 2), 0, 1))));
+
+        sut.next_token().unwrap();
+        assert_that!(sut.current().get_token_type(), is(equal_to(&TokenType::Operator(Operator::Assign))));
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 3), Position::new(1, 4), 2, 3))));
+        sut.next_token().unwrap();
+        assert_that!(sut.current().get_token_type(), is(equal_to(&TokenType::Integer(1))));
+    }
+
+    fn token_types(mut sut: Lexer) -> Vec<TokenType> {
+        let mut types = Vec::new();
+
+        loop {
+            sut.next_token().unwrap();
+            types.push(sut.current().get_token_type().clone());
+
+            if TokenType::EOF == *sut.current().get_token_type() {
+                return types;
+            }
+        }
+    }
+
+    fn lex(src: &str) -> Vec<TokenType> {
+        token_types(Lexer::new(CharacterStream::new(String::from(src))))
+    }
+
+    fn lex_with_comments(src: &str) -> Vec<TokenType> {
+        token_types(Lexer::with_comments(CharacterStream::new(String::from(src))))
+    }
+
+    #[test]
+    fn lex_comment_at_eof() {
+        assert_that!(lex("x // comment"), is(equal_to(vec![
+            TokenType::Identifier(String::from("x")),
+            TokenType::EOF])));
+        assert_that!(lex("x # comment\n"), is(equal_to(vec![
+            TokenType::Identifier(String::from("x")),
+            TokenType::EOL,
+            TokenType::EOF])));
+        assert_that!(lex("x /* comment */"), is(equal_to(vec![
+            TokenType::Identifier(String::from("x")),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_comment_inside_expression() {
+        assert_that!(lex("1 /* one */ + /* two /* nested */ */ 2 / 3"), is(equal_to(vec![
+            TokenType::Integer(1),
+            TokenType::Operator(Operator::Plus),
+            TokenType::Integer(2),
+            TokenType::Operator(Operator::Slash),
+            TokenType::Integer(3),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_block_comment_spanning_lines_has_no_eol() {
+        assert_that!(lex("x /* a\nb */ y\n"), is(equal_to(vec![
+            TokenType::Identifier(String::from("x")),
+            TokenType::Identifier(String::from("y")),
+            TokenType::EOL,
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_comment_following_string_literal() {
+        assert_that!(lex("\"a // b # c\" // d"), is(equal_to(vec![
+            TokenType::String(String::from("a // b # c")),
+            TokenType::EOF])));
+        assert_that!(lex("\"a\"/* b */\"c\"#d"), is(equal_to(vec![
+            TokenType::String(String::from("a")),
+            TokenType::String(String::from("c")),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_keeps_comments() {
+        assert_that!(lex_with_comments("x = 1 // one\n# two"), is(equal_to(vec![
+            TokenType::Identifier(String::from("x")),
+            TokenType::Operator(Operator::Assign),
+            TokenType::Integer(1),
+            TokenType::Comment(String::from("// one")),
+            TokenType::EOL,
+            TokenType::Comment(String::from("# two")),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_error() {
+        let src = CharacterStream::new(String::from("x = \"a\\qb\""));
+        let mut sut = Lexer::new(src);
+
+        sut.next_token().unwrap();
+        sut.next_token().unwrap();
+        assert_that!(sut.peek(), is(equal_to(Err(SyntaxError::new(
+            String::from("Unknown escape sequence '\\q'!"),
+            Span::new(Position::new(1, 7), Position::new(1, 9), 6, 8))))));
+        assert_that!(sut.next_token(), is(equal_to(Err(SyntaxError::new(
+            String::from("Unknown escape sequence '\\q'!"),
+            Span::new(Position::new(1, 7), Position::new(1, 9), 6, 8))))));
+    }
+
+    #[test]
+    fn lex_unicode_source() {
+        assert_that!(lex("var ä = \"😀\" // ü\n'é'"), is(equal_to(vec![
+            TokenType::Keyword(Keyword::Var),
+            TokenType::Identifier(String::from("ä")),
+            TokenType::Operator(Operator::Assign),
+            TokenType::String(String::from("😀")),
+            TokenType::EOL,
+            TokenType::Character('é'),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_identifiers_with_underscores() {
+        assert_that!(lex("my_var = _x1"), is(equal_to(vec![
+            TokenType::Identifier(String::from("my_var")),
+            TokenType::Operator(Operator::Assign),
+            TokenType::Identifier(String::from("_x1")),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_indic_identifier() {
+        assert_that!(lex("var नमस्ते = 1"), is(equal_to(vec![
+            TokenType::Keyword(Keyword::Var),
+            TokenType::Identifier(String::from("नमस्ते")),
+            TokenType::Operator(Operator::Assign),
+            TokenType::Integer(1),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_identifier_with_middle_dot() {
+        assert_that!(lex("a·b"), is(equal_to(vec![
+            TokenType::Identifier(String::from("a·b")),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_carriage_return() {
+        assert_that!(lex("x\r\ny"), is(equal_to(vec![
+            TokenType::Identifier(String::from("x")),
+            TokenType::EOL,
+            TokenType::Identifier(String::from("y")),
+            TokenType::EOF])));
+    }
+
+    #[test]
+    fn lex_unrecognized_character() {
+        let mut sut = Lexer::new(CharacterStream::new(String::from("x = @y")));
+
+        sut.next_token().unwrap();
+        sut.next_token().unwrap();
+        assert_that!(sut.next_token(), is(equal_to(Err(SyntaxError::new(
+            String::from("Unrecognized character '@'!"),
+            Span::new(Position::new(1, 5), Position::new(1, 6), 4, 5))))));
+        sut.next_token().unwrap();
+        let expected = TokenType::Identifier(String::from("y"));
+        assert_that!(sut.current().get_token_type(), is(equal_to(&expected)));
+    }
+
+    #[test]
+    fn lex_non_ascii_numeric_characters() {
+        let mut sut = Lexer::new(CharacterStream::new(String::from("½")));
+
+        assert_that!(sut.next_token(), is(equal_to(Err(SyntaxError::new(
+            String::from("Unrecognized character '\\u{bd}'!"),
+            Span::new(Position::new(1, 1), Position::new(1, 2), 0, 2))))));
+
+        let mut sut = Lexer::new(CharacterStream::new(String::from("a² x\u{663}")));
+
+        sut.next_token().unwrap();
+        let expected = TokenType::Identifier(String::from("a"));
+        assert_that!(sut.current().get_token_type(), is(equal_to(&expected)));
+        assert_that!(sut.next_token(), is(equal_to(Err(SyntaxError::new(
+            String::from("Unrecognized character '\\u{b2}'!"),
+            Span::new(Position::new(1, 2), Position::new(1, 3), 1, 3))))));
+        sut.next_token().unwrap();
+        let expected = TokenType::Identifier(String::from("x\u{663}"));
+        assert_that!(sut.current().get_token_type(), is(equal_to(&expected)));
+    }
+
+    #[test]
+    fn lex_unrecognized_control_character() {
+        let mut sut = Lexer::new(CharacterStream::new(String::from("\u{7}")));
+
+        assert_that!(sut.next_token(), is(equal_to(Err(SyntaxError::new(
+            String::from("Unrecognized character '\\u{7}'!"),
+            Span::new(Position::new(1, 1), Position::new(1, 2), 0, 1))))));
+    }
+
+    #[test]
+    fn lex_replayed_tokens() {
+        let mut source = Lexer::new(CharacterStream::new(String::from("a + 1")));
+        let mut tokens = Vec::new();
+
+        for _ in 0..3 {
+            source.next_token().unwrap();
+            tokens.push((source.current().clone(), source.span()));
+        }
+
+        let mut sut = Lexer::from_tokens(tokens);
+
+        sut.next_token().unwrap();
+        let expected = TokenType::Identifier(String::from("a"));
+        assert_that!(sut.current().get_token_type(), is(equal_to(&expected)));
+        let expected = TokenType::Operator(Operator::Plus);
+        assert_that!(sut.peek().unwrap().get_token_type(), is(equal_to(&expected)));
+        sut.next_token().unwrap();
+        sut.next_token().unwrap();
+        sut.next_token().unwrap();
+        assert_that!(sut.current().get_token_type(), is(equal_to(&TokenType::EOF)));
+        assert_that!(sut.span(), is(equal_to(Span::new(Position::new(1, 6), Position::new(1, 6), 5, 5))));
+    }
+}
@@ -2,10 +2,18 @@ use frontend::token::*;
 use frontend::lexer::SubLexer;
 use frontend::character_stream::CharacterStream;
 use frontend::character_helper::CharacterHelper;
-#[cfg(test)]
-use frontend::Position;
+use frontend::{Position, SyntaxError};
+
+/// Separator which may be used between digits to group them, e.g. `1_000_000`.
+const DIGIT_SEPARATOR: char = '_';
+
+/// Magnitude of the smallest integer, which is one more than the largest integer.
+const MIN_INTEGER_MAGNITUDE: u64 = 1 << 63;
 
 /// Recognizes a number literal token.
+///
+/// Integers may be written in decimal, hexadecimal (`0x1F`), octal (`0o17`) or binary (`0b101`)
+/// notation. Reals are always decimal. All digits may be grouped by underscores (`1_000.000_1`).
 pub struct NumberLexer {}
 
 impl NumberLexer {
@@ -16,8 +24,13 @@ impl NumberLexer {
     // Consumes all unsigned inter digits from input until a non integer digit character occurs.
     //
     // @param input must not be {@code null}
-    // @return all digits from input
+    // @return all digits from input including digit separators
     fn unsigned_integer_digits(&self, input: &mut CharacterStream) -> String {
+        self.digits(input, 10)
+    }
+
+    // Consumes all digits of the given radix and digit separators from input.
+    fn digits(&self, input: &mut CharacterStream, radix: u32) -> String {
         let mut digits = String::new();
 
         loop {
@@ -26,7 +39,7 @@ impl NumberLexer {
                 break;
             }
 
-            if !CharacterHelper::is_numeric(input.current()) {
+            if !input.current().is_digit(radix) && DIGIT_SEPARATOR != input.current() {
                 // no more digits
                 break;
             }
@@ -37,6 +50,44 @@ impl NumberLexer {
 
         digits
     }
+
+    // Scans an integer with a radix prefix like `0x`. The input must be at the leading zero.
+    fn scan_prefixed_integer(&self, input: &mut CharacterStream, position: Position, offset: usize, radix: u32)
+        -> Result<Token, SyntaxError> {
+        let mut literal = String::new();
+        literal.push(input.current());
+        input.next(); // consume 0
+        literal.push(input.current());
+        input.next(); // consume radix indicator
+        let digits = self.digits(input, radix);
+        literal.push_str(&digits);
+
+        if CharacterHelper::is_alphanumeric(input.current()) {
+            let invalid = input.current();
+            input.next(); // consume invalid digit
+            return Err(SyntaxError::new(
+                format!("Invalid digit '{}' in {} literal '{}{}'!", invalid, radix_name(radix), literal, invalid),
+                input.span_from(position, offset)));
+        }
+
+        if digits.is_empty() {
+            return Err(SyntaxError::new(
+                format!("At least one digit necessary after '{}'!", literal),
+                input.span_from(position, offset)));
+        }
+
+        check_separators(&digits)
+            .map_err(|message| SyntaxError::new(message, input.span_from(position, offset)))?;
+        let digits = strip_separators(&digits);
+
+        // Like in Java a prefixed literal may use all 64 bits, so 0xFFFF_FFFF_FFFF_FFFF is -1.
+        match u64::from_str_radix(&digits, radix) {
+            Ok(value) => Ok(Token::new(position, TokenType::Integer(value as i64), literal)),
+            Err(_) => Err(SyntaxError::new(
+                format!("Integer literal '{}' does not fit into 64 bits!", literal),
+                input.span_from(position, offset))),
+        }
+    }
 }
 
 enum Type {
@@ -45,9 +96,20 @@ enum Type {
 }
 
 impl SubLexer for NumberLexer {
-    fn scan(&self, input: &mut CharacterStream) -> Token {
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError> {
         let position = input.position();
+        let offset = input.offset();
         debug!("Start number lexing at position {}.", position);
+
+        if '0' == input.current() {
+            match input.peek() {
+                'x' | 'X' => return self.scan_prefixed_integer(input, position, offset, 16),
+                'o' | 'O' => return self.scan_prefixed_integer(input, position, offset, 8),
+                'b' | 'B' => return self.scan_prefixed_integer(input, position, offset, 2),
+                _ => (),
+            }
+        }
+
         let mut literal = String::new();
         let mut whole_digits = String::new();    // Digits before the decimal point.
         let mut fraction_digits = String::new(); // Digits after the decimal point.
@@ -59,13 +121,15 @@ impl SubLexer for NumberLexer {
         whole_digits.push_str(self.unsigned_integer_digits(input).as_str());
 
         if whole_digits.is_empty() {
-            panic!("At least one digit necessary!");
+            input.next(); // consume the character which is no digit
+            return Err(SyntaxError::new(
+                String::from("At least one digit necessary!"), input.span_from(position, offset)));
         }
 
         literal.push_str(whole_digits.as_str());
 
-        // Is there a dot, so we have a floating point number.
-        if '.' == input.current() {
+        // Is there a dot, so we have a floating point number. Two dots are a range after an integer.
+        if '.' == input.current() && '.' != input.peek() {
             number_type = Type::REAL;
             literal.push(input.current());
             input.next(); // Consume decimal point.
@@ -73,7 +137,8 @@ impl SubLexer for NumberLexer {
             fraction_digits.push_str(self.unsigned_integer_digits(input).as_str());
 
             if fraction_digits.is_empty() {
-                panic!("At least one fraction digit necessary!");
+                return Err(SyntaxError::new(
+                    String::from("At least one fraction digit necessary!"), input.span_from(position, offset)));
             }
 
             literal.push_str(fraction_digits.as_str());
@@ -95,31 +160,71 @@ impl SubLexer for NumberLexer {
             exponent_digits.push_str(self.unsigned_integer_digits(input).as_str());
 
             if exponent_digits.is_empty() {
-                panic!("At least one exponent digit necessary!");
+                return Err(SyntaxError::new(
+                    String::from("At least one exponent digit necessary!"), input.span_from(position, offset)));
             }
 
             literal.push_str(exponent_digits.as_str());
         }
 
-        let token_type;
-        match number_type {
+        for digits in &[whole_digits, fraction_digits, exponent_digits] {
+            check_separators(digits)
+                .map_err(|message| SyntaxError::new(message, input.span_from(position, offset)))?;
+        }
+
+        let value = strip_separators(&literal);
+
+        let token_type = match number_type {
             Type::INTEGER => {
-                // Compute the value of an integer number token.
-                match literal.parse::<i64>() {
-                    Ok(value) => token_type = TokenType::Integer(value),
-                    Err(error) => panic!(format!("ERROR: {}: \"{}\"", error, literal))
+                // Compute the value of an integer number token. The magnitude of i64::MIN is accepted
+                // because the lexer does not know if the literal is negated. The parser rejects it
+                // if it is not preceded by a minus.
+                match value.parse::<u64>() {
+                    Ok(value) if value <= MIN_INTEGER_MAGNITUDE => TokenType::Integer(value as i64),
+                    _ => return Err(SyntaxError::new(
+                        format!("Integer literal '{}' is out of range!", literal),
+                        input.span_from(position, offset))),
                 }
             },
             Type::REAL => {
                 // Compute the value of a real number token.
-                match literal.parse::<f64>() {
-                    Ok(value) => token_type = TokenType::Real(value),
-                    Err(error) => panic!(format!("ERROR: {}: \"{}\"", error, literal))
+                match value.parse::<f64>() {
+                    Ok(value) if value.is_finite() => TokenType::Real(value),
+                    _ => return Err(SyntaxError::new(
+                        format!("Real literal '{}' is out of range!", literal),
+                        input.span_from(position, offset))),
                 }
             }
-        }
+        };
 
-        Token::new(position, token_type, literal)
+        Ok(Token::new(position, token_type, literal))
+    }
+}
+
+// Returns an error message if a digit separator is not placed between two digits.
+fn check_separators(digits: &str) -> Result<(), String> {
+    let misplaced = digits.starts_with(DIGIT_SEPARATOR)
+        || digits.ends_with(DIGIT_SEPARATOR)
+        || digits.contains("__");
+
+    if misplaced {
+        Err(format!("Digit separator '{}' must be placed between digits!", DIGIT_SEPARATOR))
+    } else {
+        Ok(())
+    }
+}
+
+// Removes the digit separators from the given literal.
+fn strip_separators(literal: &str) -> String {
+    literal.chars().filter(|ch| DIGIT_SEPARATOR != *ch).collect()
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
     }
 }
 
@@ -127,6 +232,7 @@ impl SubLexer for NumberLexer {
 mod tests {
     use super::*;
     use hamcrest::prelude::*;
+    use frontend::Span;
 
     #[test]
     fn unsigned_integer_digits_empty() {
@@ -173,7 +279,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("42"));
         let sut = NumberLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token,
             is(equal_to(
@@ -187,7 +293,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("3.14"));
         let sut = NumberLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token,
             is(equal_to(
@@ -201,7 +307,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("7.0e-2"));
         let sut = NumberLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token,
             is(equal_to(
@@ -215,7 +321,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("7.0E-2"));
         let sut = NumberLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token,
             is(equal_to(
@@ -229,7 +335,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("7.0e2"));
         let sut = NumberLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token,
             is(equal_to(
@@ -243,7 +349,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("7.0E+2"));
         let sut = NumberLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token,
             is(equal_to(
@@ -251,4 +357,134 @@ mod tests {
             )
         );
     }
-}
\ No newline at end of file
+
+    fn scan(src: &str) -> Result<Token, SyntaxError> {
+        NumberLexer::new().scan(&mut CharacterStream::new(String::from(src)))
+    }
+
+    fn error(src: &str) -> (String, Span) {
+        let error = scan(src).unwrap_err();
+        (String::from(error.message()), error.span())
+    }
+
+    fn span(start_column: u64, end_column: u64) -> Span {
+        Span::new(
+            Position::new(1, start_column),
+            Position::new(1, end_column),
+            (start_column - 1) as usize,
+            (end_column - 1) as usize)
+    }
+
+    #[test]
+    fn unsigned_integer_digits_with_separators() {
+        let mut src = CharacterStream::new(String::from("1_000 "));
+        let sut = NumberLexer::new();
+
+        let token = sut.unsigned_integer_digits(&mut src);
+
+        assert_that!(token, is(equal_to(String::from("1_000"))));
+    }
+
+    #[test]
+    fn integer_with_separators() {
+        assert_that!(scan("1_000_000").unwrap(), is(equal_to(
+            Token::new(Position::new(1, 1), TokenType::Integer(1_000_000), String::from("1_000_000")))));
+    }
+
+    #[test]
+    fn real_with_separators() {
+        assert_that!(scan("1_000.000_5e1_0").unwrap(), is(equal_to(
+            Token::new(Position::new(1, 1), TokenType::Real(1_000.000_5e10), String::from("1_000.000_5e1_0")))));
+    }
+
+    #[test]
+    fn prefixed_integers() {
+        assert_that!(scan("0x1F").unwrap(), is(equal_to(
+            Token::new(Position::new(1, 1), TokenType::Integer(31), String::from("0x1F")))));
+        assert_that!(scan("0Xff_ff").unwrap(), is(equal_to(
+            Token::new(Position::new(1, 1), TokenType::Integer(65535), String::from("0Xff_ff")))));
+        assert_that!(scan("0o17").unwrap(), is(equal_to(
+            Token::new(Position::new(1, 1), TokenType::Integer(15), String::from("0o17")))));
+        assert_that!(scan("0b1010_1010").unwrap(), is(equal_to(
+            Token::new(Position::new(1, 1), TokenType::Integer(170), String::from("0b1010_1010")))));
+    }
+
+    #[test]
+    fn prefixed_integer_uses_all_bits() {
+        assert_that!(scan("0xFFFF_FFFF_FFFF_FFFF").unwrap().get_token_type(), is(equal_to(&TokenType::Integer(-1))));
+        assert_that!(scan("0x8000_0000_0000_0000").unwrap().get_token_type(),
+            is(equal_to(&TokenType::Integer(i64::MIN))));
+    }
+
+    #[test]
+    fn prefixed_integer_stops_at_non_digit() {
+        let mut src = CharacterStream::new(String::from("0x1f+1"));
+
+        let token = NumberLexer::new().scan(&mut src).unwrap();
+
+        assert_that!(token.get_token_type(), is(equal_to(&TokenType::Integer(31))));
+        assert_that!(src.current(), is(equal_to('+')));
+    }
+
+    #[test]
+    fn integer_followed_by_range() {
+        let mut src = CharacterStream::new(String::from("0..10"));
+
+        let token = NumberLexer::new().scan(&mut src).unwrap();
+
+        assert_that!(token.get_token_type(), is(equal_to(&TokenType::Integer(0))));
+        assert_that!(src.current(), is(equal_to('.')));
+    }
+
+    #[test]
+    fn prefixed_integer_errors() {
+        assert_that!(error("0x"), is(equal_to((
+            String::from("At least one digit necessary after '0x'!"), span(1, 3)))));
+        assert_that!(error("0b102"), is(equal_to((
+            String::from("Invalid digit '2' in binary literal '0b102'!"), span(1, 6)))));
+        assert_that!(error("0o8"), is(equal_to((
+            String::from("Invalid digit '8' in octal literal '0o8'!"), span(1, 4)))));
+        assert_that!(error("0x1_0000_0000_0000_0000"), is(equal_to((
+            String::from("Integer literal '0x1_0000_0000_0000_0000' does not fit into 64 bits!"), span(1, 24)))));
+    }
+
+    #[test]
+    fn misplaced_separators() {
+        let message = String::from("Digit separator '_' must be placed between digits!");
+
+        assert_that!(error("1_ "), is(equal_to((message.clone(), span(1, 3)))));
+        assert_that!(error("1__0"), is(equal_to((message.clone(), span(1, 5)))));
+        assert_that!(error("1_.5"), is(equal_to((message.clone(), span(1, 5)))));
+        assert_that!(error("1._5"), is(equal_to((message.clone(), span(1, 5)))));
+        assert_that!(error("1e_5"), is(equal_to((message.clone(), span(1, 5)))));
+        assert_that!(error("0x_1"), is(equal_to((message, span(1, 5)))));
+    }
+
+    #[test]
+    fn integer_out_of_range() {
+        assert_that!(error("9_223_372_036_854_775_809"), is(equal_to((
+            String::from("Integer literal '9_223_372_036_854_775_809' is out of range!"), span(1, 26)))));
+        assert_that!(error("18446744073709551616"), is(equal_to((
+            String::from("Integer literal '18446744073709551616' is out of range!"), span(1, 21)))));
+    }
+
+    #[test]
+    fn integer_magnitude_of_min() {
+        assert_that!(scan("9223372036854775808").unwrap().get_token_type(),
+            is(equal_to(&TokenType::Integer(i64::MIN))));
+    }
+
+    #[test]
+    fn real_out_of_range() {
+        assert_that!(error("1e400"), is(equal_to((
+            String::from("Real literal '1e400' is out of range!"), span(1, 6)))));
+    }
+
+    #[test]
+    fn missing_digits() {
+        assert_that!(error("1.x"), is(equal_to((
+            String::from("At least one fraction digit necessary!"), span(1, 3)))));
+        assert_that!(error("1e+"), is(equal_to((
+            String::from("At least one exponent digit necessary!"), span(1, 4)))));
+    }
+}
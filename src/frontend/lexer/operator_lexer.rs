@@ -1,8 +1,9 @@
 use frontend::lexer::SubLexer;
 use frontend::token::*;
 use frontend::character_stream::CharacterStream;
+use frontend::SyntaxError;
 #[cfg(test)]
-use frontend::Position;
+use frontend::{Position, Span};
 
 /// Recognizes a operator literal token.
 pub struct OperatorLexer {}
@@ -14,11 +15,12 @@ impl OperatorLexer {
 }
 
 impl SubLexer for OperatorLexer {
-    fn scan(&self, input: &mut CharacterStream) -> Token {
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError> {
         let position = input.position();
+        let offset = input.offset();
         debug!("Start operator lexing at position {}.", position);
 
-        match input.current() {
+        Ok(match input.current() {
             '(' => {
                 input.next(); //consume (
                 Token::new(
@@ -68,6 +70,29 @@ impl SubLexer for OperatorLexer {
                     TokenType::Comma,
                     String::from(","))
             },
+            '.' => {
+                input.next(); //consume .
+
+                if '.' == input.current() {
+                    input.next(); //consume .
+                    Token::new(
+                        position,
+                        TokenType::DotDot,
+                        String::from(".."))
+                } else {
+                    Token::new(
+                        position,
+                        TokenType::Dot,
+                        String::from("."))
+                }
+            },
+            ':' => {
+                input.next(); //consume :
+                Token::new(
+                    position,
+                    TokenType::Colon,
+                    String::from(":"))
+            },
             '=' => {
                 input.next(); //consume =
 
@@ -77,6 +102,12 @@ impl SubLexer for OperatorLexer {
                         position,
                         TokenType::Operator(Operator::Equal),
                         String::from("=="))
+                } else if '>' == input.current() {
+                    input.next(); //consume >
+                    Token::new(
+                        position,
+                        TokenType::Arrow,
+                        String::from("=>"))
                 } else {
                     Token::new(
                         position,
@@ -94,7 +125,9 @@ impl SubLexer for OperatorLexer {
                         TokenType::Operator(Operator::NotEqual),
                         String::from("!="))
                 } else {
-                    panic!("Expecting = after ! for != operator!");
+                    return Err(SyntaxError::new(
+                        String::from("Expecting = after ! for != operator!"),
+                        input.span_from(position, offset)));
                 }
             },
             '<' => {
@@ -131,41 +164,92 @@ impl SubLexer for OperatorLexer {
             },
             '+' => {
                 input.next(); //consume +
-                Token::new(
-                    position,
-                    TokenType::Operator(Operator::Plus),
-                    String::from("+"))
+
+                if '=' == input.current() {
+                    input.next(); //consume =
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::PlusAssign),
+                        String::from("+="))
+                } else {
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::Plus),
+                        String::from("+"))
+                }
             },
             '-' => {
                 input.next(); //consume -
-                Token::new(
-                    position,
-                    TokenType::Operator(Operator::Minus),
-                    String::from("-"))
+
+                if '=' == input.current() {
+                    input.next(); //consume =
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::MinusAssign),
+                        String::from("-="))
+                } else {
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::Minus),
+                        String::from("-"))
+                }
             },
             '*' => {
                 input.next(); //consume *
-                Token::new(
-                    position,
-                    TokenType::Operator(Operator::Star),
-                    String::from("*"))
+
+                if '=' == input.current() {
+                    input.next(); //consume =
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::StarAssign),
+                        String::from("*="))
+                } else {
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::Star),
+                        String::from("*"))
+                }
             },
             '/' => {
                 input.next(); //consume /
-                Token::new(
-                    position,
-                    TokenType::Operator(Operator::Slash),
-                    String::from("/"))
+
+                if '=' == input.current() {
+                    input.next(); //consume =
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::SlashAssign),
+                        String::from("/="))
+                } else {
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::Slash),
+                        String::from("/"))
+                }
             },
             '%' => {
                 input.next(); //consume %
-                Token::new(
-                    position,
-                    TokenType::Operator(Operator::Mod),
-                    String::from("%"))
+
+                if '=' == input.current() {
+                    input.next(); //consume =
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::ModAssign),
+                        String::from("%="))
+                } else {
+                    Token::new(
+                        position,
+                        TokenType::Operator(Operator::Mod),
+                        String::from("%"))
+                }
             }
-            _ => panic!("Unrecognized operator '{}'!", input.current())
-        }
+            _ => {
+                let operator = input.current();
+                input.next(); // consume bad character
+                return Err(SyntaxError::new(
+                    format!("Unrecognized operator '{}'!", operator),
+                    input.span_from(position, offset)));
+            }
+        })
     }
 }
 
@@ -179,7 +263,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("("));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -193,7 +277,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from(")"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -207,7 +291,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("["));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -221,7 +305,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("]"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -235,7 +319,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("{"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -249,7 +333,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("}"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -263,7 +347,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from(","));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -272,12 +356,40 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn scan_dot() {
+        let mut src = CharacterStream::new(String::from("."));
+        let sut = OperatorLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Dot,
+            String::from("."))
+        )));
+    }
+
+    #[test]
+    fn scan_colon() {
+        let mut src = CharacterStream::new(String::from(":"));
+        let sut = OperatorLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Colon,
+            String::from(":"))
+        )));
+    }
+
     #[test]
     fn scan_equal() {
         let mut src = CharacterStream::new(String::from("=="));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -286,12 +398,41 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn scan_dot_dot() {
+        let mut src = CharacterStream::new(String::from("..."));
+        let sut = OperatorLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::DotDot,
+            String::from(".."))
+        )));
+        assert_that!(src.current(), is(equal_to('.')));
+    }
+
+    #[test]
+    fn scan_arrow() {
+        let mut src = CharacterStream::new(String::from("=>"));
+        let sut = OperatorLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Arrow,
+            String::from("=>"))
+        )));
+    }
+
     #[test]
     fn scan_assign() {
         let mut src = CharacterStream::new(String::from("="));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -305,7 +446,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("!="));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -319,7 +460,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("<="));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -333,7 +474,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("<"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -347,7 +488,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from(">="));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -361,7 +502,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from(">"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -375,7 +516,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("+"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -389,7 +530,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("-"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -403,7 +544,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("*"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -417,7 +558,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("/"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -431,7 +572,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("%"));
         let sut = OperatorLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -439,4 +580,55 @@ mod tests {
             String::from("%"))
         )));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn scan_bang_without_equal() {
+        let mut src = CharacterStream::new(String::from("!x"));
+        let sut = OperatorLexer::new();
+
+        let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Expecting = after ! for != operator!"),
+            Span::new(Position::new(1, 1), Position::new(1, 2), 0, 1))))));
+    }
+
+    #[test]
+    fn scan_compound_assign_operators() {
+        let sut = OperatorLexer::new();
+        let operators = vec![
+            ("+=", Operator::PlusAssign),
+            ("-=", Operator::MinusAssign),
+            ("*=", Operator::StarAssign),
+            ("/=", Operator::SlashAssign),
+            ("%=", Operator::ModAssign),
+        ];
+
+        for (literal, operator) in operators {
+            let mut src = CharacterStream::new(String::from(literal));
+
+            let token = sut.scan(&mut src).unwrap();
+
+            assert_that!(token, is(equal_to(
+                Token::new(Position::new(1, 1),
+                TokenType::Operator(operator),
+                String::from(literal))
+            )));
+        }
+    }
+
+    #[test]
+    fn scan_operator_followed_by_assign_operator() {
+        let mut src = CharacterStream::new(String::from("-=="));
+        let sut = OperatorLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(Position::new(1, 1),
+            TokenType::Operator(Operator::MinusAssign),
+            String::from("-="))
+        )));
+        assert_that!(src.current(), is(equal_to('=')));
+    }
+}
@@ -1,10 +1,19 @@
-use frontend::lexer::SubLexer;
+use std::mem;
+
+use frontend::lexer::{Lexer, SubLexer};
 use frontend::token::*;
 use frontend::character_stream::CharacterStream;
-#[cfg(test)]
-use frontend::Position;
+use frontend::lexer::escape::scan_escape_sequence;
+use frontend::{Position, Span, SyntaxError};
 
 /// Recognizes a string literal (`"str"`) token.
+///
+/// Escape sequences (see [scan_escape_sequence](../escape/fn.scan_escape_sequence.html)) are
+/// replaced by the denoted character in the token value, but kept as is in the token literal.
+///
+/// A string containing `${expression}` is recognized as interpolated string. The embedded
+/// expression is scanned into tokens by a nested lexer, so it may contain anything an expression
+/// may contain, even other strings. A literal `${` is written as `\${`.
 pub struct StringLexer {}
 
 impl StringLexer {
@@ -14,35 +23,97 @@ impl StringLexer {
 }
 
 impl SubLexer for StringLexer {
-    fn scan(&self, input: &mut CharacterStream) -> Token {
+    fn scan(&self, input: &mut CharacterStream) -> Result<Token, SyntaxError> {
         let position = input.position();
+        let offset = input.offset();
         debug!("Start string lexing at position {}.", position);
         let mut value = String::new();
+        let mut literal = String::from("\"");
+        let mut parts = Vec::new();
 
         if !input.has_next() {
-            return Token::new(position, TokenType::EOF, String::from(""));
+            return Ok(Token::new(position, TokenType::EOF, String::from("")));
         }
 
         input.next(); // consume the "
 
         loop {
-            if '"' == input.current() {
-                input.next(); // consume the "
-                break; // end of string literal
+            match input.current() {
+                '"' => {
+                    input.next(); // consume the "
+                    break; // end of string literal
+                },
+                '\\' => {
+                    let (ch, escape_sequence) = scan_escape_sequence(input)?;
+                    value.push(ch);
+                    literal.push_str(&escape_sequence);
+                },
+                '$' if '{' == input.peek() => {
+                    let code_offset = input.offset();
+                    input.next(); // consume $
+                    input.next(); // consume {
+                    let tokens = scan_interpolation(input, position, offset)?;
+                    literal.push_str(&input.text_from(code_offset));
+                    parts.push(StringPart::Text(mem::take(&mut value)));
+                    parts.push(StringPart::Code(tokens));
+                },
+                END_OF_FILE => return Err(SyntaxError::new(
+                    String::from("Unterminated string literal!"),
+                    input.span_from(position, offset))),
+                ch => {
+                    value.push(ch);
+                    literal.push(ch);
+                    input.next(); // consume character
+                },
             }
+        }
 
-            if END_OF_FILE == input.current() {
-                panic!("Unterminated string literal!");
-            }
+        literal.push('"');
+
+        if parts.is_empty() {
+            return Ok(Token::new(position, TokenType::String(value), literal));
+        }
+
+        parts.push(StringPart::Text(value));
+        parts.retain(|part| StringPart::Text(String::new()) != *part);
+        Ok(Token::new(position, TokenType::InterpolatedString(parts), literal))
+    }
+}
 
-            value.push(input.current());
-            input.next(); // consume character
+// Scans the tokens of an embedded expression up to the closing brace, which is consumed.
+//
+// The nested lexer takes over the character stream meanwhile. The start is the position of the
+// string literal to report an unterminated interpolation.
+fn scan_interpolation(input: &mut CharacterStream, start: Position, start_offset: usize)
+    -> Result<Vec<(Token, Span)>, SyntaxError> {
+    let mut lexer = Lexer::new(mem::replace(input, CharacterStream::new(String::new())));
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+
+    let result = loop {
+        if let Err(e) = lexer.next_token() {
+            break Err(Some(e));
+        }
+
+        match *lexer.current().get_token_type() {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace if 0 == depth => break Ok(()),
+            TokenType::RightBrace => depth -= 1,
+            TokenType::EOL | TokenType::EOF => break Err(None),
+            _ => (),
         }
 
-        Token::new(
-            position,
-            TokenType::String(value.clone()),
-            format!("\"{}\"", value.clone()))
+        tokens.push((lexer.current().clone(), lexer.span()));
+    };
+
+    *input = lexer.input();
+
+    match result {
+        Ok(()) => Ok(tokens),
+        Err(Some(e)) => Err(e),
+        Err(None) => Err(SyntaxError::new(
+            String::from("Unterminated string interpolation!"),
+            input.span_from(start, start_offset))),
     }
 }
 
@@ -56,7 +127,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from(""));
         let sut = StringLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -66,13 +137,15 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // Panics as expected, but test fails in IDE, not on CLI
-    #[should_panic]
     fn scan_with_unexpected_end() {
         let mut src = CharacterStream::new(String::from("\""));
         let sut = StringLexer::new();
 
         let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated string literal!"),
+            Span::new(Position::new(1, 1), Position::new(1, 2), 0, 1))))));
     }
 
     #[test]
@@ -80,7 +153,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("\"\""));
         let sut = StringLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(Position::new(1, 1),
@@ -90,13 +163,15 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
-    #[should_panic]
     fn scan_unterminated_string() {
         let mut src = CharacterStream::new(String::from("\"foobar"));
         let sut = StringLexer::new();
 
         let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated string literal!"),
+            Span::new(Position::new(1, 1), Position::new(1, 8), 0, 7))))));
     }
 
     #[test]
@@ -104,7 +179,7 @@ mod tests {
         let mut src = CharacterStream::new(String::from("\"foo bar baz\""));
         let sut = StringLexer::new();
 
-        let token = sut.scan(&mut src);
+        let token = sut.scan(&mut src).unwrap();
 
         assert_that!(token, is(equal_to(
             Token::new(
@@ -113,4 +188,111 @@ mod tests {
             String::from("\"foo bar baz\""))
         )));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn scan_string_with_escape_sequences() {
+        let mut src = CharacterStream::new(String::from(r#""a\"b\\c\n\t\u{e4}" x"#));
+        let sut = StringLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(
+            Position::new(1, 1),
+            TokenType::String(String::from("a\"b\\c\n\tä")),
+            String::from(r#""a\"b\\c\n\t\u{e4}""#))
+        )));
+        assert_that!(src.current(), is(equal_to(' ')));
+    }
+
+    #[test]
+    fn scan_string_with_multi_byte_characters() {
+        let mut src = CharacterStream::new(String::from("\"Grüße 😀\""));
+        let sut = StringLexer::new();
+
+        let token = sut.scan(&mut src).unwrap();
+
+        assert_that!(token, is(equal_to(
+            Token::new(
+            Position::new(1, 1),
+            TokenType::String(String::from("Grüße 😀")),
+            String::from("\"Grüße 😀\""))
+        )));
+        assert_that!(src.has_next(), is(false));
+    }
+
+    #[test]
+    fn scan_string_with_bad_escape_sequence() {
+        let mut src = CharacterStream::new(String::from(r#""ab\xc""#));
+        let sut = StringLexer::new();
+
+        let token = sut.scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unknown escape sequence '\\x'!"),
+            Span::new(Position::new(1, 4), Position::new(1, 6), 3, 5))))));
+    }
+
+    fn code(src: &str) -> StringPart {
+        let mut lexer = Lexer::new(CharacterStream::new(String::from(src)));
+        let mut tokens = Vec::new();
+        lexer.next_token().unwrap();
+
+        while TokenType::EOF != *lexer.current().get_token_type() {
+            tokens.push((lexer.current().clone(), lexer.span()));
+            lexer.next_token().unwrap();
+        }
+
+        StringPart::Code(tokens)
+    }
+
+    fn scan_interpolated(src: &str) -> (TokenType, String) {
+        let mut src = CharacterStream::new(String::from(src));
+        let token = StringLexer::new().scan(&mut src).unwrap();
+        (token.get_token_type().clone(), token.get_literal().to_string())
+    }
+
+    #[test]
+    fn scan_interpolated_string() {
+        let (token_type, literal) = scan_interpolated("\"x = ${x}!\"");
+
+        let expected = TokenType::InterpolatedString(vec![
+            StringPart::Text(String::from("x = ")),
+            code("       x"),
+            StringPart::Text(String::from("!")),
+        ]);
+        assert_that!(token_type, is(equal_to(expected)));
+        assert_that!(&literal, is(equal_to("\"x = ${x}!\"")));
+    }
+
+    #[test]
+    fn scan_interpolated_string_with_nested_braces_and_strings() {
+        let (token_type, literal) = scan_interpolated("\"${ f(\"}\") }${1}\" rest");
+
+        let expected = TokenType::InterpolatedString(vec![
+            code("    f(\"}\")"),
+            code("              1"),
+        ]);
+        assert_that!(token_type, is(equal_to(expected)));
+        assert_that!(&literal, is(equal_to("\"${ f(\"}\") }${1}\"")));
+    }
+
+    #[test]
+    fn scan_escaped_interpolation() {
+        let (token_type, literal) = scan_interpolated(r#""\${x} $x""#);
+
+        assert_that!(token_type, is(equal_to(TokenType::String(String::from("${x} $x")))));
+        assert_that!(&literal, is(equal_to(r#""\${x} $x""#)));
+    }
+
+    #[test]
+    fn scan_unterminated_interpolation() {
+        let mut src = CharacterStream::new(String::from("\"a ${x\nb"));
+
+        let token = StringLexer::new().scan(&mut src);
+
+        assert_that!(token, is(equal_to(Err(SyntaxError::new(
+            String::from("Unterminated string interpolation!"),
+            Span::new(Position::new(1, 1), Position::new(2, 1), 0, 7))))));
+    }
+}
@@ -4,12 +4,15 @@ use std::fmt;
 
 pub mod character_stream;
 pub mod character_helper;
+pub mod formatter;
 pub mod lexer;
+pub mod module;
 pub mod parser;
 pub mod token;
+pub mod unicode_tables;
 
 /// Describes a character position in the source string.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Position {
     line: u64,
     column: u64,
@@ -22,7 +25,7 @@ impl Position {
     }
 
     /// Creates a new position.
-    fn new(line: u64, column: u64) -> Position {
+    pub fn new(line: u64, column: u64) -> Position {
         Position { line, column }
     }
 
@@ -30,6 +33,16 @@ impl Position {
     fn at(&self, p: Position) -> bool {
         p.line == self.line && p.column == self.column
     }
+
+    /// Get the line number, starting with 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// Get the column number, starting with 1.
+    pub fn column(&self) -> u64 {
+        self.column
+    }
 }
 
 impl fmt::Display for Position {
@@ -38,6 +51,92 @@ impl fmt::Display for Position {
     }
 }
 
+/// Describes a range of characters in the source string.
+///
+/// The start is inclusive and the end is exclusive: The end position and offset point to the
+/// character right after the last character of the range. The offsets are byte offsets into the
+/// source string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    start: Position,
+    end: Position,
+    start_offset: usize,
+    end_offset: usize,
+}
+
+impl Span {
+    /// Returns a null object.
+    pub fn null() -> Span {
+        Span::new(Position::null(), Position::null(), 0, 0)
+    }
+
+    /// Creates a new span.
+    pub fn new(start: Position, end: Position, start_offset: usize, end_offset: usize) -> Span {
+        Span { start, end, start_offset, end_offset }
+    }
+
+    /// Get the position of the first character.
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Get the position right after the last character.
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    /// Get the byte offset of the first character.
+    pub fn start_offset(&self) -> usize {
+        self.start_offset
+    }
+
+    /// Get the byte offset right after the last character.
+    pub fn end_offset(&self) -> usize {
+        self.end_offset
+    }
+
+    /// Creates a new span reaching from the start of this span to the end of the given one.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start, other.end, self.start_offset, other.end_offset)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Describes an error in the source code and where it occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    message: String,
+    span: Span,
+}
+
+impl SyntaxError {
+    /// Creates a new error.
+    pub fn new(message: String, span: Span) -> SyntaxError {
+        SyntaxError { message, span }
+    }
+
+    /// Get the human readable error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the location of the erroneous source.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span.start)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,4 +157,20 @@ mod tests {
         assert_that!(sut.at(Position::null()), is(true));
         assert_that!(sut.at(Position::new(42, 23)), is(false));
     }
+
+    #[test]
+    fn span_to() {
+        let first = Span::new(Position::new(1, 1), Position::new(1, 4), 0, 3);
+        let second = Span::new(Position::new(2, 5), Position::new(2, 7), 12, 14);
+
+        assert_that!(first.to(second), is(equal_to(
+            Span::new(Position::new(1, 1), Position::new(2, 7), 0, 14))));
+    }
+
+    #[test]
+    fn span_fmt() {
+        let sut = Span::new(Position::new(1, 1), Position::new(1, 4), 0, 3);
+
+        assert_that!(&format!("{}", sut), is(equal_to("[1, 1]-[1, 4]")));
+    }
 }
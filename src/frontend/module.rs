@@ -0,0 +1,261 @@
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use frontend::character_stream::CharacterStream;
+use frontend::lexer::Lexer;
+use frontend::parser::Parser;
+use frontend::{Span, SyntaxError};
+use intermediate::ast::*;
+
+/// A parsed source file.
+///
+/// The name of a module is the stem of its file name. Other modules access its declarations
+/// qualified by this name, e.g. `geometry.Point` for the record `Point` of `lib/geometry.mvm`.
+#[derive(Debug)]
+pub struct Module {
+    name: String,
+    path: PathBuf,
+    statements: Vec<Statement>,
+    imports: Vec<String>,
+    start_offset: usize,
+    end_offset: usize,
+}
+
+impl Module {
+    /// Get the name of the module.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the path of the source file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the parsed statements including the imports.
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    /// Get the names of the directly imported modules.
+    pub fn imports(&self) -> &[String] {
+        &self.imports
+    }
+
+    /// Determines if the given span lies in the source of this module.
+    pub fn contains(&self, span: Span) -> bool {
+        self.start_offset <= span.start_offset() && span.start_offset() <= self.end_offset
+    }
+}
+
+/// Describes an error in one of several source files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleError {
+    message: String,
+    path: PathBuf,
+    span: Option<Span>,
+}
+
+impl ModuleError {
+    /// Creates a new error.
+    ///
+    /// The span is the location of the error in the given file, if known.
+    pub fn new(message: String, path: PathBuf, span: Option<Span>) -> ModuleError {
+        ModuleError { message, path, span }
+    }
+
+    /// Get the human readable error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the path of the file which contains the error.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the location of the error in the file.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {} in {}", self.message, span.start(), self.path.display()),
+            None => write!(f, "{} in {}", self.message, self.path.display()),
+        }
+    }
+}
+
+/// Attributes an error of the linked program to the module whose source contains it.
+///
+/// This works because the modules are parsed with distinct offsets.
+pub fn locate(modules: &[Module], error: SyntaxError) -> ModuleError {
+    let path = modules.iter()
+        .find(|module| module.contains(error.span()))
+        .or_else(|| modules.last())
+        .map_or_else(PathBuf::new, |module| module.path.clone());
+    ModuleError::new(error.message().to_string(), path, Some(error.span()))
+}
+
+/// Loads a source file together with all modules it imports.
+///
+/// Import paths are resolved relative to the directory of the importing file. Each file is loaded
+/// once, even if it is imported by several modules. A module must not import itself, neither
+/// directly nor indirectly.
+#[derive(Default)]
+pub struct ModuleLoader {
+    modules: Vec<Module>,
+    canonical_paths: Vec<PathBuf>,
+    // The chain of modules currently being loaded as pairs of canonical and shown path.
+    loading: Vec<(PathBuf, PathBuf)>,
+    next_offset: usize,
+}
+
+impl ModuleLoader {
+    pub fn new() -> ModuleLoader {
+        ModuleLoader {
+            modules: Vec::new(),
+            canonical_paths: Vec::new(),
+            loading: Vec::new(),
+            next_offset: 0,
+        }
+    }
+
+    /// Loads the module in the given file and all modules it imports transitively.
+    ///
+    /// Returns the modules in dependency order: Each module comes after all modules it imports,
+    /// so the given module is the last one.
+    pub fn load(mut self, path: &Path) -> Result<Vec<Module>, ModuleError> {
+        self.load_module(path, None)?;
+        Ok(self.modules)
+    }
+
+    // Returns the name of the loaded module. The importer is the importing file and the span of
+    // the import statement, which is the location of errors concerning the imported file itself.
+    fn load_module(&mut self, path: &Path, importer: Option<(&Path, Span)>) -> Result<String, ModuleError> {
+        let error_at_import = |message: String| match importer {
+            Some((file, span)) => ModuleError::new(message, file.to_path_buf(), Some(span)),
+            None => ModuleError::new(message, path.to_path_buf(), None),
+        };
+
+        let canonical = match path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(_) => return Err(error_at_import(format!("Cannot read module '{}'!", path.display()))),
+        };
+
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == canonical) {
+            let chain: Vec<String> = self.loading[start..].iter()
+                .map(|(_, shown)| shown.display().to_string())
+                .chain(Some(path.display().to_string()))
+                .collect();
+            return Err(error_at_import(format!("Import cycle detected: {}!", chain.join(" -> "))));
+        }
+
+        if let Some(index) = self.canonical_paths.iter().position(|loaded| *loaded == canonical) {
+            return Ok(self.modules[index].name.clone());
+        }
+
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_string(),
+            None => return Err(error_at_import(format!("Invalid module path '{}'!", path.display()))),
+        };
+
+        if let Some(other) = self.modules.iter().find(|module| module.name == name) {
+            return Err(error_at_import(
+                format!("Module name '{}' is already used by '{}'!", name, other.path.display())));
+        }
+
+        let mut source = String::new();
+
+        if File::open(path).and_then(|mut f| f.read_to_string(&mut source)).is_err() {
+            return Err(error_at_import(format!("Cannot read module '{}'!", path.display())));
+        }
+
+        let start_offset = self.next_offset;
+        let end_offset = start_offset + source.len();
+        self.next_offset = end_offset + 1;
+        let lexer = Lexer::new(CharacterStream::with_offset(source, start_offset));
+        let statements = Parser::new(lexer).parse()
+            .map_err(|e| ModuleError::new(e.message().to_string(), path.to_path_buf(), Some(e.span())))?;
+
+        self.loading.push((canonical.clone(), path.to_path_buf()));
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut imports = Vec::new();
+
+        for statement in &statements {
+            if let StatementKind::Import(ref import) = *statement.kind() {
+                let imported = self.load_module(&directory.join(import), Some((path, statement.span())))?;
+
+                if imported == name {
+                    return Err(ModuleError::new(
+                        format!("Module '{}' can not import a module with the same name!", name),
+                        path.to_path_buf(), Some(statement.span())));
+                }
+
+                imports.push(imported);
+            }
+        }
+
+        self.loading.pop();
+        self.canonical_paths.push(canonical);
+        self.modules.push(Module { name: name.clone(), path: path.to_path_buf(), statements, imports, start_offset, end_offset });
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+
+    fn load(path: &str) -> Result<Vec<Module>, ModuleError> {
+        ModuleLoader::new().load(Path::new(path))
+    }
+
+    #[test]
+    fn load_modules_in_dependency_order() {
+        let modules = load("test/modules/main.mvm").unwrap();
+
+        let names: Vec<&str> = modules.iter().map(|m| m.name()).collect();
+        assert_that!(names, is(equal_to(vec!["util", "geometry", "main"])));
+        let expected = [String::from("util")];
+        assert_that!(modules[1].imports(), is(equal_to(&expected[..])));
+        assert_that!(modules[1].path(), is(equal_to(Path::new("test/modules/lib/geometry.mvm"))));
+    }
+
+    #[test]
+    fn load_modules_with_distinct_offsets() {
+        let modules = load("test/modules/main.mvm").unwrap();
+
+        for module in &modules {
+            for statement in module.statements() {
+                let owners = modules.iter().filter(|m| m.contains(statement.span())).count();
+                assert_that!(owners, is(equal_to(1)));
+            }
+        }
+    }
+
+    #[test]
+    fn load_detects_import_cycle() {
+        let error = load("test/modules/cycle_a.mvm").unwrap_err();
+
+        assert_that!(error.message(), is(equal_to(
+            "Import cycle detected: test/modules/cycle_a.mvm -> test/modules/cycle_b.mvm -> test/modules/cycle_a.mvm!")));
+        assert_that!(error.path(), is(equal_to(Path::new("test/modules/cycle_b.mvm"))));
+        assert_that!(error.span().unwrap().start(), is(equal_to(::frontend::Position::new(1, 1))));
+    }
+
+    #[test]
+    fn load_missing_module() {
+        let error = load("test/modules/missing_import.mvm").unwrap_err();
+
+        assert_that!(error.to_string(), is(equal_to(String::from(
+            "Cannot read module 'test/modules/nowhere.mvm'! at [2, 1] in test/modules/missing_import.mvm"))));
+        assert_that!(load("test/modules/nowhere.mvm").unwrap_err().span(), is(equal_to(None)));
+    }
+}
@@ -1,60 +1,1536 @@
+use std::mem;
+
 use frontend::lexer::Lexer;
-use frontend::token::TokenType;
+use frontend::token::{StringPart, Token, TokenType};
 use frontend::token::Keyword;
 use frontend::token::Operator;
+use frontend::{Span, SyntaxError};
+use intermediate::ast::*;
 
 /// Parses the tokens recognized by the lexer.
 pub struct Parser {
     lexer: Lexer,
+    // Set while parsing an expression followed by a block, whose brace must not start a record literal.
+    no_record_literal: bool,
 }
 
 impl Parser {
     pub fn new(lexer: Lexer) -> Parser {
-        Parser { lexer }
+        Parser { lexer, no_record_literal: false }
     }
 
-    pub fn parse(&self) {
+    /// Parses all tokens into a list of statements.
+    ///
+    /// Returns an error for the first token which does not fit the grammar.
+    pub fn parse(&mut self) -> Result<Vec<Statement>, SyntaxError> {
+        let mut statements = Vec::new();
+        self.lexer.next_token()?;
+
         loop {
-            if *self.lexer.current().get_token_type() == TokenType::EOL {
+            match *self.current_type() {
+                TokenType::EOF => break,
+                TokenType::EOL => {
+                    self.lexer.next_token()?; // skip empty line
+                    continue;
+                },
+                _ => (),
+            }
+
+            statements.push(self.parse_statement()?);
+
+            match *self.current_type() {
+                TokenType::EOL => self.lexer.next_token()?,
+                TokenType::EOF => (),
+                _ => return Err(self.unexpected("end of line")),
+            }
+        }
+
+        Ok(statements)
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, SyntaxError> {
+        match *self.current_type() {
+            TokenType::Keyword(Keyword::Const) => return self.parse_constant_declaration(),
+            TokenType::Keyword(Keyword::Var) => return self.parse_variable_declaration(),
+            TokenType::Keyword(Keyword::Record) => return self.parse_record_declaration(),
+            TokenType::Keyword(Keyword::Import) => return self.parse_import(),
+            TokenType::Keyword(Keyword::Return) => return self.parse_return(),
+            TokenType::Keyword(Keyword::Try) => return self.parse_try(),
+            TokenType::Keyword(Keyword::Throw) => return self.parse_throw(),
+            TokenType::Keyword(Keyword::If) => return self.parse_if(),
+            TokenType::Keyword(Keyword::While) => return self.parse_while(),
+            TokenType::Keyword(Keyword::For) => return self.parse_for(),
+            TokenType::Keyword(Keyword::Break) => {
+                let span = self.lexer.span();
+                self.lexer.next_token()?; // consume break
+                return Ok(Statement::new(StatementKind::Break, span));
+            },
+            TokenType::Keyword(Keyword::Continue) => {
+                let span = self.lexer.span();
+                self.lexer.next_token()?; // consume continue
+                return Ok(Statement::new(StatementKind::Continue, span));
+            },
+            TokenType::Keyword(Keyword::Fun) => {
+                // Without name it's an anonymous function expression.
+                if let TokenType::Identifier(_) = *self.lexer.peek()?.get_token_type() {
+                    return self.parse_function_declaration();
+                }
+            },
+            _ => (),
+        }
+
+        if self.is_identifier() {
+            match *self.lexer.peek()?.get_token_type() {
+                TokenType::Operator(Operator::Assign) => return self.parse_assignment(),
+                TokenType::Operator(ref operator) if compound_operator(operator).is_some() =>
+                    return self.parse_compound_assignment(),
+                _ => (),
+            }
+        }
+
+        let expression = self.parse_or_expression()?;
+
+        match *expression.kind() {
+            ExpressionKind::Index(..) | ExpressionKind::Field(..) if self.is_assignment_operator() =>
+                return self.parse_target_assignment(expression),
+            _ => (),
+        }
+
+        let span = expression.span();
+        Ok(Statement::new(StatementKind::Expression(expression), span))
+    }
+
+    fn is_assignment_operator(&self) -> bool {
+        match *self.current_type() {
+            TokenType::Operator(Operator::Assign) => true,
+            TokenType::Operator(ref operator) => compound_operator(operator).is_some(),
+            _ => false,
+        }
+    }
+
+    // The target is the already parsed element expression `array[index]` or field expression
+    // `record.field`. Unlike for variables a compound assignment keeps its operator, so that the
+    // parts of the target are evaluated only once.
+    fn parse_target_assignment(&mut self, target: Expression) -> Result<Statement, SyntaxError> {
+        let operator = match *self.current_type() {
+            TokenType::Operator(ref operator) => compound_operator(operator),
+            _ => None,
+        };
+        self.lexer.next_token()?; // consume assignment operator
+        let value = self.parse_or_expression()?;
+        let span = target.span().to(value.span());
+        let kind = match target.into_kind() {
+            ExpressionKind::Index(array, index) => StatementKind::IndexAssignment(*array, *index, operator, value),
+            ExpressionKind::Field(record, field) => StatementKind::FieldAssignment(*record, field, operator, value),
+            _ => unreachable!(),
+        };
+        Ok(Statement::new(kind, span))
+    }
+
+    fn parse_assignment(&mut self) -> Result<Statement, SyntaxError> {
+        let (identifier, value) = self.parse_assignment_parts()?;
+        let span = identifier.span().to(value.span());
+        Ok(Statement::new(StatementKind::Assignment(identifier, value), span))
+    }
+
+    // A compound assignment like `x += 1` is desugared into `x = x + 1`.
+    fn parse_compound_assignment(&mut self) -> Result<Statement, SyntaxError> {
+        let identifier = self.parse_identifier()?;
+        let operator = match *self.current_type() {
+            TokenType::Operator(ref operator) => compound_operator(operator),
+            _ => None,
+        };
+        let operator = match operator {
+            Some(operator) => operator,
+            None => return Err(self.unexpected("compound assignment operator")),
+        };
+        self.lexer.next_token()?; // consume operator
+        let value = self.parse_or_expression()?;
+        let target = Expression::new(ExpressionKind::Identifier(identifier.clone()), identifier.span());
+        let value = binary_operation(operator, target, value);
+        let span = identifier.span().to(value.span());
+        Ok(Statement::new(StatementKind::Assignment(identifier, value), span))
+    }
+
+    fn parse_assignment_parts(&mut self) -> Result<(Identifier, Expression), SyntaxError> {
+        let identifier = self.parse_identifier()?;
+        self.expect(TokenType::Operator(Operator::Assign), "'='")?;
+        let value = self.parse_or_expression()?;
+        Ok((identifier, value))
+    }
+
+    fn parse_constant_declaration(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume const
+        let (identifier, value) = self.parse_assignment_parts()?;
+        let span = start.to(value.span());
+        Ok(Statement::new(StatementKind::Constant(identifier, value), span))
+    }
+
+    fn parse_variable_declaration(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume var
+        let identifier = self.parse_identifier()?;
+
+        if let TokenType::Operator(Operator::Assign) = *self.current_type() {
+            self.lexer.next_token()?; // consume =
+            let value = self.parse_or_expression()?;
+            let span = start.to(value.span());
+            Ok(Statement::new(StatementKind::Variable(identifier, value), span))
+        } else {
+            let span = start.to(identifier.span());
+            let value = Expression::new(ExpressionKind::Nil, identifier.span());
+            Ok(Statement::new(StatementKind::Variable(identifier, value), span))
+        }
+    }
+
+    fn parse_record_declaration(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume record
+        let identifier = self.parse_identifier()?;
+        self.expect(TokenType::LeftBrace, "'{'")?;
+        let mut fields = Vec::new();
+
+        loop {
+            self.skip_new_lines()?;
+
+            if TokenType::RightBrace == *self.current_type() {
                 break;
             }
 
-            self.parse_statement();
+            fields.push(self.parse_identifier()?);
+            self.skip_new_lines()?;
+
+            if TokenType::Comma != *self.current_type() {
+                break;
+            }
+
+            self.lexer.next_token()?; // consume ,
         }
+
+        let span = start.to(self.lexer.span());
+        self.expect(TokenType::RightBrace, "'}'")?;
+        Ok(Statement::new(StatementKind::Record(identifier, fields), span))
+    }
+
+    fn parse_import(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume import
+        let path = match *self.current_type() {
+            TokenType::String(ref path) => path.clone(),
+            _ => return Err(self.unexpected("module path")),
+        };
+        let span = start.to(self.lexer.span());
+        self.lexer.next_token()?; // consume path
+        Ok(Statement::new(StatementKind::Import(path), span))
+    }
+
+    fn parse_function_declaration(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume fun
+        let identifier = self.parse_identifier()?;
+        let parameters = self.parse_parameters()?;
+        let (body, end) = self.parse_block()?;
+        Ok(Statement::new(StatementKind::Function(identifier, parameters, body), start.to(end)))
     }
 
-    fn parse_statement(&self) {
-        match *self.lexer.current().get_token_type() {
-            TokenType::Keyword(ref keyword) => {
-                match *keyword {
-                    Keyword::Const => self.parse_constant_declaration(),
-                    Keyword::Var => self.parse_variable_declaration(),
-                    _ => panic!("Unexpected keyword: {}!", keyword),
+    fn parse_function_expression(&mut self) -> Result<Expression, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume fun
+        let parameters = self.parse_parameters()?;
+        let (body, end) = self.parse_block()?;
+        Ok(Expression::new(ExpressionKind::Function(parameters, body), start.to(end)))
+    }
+
+    fn parse_parameters(&mut self) -> Result<Vec<Identifier>, SyntaxError> {
+        self.expect(TokenType::LeftParen, "'('")?;
+        let mut parameters = Vec::new();
+
+        if TokenType::RightParen != *self.current_type() {
+            loop {
+                parameters.push(self.parse_identifier()?);
+
+                if TokenType::Comma != *self.current_type() {
+                    break;
                 }
+
+                self.lexer.next_token()?; // consume ,
+            }
+        }
+
+        self.expect(TokenType::RightParen, "')'")?;
+        Ok(parameters)
+    }
+
+    // Parses statements enclosed in braces, one per line. Returns them with the span of the
+    // closing brace.
+    fn parse_block(&mut self) -> Result<(Vec<Statement>, Span), SyntaxError> {
+        self.expect(TokenType::LeftBrace, "'{'")?;
+        let mut statements = Vec::new();
+
+        loop {
+            self.skip_new_lines()?;
+
+            if TokenType::RightBrace == *self.current_type() {
+                break;
+            }
+
+            statements.push(self.allowing_record_literals(Parser::parse_statement)?);
+
+            match *self.current_type() {
+                TokenType::EOL => self.lexer.next_token()?,
+                TokenType::RightBrace => (),
+                _ => return Err(self.unexpected("end of line")),
+            }
+        }
+
+        let end = self.lexer.span();
+        self.expect(TokenType::RightBrace, "'}'")?;
+        Ok((statements, end))
+    }
+
+    fn parse_return(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume return
+
+        let value = match *self.current_type() {
+            TokenType::EOL | TokenType::EOF | TokenType::RightBrace => Expression::new(ExpressionKind::Nil, start),
+            _ => self.parse_or_expression()?,
+        };
+
+        let span = start.to(value.span());
+        Ok(Statement::new(StatementKind::Return(value), span))
+    }
+
+    // The handler may start on the line after the guarded block.
+    fn parse_try(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume try
+        let (body, _) = self.parse_block()?;
+        self.skip_new_lines()?;
+        self.expect(TokenType::Keyword(Keyword::Catch), "'catch'")?;
+        let identifier = self.parse_identifier()?;
+        let (handler, end) = self.parse_block()?;
+        Ok(Statement::new(StatementKind::Try(body, identifier, handler), start.to(end)))
+    }
+
+    fn parse_throw(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume throw
+        let value = self.parse_or_expression()?;
+        let span = start.to(value.span());
+        Ok(Statement::new(StatementKind::Throw(value), span))
+    }
+
+    // The `else` must follow the closing brace on the same line.
+    fn parse_if(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume if
+        let condition = self.parse_block_condition()?;
+        let (then_branch, mut end) = self.parse_block()?;
+        let mut else_branch = Vec::new();
+
+        if TokenType::Keyword(Keyword::Else) == *self.current_type() {
+            self.lexer.next_token()?; // consume else
+
+            if TokenType::Keyword(Keyword::If) == *self.current_type() {
+                let nested = self.parse_if()?;
+                end = nested.span();
+                else_branch.push(nested);
+            } else {
+                let (statements, span) = self.parse_block()?;
+                end = span;
+                else_branch = statements;
+            }
+        }
+
+        Ok(Statement::new(StatementKind::If(condition, then_branch, else_branch), start.to(end)))
+    }
+
+    fn parse_while(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume while
+        let condition = self.parse_block_condition()?;
+        let (body, end) = self.parse_block()?;
+        Ok(Statement::new(StatementKind::While(condition, body), start.to(end)))
+    }
+
+    // Iterates over a range if the expression is followed by `..`, over an array otherwise.
+    fn parse_for(&mut self) -> Result<Statement, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume for
+        let identifier = self.parse_identifier()?;
+        self.expect(TokenType::Keyword(Keyword::In), "'in'")?;
+        let value = self.parse_block_condition()?;
+        let end = if TokenType::DotDot == *self.current_type() {
+            self.lexer.next_token()?; // consume ..
+            Some(self.parse_block_condition()?)
+        } else {
+            None
+        };
+        let (body, span) = self.parse_block()?;
+        let kind = match end {
+            Some(end) => StatementKind::ForRange(identifier, value, end, body),
+            None => StatementKind::ForEach(identifier, value, body),
+        };
+        Ok(Statement::new(kind, start.to(span)))
+    }
+
+    fn parse_or_expression(&mut self) -> Result<Expression, SyntaxError> {
+        let mut left = self.parse_and_expression()?;
+
+        while let TokenType::Keyword(Keyword::Or) = *self.current_type() {
+            self.lexer.next_token()?; // consume or
+            let right = self.parse_and_expression()?;
+            left = binary_operation(BinaryOperator::Or, left, right);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_and_expression(&mut self) -> Result<Expression, SyntaxError> {
+        let mut left = self.parse_equal_expression()?;
+
+        while let TokenType::Keyword(Keyword::And) = *self.current_type() {
+            self.lexer.next_token()?; // consume and
+            let right = self.parse_equal_expression()?;
+            left = binary_operation(BinaryOperator::And, left, right);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_equal_expression(&mut self) -> Result<Expression, SyntaxError> {
+        let mut left = self.parse_relation_expression()?;
+
+        loop {
+            let operator = match *self.current_type() {
+                TokenType::Operator(Operator::Equal) => BinaryOperator::Equal,
+                TokenType::Operator(Operator::NotEqual) => BinaryOperator::NotEqual,
+                _ => break,
+            };
+            self.lexer.next_token()?; // consume operator
+            let right = self.parse_relation_expression()?;
+            left = binary_operation(operator, left, right);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_relation_expression(&mut self) -> Result<Expression, SyntaxError> {
+        let mut left = self.parse_simple_expression()?;
+
+        loop {
+            let operator = match *self.current_type() {
+                TokenType::Operator(Operator::LessThan) => BinaryOperator::LessThan,
+                TokenType::Operator(Operator::LessThanEqual) => BinaryOperator::LessThanEqual,
+                TokenType::Operator(Operator::GreaterThan) => BinaryOperator::GreaterThan,
+                TokenType::Operator(Operator::GreaterThanEqual) => BinaryOperator::GreaterThanEqual,
+                _ => break,
+            };
+            self.lexer.next_token()?; // consume operator
+            let right = self.parse_simple_expression()?;
+            left = binary_operation(operator, left, right);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_simple_expression(&mut self) -> Result<Expression, SyntaxError> {
+        let mut left = self.parse_term()?;
+
+        loop {
+            let operator = match *self.current_type() {
+                TokenType::Operator(Operator::Plus) => BinaryOperator::Add,
+                TokenType::Operator(Operator::Minus) => BinaryOperator::Subtract,
+                _ => break,
+            };
+            self.lexer.next_token()?; // consume operator
+            let right = self.parse_term()?;
+            left = binary_operation(operator, left, right);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_term(&mut self) -> Result<Expression, SyntaxError> {
+        let mut left = self.parse_factor()?;
+
+        loop {
+            let operator = match *self.current_type() {
+                TokenType::Operator(Operator::Star) => BinaryOperator::Multiply,
+                TokenType::Operator(Operator::Slash) => BinaryOperator::Divide,
+                TokenType::Operator(Operator::Mod) => BinaryOperator::Modulo,
+                _ => break,
+            };
+            self.lexer.next_token()?; // consume operator
+            let right = self.parse_factor()?;
+            left = binary_operation(operator, left, right);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expression, SyntaxError> {
+        let mut expression = self.parse_primary()?;
+
+        loop {
+            match *self.current_type() {
+                TokenType::LeftBracket => {
+                    self.lexer.next_token()?; // consume [
+                    let index = self.allowing_record_literals(Parser::parse_or_expression)?;
+                    let span = expression.span().to(self.lexer.span());
+                    self.expect(TokenType::RightBracket, "']'")?;
+                    expression = Expression::new(ExpressionKind::Index(Box::new(expression), Box::new(index)), span);
+                },
+                TokenType::Dot => {
+                    self.lexer.next_token()?; // consume .
+                    let field = self.parse_identifier()?;
+
+                    // A record of an imported module is constructed by its qualified name.
+                    match (expression.kind(), self.current_type()) {
+                        (ExpressionKind::Identifier(module), TokenType::LeftBrace) if !self.no_record_literal => {
+                            let name = format!("{}.{}", module.name(), field.name());
+                            let identifier = Identifier::new(name, module.span().to(field.span()));
+                            expression = self.parse_record_literal(identifier)?;
+                            continue;
+                        },
+                        _ => (),
+                    }
+
+                    let span = expression.span().to(field.span());
+                    expression = Expression::new(ExpressionKind::Field(Box::new(expression), field), span);
+                },
+                TokenType::LeftParen => {
+                    self.lexer.next_token()?; // consume (
+                    let arguments = self.parse_expression_list(TokenType::RightParen)?;
+                    let span = expression.span().to(self.lexer.span());
+                    self.expect(TokenType::RightParen, "')'")?;
+                    expression = Expression::new(ExpressionKind::Call(Box::new(expression), arguments), span);
+                },
+                _ => break,
+            }
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, SyntaxError> {
+        let span = self.lexer.span();
+        let kind = match *self.current_type() {
+            TokenType::Integer(value) => {
+                self.check_integer_range(value)?;
+                ExpressionKind::Integer(value)
+            },
+            TokenType::Real(value) => ExpressionKind::Real(value),
+            TokenType::Bool(value) => ExpressionKind::Boolean(value),
+            TokenType::Character(value) => ExpressionKind::Character(value),
+            TokenType::String(ref value) => ExpressionKind::String(value.clone()),
+            TokenType::InterpolatedString(ref parts) => {
+                let parts = parts.clone();
+                self.lexer.next_token()?; // consume string
+                return parse_interpolated_string(parts, span);
+            },
+            TokenType::Keyword(Keyword::Not) => {
+                self.lexer.next_token()?; // consume not
+                let operand = self.parse_factor()?;
+                let span = span.to(operand.span());
+                return Ok(Expression::new(
+                    ExpressionKind::UnaryOperation(UnaryOperator::Not, Box::new(operand)),
+                    span));
+            },
+            TokenType::Operator(Operator::Minus) => {
+                self.lexer.next_token()?; // consume -
+                return self.parse_negation(span);
+            },
+            TokenType::Operator(Operator::Plus) => {
+                self.lexer.next_token()?; // consume +
+                let operand = self.parse_factor()?;
+                let span = span.to(operand.span());
+                return Ok(Expression::new(
+                    ExpressionKind::UnaryOperation(UnaryOperator::Plus, Box::new(operand)),
+                    span));
+            },
+            TokenType::LeftParen => {
+                self.lexer.next_token()?; // consume (
+                let expression = self.allowing_record_literals(Parser::parse_or_expression)?;
+                let span = span.to(self.lexer.span());
+                self.expect(TokenType::RightParen, "')'")?;
+                return Ok(Expression::new(expression.into_kind(), span));
+            },
+            TokenType::LeftBracket => return self.parse_array(),
+            TokenType::Keyword(Keyword::Fun) => return self.parse_function_expression(),
+            TokenType::Keyword(Keyword::Match) => return self.parse_match(),
+            TokenType::Identifier(_) => return self.parse_identifier_or_function_call(),
+            _ => return Err(self.unexpected("expression")),
+        };
+
+        self.lexer.next_token()?; // consume literal
+        Ok(Expression::new(kind, span))
+    }
+
+    // Negated number literals are folded into one literal. This is necessary to express the smallest
+    // integer whose magnitude is not a valid integer.
+    fn parse_negation(&mut self, minus_span: Span) -> Result<Expression, SyntaxError> {
+        let kind = match *self.current_type() {
+            TokenType::Integer(value) => ExpressionKind::Integer(value.wrapping_neg()),
+            TokenType::Real(value) => ExpressionKind::Real(-value),
+            _ => {
+                let operand = self.parse_factor()?;
+                let span = minus_span.to(operand.span());
+                return Ok(Expression::new(
+                    ExpressionKind::UnaryOperation(UnaryOperator::Negate, Box::new(operand)),
+                    span));
             },
-            TokenType::Identifier(_) => {
-                match *self.lexer.peek().get_token_type() {
-                    TokenType::Operator(Operator::Assign) => self.parse_assignment(),
-                    _ => self.parse_or_expression(),
+        };
+
+        let span = minus_span.to(self.lexer.span());
+        self.lexer.next_token()?; // consume literal
+        Ok(Expression::new(kind, span))
+    }
+
+    fn parse_array(&mut self) -> Result<Expression, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume [
+        let elements = self.parse_expression_list(TokenType::RightBracket)?;
+        let span = start.to(self.lexer.span());
+        self.expect(TokenType::RightBracket, "']'")?;
+        Ok(Expression::new(ExpressionKind::Array(elements), span))
+    }
+
+    // Parses comma separated expressions until the given closing token which is not consumed.
+    fn parse_expression_list(&mut self, close: TokenType) -> Result<Vec<Expression>, SyntaxError> {
+        let mut expressions = Vec::new();
+
+        if close != *self.current_type() {
+            loop {
+                expressions.push(self.allowing_record_literals(Parser::parse_or_expression)?);
+
+                if TokenType::Comma != *self.current_type() {
+                    break;
                 }
+
+                self.lexer.next_token()?; // consume ,
+            }
+        }
+
+        Ok(expressions)
+    }
+
+    fn parse_identifier_or_function_call(&mut self) -> Result<Expression, SyntaxError> {
+        let identifier = self.parse_identifier()?;
+
+        match *self.current_type() {
+            TokenType::LeftParen => (),
+            TokenType::LeftBrace if !self.no_record_literal => return self.parse_record_literal(identifier),
+            _ => {
+                let span = identifier.span();
+                return Ok(Expression::new(ExpressionKind::Identifier(identifier), span));
             },
-            _ => panic!("Unexpected token: {}!", self.lexer.current()),
         }
+
+        self.lexer.next_token()?; // consume (
+        let arguments = self.parse_expression_list(TokenType::RightParen)?;
+        let span = identifier.span().to(self.lexer.span());
+        self.expect(TokenType::RightParen, "')'")?;
+        Ok(Expression::new(ExpressionKind::FunctionCall(identifier, arguments), span))
     }
 
-    fn parse_assignment(&self) {
-        unimplemented!();
+    fn parse_record_literal(&mut self, identifier: Identifier) -> Result<Expression, SyntaxError> {
+        self.lexer.next_token()?; // consume {
+        let mut fields = Vec::new();
+
+        loop {
+            self.skip_new_lines()?;
+
+            if TokenType::RightBrace == *self.current_type() {
+                break;
+            }
+
+            let field = self.parse_identifier()?;
+            self.expect(TokenType::Colon, "':'")?;
+            let value = self.allowing_record_literals(Parser::parse_or_expression)?;
+            fields.push((field, value));
+            self.skip_new_lines()?;
+
+            if TokenType::Comma != *self.current_type() {
+                break;
+            }
+
+            self.lexer.next_token()?; // consume ,
+        }
+
+        let span = identifier.span().to(self.lexer.span());
+        self.expect(TokenType::RightBrace, "'}'")?;
+        Ok(Expression::new(ExpressionKind::RecordLiteral(identifier, fields), span))
     }
 
-    fn parse_constant_declaration(&self) {
-        unimplemented!();
+    // Arms are separated by commas or new lines.
+    fn parse_match(&mut self) -> Result<Expression, SyntaxError> {
+        let start = self.lexer.span();
+        self.lexer.next_token()?; // consume match
+        let value = self.parse_block_condition()?;
+        self.expect(TokenType::LeftBrace, "'{'")?;
+        let mut arms = Vec::new();
+
+        loop {
+            self.skip_new_lines()?;
+
+            if TokenType::RightBrace == *self.current_type() {
+                break;
+            }
+
+            let pattern = self.parse_pattern()?;
+            self.expect(TokenType::Arrow, "'=>'")?;
+            arms.push((pattern, self.allowing_record_literals(Parser::parse_or_expression)?));
+
+            match *self.current_type() {
+                TokenType::Comma => self.lexer.next_token()?,
+                TokenType::EOL | TokenType::RightBrace => (),
+                _ => return Err(self.unexpected("end of line")),
+            }
+        }
+
+        let span = start.to(self.lexer.span());
+        self.expect(TokenType::RightBrace, "'}'")?;
+        Ok(Expression::new(ExpressionKind::Match(Box::new(value), arms), span))
     }
 
-    fn parse_variable_declaration(&self) {
-        unimplemented!();
+    fn parse_pattern(&mut self) -> Result<Pattern, SyntaxError> {
+        let mut span = self.lexer.span();
+        let kind = match *self.current_type() {
+            TokenType::Integer(value) => {
+                self.check_integer_range(value)?;
+                PatternKind::Integer(value)
+            },
+            TokenType::Operator(Operator::Minus) => {
+                self.lexer.next_token()?; // consume -
+                span = span.to(self.lexer.span());
+
+                match *self.current_type() {
+                    TokenType::Integer(value) => PatternKind::Integer(value.wrapping_neg()),
+                    _ => return Err(self.unexpected("integer")),
+                }
+            },
+            TokenType::String(ref value) => PatternKind::String(value.clone()),
+            TokenType::Bool(value) => PatternKind::Boolean(value),
+            TokenType::Identifier(_) => return self.parse_identifier_pattern(),
+            _ => return Err(self.unexpected("pattern")),
+        };
+
+        self.lexer.next_token()?; // consume literal
+        Ok(Pattern::new(kind, span))
     }
 
-    fn parse_or_expression(&self) {
-        unimplemented!();
+    // Parses a wildcard, a binding or a record pattern, whose type may be qualified by its module.
+    fn parse_identifier_pattern(&mut self) -> Result<Pattern, SyntaxError> {
+        let mut identifier = self.parse_identifier()?;
+
+        if TokenType::Dot == *self.current_type() {
+            self.lexer.next_token()?; // consume .
+            let name = self.parse_identifier()?;
+            identifier = Identifier::new(
+                format!("{}.{}", identifier.name(), name.name()), identifier.span().to(name.span()));
+
+            if TokenType::LeftBrace != *self.current_type() {
+                return Err(self.unexpected("'{'"));
+            }
+        }
+
+        let span = identifier.span();
+
+        if TokenType::LeftBrace != *self.current_type() {
+            let kind = if "_" == identifier.name() { PatternKind::Wildcard } else { PatternKind::Binding(identifier) };
+            return Ok(Pattern::new(kind, span));
+        }
+
+        self.lexer.next_token()?; // consume {
+        let mut fields = Vec::new();
+
+        loop {
+            self.skip_new_lines()?;
+
+            if TokenType::RightBrace == *self.current_type() {
+                break;
+            }
+
+            let field = self.parse_identifier()?;
+            let pattern = if TokenType::Colon == *self.current_type() {
+                self.lexer.next_token()?; // consume :
+                self.parse_pattern()?
+            } else {
+                Pattern::new(PatternKind::Binding(field.clone()), field.span())
+            };
+            fields.push((field, pattern));
+            self.skip_new_lines()?;
+
+            if TokenType::Comma != *self.current_type() {
+                break;
+            }
+
+            self.lexer.next_token()?; // consume ,
+        }
+
+        let span = span.to(self.lexer.span());
+        self.expect(TokenType::RightBrace, "'}'")?;
+        Ok(Pattern::new(PatternKind::Record(identifier, fields), span))
     }
-}
\ No newline at end of file
+
+    // Parses the expression in front of a block like the value of `match`.
+    fn parse_block_condition(&mut self) -> Result<Expression, SyntaxError> {
+        let previous = mem::replace(&mut self.no_record_literal, true);
+        let result = self.parse_or_expression();
+        self.no_record_literal = previous;
+        result
+    }
+
+    // Record literals are allowed again inside of delimiters.
+    fn allowing_record_literals<T>(&mut self, parse: fn(&mut Parser) -> Result<T, SyntaxError>)
+        -> Result<T, SyntaxError> {
+        let previous = mem::replace(&mut self.no_record_literal, false);
+        let result = parse(self);
+        self.no_record_literal = previous;
+        result
+    }
+
+    fn parse_identifier(&mut self) -> Result<Identifier, SyntaxError> {
+        let name = match *self.current_type() {
+            TokenType::Identifier(ref name) => name.clone(),
+            _ => return Err(self.unexpected("identifier")),
+        };
+        let identifier = Identifier::new(name, self.lexer.span());
+        self.lexer.next_token()?; // consume identifier
+        Ok(identifier)
+    }
+
+    // The lexer accepts the magnitude of the smallest integer which is only valid if negated.
+    // Prefixed literals like 0x8000_0000_0000_0000 denote the bit pattern and are always valid.
+    fn check_integer_range(&self, value: i64) -> Result<(), SyntaxError> {
+        let literal = self.lexer.current().get_literal();
+        let prefixed = literal.chars().nth(1).is_some_and(|ch| ch.is_alphabetic());
+
+        if i64::MIN == value && !prefixed {
+            Err(SyntaxError::new(
+                format!("Integer literal '{}' is out of range!", literal),
+                self.lexer.span()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn expect(&mut self, expected: TokenType, description: &str) -> Result<(), SyntaxError> {
+        if expected == *self.current_type() {
+            self.lexer.next_token()?;
+            Ok(())
+        } else {
+            Err(self.unexpected(description))
+        }
+    }
+
+    // Record declarations and literals as well as blocks may span multiple lines.
+    fn skip_new_lines(&mut self) -> Result<(), SyntaxError> {
+        while TokenType::EOL == *self.current_type() {
+            self.lexer.next_token()?;
+        }
+
+        Ok(())
+    }
+
+    fn is_identifier(&self) -> bool {
+        matches!(*self.current_type(), TokenType::Identifier(_))
+    }
+
+    fn current_type(&self) -> &TokenType {
+        self.lexer.current().get_token_type()
+    }
+
+    fn unexpected(&self, expected: &str) -> SyntaxError {
+        SyntaxError::new(
+            format!("Expected {} but got {}!", expected, describe(self.lexer.current())),
+            self.lexer.span())
+    }
+}
+
+// Describes the token by its text, the span of the error already tells where it is.
+fn describe(token: &Token) -> String {
+    match *token.get_token_type() {
+        TokenType::EOL => String::from("end of line"),
+        TokenType::EOF => String::from("end of file"),
+        _ => format!("'{}'", token.get_literal()),
+    }
+}
+
+// The text parts of an interpolated string become string literals covering the whole string. The
+// tokens of the embedded code parts are parsed by a separate parser each.
+fn parse_interpolated_string(parts: Vec<StringPart>, span: Span) -> Result<Expression, SyntaxError> {
+    let mut expressions = Vec::new();
+
+    for part in parts {
+        let expression = match part {
+            StringPart::Text(text) => Expression::new(ExpressionKind::String(text), span),
+            StringPart::Code(tokens) => {
+                if tokens.is_empty() {
+                    return Err(SyntaxError::new(String::from("Empty string interpolation!"), span));
+                }
+
+                let mut parser = Parser::new(Lexer::from_tokens(tokens));
+                parser.lexer.next_token()?;
+                let expression = parser.parse_or_expression()?;
+
+                if TokenType::EOF != *parser.current_type() {
+                    return Err(parser.unexpected("end of interpolation"));
+                }
+
+                expression
+            },
+        };
+        expressions.push(expression);
+    }
+
+    Ok(Expression::new(ExpressionKind::InterpolatedString(expressions), span))
+}
+
+// Maps a compound assignment operator to the binary operator it applies.
+fn compound_operator(operator: &Operator) -> Option<BinaryOperator> {
+    match *operator {
+        Operator::PlusAssign => Some(BinaryOperator::Add),
+        Operator::MinusAssign => Some(BinaryOperator::Subtract),
+        Operator::StarAssign => Some(BinaryOperator::Multiply),
+        Operator::SlashAssign => Some(BinaryOperator::Divide),
+        Operator::ModAssign => Some(BinaryOperator::Modulo),
+        _ => None,
+    }
+}
+
+fn binary_operation(operator: BinaryOperator, left: Expression, right: Expression) -> Expression {
+    let span = left.span().to(right.span());
+    Expression::new(ExpressionKind::BinaryOperation(operator, Box::new(left), Box::new(right)), span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use frontend::Position;
+    use test_support::parse;
+
+    fn span(line: u64, start_column: u64, end_column: u64, start_offset: usize) -> Span {
+        Span::new(
+            Position::new(line, start_column),
+            Position::new(line, end_column),
+            start_offset,
+            start_offset + (end_column - start_column) as usize)
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_that!(parse("\n\n"), is(equal_to(Ok(vec![]))));
+    }
+
+    #[test]
+    fn parse_variable_declaration() {
+        let statements = parse("var x = 1 + 2\n").unwrap();
+
+        assert_that!(statements, is(equal_to(vec![
+            Statement::new(
+                StatementKind::Variable(
+                    Identifier::new(String::from("x"), span(1, 5, 6, 4)),
+                    Expression::new(
+                        ExpressionKind::BinaryOperation(
+                            BinaryOperator::Add,
+                            Box::new(Expression::new(ExpressionKind::Integer(1), span(1, 9, 10, 8))),
+                            Box::new(Expression::new(ExpressionKind::Integer(2), span(1, 13, 14, 12)))),
+                        span(1, 9, 14, 8))),
+                span(1, 1, 14, 0))
+        ])));
+    }
+
+    #[test]
+    fn parse_variable_declaration_without_value() {
+        let statements = parse("var x").unwrap();
+
+        assert_that!(statements, is(equal_to(vec![
+            Statement::new(
+                StatementKind::Variable(
+                    Identifier::new(String::from("x"), span(1, 5, 6, 4)),
+                    Expression::new(ExpressionKind::Nil, span(1, 5, 6, 4))),
+                span(1, 1, 6, 0))
+        ])));
+    }
+
+    #[test]
+    fn parse_constant_declaration() {
+        let statements = parse("const answer = 42").unwrap();
+
+        assert_that!(statements, is(equal_to(vec![
+            Statement::new(
+                StatementKind::Constant(
+                    Identifier::new(String::from("answer"), span(1, 7, 13, 6)),
+                    Expression::new(ExpressionKind::Integer(42), span(1, 16, 18, 15))),
+                span(1, 1, 18, 0))
+        ])));
+    }
+
+    #[test]
+    fn parse_assignment_in_second_line() {
+        let statements = parse("var s\nz = x\n").unwrap();
+
+        let expected = Statement::new(
+            StatementKind::Assignment(
+                Identifier::new(String::from("z"), span(2, 1, 2, 6)),
+                Expression::new(
+                    ExpressionKind::Identifier(Identifier::new(String::from("x"), span(2, 5, 6, 10))),
+                    span(2, 5, 6, 10))),
+            span(2, 1, 6, 6));
+        assert_that!(&statements[1], is(equal_to(&expected)));
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let statements = parse("1 + 2 * 3").unwrap();
+
+        assert_that!(statements, is(equal_to(vec![
+            Statement::new(
+                StatementKind::Expression(Expression::new(
+                    ExpressionKind::BinaryOperation(
+                        BinaryOperator::Add,
+                        Box::new(Expression::new(ExpressionKind::Integer(1), span(1, 1, 2, 0))),
+                        Box::new(Expression::new(
+                            ExpressionKind::BinaryOperation(
+                                BinaryOperator::Multiply,
+                                Box::new(Expression::new(ExpressionKind::Integer(2), span(1, 5, 6, 4))),
+                                Box::new(Expression::new(ExpressionKind::Integer(3), span(1, 9, 10, 8)))),
+                            span(1, 5, 10, 4)))),
+                    span(1, 1, 10, 0))),
+                span(1, 1, 10, 0))
+        ])));
+    }
+
+    #[test]
+    fn parse_parenthesis_span_covers_parenthesis() {
+        let statements = parse("(1 + 2) * 3").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::Expression(ref e) => match *e.kind() {
+                ExpressionKind::BinaryOperation(BinaryOperator::Multiply, ref left, _) => {
+                    assert_that!(left.span(), is(equal_to(span(1, 1, 8, 0))));
+                },
+                _ => panic!("Expected multiplication!"),
+            },
+            _ => panic!("Expected expression statement!"),
+        }
+    }
+
+    #[test]
+    fn parse_function_call() {
+        let statements = parse("println(s, 1)").unwrap();
+
+        assert_that!(statements, is(equal_to(vec![
+            Statement::new(
+                StatementKind::Expression(Expression::new(
+                    ExpressionKind::FunctionCall(
+                        Identifier::new(String::from("println"), span(1, 1, 8, 0)),
+                        vec![
+                            Expression::new(
+                                ExpressionKind::Identifier(Identifier::new(String::from("s"), span(1, 9, 10, 8))),
+                                span(1, 9, 10, 8)),
+                            Expression::new(ExpressionKind::Integer(1), span(1, 12, 13, 11)),
+                        ]),
+                    span(1, 1, 14, 0))),
+                span(1, 1, 14, 0))
+        ])));
+    }
+
+    #[test]
+    fn parse_logical_operators() {
+        let statements = parse("not a or b and c").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::Expression(ref e) => match *e.kind() {
+                ExpressionKind::BinaryOperation(BinaryOperator::Or, ref left, ref right) => {
+                    assert_that!(left.span(), is(equal_to(span(1, 1, 6, 0))));
+                    assert_that!(right.span(), is(equal_to(span(1, 10, 17, 9))));
+                },
+                _ => panic!("Expected or!"),
+            },
+            _ => panic!("Expected expression statement!"),
+        }
+    }
+
+    #[test]
+    fn parse_error_has_position() {
+        let error = parse("var x = \n").unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Expected expression but got end of line!")));
+        assert_that!(error.span(), is(equal_to(
+            Span::new(Position::new(1, 9), Position::new(2, 1), 8, 9))));
+    }
+
+    #[test]
+    fn parse_error_two_expressions_in_one_line() {
+        let error = parse("x y").unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Expected end of line but got 'y'!")));
+        assert_that!(error.span(), is(equal_to(span(1, 3, 4, 2))));
+    }
+
+    #[test]
+    fn parse_lexer_error_has_position() {
+        let error = parse("var c = 'ab'").unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Unterminated character literal!")));
+        assert_that!(error.span(), is(equal_to(span(1, 9, 11, 8))));
+    }
+
+    fn expression(src: &str) -> Expression {
+        match *parse(src).unwrap()[0].kind() {
+            StatementKind::Expression(ref expression) => expression.clone(),
+            _ => panic!("Expected expression statement!"),
+        }
+    }
+
+    #[test]
+    fn parse_negative_literals_are_folded() {
+        assert_that!(expression("-42"), is(equal_to(
+            Expression::new(ExpressionKind::Integer(-42), span(1, 1, 4, 0)))));
+        assert_that!(expression("- 4.5"), is(equal_to(
+            Expression::new(ExpressionKind::Real(-4.5), span(1, 1, 6, 0)))));
+    }
+
+    #[test]
+    fn parse_smallest_integer() {
+        assert_that!(expression("-9223372036854775808"), is(equal_to(
+            Expression::new(ExpressionKind::Integer(i64::MIN), span(1, 1, 21, 0)))));
+        assert_that!(expression("0x8000_0000_0000_0000"), is(equal_to(
+            Expression::new(ExpressionKind::Integer(i64::MIN), span(1, 1, 22, 0)))));
+    }
+
+    #[test]
+    fn parse_smallest_integer_magnitude_without_minus() {
+        let error = parse("x = 9223372036854775808").unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Integer literal '9223372036854775808' is out of range!")));
+        assert_that!(error.span(), is(equal_to(span(1, 5, 24, 4))));
+    }
+
+    #[test]
+    fn parse_unary_operators() {
+        let x = Expression::new(ExpressionKind::Identifier(
+            Identifier::new(String::from("x"), span(1, 2, 3, 1))), span(1, 2, 3, 1));
+
+        assert_that!(expression("-x"), is(equal_to(Expression::new(
+            ExpressionKind::UnaryOperation(UnaryOperator::Negate, Box::new(x.clone())), span(1, 1, 3, 0)))));
+        assert_that!(expression("+x"), is(equal_to(Expression::new(
+            ExpressionKind::UnaryOperation(UnaryOperator::Plus, Box::new(x)), span(1, 1, 3, 0)))));
+    }
+
+    #[test]
+    fn parse_unary_minus_binds_tighter_than_multiplication() {
+        let expression = expression("-a * b - -2");
+
+        match *expression.kind() {
+            ExpressionKind::BinaryOperation(BinaryOperator::Subtract, ref left, ref right) => {
+                assert_that!(right.kind(), is(equal_to(&ExpressionKind::Integer(-2))));
+
+                match *left.kind() {
+                    ExpressionKind::BinaryOperation(BinaryOperator::Multiply, ref negation, _) =>
+                        match *negation.kind() {
+                            ExpressionKind::UnaryOperation(UnaryOperator::Negate, _) => (),
+                            _ => panic!("Expected negation!"),
+                        },
+                    _ => panic!("Expected multiplication!"),
+                }
+            },
+            _ => panic!("Expected subtraction!"),
+        }
+    }
+
+    #[test]
+    fn parse_compound_assignment() {
+        let statements = parse("x *= y + 1").unwrap();
+
+        let x = Identifier::new(String::from("x"), span(1, 1, 2, 0));
+        let y = Expression::new(
+            ExpressionKind::Identifier(Identifier::new(String::from("y"), span(1, 6, 7, 5))), span(1, 6, 7, 5));
+        let sum = binary_operation(
+            BinaryOperator::Add, y, Expression::new(ExpressionKind::Integer(1), span(1, 10, 11, 9)));
+        let value = binary_operation(
+            BinaryOperator::Multiply, Expression::new(ExpressionKind::Identifier(x.clone()), span(1, 1, 2, 0)), sum);
+        assert_that!(statements, is(equal_to(vec![
+            Statement::new(StatementKind::Assignment(x, value), span(1, 1, 11, 0))])));
+    }
+
+    #[test]
+    fn parse_all_compound_assignments() {
+        let operators = vec![
+            ("+=", BinaryOperator::Add),
+            ("-=", BinaryOperator::Subtract),
+            ("*=", BinaryOperator::Multiply),
+            ("/=", BinaryOperator::Divide),
+            ("%=", BinaryOperator::Modulo),
+        ];
+
+        for (symbol, operator) in operators {
+            let statements = parse(&format!("x {} 2", symbol)).unwrap();
+
+            match *statements[0].kind() {
+                StatementKind::Assignment(_, ref value) => match *value.kind() {
+                    ExpressionKind::BinaryOperation(actual, _, _) => assert_that!(actual, is(equal_to(operator))),
+                    _ => panic!("Expected binary operation!"),
+                },
+                _ => panic!("Expected assignment!"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_compound_assignment_is_a_statement() {
+        assert_that!(parse("y = x += 1").is_err(), is(true));
+    }
+
+    fn reference(name: &str, span: Span) -> Expression {
+        Expression::new(ExpressionKind::Identifier(Identifier::new(String::from(name), span)), span)
+    }
+
+    fn integer(value: i64, span: Span) -> Expression {
+        Expression::new(ExpressionKind::Integer(value), span)
+    }
+
+    #[test]
+    fn parse_array_literal() {
+        assert_that!(expression("[1, 2]"), is(equal_to(Expression::new(
+            ExpressionKind::Array(vec![integer(1, span(1, 2, 3, 1)), integer(2, span(1, 5, 6, 4))]),
+            span(1, 1, 7, 0)))));
+        assert_that!(expression("[]"), is(equal_to(
+            Expression::new(ExpressionKind::Array(vec![]), span(1, 1, 3, 0)))));
+    }
+
+    #[test]
+    fn parse_index() {
+        let a = reference("a", span(1, 1, 2, 0));
+        let first = Expression::new(
+            ExpressionKind::Index(Box::new(a), Box::new(integer(0, span(1, 3, 4, 2)))), span(1, 1, 5, 0));
+
+        assert_that!(expression("a[0][i]"), is(equal_to(Expression::new(
+            ExpressionKind::Index(Box::new(first), Box::new(reference("i", span(1, 6, 7, 5)))),
+            span(1, 1, 8, 0)))));
+    }
+
+    #[test]
+    fn parse_index_binds_tighter_than_unary_minus() {
+        match *expression("-a[0]").kind() {
+            ExpressionKind::UnaryOperation(UnaryOperator::Negate, ref operand) => match *operand.kind() {
+                ExpressionKind::Index(..) => (),
+                _ => panic!("Expected index!"),
+            },
+            _ => panic!("Expected negation!"),
+        }
+    }
+
+    #[test]
+    fn parse_index_assignment() {
+        let statements = parse("a[i] = 1").unwrap();
+
+        assert_that!(statements, is(equal_to(vec![Statement::new(
+            StatementKind::IndexAssignment(
+                reference("a", span(1, 1, 2, 0)),
+                reference("i", span(1, 3, 4, 2)),
+                None,
+                integer(1, span(1, 8, 9, 7))),
+            span(1, 1, 9, 0))])));
+    }
+
+    #[test]
+    fn parse_compound_index_assignment() {
+        let statements = parse("f(x)[0] -= 1").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::IndexAssignment(ref array, ref index, operator, ref value) => {
+                match *array.kind() {
+                    ExpressionKind::FunctionCall(..) => (),
+                    _ => panic!("Expected call!"),
+                }
+                assert_that!(index.clone(), is(equal_to(integer(0, span(1, 6, 7, 5)))));
+                assert_that!(operator, is(equal_to(Some(BinaryOperator::Subtract))));
+                assert_that!(value.clone(), is(equal_to(integer(1, span(1, 12, 13, 11)))));
+            },
+            _ => panic!("Expected index assignment!"),
+        }
+        assert_that!(statements[0].span(), is(equal_to(span(1, 1, 13, 0))));
+    }
+
+    #[test]
+    fn parse_explicit_element_operand_is_no_compound_assignment() {
+        let statements = parse("a[0] = a[0] + 1").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::IndexAssignment(_, _, operator, ref value) => {
+                assert_that!(operator, is(equal_to(None)));
+                match *value.kind() {
+                    ExpressionKind::BinaryOperation(BinaryOperator::Add, ..) => (),
+                    _ => panic!("Expected addition!"),
+                }
+            },
+            _ => panic!("Expected index assignment!"),
+        }
+    }
+
+    #[test]
+    fn parse_assignment_to_call_is_an_error() {
+        assert_that!(parse("f(x) = 1").is_err(), is(true));
+        assert_that!(parse("[1] = 1").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_interpolated_string() {
+        let string = span(1, 1, 12, 0);
+        let sum = Expression::new(
+            ExpressionKind::BinaryOperation(
+                BinaryOperator::Add,
+                Box::new(reference("x", span(1, 5, 6, 4))),
+                Box::new(integer(1, span(1, 9, 10, 8)))),
+            span(1, 5, 10, 4));
+        let expected = Expression::new(
+            ExpressionKind::InterpolatedString(vec![
+                Expression::new(ExpressionKind::String(String::from("a")), string),
+                sum,
+            ]),
+            string);
+
+        assert_that!(expression("\"a${x + 1}\""), is(equal_to(expected)));
+    }
+
+    #[test]
+    fn parse_interpolation_must_be_one_expression() {
+        assert_that!(parse("\"${}\"").is_err(), is(true));
+        assert_that!(parse("\"${x y}\"").is_err(), is(true));
+        assert_that!(parse("\"${x = 1}\"").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_record_declaration() {
+        let statements = parse("record Point {\n  x,\n  y\n}\nrecord Empty {}").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::Record(ref identifier, ref fields) => {
+                assert_that!(identifier.name(), is(equal_to("Point")));
+                let names: Vec<&str> = fields.iter().map(|f| f.name()).collect();
+                assert_that!(names, is(equal_to(vec!["x", "y"])));
+            },
+            _ => panic!("Expected record declaration!"),
+        }
+        assert_that!(statements[0].span().end(), is(equal_to(Position::new(4, 2))));
+        assert_that!(statements.len(), is(equal_to(2)));
+    }
+
+    #[test]
+    fn parse_record_literal_and_field_access() {
+        let expected = Expression::new(
+            ExpressionKind::Field(
+                Box::new(Expression::new(
+                    ExpressionKind::RecordLiteral(
+                        Identifier::new(String::from("P"), span(1, 1, 2, 0)),
+                        vec![(Identifier::new(String::from("x"), span(1, 5, 6, 4)), integer(1, span(1, 8, 9, 7)))]),
+                    span(1, 1, 11, 0))),
+                Identifier::new(String::from("x"), span(1, 12, 13, 11))),
+            span(1, 1, 13, 0));
+
+        assert_that!(expression("P { x: 1 }.x"), is(equal_to(expected)));
+    }
+
+    #[test]
+    fn parse_field_assignment() {
+        let statements = parse("a[0].x += 1").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::FieldAssignment(ref record, ref field, operator, ref value) => {
+                match *record.kind() {
+                    ExpressionKind::Index(..) => (),
+                    _ => panic!("Expected index!"),
+                }
+                assert_that!(field.name(), is(equal_to("x")));
+                assert_that!(operator, is(equal_to(Some(BinaryOperator::Add))));
+                assert_that!(value.clone(), is(equal_to(integer(1, span(1, 11, 12, 10)))));
+            },
+            _ => panic!("Expected field assignment!"),
+        }
+    }
+
+    #[test]
+    fn parse_import_and_qualified_record_literal() {
+        let statements = parse("import \"lib/geometry.mvm\"\ngeometry.Point { x: 1 }").unwrap();
+
+        let import = StatementKind::Import(String::from("lib/geometry.mvm"));
+        assert_that!(statements[0].kind(), is(equal_to(&import)));
+        match *statements[1].kind() {
+            StatementKind::Expression(ref e) => match *e.kind() {
+                ExpressionKind::RecordLiteral(ref identifier, _) =>
+                    assert_that!(identifier.name(), is(equal_to("geometry.Point"))),
+                _ => panic!("Expected record literal!"),
+            },
+            _ => panic!("Expected expression!"),
+        }
+        assert_that!(parse("import geometry").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_malformed_records() {
+        assert_that!(parse("record P { x y }").is_err(), is(true));
+        assert_that!(parse("P { x = 1 }").is_err(), is(true));
+        assert_that!(parse("p.1").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_functions_and_calls() {
+        let statements = parse("fun add(a, b) {\n  return a + b\n}\nvar f = fun() { return }\nadd(1, 2)(3)").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::Function(ref identifier, ref parameters, ref body) => {
+                assert_that!(identifier.name(), is(equal_to("add")));
+                assert_that!(parameters.len(), is(equal_to(2)));
+                assert_that!(statements[0].span(), is(equal_to(Span::new(Position::new(1, 1), Position::new(3, 2), 0, 32))));
+                assert_that!(body.len(), is(equal_to(1)));
+            },
+            _ => panic!("Expected function declaration!"),
+        }
+        match *statements[1].kind() {
+            StatementKind::Variable(_, ref value) => match *value.kind() {
+                ExpressionKind::Function(ref parameters, ref body) => {
+                    assert_that!(parameters.is_empty(), is(true));
+                    let expected = StatementKind::Return(Expression::new(ExpressionKind::Nil, span(4, 17, 23, 49)));
+                    assert_that!(body[0].kind(), is(equal_to(&expected)));
+                },
+                _ => panic!("Expected function expression!"),
+            },
+            _ => panic!("Expected variable declaration!"),
+        }
+        match *statements[2].kind() {
+            StatementKind::Expression(ref e) => match *e.kind() {
+                ExpressionKind::Call(ref function, ref arguments) => {
+                    assert_that!(arguments.len(), is(equal_to(1)));
+                    match *function.kind() {
+                        ExpressionKind::FunctionCall(ref identifier, _) =>
+                            assert_that!(identifier.name(), is(equal_to("add"))),
+                        _ => panic!("Expected function call!"),
+                    }
+                },
+                _ => panic!("Expected call!"),
+            },
+            _ => panic!("Expected expression!"),
+        }
+    }
+
+    #[test]
+    fn parse_try_and_throw() {
+        let statements = parse("try {\n  throw 1\n}\ncatch e { println(e) }").unwrap();
+
+        assert_that!(statements.len(), is(equal_to(1)));
+        assert_that!(statements[0].span(), is(equal_to(Span::new(Position::new(1, 1), Position::new(4, 23), 0, 40))));
+        match *statements[0].kind() {
+            StatementKind::Try(ref body, ref identifier, ref handler) => {
+                let expected = StatementKind::Throw(integer(1, span(2, 9, 10, 14)));
+                assert_that!(body[0].kind(), is(equal_to(&expected)));
+                assert_that!(identifier.name(), is(equal_to("e")));
+                assert_that!(handler.len(), is(equal_to(1)));
+            },
+            _ => panic!("Expected try statement!"),
+        }
+        assert_that!(parse("try {}").is_err(), is(true));
+        assert_that!(parse("try {} catch {}").is_err(), is(true));
+        assert_that!(parse("throw").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_match() {
+        let statements = parse("match x {\n  -1 => 1, \"a\" => 2\n  true => 3\n  m.P { x: 0, y } => y\n  _ => 4\n}").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::Expression(ref expression) => match *expression.kind() {
+                ExpressionKind::Match(_, ref arms) => {
+                    let patterns: Vec<&PatternKind> = arms.iter().map(|(pattern, _)| pattern.kind()).collect();
+                    assert_that!(patterns.len(), is(equal_to(5)));
+                    assert_that!(patterns[0], is(equal_to(&PatternKind::Integer(-1))));
+                    let expected = PatternKind::String(String::from("a"));
+                    assert_that!(patterns[1], is(equal_to(&expected)));
+                    assert_that!(patterns[2], is(equal_to(&PatternKind::Boolean(true))));
+                    match *patterns[3] {
+                        PatternKind::Record(ref identifier, ref fields) => {
+                            assert_that!(identifier.name(), is(equal_to("m.P")));
+                            assert_that!(fields[0].1.kind(), is(equal_to(&PatternKind::Integer(0))));
+                            assert_that!(fields[1].1.is_irrefutable(), is(true));
+                        },
+                        _ => panic!("Expected record pattern!"),
+                    }
+                    assert_that!(patterns[4], is(equal_to(&PatternKind::Wildcard)));
+                },
+                _ => panic!("Expected match expression!"),
+            },
+            _ => panic!("Expected expression statement!"),
+        }
+    }
+
+    #[test]
+    fn parse_match_value_without_record_literal() {
+        assert_that!(parse("match P { x: 1 } { _ => 1 }").is_err(), is(true));
+        assert_that!(parse("match (P { x: 1 }) { _ => 1 }").is_ok(), is(true));
+        assert_that!(parse("match f(P { x: 1 }) { _ => fun() { return P { x: 1 } } }").is_ok(), is(true));
+        assert_that!(parse("match x { 1 => 2 3 => 4 }").is_err(), is(true));
+        assert_that!(parse("match x { 1.5 => 2 }").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_if_else() {
+        let statements = parse("if a {\n  x = 1\n} else if b {\n} else { x = 2 }\nif c {}").unwrap();
+
+        assert_that!(statements.len(), is(equal_to(2)));
+        assert_that!(statements[0].span(), is(equal_to(Span::new(Position::new(1, 1), Position::new(4, 17), 0, 45))));
+        match *statements[0].kind() {
+            StatementKind::If(_, ref then_branch, ref else_branch) => {
+                assert_that!(then_branch.len(), is(equal_to(1)));
+                match *else_branch[0].kind() {
+                    StatementKind::If(_, ref then_branch, ref else_branch) => {
+                        assert_that!(then_branch.is_empty(), is(true));
+                        assert_that!(else_branch.len(), is(equal_to(1)));
+                    },
+                    _ => panic!("Expected nested if statement!"),
+                }
+            },
+            _ => panic!("Expected if statement!"),
+        }
+        assert_that!(parse("if a {}\nelse {}").is_err(), is(true));
+        assert_that!(parse("if P { x: 1 } {}").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_loops() {
+        let statements = parse("while x < 1 { break }\nfor i in 0..n { continue }\nfor e in [1, 2] {}").unwrap();
+
+        match *statements[0].kind() {
+            StatementKind::While(_, ref body) => assert_that!(body[0].kind(), is(equal_to(&StatementKind::Break))),
+            _ => panic!("Expected while statement!"),
+        }
+        match *statements[1].kind() {
+            StatementKind::ForRange(ref identifier, ref start, _, ref body) => {
+                assert_that!(identifier.name(), is(equal_to("i")));
+                assert_that!(start.kind(), is(equal_to(&ExpressionKind::Integer(0))));
+                assert_that!(body[0].kind(), is(equal_to(&StatementKind::Continue)));
+            },
+            _ => panic!("Expected for statement over range!"),
+        }
+        match *statements[2].kind() {
+            StatementKind::ForEach(ref identifier, _, _) => assert_that!(identifier.name(), is(equal_to("e"))),
+            _ => panic!("Expected for statement over array!"),
+        }
+        assert_that!(parse("for i 0..1 {}").is_err(), is(true));
+        assert_that!(parse("for 1 in a {}").is_err(), is(true));
+        assert_that!(parse("while true").is_err(), is(true));
+    }
+
+    #[test]
+    fn parse_malformed_functions() {
+        assert_that!(parse("fun f(a b) {}").is_err(), is(true));
+        assert_that!(parse("fun f() { return 1 2 }").is_err(), is(true));
+        assert_that!(parse("fun f() {\n  return 1\n").is_err(), is(true));
+        assert_that!(parse("fun f()").is_err(), is(true));
+    }
+}
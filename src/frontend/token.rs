@@ -1,11 +1,11 @@
 use std::fmt;
-use frontend::Position;
+use frontend::{Position, Span};
 
 /// Sentinel to indicate end of file.
 pub const END_OF_FILE: char = 0 as char;
 
 /// Defines a recognized token in the source.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     position: Position,
     token_type: TokenType,
@@ -22,6 +22,11 @@ impl Token {
     pub fn get_token_type(&self) -> &TokenType {
         &self.token_type
     }
+
+    /// Get the literal text of the token as found in the source.
+    pub fn get_literal(&self) -> &str {
+        &self.literal
+    }
 }
 
 impl fmt::Display for Token {
@@ -31,13 +36,15 @@ impl fmt::Display for Token {
 }
 
 /// Types of tokens.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Types:
     Integer(i64),
     Real(f64),
     Bool(bool),
     String(String),
+    /// String with embedded `${expression}` parts.
+    InterpolatedString(Vec<StringPart>),
     Character(char),
     // Literals:
     Keyword(Keyword),
@@ -50,22 +57,40 @@ pub enum TokenType {
     LeftBracket,
     RightBracket,
     Comma,
+    /// Separates a field from its record: `p.x`.
+    Dot,
+    /// Separates the bounds of a range: `0..10`.
+    DotDot,
+    /// Separates a field from its value in a record literal: `x: 1`.
+    Colon,
+    /// Separates a pattern from its value in a match arm: `1 => "one"`.
+    Arrow,
     // Operators:
     Operator(Operator),
     // Other:
+    /// Comment including its delimiters, only emitted if the lexer keeps comments.
+    Comment(String),
     EOL,
     EOF,
 }
 
-
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
+/// Part of an interpolated string literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    /// Literal text with the escape sequences already replaced.
+    Text(String),
+    /// Tokens of an embedded expression without the surrounding `${` and `}`.
+    Code(Vec<(Token, Span)>),
+}
+
 /// Reserved keywords.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Keyword {
     And,
     Or,
@@ -75,6 +100,18 @@ pub enum Keyword {
     While,
     Const,
     Var,
+    Record,
+    Import,
+    Fun,
+    Return,
+    Try,
+    Catch,
+    Throw,
+    Match,
+    For,
+    In,
+    Break,
+    Continue,
 }
 
 impl Keyword {
@@ -89,6 +126,18 @@ impl Keyword {
             "while" => Keyword::While,
             "const" => Keyword::Const,
             "var" => Keyword::Var,
+            "record" => Keyword::Record,
+            "import" => Keyword::Import,
+            "fun" => Keyword::Fun,
+            "return" => Keyword::Return,
+            "try" => Keyword::Try,
+            "catch" => Keyword::Catch,
+            "throw" => Keyword::Throw,
+            "match" => Keyword::Match,
+            "for" => Keyword::For,
+            "in" => Keyword::In,
+            "break" => Keyword::Break,
+            "continue" => Keyword::Continue,
             _ => panic!("Unrecognized keyword '{}'!", literal),
         }
     }
@@ -101,9 +150,15 @@ impl fmt::Display for Keyword {
 }
 
 /// Available operators.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
     Assign,
+    // compound assign operators
+    PlusAssign,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
+    ModAssign,
     // compare operators
     Equal,
     NotEqual,
@@ -1,23 +1,149 @@
+use std::fmt;
+
+use frontend::Span;
+
 /// The base building block are statements.
-pub enum Statement {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    kind: StatementKind,
+    span: Span,
+}
+
+impl Statement {
+    /// Creates a new statement.
+    pub fn new(kind: StatementKind, span: Span) -> Statement {
+        Statement { kind, span }
+    }
+
+    /// Get the kind of statement.
+    pub fn kind(&self) -> &StatementKind {
+        &self.kind
+    }
+
+    /// Get the source range of the whole statement.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// The various statements.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
     /// Assignment statement: `identifier = expression`.
     Assignment(Identifier, Expression),
+    /// Assignment of an array element: `array[index] = expression`.
+    ///
+    /// The parts are the array, the index, the operator of a compound assignment like `a[i] += 1`
+    /// and the assigned value, which is the right operand of the operator for compound assignments.
+    /// Array and index are evaluated only once in both cases.
+    IndexAssignment(Expression, Expression, Option<BinaryOperator>, Expression),
+    /// Assignment of a record field: `record.field = expression`.
+    ///
+    /// The parts are the record, the field, the operator of a compound assignment like `p.x += 1`
+    /// and the assigned value like for `IndexAssignment`.
+    FieldAssignment(Expression, Identifier, Option<BinaryOperator>, Expression),
     /// Constant declaration: `const identifier = expression`.
-    Constant((Identifier, Expression)),
+    Constant(Identifier, Expression),
     /// Variable declaration: `var identifier` or `var identifier = expression`.
-    Variable((Identifier, Expression)),
+    Variable(Identifier, Expression),
+    /// Record declaration with the names of its fields: `record Point { x, y }`.
+    Record(Identifier, Vec<Identifier>),
+    /// Import of another module by its path relative to the importing file: `import "lib/other.mvm"`.
+    ///
+    /// Imports are resolved by the `ModuleLoader` and removed when the modules are linked.
+    Import(String),
+    /// Function declaration with its parameters and body: `fun name(a, b) { ... }`.
+    ///
+    /// Declares a constant holding the function, which is already visible in the body.
+    Function(Identifier, Vec<Identifier>, Vec<Statement>),
+    /// Return from a function with the given result: `return expression`. The result of a
+    /// plain `return` is nil.
+    Return(Expression),
+    /// Exception handling: `try { ... } catch identifier { ... }`.
+    ///
+    /// The parts are the guarded statements, the variable holding the caught exception and the
+    /// statements of the handler.
+    Try(Vec<Statement>, Identifier, Vec<Statement>),
+    /// Throwing an exception with the given value: `throw expression`.
+    Throw(Expression),
+    /// Conditional statement: `if condition { ... } else { ... }`.
+    ///
+    /// The statements of the else branch are empty without `else`. An `else if` is a branch with
+    /// just the nested `If`.
+    If(Expression, Vec<Statement>, Vec<Statement>),
+    /// Loop while the condition holds: `while condition { ... }`.
+    While(Expression, Vec<Statement>),
+    /// Loop over a range of integers: `for identifier in start..end { ... }`.
+    ///
+    /// The bounds are evaluated once, the end is exclusive.
+    ForRange(Identifier, Expression, Expression, Vec<Statement>),
+    /// Loop over the elements of an array: `for identifier in array { ... }`.
+    ForEach(Identifier, Expression, Vec<Statement>),
+    /// Leave the innermost loop: `break`.
+    Break,
+    /// Continue with the next iteration of the innermost loop: `continue`.
+    Continue,
     /// Expression statement.
     Expression(Expression),
 }
 
 /// Defines an identifier.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Identifier {
     /// Name of the identifier.
     name: String,
+    span: Span,
+}
+
+impl Identifier {
+    /// Creates a new identifier.
+    pub fn new(name: String, span: Span) -> Identifier {
+        Identifier { name, span }
+    }
+
+    /// Get the name of the identifier.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the source range of the identifier.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Defines an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    kind: ExpressionKind,
+    span: Span,
+}
+
+impl Expression {
+    /// Creates a new expression.
+    pub fn new(kind: ExpressionKind, span: Span) -> Expression {
+        Expression { kind, span }
+    }
+
+    /// Get the kind of expression.
+    pub fn kind(&self) -> &ExpressionKind {
+        &self.kind
+    }
+
+    /// Get the source range of the whole expression including all sub expressions.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Consumes the expression and returns its kind.
+    pub fn into_kind(self) -> ExpressionKind {
+        self.kind
+    }
 }
 
 /// The various expressions.
-pub enum Expression {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
     /// Nil expression used e.g. for variable declaration without initial value.
     Nil,
     /// Integer literal expression.
@@ -26,15 +152,90 @@ pub enum Expression {
     Real(f64),
     /// String literal expression.
     String(String),
+    /// String with embedded expressions: `"x = ${x}"`. The parts are concatenated in order.
+    InterpolatedString(Vec<Expression>),
+    /// Character literal expression.
+    Character(char),
     /// Boolean literal expression.
     Boolean(bool),
+    /// Reference to a variable or constant.
+    Identifier(Identifier),
+    /// Call of a function with its arguments.
+    ///
+    /// The identifier is either a variable holding a function or the name of a builtin function.
+    FunctionCall(Identifier, Vec<Expression>),
+    /// Call of the function resulting from an expression: `make(1)(2)`.
+    Call(Box<Expression>, Vec<Expression>),
+    /// Anonymous function with its parameters and body: `fun(a, b) { ... }`.
+    Function(Vec<Identifier>, Vec<Statement>),
+    /// Array literal with its elements.
+    Array(Vec<Expression>),
+    /// Access of an array element: `array[index]`.
+    Index(Box<Expression>, Box<Expression>),
+    /// New record with the values of its fields: `Point { x: 1, y: 2 }`.
+    RecordLiteral(Identifier, Vec<(Identifier, Expression)>),
+    /// Access of a record field: `record.field`.
+    Field(Box<Expression>, Identifier),
     /// Binary operation expression.
     BinaryOperation(BinaryOperator, Box<Expression>, Box<Expression>),
     /// Unary operation expression.
     UnaryOperation(UnaryOperator, Box<Expression>),
+    /// Pattern matching: `match value { 1 => "one", _ => "other" }`.
+    ///
+    /// The result is the value of the first arm whose pattern matches the value.
+    Match(Box<Expression>, Vec<(Pattern, Expression)>),
+}
+
+/// Defines a pattern of a match arm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    kind: PatternKind,
+    span: Span,
+}
+
+impl Pattern {
+    /// Creates a new pattern.
+    pub fn new(kind: PatternKind, span: Span) -> Pattern {
+        Pattern { kind, span }
+    }
+
+    /// Get the kind of pattern.
+    pub fn kind(&self) -> &PatternKind {
+        &self.kind
+    }
+
+    /// Get the source range of the whole pattern.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Determines if the pattern matches all values.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self.kind, PatternKind::Wildcard | PatternKind::Binding(_))
+    }
+}
+
+/// The various patterns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// Matches any value: `_`.
+    Wildcard,
+    /// Matches any value and binds it to a new variable.
+    Binding(Identifier),
+    /// Matches an equal integer.
+    Integer(i64),
+    /// Matches an equal string.
+    String(String),
+    /// Matches an equal boolean.
+    Boolean(bool),
+    /// Matches a record of the given type whose fields match the patterns: `Point { x: 0, y }`.
+    ///
+    /// A field without pattern binds its value to a variable named like the field.
+    Record(Identifier, Vec<(Identifier, Pattern)>),
 }
 
 /// Binary operators.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum BinaryOperator {
     /// Assign operator.
     Assign,
@@ -70,7 +271,43 @@ pub enum BinaryOperator {
 }
 
 /// Unary operators.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum UnaryOperator {
     /// Logical not operator.
     Not,
+    /// Arithmetic negation operator.
+    Negate,
+    /// Arithmetic identity operator.
+    Plus,
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BinaryOperator::Assign => write!(f, "="),
+            BinaryOperator::Add => write!(f, "+"),
+            BinaryOperator::Subtract => write!(f, "-"),
+            BinaryOperator::Multiply => write!(f, "*"),
+            BinaryOperator::Divide => write!(f, "/"),
+            BinaryOperator::Modulo => write!(f, "%"),
+            BinaryOperator::Equal => write!(f, "=="),
+            BinaryOperator::NotEqual => write!(f, "!="),
+            BinaryOperator::LessThan => write!(f, "<"),
+            BinaryOperator::LessThanEqual => write!(f, "<="),
+            BinaryOperator::GreaterThan => write!(f, ">"),
+            BinaryOperator::GreaterThanEqual => write!(f, ">="),
+            BinaryOperator::And => write!(f, "and"),
+            BinaryOperator::Or => write!(f, "or"),
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnaryOperator::Not => write!(f, "not"),
+            UnaryOperator::Negate => write!(f, "-"),
+            UnaryOperator::Plus => write!(f, "+"),
+        }
+    }
 }
@@ -0,0 +1,714 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+
+use backend::{StackTraceElement, VmError, MAX_CALL_DEPTH};
+use backend::heap::Heap;
+use backend::image::{Function, RecordType};
+use backend::value::{self, Value};
+use frontend::Span;
+use intermediate::ast::*;
+use intermediate::semantic::{Analysis, Builtin};
+use intermediate::visitor::Visitor;
+
+/// Stack size of a thread which runs the interpreter.
+///
+/// Each call of the interpreted program nests several calls of the interpreter, so the default
+/// stack of a thread is too small for the maximum call depth.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Executes the abstract syntax tree directly, without compiling it to byte code.
+///
+/// The values are the ones of the virtual machine and live in the same heap, so the interpreter
+/// behaves like the compiled program: It prints the same output and fails with the same errors.
+/// This makes it a reference for the compiler and the virtual machine.
+///
+/// Variables are resolved by the semantic analysis. Each variable lives in an upvalue in the heap
+/// which is created when its declaration is executed. The environment of a running function maps
+/// the ids of its variables to their upvalues, so closures simply share the upvalues of their
+/// captured variables. A closure is a heap closure whose function address is the index of its
+/// declaration in the interpreter.
+pub struct Interpreter {
+    analysis: Analysis,
+    heap: Heap,
+    environment: Environment,
+    strings: HashMap<String, Value>,
+    records: HashMap<String, Rc<RecordType>>,
+    declarations: Vec<Rc<Declaration>>,
+    functions: HashMap<Span, Rc<Function>>,
+    depth: usize,
+    output: Box<dyn Write>,
+}
+
+/// The variables of a running function by id.
+#[derive(Default)]
+struct Environment {
+    variables: HashMap<usize, Value>,
+}
+
+/// Parameters and body of a function, which are executed by calling its closures, and the ids of
+/// the variables in the order of the upvalues of its closures.
+struct Declaration {
+    parameters: Vec<Identifier>,
+    body: Vec<Statement>,
+    captures: Vec<usize>,
+}
+
+/// Reasons to leave the normal flow of execution, which are passed up to the statement which
+/// handles them.
+pub enum Unwind {
+    /// `break` leaves the innermost loop.
+    Break,
+    /// `continue` continues with the next iteration of the innermost loop.
+    Continue,
+    /// `return` leaves the called function with the value.
+    Return(Value),
+    /// A thrown value or runtime error, handled by the innermost `try`.
+    Throw(Exception),
+}
+
+/// A thrown value or runtime error on its way to a handler.
+pub struct Exception {
+    value: Option<Value>,
+    error: VmError,
+    stack_trace: Vec<StackTraceElement>,
+}
+
+impl Exception {
+    fn new(value: Option<Value>, error: VmError) -> Exception {
+        Exception { value, error, stack_trace: Vec::new() }
+    }
+}
+
+/// Result of executing a statement or evaluating an expression. Statements result in nil.
+pub type Evaluation = Result<Value, Unwind>;
+
+impl Interpreter {
+    /// Creates an interpreter for the analysed program which prints to STDOUT.
+    pub fn new(analysis: Analysis) -> Interpreter {
+        Interpreter::with_output(analysis, Box::new(io::stdout()))
+    }
+
+    /// Creates an interpreter for the analysed program which prints to the given output.
+    pub fn with_output(analysis: Analysis, output: Box<dyn Write>) -> Interpreter {
+        Interpreter {
+            analysis,
+            heap: Heap::new(),
+            environment: Environment::default(),
+            strings: HashMap::new(),
+            records: HashMap::new(),
+            declarations: Vec::new(),
+            functions: HashMap::new(),
+            depth: 0,
+            output,
+        }
+    }
+
+    /// Executes the statements of the program the analysis belongs to.
+    ///
+    /// Returns the error of an uncaught exception like the virtual machine.
+    pub fn run(&mut self, statements: &[Statement]) -> Result<(), VmError> {
+        let result = self.execute(statements);
+        let _ = self.output.flush();
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(Unwind::Throw(exception)) => Err(exception.error.with_stack_trace(exception.stack_trace)),
+            Err(_) => unreachable!("Break, continue and return are checked!"),
+        }
+    }
+
+    fn execute(&mut self, statements: &[Statement]) -> Result<(), Unwind> {
+        for statement in statements {
+            self.visit_statement(statement)?;
+        }
+
+        Ok(())
+    }
+
+    // Runs the body of a loop. Returns whether the loop continues.
+    fn execute_loop_body(&mut self, body: &[Statement]) -> Result<bool, Unwind> {
+        match self.execute(body) {
+            Ok(()) | Err(Unwind::Continue) => Ok(true),
+            Err(Unwind::Break) => Ok(false),
+            Err(unwind) => Err(unwind),
+        }
+    }
+
+    // Get the upvalue of the variable, which is nil if its declaration was not executed yet.
+    fn upvalue(&mut self, id: usize) -> Value {
+        let heap = &mut self.heap;
+        *self.environment.variables.entry(id).or_insert_with(|| heap.new_upvalue(Value::Nil))
+    }
+
+    fn id(&self, identifier: &Identifier) -> usize {
+        self.analysis.variable(identifier).expect("Variables are resolved by the type checker!").id()
+    }
+
+    // A declaration creates a new upvalue, so closures of earlier executions keep theirs.
+    fn declare(&mut self, identifier: &Identifier, value: Value) {
+        let id = self.id(identifier);
+        let upvalue = self.heap.new_upvalue(value);
+        self.environment.variables.insert(id, upvalue);
+    }
+
+    fn load(&mut self, identifier: &Identifier) -> Value {
+        let upvalue = self.upvalue(self.id(identifier));
+        self.heap.upvalue(upvalue).expect("Variables live in upvalues!")
+    }
+
+    fn store(&mut self, identifier: &Identifier, value: Value) {
+        let upvalue = self.upvalue(self.id(identifier));
+        self.heap.set_upvalue(upvalue, value).expect("Variables live in upvalues!");
+    }
+
+    // The declaration of the function is stored once, its closures share it.
+    fn closure(&mut self, name: &str, parameters: &[Identifier], body: &[Statement], span: Span) -> Value {
+        let function = match self.functions.get(&span) {
+            Some(function) => Rc::clone(function),
+            None => {
+                let function = Rc::new(Function::new(
+                    name.to_string(), self.declarations.len(), parameters.len() as u8, Vec::new()));
+                self.declarations.push(Rc::new(Declaration {
+                    parameters: parameters.to_vec(),
+                    body: body.to_vec(),
+                    captures: self.analysis.captures(span).to_vec(),
+                }));
+                self.functions.insert(span, Rc::clone(&function));
+                function
+            },
+        };
+        let declaration = Rc::clone(&self.declarations[function.address()]);
+        let upvalues = declaration.captures.iter().map(|&id| self.upvalue(id)).collect();
+        self.heap.new_closure(function, upvalues)
+    }
+
+    // Runs the function of the closure in a new environment with its captured variables and the
+    // arguments as parameters. Exceptions leaving the function record the call in their stack trace.
+    fn call(&mut self, callee: Value, arguments: Vec<Value>, span: Span) -> Evaluation {
+        let (function, upvalues) = match self.heap.closure(callee) {
+            Ok((function, upvalues)) => (Rc::clone(function), upvalues.to_vec()),
+            Err(e) => return Err(error(e, span)),
+        };
+        function.check_arity(arguments.len()).map_err(|e| error(e, span))?;
+
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(error("Call stack overflow!", span));
+        }
+
+        let declaration = Rc::clone(&self.declarations[function.address()]);
+        let mut environment = Environment::default();
+        environment.variables.extend(declaration.captures.iter().cloned().zip(upvalues));
+        let caller = mem::replace(&mut self.environment, environment);
+        self.depth += 1;
+
+        for (parameter, argument) in declaration.parameters.iter().zip(arguments) {
+            self.declare(parameter, argument);
+        }
+
+        let result = self.execute(&declaration.body);
+        self.depth -= 1;
+        self.environment = caller;
+
+        match result {
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Throw(mut exception)) => {
+                exception.stack_trace.push(StackTraceElement::new(function.name().to_string(), Some(span.start())));
+                Err(Unwind::Throw(exception))
+            },
+            Err(_) => unreachable!("Break and continue are checked!"),
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Expression]) -> Result<Vec<Value>, Unwind> {
+        let mut values = Vec::with_capacity(arguments.len());
+
+        for argument in arguments {
+            values.push(self.visit_expression(argument)?);
+        }
+
+        Ok(values)
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, argument: &Expression, span: Span) -> Evaluation {
+        let value = self.visit_expression(argument)?;
+
+        match builtin {
+            Builtin::Length => {
+                let length = self.heap.array(value).map_err(|e| error(e, span))?.len();
+                Ok(Value::Integer(length as i64))
+            },
+            Builtin::Print | Builtin::PrintLn => {
+                let mut text = self.heap.display(value);
+
+                if Builtin::PrintLn == builtin {
+                    text.push('\n');
+                }
+
+                self.output.write_all(text.as_bytes())
+                    .map_err(|_| Unwind::Throw(Exception::new(None, VmError::new(String::from("Can't write output!"), None))))?;
+                Ok(Value::Nil)
+            },
+        }
+    }
+
+    // Adding two strings joins them, everything else is a numeric operation or a comparison.
+    fn operate(&mut self, operator: BinaryOperator, left: Value, right: Value, span: Span) -> Evaluation {
+        let result = match (operator, left, right) {
+            (BinaryOperator::Add, Value::String(_), Value::String(_)) => self.heap.concat(left, right),
+            (BinaryOperator::Add, _, _)
+                | (BinaryOperator::Subtract, _, _)
+                | (BinaryOperator::Multiply, _, _)
+                | (BinaryOperator::Divide, _, _)
+                | (BinaryOperator::Modulo, _, _) => value::arithmetic(operator, left, right),
+            _ => self.heap.compare(operator, left, right),
+        };
+        result.map_err(|e| error(e, span))
+    }
+
+    fn field(&self, record: Value, field: &Identifier, span: Span) -> Evaluation {
+        let index = self.heap.field_index(record, field.name()).map_err(|e| error(e, span))?;
+        self.heap.field(record, index).map_err(|e| error(e, span))
+    }
+
+    fn set_field(&mut self, record: Value, field: &Identifier, value: Value, span: Span) -> Result<(), Unwind> {
+        let index = self.heap.field_index(record, field.name()).map_err(|e| error(e, span))?;
+        self.heap.set_field(record, index, value).map_err(|e| error(e, span))
+    }
+
+    // Record types are known from the analysis, even if their declaration was not executed yet.
+    fn record_type(&mut self, name: &str) -> Rc<RecordType> {
+        let analysis = &self.analysis;
+        let record_type = self.records.entry(name.to_string()).or_insert_with(|| {
+            let fields = analysis.record_fields(name).expect("Record types are checked!").to_vec();
+            Rc::new(RecordType::new(name.to_string(), fields))
+        });
+        Rc::clone(record_type)
+    }
+
+    // Only booleans are allowed as conditions, there is no implicit truthiness of other values.
+    fn boolean(&self, value: Value, span: Span) -> Result<bool, Unwind> {
+        match value {
+            Value::Boolean(value) => Ok(value),
+            _ => Err(error(format!("Expected Boolean but got {}!", value.type_name()), span)),
+        }
+    }
+
+    fn integer(&self, value: Value, span: Span) -> Result<i64, Unwind> {
+        match value {
+            Value::Integer(value) => Ok(value),
+            _ => Err(error(format!("Expected Integer but got {}!", value.type_name()), span)),
+        }
+    }
+
+    // Tests the pattern against the value and binds its variables on the way.
+    fn matches(&mut self, pattern: &Pattern, value: Value) -> Result<bool, Unwind> {
+        let span = pattern.span();
+
+        match *pattern.kind() {
+            PatternKind::Wildcard => Ok(true),
+            PatternKind::Binding(ref identifier) => {
+                self.declare(identifier, value);
+                Ok(true)
+            },
+            // Reals are equal to the integers with the same value like for `==`.
+            PatternKind::Integer(expected) =>
+                Ok(Value::Boolean(true) == value::compare(BinaryOperator::Equal, value, Value::Integer(expected))
+                    .map_err(|e| error(e, span))?),
+            PatternKind::String(ref expected) => Ok(self.heap.string(value).is_ok_and(|actual| actual == expected)),
+            PatternKind::Boolean(expected) => Ok(Value::Boolean(expected) == value),
+            PatternKind::Record(ref identifier, ref fields) => {
+                if !self.heap.record_type(value).is_ok_and(|actual| actual.name() == identifier.name()) {
+                    return Ok(false);
+                }
+
+                for (field, pattern) in fields {
+                    let field_value = self.field(value, field, span)?;
+
+                    if !self.matches(pattern, field_value)? {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
+            },
+        }
+    }
+}
+
+impl Visitor<Evaluation> for Interpreter {
+    fn visit_identifier(&mut self, n: &Identifier) -> Evaluation {
+        Ok(self.load(n))
+    }
+
+    fn visit_statement(&mut self, s: &Statement) -> Evaluation {
+        let span = s.span();
+
+        match *s.kind() {
+            StatementKind::Variable(ref identifier, ref value) | StatementKind::Constant(ref identifier, ref value) => {
+                let value = self.visit_expression(value)?;
+                self.declare(identifier, value);
+            },
+            StatementKind::Assignment(ref identifier, ref value) => {
+                let value = self.visit_expression(value)?;
+                self.store(identifier, value);
+            },
+            StatementKind::IndexAssignment(ref array, ref index, operator, ref value) => {
+                let array_value = self.visit_expression(array)?;
+                let index_value = self.visit_expression(index)?;
+                let value = match operator {
+                    // Compound assignments operate on the element of the already evaluated target.
+                    Some(operator) => {
+                        let element = self.heap.element(array_value, index_value).map_err(|e| error(e, span))?;
+                        let right = self.visit_expression(value)?;
+                        self.operate(operator, element, right, span)?
+                    },
+                    None => self.visit_expression(value)?,
+                };
+                self.heap.set_element(array_value, index_value, value).map_err(|e| error(e, span))?;
+            },
+            StatementKind::FieldAssignment(ref record, ref field, operator, ref value) => {
+                let record_value = self.visit_expression(record)?;
+                let value = match operator {
+                    // Compound assignments operate on the field of the already evaluated record.
+                    Some(operator) => {
+                        let current = self.field(record_value, field, span)?;
+                        let right = self.visit_expression(value)?;
+                        self.operate(operator, current, right, span)?
+                    },
+                    None => self.visit_expression(value)?,
+                };
+                self.set_field(record_value, field, value, span)?;
+            },
+            StatementKind::Record(ref identifier, _) => {
+                self.record_type(identifier.name());
+            },
+            StatementKind::Import(_) => unreachable!("Imports are rejected by the type checker!"),
+            StatementKind::Function(ref identifier, ref parameters, ref body) => {
+                // Declared before the closure is created, so the function may capture itself.
+                self.declare(identifier, Value::Nil);
+                let closure = self.closure(identifier.name(), parameters, body, span);
+                self.store(identifier, closure);
+            },
+            StatementKind::Return(ref value) => {
+                let value = self.visit_expression(value)?;
+                return Err(Unwind::Return(value));
+            },
+            StatementKind::Try(ref body, ref identifier, ref handler) => match self.execute(body) {
+                Err(Unwind::Throw(exception)) => {
+                    let value = match exception.value {
+                        Some(value) => value,
+                        None => self.heap.new_string(exception.error.message().to_string()),
+                    };
+                    self.declare(identifier, value);
+                    self.execute(handler)?;
+                },
+                result => result?,
+            },
+            StatementKind::Throw(ref value) => {
+                let value = self.visit_expression(value)?;
+                let message = format!("Uncaught exception '{}'!", self.heap.display(value));
+                return Err(Unwind::Throw(Exception::new(Some(value), VmError::new(message, Some(span.start())))));
+            },
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => {
+                let condition = self.visit_expression(condition)?;
+
+                if self.boolean(condition, span)? {
+                    self.execute(then_branch)?;
+                } else {
+                    self.execute(else_branch)?;
+                }
+            },
+            StatementKind::While(ref condition, ref body) => loop {
+                let condition = self.visit_expression(condition)?;
+
+                if !self.boolean(condition, span)? || !self.execute_loop_body(body)? {
+                    break;
+                }
+            },
+            // The bounds are evaluated once.
+            StatementKind::ForRange(ref identifier, ref start, ref end, ref body) => {
+                let start = self.visit_expression(start)?;
+                let mut counter = self.integer(start, span)?;
+                let end = self.visit_expression(end)?;
+                let end = self.integer(end, span)?;
+
+                while counter < end {
+                    self.declare(identifier, Value::Integer(counter));
+
+                    if !self.execute_loop_body(body)? {
+                        break;
+                    }
+
+                    counter = counter.wrapping_add(1);
+                }
+            },
+            // The length is read in each iteration like by the compiled loop.
+            StatementKind::ForEach(ref identifier, ref array, ref body) => {
+                let array = self.visit_expression(array)?;
+                let mut index = 0;
+
+                while index < self.heap.array(array).map_err(|e| error(e, span))?.len() {
+                    let element = self.heap.element(array, Value::Integer(index as i64)).map_err(|e| error(e, span))?;
+                    self.declare(identifier, element);
+
+                    if !self.execute_loop_body(body)? {
+                        break;
+                    }
+
+                    index += 1;
+                }
+            },
+            StatementKind::Break => return Err(Unwind::Break),
+            StatementKind::Continue => return Err(Unwind::Continue),
+            StatementKind::Expression(ref expression) => {
+                self.visit_expression(expression)?;
+            },
+        }
+
+        Ok(Value::Nil)
+    }
+
+    fn visit_expression(&mut self, e: &Expression) -> Evaluation {
+        let span = e.span();
+
+        match *e.kind() {
+            ExpressionKind::Nil => Ok(Value::Nil),
+            ExpressionKind::Integer(value) => Ok(Value::Integer(value)),
+            ExpressionKind::Real(value) => Ok(Value::Real(value)),
+            ExpressionKind::Character(value) => Ok(Value::Character(value)),
+            ExpressionKind::Boolean(value) => Ok(Value::Boolean(value)),
+            // Literals are allocated once like the string pool of an image.
+            ExpressionKind::String(ref value) => {
+                let heap = &mut self.heap;
+                Ok(*self.strings.entry(value.clone()).or_insert_with(|| heap.new_string(value.clone())))
+            },
+            ExpressionKind::InterpolatedString(ref parts) => {
+                let mut content = String::new();
+
+                for part in parts {
+                    let value = self.visit_expression(part)?;
+                    content.push_str(&self.heap.display(value));
+                }
+
+                Ok(self.heap.new_string(content))
+            },
+            ExpressionKind::Identifier(ref identifier) => self.visit_identifier(identifier),
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) if self.analysis.variable(identifier).is_some() => {
+                let callee = self.load(identifier);
+                let arguments = self.arguments(arguments)?;
+                self.call(callee, arguments, span)
+            },
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) => {
+                let builtin = Builtin::for_name(identifier.name()).expect("Functions are resolved by the type checker!");
+                self.call_builtin(builtin, &arguments[0], span)
+            },
+            ExpressionKind::Call(ref function, ref arguments) => {
+                let callee = self.visit_expression(function)?;
+                let arguments = self.arguments(arguments)?;
+                self.call(callee, arguments, span)
+            },
+            ExpressionKind::Function(ref parameters, ref body) => Ok(self.closure("", parameters, body, span)),
+            ExpressionKind::Array(ref elements) => {
+                let elements = self.arguments(elements)?;
+                Ok(self.heap.new_array(elements))
+            },
+            ExpressionKind::Index(ref array, ref index) => {
+                let array = self.visit_expression(array)?;
+                let index = self.visit_expression(index)?;
+                self.heap.element(array, index).map_err(|e| error(e, span))
+            },
+            // The fields are set in source order on the new record.
+            ExpressionKind::RecordLiteral(ref identifier, ref fields) => {
+                let record_type = self.record_type(identifier.name());
+                let record = self.heap.new_record(record_type);
+
+                for (field, value) in fields {
+                    let value = self.visit_expression(value)?;
+                    self.set_field(record, field, value, field.span())?;
+                }
+
+                Ok(record)
+            },
+            ExpressionKind::Field(ref record, ref field) => {
+                let record = self.visit_expression(record)?;
+                self.field(record, field, span)
+            },
+            // The right operand is only evaluated if the left one does not determine the result.
+            ExpressionKind::BinaryOperation(operator @ BinaryOperator::And, ref left, ref right)
+                | ExpressionKind::BinaryOperation(operator @ BinaryOperator::Or, ref left, ref right) => {
+                let short_circuit = BinaryOperator::Or == operator;
+                let left = self.visit_expression(left)?;
+
+                if self.boolean(left, span)? == short_circuit {
+                    return Ok(Value::Boolean(short_circuit));
+                }
+
+                let right = self.visit_expression(right)?;
+                self.boolean(right, span).map(Value::Boolean)
+            },
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) => {
+                let left = self.visit_expression(left)?;
+                let right = self.visit_expression(right)?;
+                self.operate(operator, left, right, span)
+            },
+            ExpressionKind::UnaryOperation(operator, ref operand) => {
+                let operand = self.visit_expression(operand)?;
+
+                match operator {
+                    UnaryOperator::Negate => value::negate(operand).map_err(|e| error(e, span)),
+                    UnaryOperator::Plus => Ok(operand),
+                    UnaryOperator::Not => self.boolean(operand, span).map(|operand| Value::Boolean(!operand)),
+                }
+            },
+            ExpressionKind::Match(ref value, ref arms) => {
+                let value = self.visit_expression(value)?;
+
+                for (pattern, arm) in arms {
+                    if self.matches(pattern, value)? {
+                        return self.visit_expression(arm);
+                    }
+                }
+
+                Err(error(format!("No match for value '{}'!", self.heap.display(value)), span))
+            },
+        }
+    }
+}
+
+// Runtime errors are thrown like the errors of the virtual machine, located at the start of the
+// failing statement or expression.
+fn error<S: Into<String>>(message: S, span: Span) -> Unwind {
+    Unwind::Throw(Exception::new(None, VmError::new(message.into(), Some(span.start()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use std::fs;
+    use std::path::Path;
+    use std::thread;
+    use backend::VirtualMachine;
+    use backend::compiler::Compiler;
+    use frontend::module::{Module, ModuleLoader};
+    use frontend::Position;
+    use intermediate::linker;
+    use intermediate::optimize::Level;
+    use intermediate::semantic::TypeChecker;
+    use test_support::{parse, SharedOutput};
+
+    // Runs in a thread of its own, whose stack is big enough for deep recursion.
+    fn interpret(statements: Vec<Statement>) -> (Result<(), VmError>, String) {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let analysis = TypeChecker::new().check(&statements).unwrap();
+                let output = SharedOutput::new();
+                let result = Interpreter::with_output(analysis, Box::new(output.clone())).run(&statements);
+                (result, output.text())
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn interpret_source(src: &str) -> (Result<(), VmError>, String) {
+        interpret(parse(src).unwrap())
+    }
+
+    // Runs the program by the virtual machine, compiled at the given level.
+    fn run_compiled(modules: &[Module], level: Level) -> (Result<(), VmError>, String) {
+        let output = SharedOutput::new();
+        let image = Compiler::with_level(level).compile_modules(modules).unwrap().0;
+        let result = VirtualMachine::with_output(image, Box::new(output.clone())).run();
+        (result, output.text())
+    }
+
+    #[test]
+    fn interpret_program() {
+        let (result, output) = interpret_source(concat!(
+            "record P { x, y }\nvar p = P { x: 1, y: [2.5] }\np.x += 2\n",
+            "fun f(n) { return match n { 0 => \"zero\", _ => \"${n}!\" } }\n",
+            "for i in 0..p.x { print(f(i)) }\nprintln(p)"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("zero1!2!P { x: 3, y: [2.5] }\n")));
+    }
+
+    #[test]
+    fn interpret_compound_assignment_evaluates_target_once() {
+        let (result, output) = interpret_source(concat!(
+            "record P { x, y }\nvar calls = 0\nvar l = [P { x: 1, y: 2 }]\n",
+            "fun target() {\n  calls += 1\n  return l\n}\n",
+            "target()[calls - 1].x += 10\ntarget()[0].y *= 3\nprintln(l)\nprintln(calls)"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("[P { x: 11, y: 6 }]\n2\n")));
+    }
+
+    #[test]
+    fn interpret_closures_share_variables() {
+        let (result, output) = interpret_source(concat!(
+            "var x = 1\nvar get = fun() { return x }\nvar set = fun(v) { x = v }\n",
+            "set(5)\nprintln(get())\nx += 1\nprintln(get())"));
+
+        assert_that!(result, is(equal_to(Ok(()))));
+        assert_that!(&output, is(equal_to("5\n6\n")));
+    }
+
+    #[test]
+    fn interpret_errors() {
+        let (result, output) = interpret_source("println(1)\nvar a = [1]\nprintln(a[0] / (a[0] - 1))");
+
+        assert_that!(&output, is(equal_to("1\n")));
+        assert_that!(result, is(equal_to(Err(VmError::new(
+            String::from("Division by zero!"), Some(Position::new(3, 9)))))));
+
+        let (result, _) = interpret_source("fun f() {\n  return f()\n}\nf()");
+        let error = result.unwrap_err();
+        assert_that!(error.to_string(), is(equal_to(String::from(concat!(
+            "Call stack overflow! at [2, 10]\n",
+            "    in <fun f> called at [2, 10]\n",
+            "    ... repeated 1022 more times\n",
+            "    in <fun f> called at [4, 1]")))));
+        assert_that!(error.stack_trace().len(), is(equal_to(MAX_CALL_DEPTH)));
+    }
+
+    #[test]
+    fn interpret_uncaught_exception_has_stack_trace() {
+        let (result, _) = interpret_source(concat!(
+            "fun boom(x) {\n  return fun() { throw x }\n}\n",
+            "fun outer() {\n  return boom(1)()\n}\n",
+            "fun f() { outer() }\nf()"));
+
+        assert_that!(result.unwrap_err().to_string(), is(equal_to(String::from(concat!(
+            "Uncaught exception '1'! at [2, 18]\n",
+            "    in <fun> called at [5, 10]\n",
+            "    in <fun outer> called at [7, 11]\n",
+            "    in <fun f> called at [8, 1]")))));
+    }
+
+    #[test]
+    fn interpreter_and_virtual_machine_agree() {
+        let mut paths: Vec<_> = fs::read_dir("test/programs").unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        paths.push(Path::new("test/modules/main.mvm").to_path_buf());
+
+        for path in paths {
+            let modules = ModuleLoader::new().load(&path).unwrap();
+            let interpreted = interpret(linker::link(&modules).unwrap());
+
+            for &level in &[Level::O0, Level::O1] {
+                let compiled = run_compiled(&modules, level);
+
+                assert_that!(&interpreted.1, is(equal_to(&compiled.1)));
+                assert_that!(&interpreted.0, is(equal_to(&compiled.0)));
+            }
+        }
+    }
+}
@@ -0,0 +1,581 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use backend::image::{Capture, RecordType};
+use frontend::Span;
+use intermediate::ast::{BinaryOperator, UnaryOperator};
+
+/// Lower level representation of a program between the AST and the byte code.
+///
+/// Each function is three-address code grouped into basic blocks, which end in a terminator
+/// leading to the next blocks and so form the control-flow graph. The first function is the top
+/// level code of the program.
+///
+/// Instructions compute temporaries from temporaries, which are assigned once. Values of different
+/// paths are joined by the parameters of the block they continue at. Variables live in the local
+/// slots of the function or in upvalues like in the virtual machine. Temporaries are used once, in
+/// the reverse order of their definition, so they map directly onto the operand stack: The
+/// emitter keeps them there and `dup` creates a temporary which is used in addition to its
+/// original.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    records: Vec<RecordType>,
+    functions: Vec<Function>,
+}
+
+impl Program {
+    pub fn new(records: Vec<RecordType>, functions: Vec<Function>) -> Program {
+        Program { records, functions }
+    }
+
+    /// Get the declared record types.
+    pub fn records(&self) -> &[RecordType] {
+        &self.records
+    }
+
+    /// Get the functions, the top level code first.
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Removes the blocks of all functions which are never executed.
+    pub fn remove_unreachable_blocks(&mut self) {
+        for function in &mut self.functions {
+            function.remove_unreachable_blocks();
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "f{} {}:", index, function)?;
+
+            for (id, block) in function.blocks.iter().enumerate() {
+                write!(f, "B{}", id)?;
+
+                if !block.parameters.is_empty() {
+                    write!(f, "({})", join(&block.parameters))?;
+                }
+
+                if let Some(guard) = block.guard {
+                    write!(f, " [catch B{}]", function.guards[guard].handler)?;
+                }
+
+                writeln!(f, ":")?;
+
+                for instruction in &block.instructions {
+                    writeln!(f, "    {}", instruction.kind)?;
+                }
+
+                writeln!(f, "    {}", block.terminator.kind)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Function with its code as control-flow graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    name: String,
+    arity: u8,
+    captures: Vec<Capture>,
+    blocks: Vec<Block>,
+    guards: Vec<Guard>,
+}
+
+impl Function {
+    pub fn new(name: String, arity: u8, captures: Vec<Capture>, blocks: Vec<Block>, guards: Vec<Guard>) -> Function {
+        Function { name, arity, captures, blocks, guards }
+    }
+
+    /// Get the name of the function, which is empty for anonymous functions.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the number of parameters, which are the first local slots.
+    pub fn arity(&self) -> u8 {
+        self.arity
+    }
+
+    /// Get where the upvalues of a new closure come from.
+    pub fn captures(&self) -> &[Capture] {
+        &self.captures
+    }
+
+    /// Get the basic blocks in the order of the code, the entry block first.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Get the try blocks, which are referenced by the blocks they guard.
+    pub fn guards(&self) -> &[Guard] {
+        &self.guards
+    }
+
+    /// Get the guards covering the block, the innermost first.
+    pub fn guards_of(&self, block: &Block) -> Vec<usize> {
+        let mut guards = Vec::new();
+        let mut current = block.guard;
+
+        while let Some(guard) = current {
+            guards.push(guard);
+            current = self.guards[guard].parent;
+        }
+
+        guards
+    }
+
+    // The entry block is reachable and so is each successor of a reachable block and the handler
+    // of each try block covering one. Guards without reachable blocks are removed as well.
+    fn remove_unreachable_blocks(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending = vec![0];
+
+        while let Some(id) = pending.pop() {
+            if reachable[id] {
+                continue;
+            }
+
+            reachable[id] = true;
+            pending.extend(self.blocks[id].successors());
+            pending.extend(self.guards_of(&self.blocks[id]).into_iter().map(|guard| self.guards[guard].handler));
+        }
+
+        let mut used = vec![false; self.guards.len()];
+
+        for (id, block) in self.blocks.iter().enumerate() {
+            if reachable[id] {
+                for guard in self.guards_of(block) {
+                    used[guard] = true;
+                }
+            }
+        }
+
+        let blocks = renumber(&reachable);
+        let guards = renumber(&used);
+        let old_blocks = self.blocks.drain(..).collect::<Vec<_>>();
+        let old_guards = self.guards.drain(..).collect::<Vec<_>>();
+        self.blocks = old_blocks.into_iter()
+            .enumerate()
+            .filter(|&(id, _)| reachable[id])
+            .map(|(_, block)| block.renumber(&blocks, &guards))
+            .collect();
+        self.guards = old_guards.into_iter()
+            .enumerate()
+            .filter(|&(id, _)| used[id])
+            .map(|(_, guard)| Guard::new(blocks[&guard.handler], guard.parent.map(|parent| guards[&parent])))
+            .collect();
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<anonymous>/{}", self.arity)?;
+        } else {
+            write!(f, "{}/{}", self.name, self.arity)?;
+        }
+
+        if !self.captures.is_empty() {
+            let captures: Vec<String> = self.captures.iter()
+                .map(|capture| match *capture {
+                    Capture::Local(index) => format!("x{}", index),
+                    Capture::Upvalue(index) => format!("u{}", index),
+                })
+                .collect();
+            write!(f, " [{}]", captures.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Maps the indices of the kept elements to their new indices.
+fn renumber(kept: &[bool]) -> HashMap<usize, usize> {
+    kept.iter()
+        .enumerate()
+        .filter(|&(_, &keep)| keep)
+        .enumerate()
+        .map(|(new, (old, _))| (old, new))
+        .collect()
+}
+
+/// Sequence of instructions which is only entered at its start and left by its terminator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    parameters: Vec<Temporary>,
+    instructions: Vec<Instruction>,
+    terminator: Terminator,
+    guard: Option<usize>,
+}
+
+impl Block {
+    pub fn new(parameters: Vec<Temporary>, instructions: Vec<Instruction>, terminator: Terminator,
+               guard: Option<usize>) -> Block {
+        Block { parameters, instructions, terminator, guard }
+    }
+
+    /// Get the temporaries the values passed by the jumps to the block are assigned to.
+    pub fn parameters(&self) -> &[Temporary] {
+        &self.parameters
+    }
+
+    /// Get the instructions before the terminator.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Get the last instruction, which leaves the block.
+    pub fn terminator(&self) -> &Terminator {
+        &self.terminator
+    }
+
+    /// Get the innermost try block guarding the instructions of the block.
+    pub fn guard(&self) -> Option<usize> {
+        self.guard
+    }
+
+    /// Get the blocks the terminator may continue at.
+    pub fn successors(&self) -> Vec<usize> {
+        match *self.terminator.kind() {
+            TerminatorKind::Jump(target, _) => vec![target],
+            TerminatorKind::Branch(_, then_block, else_block) => vec![then_block, else_block],
+            TerminatorKind::Switch(_, _, ref targets, default) =>
+                targets.iter().cloned().chain(Some(default)).collect(),
+            TerminatorKind::Return(_)
+                | TerminatorKind::Throw(_)
+                | TerminatorKind::NoMatch(_)
+                | TerminatorKind::Halt => Vec::new(),
+        }
+    }
+
+    fn renumber(self, blocks: &HashMap<usize, usize>, guards: &HashMap<usize, usize>) -> Block {
+        let kind = match self.terminator.kind {
+            TerminatorKind::Jump(target, arguments) => TerminatorKind::Jump(blocks[&target], arguments),
+            TerminatorKind::Branch(condition, then_block, else_block) =>
+                TerminatorKind::Branch(condition, blocks[&then_block], blocks[&else_block]),
+            TerminatorKind::Switch(value, low, targets, default) => TerminatorKind::Switch(
+                value, low, targets.iter().map(|target| blocks[target]).collect(), blocks[&default]),
+            kind => kind,
+        };
+        let terminator = Terminator::new(kind, self.terminator.span);
+        Block::new(self.parameters, self.instructions, terminator, self.guard.map(|guard| guards[&guard]))
+    }
+}
+
+/// Try block whose handler continues exceptions thrown in the blocks it guards.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Guard {
+    handler: usize,
+    parent: Option<usize>,
+}
+
+impl Guard {
+    pub fn new(handler: usize, parent: Option<usize>) -> Guard {
+        Guard { handler, parent }
+    }
+
+    /// Get the block which starts with the thrown exception.
+    pub fn handler(&self) -> usize {
+        self.handler
+    }
+
+    /// Get the enclosing try block of the same function.
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+}
+
+/// Value computed by an instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Temporary(pub usize);
+
+impl fmt::Display for Temporary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "t{}", self.0)
+    }
+}
+
+fn join(temporaries: &[Temporary]) -> String {
+    temporaries.iter().map(|temporary| temporary.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Literal value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Nil,
+    Integer(i64),
+    Real(f64),
+    Character(char),
+    String(String),
+    Boolean(bool),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Constant::Nil => write!(f, "nil"),
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::Real(value) => write!(f, "{:?}", value),
+            Constant::Character(value) => write!(f, "{:?}", value),
+            Constant::String(ref value) => write!(f, "{:?}", value),
+            Constant::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Storage of a variable.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Place {
+    /// Local slot of the function.
+    Local(u8),
+    /// Upvalue held by the local slot, because the variable is captured by closures.
+    Cell(u8),
+    /// Upvalue of the closure executing the function.
+    Upvalue(u8),
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Place::Local(slot) => write!(f, "x{}", slot),
+            Place::Cell(slot) => write!(f, "*x{}", slot),
+            Place::Upvalue(index) => write!(f, "u{}", index),
+        }
+    }
+}
+
+/// Statically known type of the values of an instruction, which selects the byte code for it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Kind {
+    Integer,
+    String,
+    /// The type is checked at runtime.
+    Any,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Kind::Integer => write!(f, " : int"),
+            Kind::String => write!(f, " : string"),
+            Kind::Any => Ok(()),
+        }
+    }
+}
+
+/// Field of a record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    /// Field with the given index and name of a record with a statically known type.
+    Index(u8, String),
+    /// Field looked up by its name at runtime.
+    Name(String),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Field::Index(_, ref name) => write!(f, ".{}", name),
+            Field::Name(ref name) => write!(f, ".{}?", name),
+        }
+    }
+}
+
+/// Instruction inside of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    kind: InstructionKind,
+    span: Span,
+}
+
+impl Instruction {
+    pub fn new(kind: InstructionKind, span: Span) -> Instruction {
+        Instruction { kind, span }
+    }
+
+    pub fn kind(&self) -> &InstructionKind {
+        &self.kind
+    }
+
+    /// Get the source code the instruction was generated from.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Kinds of instructions, the defined temporary first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionKind {
+    Constant(Temporary, Constant),
+    /// Copy of a temporary which is used in addition to it.
+    Duplicate(Temporary, Temporary),
+    /// Copies of two temporaries which are used in addition to them.
+    DuplicatePair(Temporary, Temporary, Temporary, Temporary),
+    Load(Temporary, Place, Kind),
+    Store(Place, Temporary, Kind),
+    /// Puts a new upvalue holding nil into the local slot.
+    NewCell(u8),
+    Binary(Temporary, BinaryOperator, Temporary, Temporary, Kind),
+    Unary(Temporary, UnaryOperator, Temporary, Kind),
+    /// Call of the closure with the arguments.
+    Call(Temporary, Temporary, Vec<Temporary>),
+    /// Creates a closure of the function with the given index in the program.
+    Closure(Temporary, usize),
+    Array(Temporary, Vec<Temporary>),
+    Length(Temporary, Temporary),
+    GetElement(Temporary, Temporary, Temporary),
+    SetElement(Temporary, Temporary, Temporary),
+    /// Creates a record of the given type with all fields nil.
+    NewRecord(Temporary, String),
+    GetField(Temporary, Temporary, Field),
+    SetField(Temporary, Field, Temporary),
+    IsRecord(Temporary, Temporary, String),
+    /// Joins the printed form of the values into a new string.
+    BuildString(Temporary, Vec<Temporary>),
+    Print(Temporary),
+    PrintLn(Temporary),
+    Discard(Temporary),
+    /// The exception at the start of the handler of a try block.
+    Catch(Temporary),
+}
+
+impl fmt::Display for InstructionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InstructionKind::Constant(target, ref value) => write!(f, "{} = {}", target, value),
+            InstructionKind::Duplicate(target, value) => write!(f, "{} = dup {}", target, value),
+            InstructionKind::DuplicatePair(first, second, left, right) =>
+                write!(f, "{}, {} = dup {}, {}", first, second, left, right),
+            InstructionKind::Load(target, place, kind) => write!(f, "{} = {}{}", target, place, kind),
+            InstructionKind::Store(place, value, kind) => write!(f, "{} = {}{}", place, value, kind),
+            InstructionKind::NewCell(slot) => write!(f, "x{} = cell", slot),
+            InstructionKind::Binary(target, operator, left, right, kind) =>
+                write!(f, "{} = {} {} {}{}", target, left, operator, right, kind),
+            InstructionKind::Unary(target, UnaryOperator::Not, operand, kind) =>
+                write!(f, "{} = not {}{}", target, operand, kind),
+            InstructionKind::Unary(target, operator, operand, kind) =>
+                write!(f, "{} = {}{}{}", target, operator, operand, kind),
+            InstructionKind::Call(target, function, ref arguments) =>
+                write!(f, "{} = {}({})", target, function, join(arguments)),
+            InstructionKind::Closure(target, function) => write!(f, "{} = closure f{}", target, function),
+            InstructionKind::Array(target, ref elements) => write!(f, "{} = [{}]", target, join(elements)),
+            InstructionKind::Length(target, array) => write!(f, "{} = len({})", target, array),
+            InstructionKind::GetElement(target, array, index) => write!(f, "{} = {}[{}]", target, array, index),
+            InstructionKind::SetElement(array, index, value) => write!(f, "{}[{}] = {}", array, index, value),
+            InstructionKind::NewRecord(target, ref record) => write!(f, "{} = new {}", target, record),
+            InstructionKind::GetField(target, record, ref field) => write!(f, "{} = {}{}", target, record, field),
+            InstructionKind::SetField(record, ref field, value) => write!(f, "{}{} = {}", record, field, value),
+            InstructionKind::IsRecord(target, value, ref record) => write!(f, "{} = {} is {}", target, value, record),
+            InstructionKind::BuildString(target, ref parts) => write!(f, "{} = string({})", target, join(parts)),
+            InstructionKind::Print(value) => write!(f, "print {}", value),
+            InstructionKind::PrintLn(value) => write!(f, "println {}", value),
+            InstructionKind::Discard(value) => write!(f, "pop {}", value),
+            InstructionKind::Catch(target) => write!(f, "{} = catch", target),
+        }
+    }
+}
+
+/// Last instruction of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terminator {
+    kind: TerminatorKind,
+    span: Span,
+}
+
+impl Terminator {
+    pub fn new(kind: TerminatorKind, span: Span) -> Terminator {
+        Terminator { kind, span }
+    }
+
+    pub fn kind(&self) -> &TerminatorKind {
+        &self.kind
+    }
+
+    /// Get the source code the terminator was generated from.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Kinds of terminators, which refer to blocks by their index in the function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TerminatorKind {
+    /// Continues at the block, whose parameters get the arguments.
+    Jump(usize, Vec<Temporary>),
+    /// Continues at the first block if the condition is true and at the second otherwise.
+    Branch(Temporary, usize, usize),
+    /// Continues at the target of the integer value in the consecutive targets for the values
+    /// starting at the lowest one, or at the default block.
+    Switch(Temporary, i64, Vec<usize>, usize),
+    Return(Temporary),
+    Throw(Temporary),
+    /// Fails because no arm of a match matches the value.
+    NoMatch(Temporary),
+    /// End of the program.
+    Halt,
+}
+
+impl fmt::Display for TerminatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TerminatorKind::Jump(target, ref arguments) if arguments.is_empty() => write!(f, "jump B{}", target),
+            TerminatorKind::Jump(target, ref arguments) => write!(f, "jump B{}({})", target, join(arguments)),
+            TerminatorKind::Branch(condition, then_block, else_block) =>
+                write!(f, "if {} then B{} else B{}", condition, then_block, else_block),
+            TerminatorKind::Switch(value, low, ref targets, default) => {
+                let targets: Vec<String> = targets.iter().map(|target| format!("B{}", target)).collect();
+                write!(f, "switch {} from {} [{}] else B{}", value, low, targets.join(", "), default)
+            },
+            TerminatorKind::Return(value) => write!(f, "return {}", value),
+            TerminatorKind::Throw(value) => write!(f, "throw {}", value),
+            TerminatorKind::NoMatch(value) => write!(f, "nomatch {}", value),
+            TerminatorKind::Halt => write!(f, "halt"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hamcrest::prelude::*;
+    use test_support::lower;
+
+    #[test]
+    fn remove_unreachable_blocks_and_guards() {
+        let mut program = lower("var b = true\nwhile b {\n  break\n  try { throw 1 } catch e {}\n}\nprintln(b)");
+        program.remove_unreachable_blocks();
+
+        assert_that!(&program.to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\n",
+            "B0:\n    t0 = true\n    x0 = t0\n    jump B1\n",
+            "B1:\n    t1 = x0\n    if t1 then B2 else B3\n",
+            "B2:\n    jump B3\n",
+            "B3:\n    t4 = x0\n    println t4\n    halt\n"))));
+        assert_that!(program.functions()[0].guards().is_empty(), is(true));
+    }
+
+    #[test]
+    fn keep_handlers_of_reachable_blocks() {
+        let mut program = lower("try {\n  throw 1\n} catch e {}\nfun f() {\n  return 1\n}");
+        program.remove_unreachable_blocks();
+
+        assert_that!(&program.to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\n",
+            "B0 [catch B1]:\n    t0 = 1\n    throw t0\n",
+            "B1:\n    t1 = catch\n    x0 = t1\n    jump B2\n",
+            "B2:\n    t2 = closure f1\n    x1 = t2\n    halt\n",
+            "\n",
+            "f1 f/0:\n",
+            "B0:\n    t0 = 1\n    return t0\n"))));
+        let guards = program.functions()[0].guards_of(&program.functions()[0].blocks()[0]);
+        assert_that!(guards, is(equal_to(vec![0])));
+    }
+}
@@ -0,0 +1,272 @@
+use frontend::module::Module;
+use frontend::{Span, SyntaxError};
+use intermediate::ast::*;
+use intermediate::semantic::Builtin;
+
+/// Links modules in dependency order into one program.
+///
+/// The statements of all modules are concatenated, so the code of each module runs once before
+/// the code of the modules importing it. The declarations of a module live in its own namespace:
+/// Variables, constants, functions and records of all but the last (main) module are renamed to
+/// their name qualified by the module name, e.g. `Point` of the module `geometry` becomes
+/// `geometry.Point`.
+/// In turn the accesses `geometry.Point` of importing modules are resolved to this name. The
+/// imports themselves are removed. Only imports at top level are loaded, imports in a function or
+/// block are rejected.
+pub fn link(modules: &[Module]) -> Result<Vec<Statement>, SyntaxError> {
+    let mut statements = Vec::new();
+
+    for (index, module) in modules.iter().enumerate() {
+        let prefix = if index + 1 < modules.len() { Some(module.name()) } else { None };
+        let linker = Linker { prefix, imports: module.imports() };
+
+        for statement in module.statements() {
+            if let StatementKind::Import(_) = *statement.kind() {
+                continue;
+            }
+
+            statements.push(linker.link_statement(statement)?);
+        }
+    }
+
+    Ok(statements)
+}
+
+struct Linker<'a> {
+    prefix: Option<&'a str>,
+    imports: &'a [String],
+}
+
+impl<'a> Linker<'a> {
+    fn link_statement(&self, statement: &Statement) -> Result<Statement, SyntaxError> {
+        let kind = match *statement.kind() {
+            StatementKind::Assignment(ref identifier, ref value) =>
+                StatementKind::Assignment(self.declaration(identifier)?, self.link_expression(value)?),
+            StatementKind::IndexAssignment(ref array, ref index, operator, ref value) => StatementKind::IndexAssignment(
+                self.link_expression(array)?, self.link_expression(index)?, operator, self.link_expression(value)?),
+            StatementKind::FieldAssignment(ref record, ref field, operator, ref value) => match self.module_member(record, field) {
+                // Like for variables a compound assignment to a member is desugared into a binary operation.
+                Some(member) => {
+                    let value = self.link_expression(value)?;
+                    let value = match operator {
+                        Some(operator) => {
+                            let span = member.span().to(value.span());
+                            let target = Expression::new(ExpressionKind::Identifier(member.clone()), member.span());
+                            Expression::new(ExpressionKind::BinaryOperation(operator, Box::new(target), Box::new(value)), span)
+                        },
+                        None => value,
+                    };
+                    StatementKind::Assignment(member, value)
+                },
+                None => StatementKind::FieldAssignment(
+                    self.link_expression(record)?, field.clone(), operator, self.link_expression(value)?),
+            },
+            StatementKind::Constant(ref identifier, ref value) =>
+                StatementKind::Constant(self.declaration(identifier)?, self.link_expression(value)?),
+            StatementKind::Variable(ref identifier, ref value) =>
+                StatementKind::Variable(self.declaration(identifier)?, self.link_expression(value)?),
+            StatementKind::Record(ref identifier, ref fields) =>
+                StatementKind::Record(self.qualify(identifier), fields.clone()),
+            StatementKind::Import(_) =>
+                return Err(SyntaxError::new(String::from("Import must be at top level!"), statement.span())),
+            StatementKind::Function(ref identifier, ref parameters, ref body) => {
+                let (parameters, body) = self.link_function(parameters, body)?;
+                StatementKind::Function(self.declaration(identifier)?, parameters, body)
+            },
+            StatementKind::Return(ref value) => StatementKind::Return(self.link_expression(value)?),
+            StatementKind::Try(ref body, ref identifier, ref handler) => StatementKind::Try(
+                self.link_statements(body)?, self.declaration(identifier)?, self.link_statements(handler)?),
+            StatementKind::Throw(ref value) => StatementKind::Throw(self.link_expression(value)?),
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => StatementKind::If(
+                self.link_expression(condition)?, self.link_statements(then_branch)?, self.link_statements(else_branch)?),
+            StatementKind::While(ref condition, ref body) =>
+                StatementKind::While(self.link_expression(condition)?, self.link_statements(body)?),
+            StatementKind::ForRange(ref identifier, ref start, ref end, ref body) => StatementKind::ForRange(
+                self.declaration(identifier)?, self.link_expression(start)?, self.link_expression(end)?,
+                self.link_statements(body)?),
+            StatementKind::ForEach(ref identifier, ref array, ref body) => StatementKind::ForEach(
+                self.declaration(identifier)?, self.link_expression(array)?, self.link_statements(body)?),
+            StatementKind::Break => StatementKind::Break,
+            StatementKind::Continue => StatementKind::Continue,
+            StatementKind::Expression(ref expression) => StatementKind::Expression(self.link_expression(expression)?),
+        };
+
+        Ok(Statement::new(kind, statement.span()))
+    }
+
+    fn link_expression(&self, expression: &Expression) -> Result<Expression, SyntaxError> {
+        let kind = match *expression.kind() {
+            ExpressionKind::InterpolatedString(ref parts) =>
+                ExpressionKind::InterpolatedString(self.link_expressions(parts)?),
+            ExpressionKind::Identifier(ref identifier) => ExpressionKind::Identifier(self.reference(identifier)?),
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) => {
+                let identifier = match Builtin::for_name(identifier.name()) {
+                    Some(_) => identifier.clone(),
+                    None => self.reference(identifier)?,
+                };
+                ExpressionKind::FunctionCall(identifier, self.link_expressions(arguments)?)
+            },
+            ExpressionKind::Call(ref function, ref arguments) =>
+                ExpressionKind::Call(Box::new(self.link_expression(function)?), self.link_expressions(arguments)?),
+            ExpressionKind::Function(ref parameters, ref body) => {
+                let (parameters, body) = self.link_function(parameters, body)?;
+                ExpressionKind::Function(parameters, body)
+            },
+            ExpressionKind::Array(ref elements) => ExpressionKind::Array(self.link_expressions(elements)?),
+            ExpressionKind::Index(ref array, ref index) => ExpressionKind::Index(
+                Box::new(self.link_expression(array)?), Box::new(self.link_expression(index)?)),
+            ExpressionKind::RecordLiteral(ref identifier, ref fields) => {
+                let mut linked = Vec::with_capacity(fields.len());
+
+                for (field, value) in fields {
+                    linked.push((field.clone(), self.link_expression(value)?));
+                }
+
+                ExpressionKind::RecordLiteral(self.record_name(identifier)?, linked)
+            },
+            ExpressionKind::Field(ref record, ref field) => match self.module_member(record, field) {
+                Some(member) => ExpressionKind::Identifier(member),
+                None => ExpressionKind::Field(Box::new(self.link_expression(record)?), field.clone()),
+            },
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) => ExpressionKind::BinaryOperation(
+                operator, Box::new(self.link_expression(left)?), Box::new(self.link_expression(right)?)),
+            ExpressionKind::UnaryOperation(operator, ref operand) =>
+                ExpressionKind::UnaryOperation(operator, Box::new(self.link_expression(operand)?)),
+            ExpressionKind::Match(ref value, ref arms) => {
+                let mut linked = Vec::with_capacity(arms.len());
+
+                for (pattern, arm) in arms {
+                    linked.push((self.link_pattern(pattern)?, self.link_expression(arm)?));
+                }
+
+                ExpressionKind::Match(Box::new(self.link_expression(value)?), linked)
+            },
+            _ => expression.kind().clone(),
+        };
+
+        Ok(Expression::new(kind, expression.span()))
+    }
+
+    fn link_pattern(&self, pattern: &Pattern) -> Result<Pattern, SyntaxError> {
+        let kind = match *pattern.kind() {
+            PatternKind::Binding(ref identifier) => PatternKind::Binding(self.declaration(identifier)?),
+            PatternKind::Record(ref identifier, ref fields) => {
+                let mut linked = Vec::with_capacity(fields.len());
+
+                for (field, pattern) in fields {
+                    linked.push((field.clone(), self.link_pattern(pattern)?));
+                }
+
+                PatternKind::Record(self.record_name(identifier)?, linked)
+            },
+            _ => pattern.kind().clone(),
+        };
+
+        Ok(Pattern::new(kind, pattern.span()))
+    }
+
+    fn link_expressions(&self, expressions: &[Expression]) -> Result<Vec<Expression>, SyntaxError> {
+        expressions.iter().map(|e| self.link_expression(e)).collect()
+    }
+
+    fn link_function(&self, parameters: &[Identifier], body: &[Statement])
+        -> Result<(Vec<Identifier>, Vec<Statement>), SyntaxError> {
+        let parameters = parameters.iter()
+            .map(|parameter| self.declaration(parameter))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((parameters, self.link_statements(body)?))
+    }
+
+    fn link_statements(&self, statements: &[Statement]) -> Result<Vec<Statement>, SyntaxError> {
+        statements.iter().map(|s| self.link_statement(s)).collect()
+    }
+
+    fn reference(&self, identifier: &Identifier) -> Result<Identifier, SyntaxError> {
+        if self.is_module(identifier.name()) {
+            return Err(SyntaxError::new(
+                format!("Module '{}' can not be used as value!", identifier.name()), identifier.span()));
+        }
+
+        Ok(self.qualify(identifier))
+    }
+
+    // Declared names must not hide an imported module.
+    fn declaration(&self, identifier: &Identifier) -> Result<Identifier, SyntaxError> {
+        if self.is_module(identifier.name()) {
+            return Err(SyntaxError::new(
+                format!("Variable '{}' hides the imported module!", identifier.name()), identifier.span()));
+        }
+
+        Ok(self.qualify(identifier))
+    }
+
+    // Resolves the access `module.member` of a declaration of an imported module.
+    fn module_member(&self, record: &Expression, member: &Identifier) -> Option<Identifier> {
+        match *record.kind() {
+            ExpressionKind::Identifier(ref module) if self.is_module(module.name()) =>
+                Some(qualified(module.name(), member.name(), module.span().to(member.span()))),
+            _ => None,
+        }
+    }
+
+    // The parser joins the qualified name of a record of another module like `geometry.Point`.
+    fn record_name(&self, identifier: &Identifier) -> Result<Identifier, SyntaxError> {
+        match identifier.name().find('.') {
+            Some(end) if self.is_module(&identifier.name()[..end]) => Ok(identifier.clone()),
+            Some(end) => Err(SyntaxError::new(
+                format!("Unknown module '{}'!", &identifier.name()[..end]), identifier.span())),
+            None => Ok(self.qualify(identifier)),
+        }
+    }
+
+    fn qualify(&self, identifier: &Identifier) -> Identifier {
+        match self.prefix {
+            Some(prefix) => qualified(prefix, identifier.name(), identifier.span()),
+            None => identifier.clone(),
+        }
+    }
+
+    fn is_module(&self, name: &str) -> bool {
+        self.imports.iter().any(|import| import == name)
+    }
+}
+
+fn qualified(module: &str, name: &str, span: Span) -> Identifier {
+    Identifier::new(format!("{}.{}", module, name), span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use std::path::Path;
+    use frontend::module::ModuleLoader;
+    use intermediate::printer::{print, Format};
+
+    #[test]
+    fn link_qualifies_names_of_imported_modules() {
+        let modules = ModuleLoader::new().load(Path::new("test/modules/main.mvm")).unwrap();
+
+        let statements = link(&modules).unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+r#"(var util.start 41)
+(record geometry.Point x y)
+(var geometry.count util.start)
+(const geometry.origin (new geometry.Point (x 0) (y 0)))
+(var p (new geometry.Point (x 1) (y 2)))
+(= geometry.count (+ geometry.count 1))
+(call println (interpolate p " " geometry.count " " (field geometry.origin x)))
+"#)));
+    }
+
+    #[test]
+    fn link_rejects_nested_import() {
+        let modules = ModuleLoader::new().load(Path::new("test/modules/nested_import.mvm")).unwrap();
+
+        let error = link(&modules).unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Import must be at top level!")));
+        assert_that!(error.span().start(), is(equal_to(::frontend::Position::new(3, 5))));
+    }
+}
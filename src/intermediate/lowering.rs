@@ -0,0 +1,1191 @@
+use std::collections::HashMap;
+
+use backend::image::{Capture, RecordType};
+use frontend::{Span, SyntaxError};
+use intermediate::ast::*;
+use intermediate::ir::{self, Block, Constant, Field, Guard, InstructionKind, Kind, Place, Temporary, TerminatorKind};
+use intermediate::semantic::{Analysis, Builtin, Type, Variable};
+
+/// Minimal number of integer patterns of a match compiled to a jump table.
+const MIN_JUMP_TABLE_ARMS: usize = 3;
+
+/// Translates the analysed program into the intermediate representation.
+///
+/// Each variable gets its own local slot in the function declaring it. Variables captured by
+/// closures live in upvalues. Fields of records with a statically known type are addressed by
+/// index, all others by name.
+///
+/// The blocks are laid out in the order the byte code is generated in: Code following a `return`,
+/// `throw`, `break` or `continue` is kept in a block of its own, which is never entered. Each
+/// function declared in a try block is not guarded by it. Loops keep their state in local slots
+/// of their own and declare the loop variable anew in each iteration, so closures capture the
+/// value of their iteration. A `match` tests the patterns one after another, unless all are dense
+/// integers which select the arm by a switch.
+///
+/// Returns an error if the program exceeds the limits of the byte code.
+pub fn lower(statements: &[Statement], analysis: &Analysis) -> Result<ir::Program, SyntaxError> {
+    let mut lowering = Lowering {
+        analysis,
+        records: Vec::new(),
+        functions: vec![None],
+        scopes: vec![FunctionScope::new(Vec::new())],
+    };
+
+    lowering.statements(statements)?;
+    lowering.terminate(TerminatorKind::Halt, Span::null());
+    let scope = lowering.scopes.pop().expect("Top level scope!");
+    lowering.functions[0] = Some(scope.finish(String::new(), 0, Vec::new()));
+    let functions = lowering.functions.into_iter().map(|function| function.expect("Lowered function!")).collect();
+    Ok(ir::Program::new(lowering.records, functions))
+}
+
+struct Lowering<'a> {
+    analysis: &'a Analysis,
+    records: Vec<RecordType>,
+    // Functions by index, which are added once their body is lowered.
+    functions: Vec<Option<ir::Function>>,
+    scopes: Vec<FunctionScope>,
+}
+
+/// Variables and code of the function being lowered.
+struct FunctionScope {
+    // Local slots by variable id.
+    slots: HashMap<usize, u8>,
+    // Ids of the captured variables in the order of the upvalues of the closure.
+    upvalues: Vec<usize>,
+    // Number of local slots holding the state of loops instead of variables.
+    temporaries: usize,
+    // The enclosing loops of the function, the innermost last.
+    loops: Vec<Loop>,
+    blocks: Vec<PartialBlock>,
+    // Started blocks in the order of the code.
+    layout: Vec<usize>,
+    // The block instructions are added to, if any. A new one is started by the next instruction.
+    current: Option<usize>,
+    guards: Vec<Guard>,
+    // The innermost try block around the current code.
+    guard: Option<usize>,
+    next_temporary: usize,
+}
+
+/// Block of the function being lowered, which is terminated later.
+struct PartialBlock {
+    parameters: Vec<Temporary>,
+    instructions: Vec<ir::Instruction>,
+    terminator: Option<ir::Terminator>,
+    guard: Option<usize>,
+}
+
+/// Blocks where `continue` and `break` of a loop continue.
+struct Loop {
+    next: usize,
+    exit: usize,
+}
+
+/// Where a variable is accessed from the function being lowered.
+enum Access {
+    /// Local slot, which holds the upvalue if the variable is captured.
+    Local(u8, bool),
+    /// Upvalue of the closure with the given index.
+    Upvalue(u8),
+}
+
+impl FunctionScope {
+    fn new(upvalues: Vec<usize>) -> FunctionScope {
+        FunctionScope {
+            slots: HashMap::new(),
+            upvalues,
+            temporaries: 0,
+            loops: Vec::new(),
+            blocks: Vec::new(),
+            layout: Vec::new(),
+            current: None,
+            guards: Vec::new(),
+            guard: None,
+            next_temporary: 0,
+        }
+    }
+
+    fn temporary(&mut self) -> Temporary {
+        self.next_temporary += 1;
+        Temporary(self.next_temporary - 1)
+    }
+
+    // Creates a block which is started later.
+    fn block(&mut self) -> usize {
+        self.blocks.push(PartialBlock { parameters: Vec::new(), instructions: Vec::new(), terminator: None, guard: None });
+        self.blocks.len() - 1
+    }
+
+    // Continues the code in the block, which the current block falls through to.
+    fn start(&mut self, block: usize, span: Span) {
+        if self.current.is_some() {
+            self.terminate(TerminatorKind::Jump(block, Vec::new()), span);
+        }
+
+        self.blocks[block].guard = self.guard;
+        self.layout.push(block);
+        self.current = Some(block);
+    }
+
+    fn emit(&mut self, kind: InstructionKind, span: Span) {
+        let block = self.current_block();
+        self.blocks[block].instructions.push(ir::Instruction::new(kind, span));
+    }
+
+    fn terminate(&mut self, kind: TerminatorKind, span: Span) {
+        let block = self.current_block();
+        self.blocks[block].terminator = Some(ir::Terminator::new(kind, span));
+        self.current = None;
+    }
+
+    // Get the block instructions are added to and starts a new one if there is none.
+    fn current_block(&mut self) -> usize {
+        match self.current {
+            Some(block) => block,
+            None => {
+                let block = self.block();
+                self.blocks[block].guard = self.guard;
+                self.layout.push(block);
+                self.current = Some(block);
+                block
+            },
+        }
+    }
+
+    // Lets the following code be guarded by the given try block, which starts a new block unless
+    // the current one is still empty.
+    fn guard_with(&mut self, guard: Option<usize>, span: Span) {
+        match self.current {
+            Some(block) if self.blocks[block].instructions.is_empty() => self.blocks[block].guard = guard,
+            Some(_) => {
+                let block = self.block();
+                self.guard = guard;
+                self.start(block, span);
+            },
+            None => (),
+        }
+
+        self.guard = guard;
+    }
+
+    // Numbers the blocks in the order of the code.
+    fn finish(self, name: String, arity: u8, captures: Vec<Capture>) -> ir::Function {
+        let numbers: HashMap<usize, usize> = self.layout.iter().enumerate().map(|(number, &block)| (block, number)).collect();
+        let mut blocks: Vec<Option<PartialBlock>> = self.blocks.into_iter().map(Some).collect();
+        let blocks = self.layout.iter()
+            .map(|&block| {
+                let block = blocks[block].take().expect("Blocks are started once!");
+                let terminator = block.terminator.expect("Blocks are terminated!");
+                let kind = match terminator.kind().clone() {
+                    TerminatorKind::Jump(target, arguments) => TerminatorKind::Jump(numbers[&target], arguments),
+                    TerminatorKind::Branch(condition, then_block, else_block) =>
+                        TerminatorKind::Branch(condition, numbers[&then_block], numbers[&else_block]),
+                    TerminatorKind::Switch(value, low, targets, default) => TerminatorKind::Switch(
+                        value, low, targets.iter().map(|target| numbers[target]).collect(), numbers[&default]),
+                    kind => kind,
+                };
+                let terminator = ir::Terminator::new(kind, terminator.span());
+                Block::new(block.parameters, block.instructions, terminator, block.guard)
+            })
+            .collect();
+        let guards = self.guards.iter().map(|guard| Guard::new(numbers[&guard.handler()], guard.parent())).collect();
+        ir::Function::new(name, arity, captures, blocks, guards)
+    }
+}
+
+impl<'a> Lowering<'a> {
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), SyntaxError> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), SyntaxError> {
+        let span = statement.span();
+
+        match *statement.kind() {
+            StatementKind::Variable(ref identifier, ref value)
+                | StatementKind::Constant(ref identifier, ref value)
+                | StatementKind::Assignment(ref identifier, ref value) => {
+                self.declare(identifier)?;
+                let value = self.expression(value)?;
+                self.store(identifier, value, span)?;
+            },
+            StatementKind::IndexAssignment(ref array, ref index, operator, ref value) => {
+                let array_value = self.expression(array)?;
+                let index_value = self.expression(index)?;
+
+                let value = match operator {
+                    // Compound assignments load the element from copies of the array and index.
+                    Some(operator) => {
+                        let (array_copy, index_copy) = (self.temporary(), self.temporary());
+                        self.emit(InstructionKind::DuplicatePair(array_copy, index_copy, array_value, index_value), span);
+                        let element = self.temporary();
+                        self.emit(InstructionKind::GetElement(element, array_copy, index_copy), span);
+                        let right_value = self.expression(value)?;
+                        let analysis = self.analysis;
+                        let element_type = match *analysis.type_of(array) {
+                            Type::Array(ref element_type) => element_type,
+                            _ => &Type::Any,
+                        };
+                        self.binary(operator, element_type, analysis.type_of(value), element, right_value, span)?
+                    },
+                    None => self.expression(value)?,
+                };
+
+                self.emit(InstructionKind::SetElement(array_value, index_value, value), span);
+            },
+            StatementKind::FieldAssignment(ref record, ref field, operator, ref value) => {
+                let record_value = self.expression(record)?;
+                let record_field = self.field(self.analysis.type_of(record), field);
+
+                let value = match operator {
+                    // Compound assignments load the field from a copy of the record.
+                    Some(operator) => {
+                        let copy = self.temporary();
+                        self.emit(InstructionKind::Duplicate(copy, record_value), span);
+                        let current = self.temporary();
+                        self.emit(InstructionKind::GetField(current, copy, record_field.clone()), span);
+                        let right_value = self.expression(value)?;
+                        let analysis = self.analysis;
+                        self.binary(operator, &Type::Any, analysis.type_of(value), current, right_value, span)?
+                    },
+                    None => self.expression(value)?,
+                };
+
+                self.emit(InstructionKind::SetField(record_value, record_field, value), span);
+            },
+            StatementKind::Record(ref identifier, ref fields) => {
+                if fields.len() > usize::from(u8::MAX) + 1 {
+                    return Err(SyntaxError::new(String::from("Too many fields!"), identifier.span()));
+                }
+
+                if self.records.len() > usize::from(u16::MAX) {
+                    return Err(SyntaxError::new(String::from("Too many records!"), identifier.span()));
+                }
+
+                let fields = fields.iter().map(|field| field.name().to_string()).collect();
+                self.records.push(RecordType::new(identifier.name().to_string(), fields));
+            },
+            StatementKind::Import(_) => unreachable!("Imports are rejected by the type checker!"),
+            StatementKind::Function(ref identifier, ref parameters, ref body) => {
+                // Declared before the closure is created, so the function may capture itself.
+                self.declare(identifier)?;
+                let closure = self.function(identifier.name(), parameters, body, span)?;
+                self.store(identifier, closure, span)?;
+            },
+            StatementKind::Return(ref value) => {
+                let value = self.expression(value)?;
+                self.terminate(TerminatorKind::Return(value), span);
+            },
+            StatementKind::Try(ref body, ref identifier, ref handler) => self.try_catch(body, identifier, handler, span)?,
+            StatementKind::Throw(ref value) => {
+                let value = self.expression(value)?;
+                self.terminate(TerminatorKind::Throw(value), span);
+            },
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) =>
+                self.if_else(condition, then_branch, else_branch, span)?,
+            StatementKind::While(ref condition, ref body) => self.while_loop(condition, body, span)?,
+            StatementKind::ForRange(ref identifier, ref start, ref end, ref body) =>
+                self.for_range(identifier, start, end, body, span)?,
+            StatementKind::ForEach(ref identifier, ref array, ref body) => self.for_each(identifier, array, body, span)?,
+            StatementKind::Break => {
+                let exit = self.current_loop().exit;
+                self.terminate(TerminatorKind::Jump(exit, Vec::new()), span);
+            },
+            StatementKind::Continue => {
+                let next = self.current_loop().next;
+                self.terminate(TerminatorKind::Jump(next, Vec::new()), span);
+            },
+            StatementKind::Expression(ref expression) => match *expression.kind() {
+                // Printing leaves no value which must be discarded.
+                ExpressionKind::FunctionCall(ref identifier, ref arguments)
+                    if self.analysis.variable(identifier).is_none()
+                        && Builtin::for_name(identifier.name()) != Some(Builtin::Length) => {
+                    self.print(identifier, &arguments[0])?;
+                },
+                _ => {
+                    let value = self.expression(expression)?;
+                    self.emit(InstructionKind::Discard(value), expression.span());
+                },
+            },
+        }
+
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result<Temporary, SyntaxError> {
+        let span = expression.span();
+
+        let kind = match *expression.kind() {
+            ExpressionKind::Nil => self.constant(Constant::Nil),
+            ExpressionKind::Integer(value) => self.constant(Constant::Integer(value)),
+            ExpressionKind::Real(value) => self.constant(Constant::Real(value)),
+            ExpressionKind::Character(value) => self.constant(Constant::Character(value)),
+            ExpressionKind::String(ref value) => self.constant(Constant::String(value.clone())),
+            ExpressionKind::Boolean(value) => self.constant(Constant::Boolean(value)),
+            ExpressionKind::InterpolatedString(ref parts) => {
+                if parts.len() > usize::from(u16::MAX) {
+                    return Err(SyntaxError::new(String::from("Too many interpolated parts!"), span));
+                }
+
+                let parts = self.expressions(parts)?;
+                InstructionKind::BuildString(self.temporary(), parts)
+            },
+            ExpressionKind::Identifier(ref identifier) => return self.load(identifier, span),
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) if self.analysis.variable(identifier).is_some() => {
+                let function = self.load(identifier, identifier.span())?;
+                return self.call(function, arguments, span);
+            },
+            ExpressionKind::Call(ref function, ref arguments) => {
+                let function = self.expression(function)?;
+                return self.call(function, arguments, span);
+            },
+            ExpressionKind::Function(ref parameters, ref body) => return self.function("", parameters, body, span),
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) => {
+                if Some(Builtin::Length) == Builtin::for_name(identifier.name()) {
+                    let array = self.expression(&arguments[0])?;
+                    InstructionKind::Length(self.temporary(), array)
+                } else {
+                    self.print(identifier, &arguments[0])?;
+                    self.constant(Constant::Nil)
+                }
+            },
+            ExpressionKind::Array(ref elements) => {
+                if elements.len() > usize::from(u16::MAX) {
+                    return Err(SyntaxError::new(String::from("Too many array elements!"), span));
+                }
+
+                let elements = self.expressions(elements)?;
+                InstructionKind::Array(self.temporary(), elements)
+            },
+            ExpressionKind::Index(ref array, ref index) => {
+                let array = self.expression(array)?;
+                let index = self.expression(index)?;
+                InstructionKind::GetElement(self.temporary(), array, index)
+            },
+            // The fields are set in source order on the new record.
+            ExpressionKind::RecordLiteral(ref identifier, ref fields) => {
+                let record = self.temporary();
+                self.emit(InstructionKind::NewRecord(record, identifier.name().to_string()), span);
+                let record_type = Type::Record(identifier.name().to_string());
+
+                for (field, value) in fields {
+                    let copy = self.temporary();
+                    self.emit(InstructionKind::Duplicate(copy, record), field.span());
+                    let value = self.expression(value)?;
+                    let field_access = self.field(&record_type, field);
+                    self.emit(InstructionKind::SetField(copy, field_access, value), field.span());
+                }
+
+                return Ok(record);
+            },
+            ExpressionKind::Field(ref record, ref field) => {
+                let record_value = self.expression(record)?;
+                let field = self.field(self.analysis.type_of(record), field);
+                InstructionKind::GetField(self.temporary(), record_value, field)
+            },
+            ExpressionKind::BinaryOperation(operator @ BinaryOperator::And, ref left, ref right)
+                | ExpressionKind::BinaryOperation(operator @ BinaryOperator::Or, ref left, ref right) =>
+                return self.logical_operation(operator, left, right, span),
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) => {
+                let left_value = self.expression(left)?;
+                let right_value = self.expression(right)?;
+                let analysis = self.analysis;
+                return self.binary(operator, analysis.type_of(left), analysis.type_of(right), left_value, right_value, span);
+            },
+            ExpressionKind::UnaryOperation(operator, ref operand) => {
+                let value = self.expression(operand)?;
+
+                match operator {
+                    UnaryOperator::Plus => return Ok(value),
+                    UnaryOperator::Negate => {
+                        let kind = if Type::Integer == *self.analysis.type_of(operand) { Kind::Integer } else { Kind::Any };
+                        InstructionKind::Unary(self.temporary(), operator, value, kind)
+                    },
+                    UnaryOperator::Not => InstructionKind::Unary(self.temporary(), operator, value, Kind::Any),
+                }
+            },
+            ExpressionKind::Match(ref value, ref arms) => return self.match_arms(value, arms, span),
+        };
+
+        let target = defined(&kind);
+        self.emit(kind, span);
+        Ok(target)
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) -> Result<Vec<Temporary>, SyntaxError> {
+        expressions.iter().map(|expression| self.expression(expression)).collect()
+    }
+
+    fn constant(&mut self, value: Constant) -> InstructionKind {
+        InstructionKind::Constant(self.temporary(), value)
+    }
+
+    fn binary(&mut self, operator: BinaryOperator, left_type: &Type, right_type: &Type, left_value: Temporary,
+              right_value: Temporary, span: Span) -> Result<Temporary, SyntaxError> {
+        let kind = match (left_type, right_type) {
+            (&Type::Integer, &Type::Integer) => Kind::Integer,
+            (&Type::String, &Type::String) => Kind::String,
+            _ => Kind::Any,
+        };
+
+        if let BinaryOperator::Assign | BinaryOperator::And | BinaryOperator::Or = operator {
+            return Err(unsupported(&format!("Operator '{}'", operator), span));
+        }
+
+        let target = self.temporary();
+        self.emit(InstructionKind::Binary(target, operator, left_value, right_value, kind), span);
+        Ok(target)
+    }
+
+    // Tests the value of the match on each pattern in turn. The value is discarded before the arm
+    // whose pattern matches:
+    //
+    //     t0 = <value>
+    //     <tests of pattern 1>      each test: t1 = dup t0, <fields>, <test>, if ... else NEXT
+    //     pop t0
+    //     t2 = <arm 1>
+    //     jump END(t2)
+    // NEXT:
+    //     ...
+    //     nomatch t0
+    // END(t3):
+    //
+    // Variables bound by a pattern are stored before its arm.
+    fn match_arms(&mut self, value: &Expression, arms: &[(Pattern, Expression)], span: Span)
+        -> Result<Temporary, SyntaxError> {
+        let value = self.expression(value)?;
+
+        if let Some((low, high)) = dense_integers(arms) {
+            return self.switch(value, arms, low, high, span);
+        }
+
+        let end = self.block();
+        let result = self.temporary();
+        self.scope().blocks[end].parameters.push(result);
+
+        for (pattern, arm) in arms {
+            let next = self.block();
+            self.pattern(pattern, value, &mut Vec::new(), next)?;
+            self.emit(InstructionKind::Discard(value), arm.span());
+            let arm_value = self.expression(arm)?;
+            self.terminate(TerminatorKind::Jump(end, vec![arm_value]), span);
+            self.start(next, span);
+        }
+
+        self.terminate(TerminatorKind::NoMatch(value), span);
+        self.start(end, span);
+        Ok(result)
+    }
+
+    // Selects the arm of the integer by a switch, whose default continues at the final
+    // irrefutable arm if there is one:
+    //
+    //     t0 = <value>
+    //     t1 = dup t0
+    //     switch t1 from LOW [ARM, ...] else DEFAULT
+    // ARM:
+    //     pop t0
+    //     t2 = <arm>
+    //     jump END(t2)
+    //     ...
+    // DEFAULT:
+    //     <binding of the irrefutable pattern>
+    //     pop t0
+    //     t3 = <irrefutable arm>    or    nomatch t0
+    //     jump END(t3)
+    // END(t4):
+    fn switch(&mut self, value: Temporary, arms: &[(Pattern, Expression)], low: i64, high: i64, span: Span)
+        -> Result<Temporary, SyntaxError> {
+        let copy = self.temporary();
+        self.emit(InstructionKind::Duplicate(copy, value), span);
+        let mut targets: Vec<Option<usize>> = vec![None; (high - low) as usize + 1];
+        let mut arm_blocks = Vec::with_capacity(arms.len());
+
+        for (pattern, arm) in arms {
+            let value = match *pattern.kind() {
+                PatternKind::Integer(value) => value,
+                _ => break,
+            };
+
+            // Duplicates are unreachable like for the tests of the patterns.
+            if targets[(value - low) as usize].is_none() {
+                let block = self.block();
+                targets[(value - low) as usize] = Some(block);
+                arm_blocks.push((block, arm));
+            }
+        }
+
+        let default = self.block();
+        let end = self.block();
+        let result = self.temporary();
+        self.scope().blocks[end].parameters.push(result);
+        let targets = targets.into_iter().map(|target| target.unwrap_or(default)).collect();
+        self.terminate(TerminatorKind::Switch(copy, low, targets, default), span);
+
+        for (block, arm) in arm_blocks {
+            self.start(block, span);
+            self.emit(InstructionKind::Discard(value), arm.span());
+            let arm_value = self.expression(arm)?;
+            self.terminate(TerminatorKind::Jump(end, vec![arm_value]), span);
+        }
+
+        self.start(default, span);
+
+        match arms.last() {
+            Some((pattern, arm)) if pattern.is_irrefutable() => {
+                self.pattern(pattern, value, &mut Vec::new(), default)?;
+                self.emit(InstructionKind::Discard(value), arm.span());
+                let arm_value = self.expression(arm)?;
+                self.terminate(TerminatorKind::Jump(end, vec![arm_value]), span);
+            },
+            _ => self.terminate(TerminatorKind::NoMatch(value), span),
+        }
+
+        self.start(end, span);
+        Ok(result)
+    }
+
+    // Tests the part of the value at the path of fields against the pattern. Failed tests continue
+    // at the next block.
+    fn pattern(&mut self, pattern: &Pattern, value: Temporary, path: &mut Vec<Field>, next: usize)
+        -> Result<(), SyntaxError> {
+        let span = pattern.span();
+
+        let expected = match *pattern.kind() {
+            PatternKind::Wildcard => return Ok(()),
+            PatternKind::Binding(ref identifier) => {
+                self.declare(identifier)?;
+                let part = self.path(value, path, span);
+                return self.store(identifier, part, span);
+            },
+            PatternKind::Integer(expected) => Constant::Integer(expected),
+            PatternKind::String(ref expected) => Constant::String(expected.clone()),
+            PatternKind::Boolean(expected) => Constant::Boolean(expected),
+            // The fields are accessed by index once the type of the record is tested.
+            PatternKind::Record(ref identifier, ref fields) => {
+                let record = self.records.iter()
+                    .find(|record| record.name() == identifier.name())
+                    .cloned()
+                    .expect("Record types are checked!");
+                let part = self.path(value, path, span);
+                let test = self.temporary();
+                self.emit(InstructionKind::IsRecord(test, part, identifier.name().to_string()), span);
+                self.test(test, next, span);
+
+                for (field, pattern) in fields {
+                    let index = record.field_index(field.name()).expect("Fields are checked!");
+                    path.push(Field::Index(index as u8, field.name().to_string()));
+                    self.pattern(pattern, value, path, next)?;
+                    path.pop();
+                }
+
+                return Ok(());
+            },
+        };
+
+        let part = self.path(value, path, span);
+        let kind = self.constant(expected);
+        let expected = defined(&kind);
+        self.emit(kind, span);
+        let test = self.temporary();
+        self.emit(InstructionKind::Binary(test, BinaryOperator::Equal, part, expected, Kind::Any), span);
+        self.test(test, next, span);
+        Ok(())
+    }
+
+    // Get a copy of the value, or of its field at the path.
+    fn path(&mut self, value: Temporary, path: &[Field], span: Span) -> Temporary {
+        let mut part = self.temporary();
+        self.emit(InstructionKind::Duplicate(part, value), span);
+
+        for field in path {
+            let record = part;
+            part = self.temporary();
+            self.emit(InstructionKind::GetField(part, record, field.clone()), span);
+        }
+
+        part
+    }
+
+    // Continues in a new block if the test succeeded, at the next block otherwise.
+    fn test(&mut self, test: Temporary, next: usize, span: Span) {
+        let matched = self.block();
+        self.terminate(TerminatorKind::Branch(test, matched, next), span);
+        self.start(matched, span);
+    }
+
+    // The right operand is only evaluated if the left one does not determine the result already:
+    //
+    //     t0 = <left>                   t0 = <left>
+    //     if t0 then RIGHT else FALSE   if t0 then TRUE else RIGHT
+    // RIGHT:                        RIGHT:
+    //     t1 = <right>                  t1 = <right>
+    //     if t1 then TRUE else FALSE    if t1 then TRUE else FALSE
+    // TRUE:                         FALSE:
+    //     t2 = true                     t3 = false
+    //     jump END(t2)                  jump END(t3)
+    // FALSE:                        TRUE:
+    //     t3 = false                    t2 = true
+    //     jump END(t3)                  jump END(t2)
+    // END(t4):                      END(t4):
+    //
+    // Both operands are tested by a branch, so the result is always a boolean.
+    fn logical_operation(&mut self, operator: BinaryOperator, left: &Expression, right: &Expression, span: Span)
+        -> Result<Temporary, SyntaxError> {
+        let left_value = self.expression(left)?;
+        let (right_block, true_block, false_block, end) = (self.block(), self.block(), self.block(), self.block());
+        let result = self.temporary();
+        self.scope().blocks[end].parameters.push(result);
+
+        let (short_circuit, other) = match operator {
+            BinaryOperator::And => {
+                self.terminate(TerminatorKind::Branch(left_value, right_block, false_block), span);
+                (false_block, true_block)
+            },
+            _ => {
+                self.terminate(TerminatorKind::Branch(left_value, true_block, right_block), span);
+                (true_block, false_block)
+            },
+        };
+
+        self.start(right_block, span);
+        let right_value = self.expression(right)?;
+        self.terminate(TerminatorKind::Branch(right_value, true_block, false_block), span);
+
+        for &block in &[other, short_circuit] {
+            self.start(block, span);
+            let value = self.temporary();
+            self.emit(InstructionKind::Constant(value, Constant::Boolean(block == true_block)), span);
+            self.terminate(TerminatorKind::Jump(end, vec![value]), span);
+        }
+
+        self.start(end, span);
+        Ok(result)
+    }
+
+    fn call(&mut self, function: Temporary, arguments: &[Expression], span: Span) -> Result<Temporary, SyntaxError> {
+        if arguments.len() > usize::from(u8::MAX) {
+            return Err(SyntaxError::new(String::from("Too many arguments!"), span));
+        }
+
+        let arguments = self.expressions(arguments)?;
+        let result = self.temporary();
+        self.emit(InstructionKind::Call(result, function, arguments), span);
+        Ok(result)
+    }
+
+    // Lowers the body into a function of its own and creates the closure. Captured parameters are
+    // moved into upvalues on entry. The body returns nil if it ends without return.
+    fn function(&mut self, name: &str, parameters: &[Identifier], body: &[Statement], span: Span)
+        -> Result<Temporary, SyntaxError> {
+        if parameters.len() > usize::from(u8::MAX) {
+            return Err(SyntaxError::new(String::from("Too many parameters!"), span));
+        }
+
+        if self.analysis.captures(span).len() > usize::from(u8::MAX) + 1 {
+            return Err(SyntaxError::new(String::from("Too many captured variables!"), span));
+        }
+
+        // The top level code is no function of the image.
+        if self.functions.len() > usize::from(u16::MAX) + 1 {
+            return Err(SyntaxError::new(String::from("Too many functions!"), span));
+        }
+
+        let index = self.functions.len();
+        self.functions.push(None);
+        self.scopes.push(FunctionScope::new(self.analysis.captures(span).to_vec()));
+
+        for parameter in parameters {
+            let variable = self.variable(parameter);
+            let slot = self.slot(variable, parameter)?;
+
+            if variable.is_captured() {
+                let value = self.temporary();
+                self.emit(InstructionKind::Load(value, Place::Local(slot), Kind::Any), parameter.span());
+                self.declare(parameter)?;
+                self.store(parameter, value, parameter.span())?;
+            }
+        }
+
+        self.statements(body)?;
+        let nil = self.temporary();
+        self.emit(InstructionKind::Constant(nil, Constant::Nil), span);
+        self.terminate(TerminatorKind::Return(nil), span);
+        let scope = self.scopes.pop().expect("Function scope!");
+        let mut captures = Vec::with_capacity(scope.upvalues.len());
+
+        for &id in &scope.upvalues {
+            captures.push(match self.scope_ref().slots.get(&id) {
+                Some(&slot) => Capture::Local(slot),
+                None => Capture::Upvalue(self.upvalue_index(id).expect("Captures are transitive!")),
+            });
+        }
+
+        self.functions[index] = Some(scope.finish(name.to_string(), parameters.len() as u8, captures));
+        let closure = self.temporary();
+        self.emit(InstructionKind::Closure(closure, index), span);
+        Ok(closure)
+    }
+
+    // The blocks of the body are guarded by the try block, whose handler stores the exception
+    // into its variable:
+    //
+    //     <body>                    guarded
+    //     jump END
+    // HANDLER:
+    //     t0 = catch
+    //     x0 = t0
+    //     <handler>
+    // END:
+    fn try_catch(&mut self, body: &[Statement], identifier: &Identifier, handler: &[Statement], span: Span)
+        -> Result<(), SyntaxError> {
+        let handler_block = self.block();
+        let end = self.block();
+        let parent = self.scope_ref().guard;
+        let guard = self.scope_ref().guards.len();
+        self.scope().guards.push(Guard::new(handler_block, parent));
+        self.scope().guard_with(Some(guard), span);
+        self.statements(body)?;
+        self.scope().guard_with(parent, span);
+        self.terminate(TerminatorKind::Jump(end, Vec::new()), span);
+        self.start(handler_block, span);
+        let exception = self.temporary();
+        self.emit(InstructionKind::Catch(exception), identifier.span());
+        self.declare(identifier)?;
+        self.store(identifier, exception, identifier.span())?;
+        self.statements(handler)?;
+        self.start(end, span);
+        Ok(())
+    }
+
+    //     t0 = <condition>
+    //     if t0 then THEN else ELSE
+    // THEN:
+    //     <then>
+    //     jump END
+    // ELSE:
+    //     <else>
+    // END:
+    //
+    // Without else branch the else block is the end.
+    fn if_else(&mut self, condition: &Expression, then_branch: &[Statement], else_branch: &[Statement], span: Span)
+        -> Result<(), SyntaxError> {
+        let condition = self.expression(condition)?;
+        let (then_block, else_block) = (self.block(), self.block());
+        self.terminate(TerminatorKind::Branch(condition, then_block, else_block), span);
+        self.start(then_block, span);
+        self.statements(then_branch)?;
+
+        if else_branch.is_empty() {
+            self.start(else_block, span);
+            return Ok(());
+        }
+
+        let end = self.block();
+        self.terminate(TerminatorKind::Jump(end, Vec::new()), span);
+        self.start(else_block, span);
+        self.statements(else_branch)?;
+        self.start(end, span);
+        Ok(())
+    }
+
+    // LOOP:
+    //     t0 = <condition>
+    //     if t0 then BODY else EXIT
+    // BODY:
+    //     <body>
+    //     jump LOOP
+    // EXIT:
+    //
+    // `continue` jumps to LOOP, `break` to EXIT.
+    fn while_loop(&mut self, condition: &Expression, body: &[Statement], span: Span) -> Result<(), SyntaxError> {
+        let (loop_block, body_block, exit) = (self.block(), self.block(), self.block());
+        self.start(loop_block, span);
+        let condition = self.expression(condition)?;
+        self.terminate(TerminatorKind::Branch(condition, body_block, exit), span);
+        self.start(body_block, span);
+        self.loop_body(body, loop_block, exit)?;
+        self.terminate(TerminatorKind::Jump(loop_block, Vec::new()), span);
+        self.start(exit, span);
+        Ok(())
+    }
+
+    // The bounds are kept in local slots of the loop:
+    //
+    //     t0 = <start>
+    //     x0 = t0 : int             counter
+    //     t1 = <end>
+    //     x1 = t1 : int             end
+    // LOOP:
+    //     t2 = x0 : int
+    //     t3 = x1 : int
+    //     t4 = t2 < t3 : int
+    //     if t4 then BODY else EXIT
+    // BODY:
+    //     t5 = x0 : int
+    //     x2 = t5 : int             variable
+    //     <body>
+    // NEXT:
+    //     <increment of x0>
+    //     jump LOOP
+    // EXIT:
+    //
+    // `continue` jumps to NEXT, `break` to EXIT.
+    fn for_range(&mut self, identifier: &Identifier, start: &Expression, end: &Expression, body: &[Statement],
+                 span: Span) -> Result<(), SyntaxError> {
+        let counter = self.temporary_slot(span)?;
+        let last = self.temporary_slot(span)?;
+        let start = self.expression(start)?;
+        self.emit(InstructionKind::Store(Place::Local(counter), start, Kind::Integer), span);
+        let end = self.expression(end)?;
+        self.emit(InstructionKind::Store(Place::Local(last), end, Kind::Integer), span);
+        let (loop_block, body_block, next, exit) = (self.block(), self.block(), self.block(), self.block());
+        self.start(loop_block, span);
+        let current = self.temporary();
+        self.emit(InstructionKind::Load(current, Place::Local(counter), Kind::Integer), span);
+        let end = self.temporary();
+        self.emit(InstructionKind::Load(end, Place::Local(last), Kind::Integer), span);
+        let condition = self.temporary();
+        self.emit(InstructionKind::Binary(condition, BinaryOperator::LessThan, current, end, Kind::Integer), span);
+        self.terminate(TerminatorKind::Branch(condition, body_block, exit), span);
+        self.start(body_block, span);
+        self.declare(identifier)?;
+        let current = self.temporary();
+        self.emit(InstructionKind::Load(current, Place::Local(counter), Kind::Integer), identifier.span());
+        self.store(identifier, current, identifier.span())?;
+        self.loop_body(body, next, exit)?;
+        self.start(next, span);
+        self.increment(counter, loop_block, span);
+        self.start(exit, span);
+        Ok(())
+    }
+
+    // Like a range over the indices of the array, which is evaluated once:
+    //
+    //     t0 = <array>
+    //     x0 = t0                   array
+    //     t1 = 0
+    //     x1 = t1 : int             index
+    // LOOP:
+    //     t2 = x1 : int
+    //     t3 = x0
+    //     t4 = len(t3)
+    //     t5 = t2 < t4
+    //     if t5 then BODY else EXIT
+    // BODY:
+    //     t6 = x0
+    //     t7 = x1 : int
+    //     t8 = t6[t7]
+    //     x2 = t8                   variable
+    //     <body>
+    // NEXT:
+    //     ...
+    fn for_each(&mut self, identifier: &Identifier, array: &Expression, body: &[Statement], span: Span)
+        -> Result<(), SyntaxError> {
+        let elements = self.temporary_slot(span)?;
+        let index = self.temporary_slot(span)?;
+        let array = self.expression(array)?;
+        self.emit(InstructionKind::Store(Place::Local(elements), array, Kind::Any), span);
+        let zero = self.temporary();
+        self.emit(InstructionKind::Constant(zero, Constant::Integer(0)), span);
+        self.emit(InstructionKind::Store(Place::Local(index), zero, Kind::Integer), span);
+        let (loop_block, body_block, next, exit) = (self.block(), self.block(), self.block(), self.block());
+        self.start(loop_block, span);
+        let current = self.temporary();
+        self.emit(InstructionKind::Load(current, Place::Local(index), Kind::Integer), span);
+        let array = self.temporary();
+        self.emit(InstructionKind::Load(array, Place::Local(elements), Kind::Any), span);
+        let length = self.temporary();
+        self.emit(InstructionKind::Length(length, array), span);
+        let condition = self.temporary();
+        self.emit(InstructionKind::Binary(condition, BinaryOperator::LessThan, current, length, Kind::Any), span);
+        self.terminate(TerminatorKind::Branch(condition, body_block, exit), span);
+        self.start(body_block, span);
+        self.declare(identifier)?;
+        let array = self.temporary();
+        self.emit(InstructionKind::Load(array, Place::Local(elements), Kind::Any), identifier.span());
+        let current = self.temporary();
+        self.emit(InstructionKind::Load(current, Place::Local(index), Kind::Integer), identifier.span());
+        let element = self.temporary();
+        self.emit(InstructionKind::GetElement(element, array, current), identifier.span());
+        self.store(identifier, element, identifier.span())?;
+        self.loop_body(body, next, exit)?;
+        self.start(next, span);
+        self.increment(index, loop_block, span);
+        self.start(exit, span);
+        Ok(())
+    }
+
+    // Increments the integer in the local slot and continues at the start of the loop.
+    fn increment(&mut self, slot: u8, loop_block: usize, span: Span) {
+        let (current, one, sum) = (self.temporary(), self.temporary(), self.temporary());
+        self.emit(InstructionKind::Load(current, Place::Local(slot), Kind::Integer), span);
+        self.emit(InstructionKind::Constant(one, Constant::Integer(1)), span);
+        self.emit(InstructionKind::Binary(sum, BinaryOperator::Add, current, one, Kind::Integer), span);
+        self.emit(InstructionKind::Store(Place::Local(slot), sum, Kind::Integer), span);
+        self.terminate(TerminatorKind::Jump(loop_block, Vec::new()), span);
+    }
+
+    // Lets `continue` and `break` in the body continue at the given blocks.
+    fn loop_body(&mut self, body: &[Statement], next: usize, exit: usize) -> Result<(), SyntaxError> {
+        self.scope().loops.push(Loop { next, exit });
+        let result = self.statements(body);
+        self.scope().loops.pop();
+        result
+    }
+
+    fn print(&mut self, identifier: &Identifier, argument: &Expression) -> Result<(), SyntaxError> {
+        let value = self.expression(argument)?;
+
+        match Builtin::for_name(identifier.name()) {
+            Some(Builtin::PrintLn) => self.emit(InstructionKind::PrintLn(value), identifier.span()),
+            _ => self.emit(InstructionKind::Print(value), identifier.span()),
+        }
+
+        Ok(())
+    }
+
+    // Get the field by index if the record type is known, by name otherwise.
+    fn field(&self, record_type: &Type, field: &Identifier) -> Field {
+        let index = match *record_type {
+            Type::Record(ref name) => self.records.iter()
+                .find(|record| record.name() == name.as_str())
+                .and_then(|record| record.field_index(field.name())),
+            _ => None,
+        };
+
+        match index {
+            Some(index) => Field::Index(index as u8, field.name().to_string()),
+            None => Field::Name(field.name().to_string()),
+        }
+    }
+
+    // A captured variable gets a new upvalue where it's declared.
+    fn declare(&mut self, identifier: &Identifier) -> Result<(), SyntaxError> {
+        let variable = self.variable(identifier);
+
+        if variable.is_captured() && variable.declaration() == identifier.span() {
+            let slot = self.slot(variable, identifier)?;
+            self.emit(InstructionKind::NewCell(slot), identifier.span());
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, identifier: &Identifier, span: Span) -> Result<Temporary, SyntaxError> {
+        let (place, kind) = self.place(identifier)?;
+        let value = self.temporary();
+        self.emit(InstructionKind::Load(value, place, kind), span);
+        Ok(value)
+    }
+
+    fn store(&mut self, identifier: &Identifier, value: Temporary, span: Span) -> Result<(), SyntaxError> {
+        let (place, kind) = self.place(identifier)?;
+        self.emit(InstructionKind::Store(place, value, kind), span);
+        Ok(())
+    }
+
+    // Integers in local slots which are no upvalues are accessed by the integer instructions.
+    fn place(&mut self, identifier: &Identifier) -> Result<(Place, Kind), SyntaxError> {
+        let variable = self.variable(identifier);
+
+        Ok(match self.access(variable, identifier)? {
+            Access::Local(slot, true) => (Place::Cell(slot), Kind::Any),
+            Access::Local(slot, false) if Type::Integer == *variable.value_type() => (Place::Local(slot), Kind::Integer),
+            Access::Local(slot, false) => (Place::Local(slot), Kind::Any),
+            Access::Upvalue(index) => (Place::Upvalue(index), Kind::Any),
+        })
+    }
+
+    fn variable(&self, identifier: &Identifier) -> &'a Variable {
+        self.analysis.variable(identifier).expect("Variables are resolved by the type checker!")
+    }
+
+    // Variables of enclosing functions are upvalues, all others are local.
+    fn access(&mut self, variable: &Variable, identifier: &Identifier) -> Result<Access, SyntaxError> {
+        if let Some(index) = self.upvalue_index(variable.id()) {
+            return Ok(Access::Upvalue(index));
+        }
+
+        let slot = self.slot(variable, identifier)?;
+        Ok(Access::Local(slot, variable.is_captured()))
+    }
+
+    fn upvalue_index(&self, id: usize) -> Option<u8> {
+        self.scope_ref().upvalues.iter().position(|&upvalue| upvalue == id).map(|index| index as u8)
+    }
+
+    // Get the local slot of the variable and allocates one if it's the first use.
+    fn slot(&mut self, variable: &Variable, identifier: &Identifier) -> Result<u8, SyntaxError> {
+        let scope = self.scope();
+
+        if let Some(slot) = scope.slots.get(&variable.id()) {
+            return Ok(*slot);
+        }
+
+        let slot = scope.slots.len() + scope.temporaries;
+
+        if slot > usize::from(u8::MAX) {
+            return Err(SyntaxError::new(String::from("Too many variables!"), identifier.span()));
+        }
+
+        scope.slots.insert(variable.id(), slot as u8);
+        Ok(slot as u8)
+    }
+
+    // Allocates a local slot which belongs to no variable.
+    fn temporary_slot(&mut self, span: Span) -> Result<u8, SyntaxError> {
+        let scope = self.scope();
+        let slot = scope.slots.len() + scope.temporaries;
+
+        if slot > usize::from(u8::MAX) {
+            return Err(SyntaxError::new(String::from("Too many variables!"), span));
+        }
+
+        scope.temporaries += 1;
+        Ok(slot as u8)
+    }
+
+    fn current_loop(&mut self) -> &Loop {
+        self.scope().loops.last().expect("Loops are checked by the type checker!")
+    }
+
+    fn scope_ref(&self) -> &FunctionScope {
+        self.scopes.last().expect("Top level scope!")
+    }
+
+    fn scope(&mut self) -> &mut FunctionScope {
+        self.scopes.last_mut().expect("Top level scope!")
+    }
+
+    fn temporary(&mut self) -> Temporary {
+        self.scope().temporary()
+    }
+
+    fn block(&mut self) -> usize {
+        self.scope().block()
+    }
+
+    fn start(&mut self, block: usize, span: Span) {
+        self.scope().start(block, span)
+    }
+
+    fn emit(&mut self, kind: InstructionKind, span: Span) {
+        self.scope().emit(kind, span)
+    }
+
+    fn terminate(&mut self, kind: TerminatorKind, span: Span) {
+        self.scope().terminate(kind, span)
+    }
+}
+
+// Get the temporary defined by the instruction.
+fn defined(kind: &InstructionKind) -> Temporary {
+    match *kind {
+        InstructionKind::Constant(target, _)
+            | InstructionKind::Length(target, _)
+            | InstructionKind::Array(target, _)
+            | InstructionKind::GetElement(target, _, _)
+            | InstructionKind::GetField(target, _, _)
+            | InstructionKind::BuildString(target, _)
+            | InstructionKind::Unary(target, _, _, _) => target,
+        _ => unreachable!("Only expressions are lowered to a single instruction!"),
+    }
+}
+
+// Get the lowest and highest value if all arms but an irrefutable last one match integers which are
+// dense enough for a switch.
+fn dense_integers(arms: &[(Pattern, Expression)]) -> Option<(i64, i64)> {
+    let tested = match arms.last() {
+        Some((pattern, _)) if pattern.is_irrefutable() => &arms[..arms.len() - 1],
+        _ => arms,
+    };
+    let mut values = Vec::with_capacity(tested.len());
+
+    for (pattern, _) in tested {
+        match *pattern.kind() {
+            PatternKind::Integer(value) => values.push(value),
+            _ => return None,
+        }
+    }
+
+    let low = *values.iter().min()?;
+    let high = *values.iter().max()?;
+    let range = (high as i128) - (low as i128) + 1;
+
+    if values.len() < MIN_JUMP_TABLE_ARMS || range > 2 * values.len() as i128 || range > i128::from(u16::MAX) {
+        return None;
+    }
+
+    Some((low, high))
+}
+
+fn unsupported(feature: &str, span: Span) -> SyntaxError {
+    SyntaxError::new(format!("{} is not supported!", feature), span)
+}
+
+#[cfg(test)]
+mod tests {
+    use hamcrest::prelude::*;
+    use test_support;
+
+    #[test]
+    fn lower_straight_line_code() {
+        assert_that!(&test_support::lower("var x = 1 + 2\nvar s = \"a\"\nprintln(-x)\ns + s").to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\nB0:\n",
+            "    t0 = 1\n    t1 = 2\n    t2 = t0 + t1 : int\n    x0 = t2 : int\n",
+            "    t3 = \"a\"\n    x1 = t3\n",
+            "    t4 = x0 : int\n    t5 = -t4 : int\n    println t5\n",
+            "    t6 = x1\n    t7 = x1\n    t8 = t6 + t7 : string\n    pop t8\n",
+            "    halt\n"))));
+    }
+
+    #[test]
+    fn lower_if_and_while_to_blocks() {
+        assert_that!(&test_support::lower("var b = true\nwhile b {\n  if b { break }\n}").to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\n",
+            "B0:\n    t0 = true\n    x0 = t0\n    jump B1\n",
+            "B1:\n    t1 = x0\n    if t1 then B2 else B5\n",
+            "B2:\n    t2 = x0\n    if t2 then B3 else B4\n",
+            "B3:\n    jump B5\n",
+            "B4:\n    jump B1\n",
+            "B5:\n    halt\n"))));
+    }
+
+    #[test]
+    fn lower_logical_operation_with_block_parameter() {
+        assert_that!(&test_support::lower("var x = true or false").to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\n",
+            "B0:\n    t0 = true\n    if t0 then B3 else B1\n",
+            "B1:\n    t2 = false\n    if t2 then B3 else B2\n",
+            "B2:\n    t3 = false\n    jump B4(t3)\n",
+            "B3:\n    t4 = true\n    jump B4(t4)\n",
+            "B4(t1):\n    x0 = t1\n    halt\n"))));
+    }
+
+    #[test]
+    fn lower_try_to_guarded_blocks() {
+        assert_that!(&test_support::lower("try {\n    throw 1\n} catch e {\n    println(e)\n}").to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\n",
+            "B0 [catch B2]:\n    t0 = 1\n    throw t0\n",
+            "B1:\n    jump B3\n",
+            "B2:\n    t1 = catch\n    x0 = t1\n    t2 = x0\n    println t2\n    jump B3\n",
+            "B3:\n    halt\n"))));
+    }
+
+    #[test]
+    fn lower_functions_with_captures() {
+        assert_that!(&test_support::lower("var a = 1\nfun f() { return a }").to_string(), is(equal_to(concat!(
+            "f0 <anonymous>/0:\n",
+            "B0:\n    x0 = cell\n    t0 = 1\n    *x0 = t0\n    t1 = closure f1\n    x1 = t1\n    halt\n",
+            "\n",
+            "f1 f/0 [x0]:\n",
+            "B0:\n    t0 = u0\n    return t0\n",
+            "B1:\n    t1 = nil\n    return t1\n"))));
+    }
+}
@@ -2,6 +2,12 @@
 
 // https://github.com/rust-unofficial/patterns/blob/master/patterns/visitor.md
 
-mod ast;
-mod visitor;
-
+pub mod ast;
+pub mod interpreter;
+pub mod ir;
+pub mod linker;
+pub mod lowering;
+pub mod optimize;
+pub mod printer;
+pub mod semantic;
+pub mod visitor;
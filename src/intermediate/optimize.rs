@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use backend::value::{self, Value};
+use intermediate::ast::*;
+use intermediate::semantic::Analysis;
+
+/// Available optimization levels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Level {
+    /// The program is compiled as written.
+    O0,
+    /// Constant expressions are folded, constants propagated and unreachable code removed. The
+    /// generated byte code is improved by the peephole optimizer.
+    O1,
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(original: &str) -> Result<Self, Self::Err> {
+        match original {
+            "0" => Ok(Level::O0),
+            "1" => Ok(Level::O1),
+            l => Err(format!("Unknown optimization level '{}'!", l)),
+        }
+    }
+}
+
+/// Simplifies the analysed program without changing its behavior.
+///
+/// - Binary and unary operations on literals are replaced by their result, e.g. `1 + 2 * 3` by
+///   `7`. Operations which fail at runtime, like a division by zero, are kept to fail there.
+/// - References to constants initialized with a literal are replaced by the literal.
+/// - Statements after `return`, `throw`, `break` and `continue` are removed.
+/// - Conditionals with a constant condition are replaced by the taken branch, loops which never
+///   run are removed. The branch is inlined unless it declares variables, which would leak into
+///   the enclosing scope.
+///
+/// The optimized expressions keep the spans of the original ones, so errors are still reported at
+/// the original source positions.
+pub fn optimize(statements: &[Statement], analysis: &Analysis) -> Vec<Statement> {
+    Optimizer { analysis, constants: HashMap::new() }.block(statements)
+}
+
+struct Optimizer<'a> {
+    analysis: &'a Analysis,
+    // Literal values of the constants by variable id.
+    constants: HashMap<usize, ExpressionKind>,
+}
+
+impl<'a> Optimizer<'a> {
+    // Constants are declared before they are used, so they are known once the references are reached.
+    fn block(&mut self, statements: &[Statement]) -> Vec<Statement> {
+        let mut block = Vec::with_capacity(statements.len());
+
+        for statement in statements {
+            self.statement(statement, &mut block);
+
+            if block.last().is_some_and(never_completes) {
+                break;
+            }
+        }
+
+        block
+    }
+
+    // Appends the optimized statement to the block, if it's not removed.
+    fn statement(&mut self, statement: &Statement, block: &mut Vec<Statement>) {
+        let kind = match *statement.kind() {
+            StatementKind::Assignment(ref identifier, ref value) =>
+                StatementKind::Assignment(identifier.clone(), self.expression(value)),
+            StatementKind::IndexAssignment(ref array, ref index, operator, ref value) => StatementKind::IndexAssignment(
+                self.expression(array), self.expression(index), operator, self.expression(value)),
+            StatementKind::FieldAssignment(ref record, ref field, operator, ref value) => StatementKind::FieldAssignment(
+                self.expression(record), field.clone(), operator, self.expression(value)),
+            StatementKind::Constant(ref identifier, ref value) => {
+                let value = self.expression(value);
+
+                if is_literal(value.kind()) {
+                    if let Some(variable) = self.analysis.variable(identifier) {
+                        self.constants.insert(variable.id(), value.kind().clone());
+                    }
+                }
+
+                StatementKind::Constant(identifier.clone(), value)
+            },
+            StatementKind::Variable(ref identifier, ref value) =>
+                StatementKind::Variable(identifier.clone(), self.expression(value)),
+            StatementKind::Function(ref identifier, ref parameters, ref body) =>
+                StatementKind::Function(identifier.clone(), parameters.clone(), self.block(body)),
+            StatementKind::Return(ref value) => StatementKind::Return(self.expression(value)),
+            StatementKind::Try(ref body, ref identifier, ref handler) =>
+                StatementKind::Try(self.block(body), identifier.clone(), self.block(handler)),
+            StatementKind::Throw(ref value) => StatementKind::Throw(self.expression(value)),
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => {
+                let condition = self.expression(condition);
+
+                match *condition.kind() {
+                    ExpressionKind::Boolean(value) => {
+                        let branch = self.block(if value { then_branch } else { else_branch });
+
+                        if !branch.iter().any(declares) {
+                            block.extend(branch);
+                            return;
+                        }
+
+                        let condition = Expression::new(ExpressionKind::Boolean(true), condition.span());
+                        StatementKind::If(condition, branch, Vec::new())
+                    },
+                    _ => StatementKind::If(condition, self.block(then_branch), self.block(else_branch)),
+                }
+            },
+            StatementKind::While(ref condition, ref body) => {
+                let condition = self.expression(condition);
+
+                if ExpressionKind::Boolean(false) == *condition.kind() {
+                    return;
+                }
+
+                StatementKind::While(condition, self.block(body))
+            },
+            StatementKind::ForRange(ref identifier, ref start, ref end, ref body) => StatementKind::ForRange(
+                identifier.clone(), self.expression(start), self.expression(end), self.block(body)),
+            StatementKind::ForEach(ref identifier, ref array, ref body) =>
+                StatementKind::ForEach(identifier.clone(), self.expression(array), self.block(body)),
+            StatementKind::Expression(ref expression) => StatementKind::Expression(self.expression(expression)),
+            ref kind => kind.clone(),
+        };
+
+        block.push(Statement::new(kind, statement.span()));
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Expression {
+        let kind = match *expression.kind() {
+            ExpressionKind::Identifier(ref identifier) => match self.constant(identifier) {
+                Some(literal) => literal,
+                None => ExpressionKind::Identifier(identifier.clone()),
+            },
+            ExpressionKind::InterpolatedString(ref parts) => ExpressionKind::InterpolatedString(self.expressions(parts)),
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) =>
+                ExpressionKind::FunctionCall(identifier.clone(), self.expressions(arguments)),
+            ExpressionKind::Call(ref function, ref arguments) =>
+                ExpressionKind::Call(Box::new(self.expression(function)), self.expressions(arguments)),
+            ExpressionKind::Function(ref parameters, ref body) =>
+                ExpressionKind::Function(parameters.clone(), self.block(body)),
+            ExpressionKind::Array(ref elements) => ExpressionKind::Array(self.expressions(elements)),
+            ExpressionKind::Index(ref array, ref index) =>
+                ExpressionKind::Index(Box::new(self.expression(array)), Box::new(self.expression(index))),
+            ExpressionKind::RecordLiteral(ref identifier, ref fields) => ExpressionKind::RecordLiteral(
+                identifier.clone(),
+                fields.iter().map(|(field, value)| (field.clone(), self.expression(value))).collect()),
+            ExpressionKind::Field(ref record, ref field) =>
+                ExpressionKind::Field(Box::new(self.expression(record)), field.clone()),
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                fold_binary(operator, left.kind(), right.kind())
+                    .unwrap_or_else(|| ExpressionKind::BinaryOperation(operator, Box::new(left), Box::new(right)))
+            },
+            ExpressionKind::UnaryOperation(operator, ref operand) => {
+                let operand = self.expression(operand);
+                fold_unary(operator, operand.kind())
+                    .unwrap_or_else(|| ExpressionKind::UnaryOperation(operator, Box::new(operand)))
+            },
+            ExpressionKind::Match(ref value, ref arms) => ExpressionKind::Match(
+                Box::new(self.expression(value)),
+                arms.iter().map(|(pattern, arm)| (pattern.clone(), self.expression(arm))).collect()),
+            ref kind => kind.clone(),
+        };
+
+        Expression::new(kind, expression.span())
+    }
+
+    fn expressions(&mut self, expressions: &[Expression]) -> Vec<Expression> {
+        expressions.iter().map(|expression| self.expression(expression)).collect()
+    }
+
+    fn constant(&self, identifier: &Identifier) -> Option<ExpressionKind> {
+        self.analysis.variable(identifier).and_then(|variable| self.constants.get(&variable.id())).cloned()
+    }
+}
+
+fn never_completes(statement: &Statement) -> bool {
+    matches!(*statement.kind(),
+        StatementKind::Return(_) | StatementKind::Throw(_) | StatementKind::Break | StatementKind::Continue)
+}
+
+fn declares(statement: &Statement) -> bool {
+    matches!(*statement.kind(),
+        StatementKind::Variable(..) | StatementKind::Constant(..) | StatementKind::Function(..) | StatementKind::Record(..))
+}
+
+fn is_literal(kind: &ExpressionKind) -> bool {
+    matches!(*kind,
+        ExpressionKind::Integer(_) | ExpressionKind::Real(_) | ExpressionKind::String(_)
+            | ExpressionKind::Character(_) | ExpressionKind::Boolean(_))
+}
+
+// Logical operators only need the left operand if it determines the result, their right operand
+// must be a boolean literal otherwise because it's checked at runtime.
+fn fold_binary(operator: BinaryOperator, left: &ExpressionKind, right: &ExpressionKind) -> Option<ExpressionKind> {
+    match (operator, left, right) {
+        (BinaryOperator::And, ExpressionKind::Boolean(false), _) => Some(ExpressionKind::Boolean(false)),
+        (BinaryOperator::Or, ExpressionKind::Boolean(true), _) => Some(ExpressionKind::Boolean(true)),
+        (BinaryOperator::And, ExpressionKind::Boolean(true), ExpressionKind::Boolean(right))
+            | (BinaryOperator::Or, ExpressionKind::Boolean(false), ExpressionKind::Boolean(right)) =>
+            Some(ExpressionKind::Boolean(*right)),
+        (BinaryOperator::And, _, _) | (BinaryOperator::Or, _, _) => None,
+        (BinaryOperator::Add, ExpressionKind::String(left), ExpressionKind::String(right)) =>
+            Some(ExpressionKind::String(left.clone() + right)),
+        // Strings are compared by content like by the heap.
+        (_, ExpressionKind::String(left), ExpressionKind::String(right)) =>
+            value::apply_ordering(operator, left.partial_cmp(right)).ok().and_then(literal),
+        _ => {
+            let (left, right) = (value(left)?, value(right)?);
+            let result = match operator {
+                BinaryOperator::Add
+                    | BinaryOperator::Subtract
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulo => value::arithmetic(operator, left, right),
+                _ => value::compare(operator, left, right),
+            };
+            result.ok().and_then(literal)
+        },
+    }
+}
+
+fn fold_unary(operator: UnaryOperator, operand: &ExpressionKind) -> Option<ExpressionKind> {
+    match (operator, operand) {
+        (UnaryOperator::Not, ExpressionKind::Boolean(operand)) => Some(ExpressionKind::Boolean(!*operand)),
+        (UnaryOperator::Negate, _) => value::negate(value(operand)?).ok().and_then(literal),
+        (UnaryOperator::Plus, ExpressionKind::Integer(_)) | (UnaryOperator::Plus, ExpressionKind::Real(_)) =>
+            Some(operand.clone()),
+        _ => None,
+    }
+}
+
+// Get the value of a literal which lives outside of the heap.
+fn value(kind: &ExpressionKind) -> Option<Value> {
+    match *kind {
+        ExpressionKind::Integer(value) => Some(Value::Integer(value)),
+        ExpressionKind::Real(value) => Some(Value::Real(value)),
+        ExpressionKind::Character(value) => Some(Value::Character(value)),
+        ExpressionKind::Boolean(value) => Some(Value::Boolean(value)),
+        _ => None,
+    }
+}
+
+fn literal(value: Value) -> Option<ExpressionKind> {
+    match value {
+        Value::Integer(value) => Some(ExpressionKind::Integer(value)),
+        Value::Real(value) => Some(ExpressionKind::Real(value)),
+        Value::Character(value) => Some(ExpressionKind::Character(value)),
+        Value::Boolean(value) => Some(ExpressionKind::Boolean(value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use intermediate::printer::{print, Format};
+    use intermediate::semantic::TypeChecker;
+    use test_support::parse;
+
+    fn optimized(src: &str) -> String {
+        let statements = parse(src).unwrap();
+        let analysis = TypeChecker::new().check(&statements).unwrap();
+        print(&optimize(&statements, &analysis), Format::SExpression)
+    }
+
+    #[test]
+    fn level_from_str() {
+        assert_that!("0".parse::<Level>(), is(equal_to(Ok(Level::O0))));
+        assert_that!("1".parse::<Level>(), is(equal_to(Ok(Level::O1))));
+        assert_that!("2".parse::<Level>(), is(equal_to(Err(String::from("Unknown optimization level '2'!")))));
+    }
+
+    #[test]
+    fn fold_constant_expressions() {
+        assert_that!(&optimized("1 + 2 * 3"), is(equal_to("7\n")));
+        assert_that!(&optimized(concat!(
+            "println(-(2 - 5) % 2)\nprintln(1 / 2.0 + 1)\nprintln(not (1 < 2) or 'a' == 'a')\n",
+            "println(\"a\" + \"b\" >= \"ab\")\nprintln(9223372036854775807 + 1)")),
+            is(equal_to("(call println 1)\n(call println 1.5)\n(call println true)\n(call println true)\n(call println -9223372036854775808)\n")));
+    }
+
+    #[test]
+    fn keep_expressions_which_fail_or_are_not_constant() {
+        assert_that!(&optimized("var x = 1\nprintln(1 / 0)\nprintln(x * (2 + 3))"),
+            is(equal_to("(var x 1)\n(call println (/ 1 0))\n(call println (* x 5))\n")));
+        assert_that!(&optimized("var b\nprintln(true and b)\nprintln(false and b)\nprintln(b or true)"),
+            is(equal_to("(var b nil)\n(call println (and true b))\n(call println false)\n(call println (or b true))\n")));
+    }
+
+    #[test]
+    fn propagate_constants() {
+        assert_that!(&optimized(concat!(
+            "const A = 2\nconst B = A * 3\nconst C = [B]\nprintln(A + B)\n",
+            "fun f(A) { return A }\nfun g() { return B }\nprintln(C)")), is(equal_to(concat!(
+            "(const A 2)\n(const B 6)\n(const C (array 6))\n(call println 8)\n",
+            "(fun f (A) (return A))\n(fun g () (return 6))\n(call println C)\n"))));
+    }
+
+    #[test]
+    fn remove_unreachable_code() {
+        assert_that!(&optimized(concat!(
+            "fun f(x) {\n  return x\n  println(x)\n}\n",
+            "while true {\n  if 1 > 0 { break }\n  println(1)\n}\n",
+            "for i in 0..2 {\n  continue\n  throw i\n}")), is(equal_to(
+            "(fun f (x) (return x))\n(while true (break))\n(for i (range 0 2) (continue))\n")));
+    }
+
+    #[test]
+    fn remove_constant_conditions() {
+        assert_that!(&optimized(concat!(
+            "const DEBUG = false\nif DEBUG { println(1) }\nif not DEBUG { println(2) } else { println(3) }\n",
+            "while DEBUG { println(4) }\nif DEBUG { println(5) } else { var x = 6 }")), is(equal_to(
+            "(const DEBUG false)\n(call println 2)\n(if true ((var x 6)) ())\n")));
+    }
+}
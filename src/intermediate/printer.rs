@@ -0,0 +1,827 @@
+use std::str::FromStr;
+
+use frontend::Span;
+use intermediate::ast::*;
+use intermediate::visitor::Visitor;
+
+/// Available output formats to print an AST.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    /// Indented tree with one node per line.
+    Tree,
+    /// One S-expression per statement.
+    SExpression,
+    /// JSON array with one object per statement.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(original: &str) -> Result<Self, Self::Err> {
+        match original {
+            "tree" => Ok(Format::Tree),
+            "sexp" => Ok(Format::SExpression),
+            "json" => Ok(Format::Json),
+            f => Err(format!("Unknown AST format '{}'!", f)),
+        }
+    }
+}
+
+/// Renders the given statements in the given format.
+pub fn print(statements: &[Statement], format: Format) -> String {
+    match format {
+        Format::Tree => TreePrinter::new().print(statements),
+        Format::SExpression => SExpressionPrinter::new().print(statements),
+        Format::Json => JsonPrinter::new().print(statements),
+    }
+}
+
+/// Prints the AST as indented tree like:
+///
+/// ```text
+/// Program
+/// +-- Variable [1, 1]-[1, 14]
+///     +-- Identifier x [1, 5]-[1, 6]
+///     +-- BinaryOperation + [1, 9]-[1, 14]
+///         +-- Integer 1 [1, 9]-[1, 10]
+///         +-- Integer 2 [1, 13]-[1, 14]
+/// ```
+#[derive(Default)]
+pub struct TreePrinter;
+
+impl TreePrinter {
+    pub fn new() -> TreePrinter {
+        TreePrinter
+    }
+
+    /// Renders the whole program.
+    pub fn print(&mut self, statements: &[Statement]) -> String {
+        let children = statements.iter()
+            .map(|s| self.visit_statement(s))
+            .collect();
+        let mut buffer = tree_node(String::from("Program"), children);
+        buffer.push('\n');
+        buffer
+    }
+}
+
+// The operator of a compound assignment, e.g. `+`, or nothing for a plain assignment.
+fn compound_operator(operator: Option<BinaryOperator>) -> String {
+    operator.map_or(String::new(), |operator| operator.to_string())
+}
+
+// The assignment operator of a compound assignment, e.g. ` +=`, or nothing for a plain assignment.
+fn compound_suffix(operator: Option<BinaryOperator>) -> String {
+    operator.map_or(String::new(), |operator| format!(" {}=", operator))
+}
+
+fn tree_node(label: String, children: Vec<String>) -> String {
+    let mut buffer = label;
+    let last = children.len();
+
+    for (index, child) in children.iter().enumerate() {
+        let is_last = index + 1 == last;
+
+        for (line_number, line) in child.lines().enumerate() {
+            buffer.push('\n');
+            buffer.push_str(match (line_number, is_last) {
+                (0, _) => "+-- ",
+                (_, false) => "|   ",
+                (_, true) => "    ",
+            });
+            buffer.push_str(line);
+        }
+    }
+
+    buffer
+}
+
+impl Visitor<String> for TreePrinter {
+    fn visit_identifier(&mut self, n: &Identifier) -> String {
+        format!("Identifier {} {}", n.name(), n.span())
+    }
+
+    fn visit_statement(&mut self, s: &Statement) -> String {
+        let (label, children) = match *s.kind() {
+            StatementKind::Assignment(ref i, ref e) =>
+                ("Assignment", vec![self.visit_identifier(i), self.visit_expression(e)]),
+            StatementKind::IndexAssignment(ref a, ref i, operator, ref e) => {
+                let children = vec![self.visit_expression(a), self.visit_expression(i), self.visit_expression(e)];
+                return tree_node(format!("IndexAssignment{} {}", compound_suffix(operator), s.span()), children);
+            },
+            StatementKind::Constant(ref i, ref e) =>
+                ("Constant", vec![self.visit_identifier(i), self.visit_expression(e)]),
+            StatementKind::Variable(ref i, ref e) =>
+                ("Variable", vec![self.visit_identifier(i), self.visit_expression(e)]),
+            StatementKind::FieldAssignment(ref r, ref f, operator, ref e) => {
+                let children = vec![self.visit_expression(r), self.visit_identifier(f), self.visit_expression(e)];
+                return tree_node(format!("FieldAssignment{} {}", compound_suffix(operator), s.span()), children);
+            },
+            StatementKind::Record(ref i, ref fields) => {
+                let mut children = vec![self.visit_identifier(i)];
+                children.extend(fields.iter().map(|f| self.visit_identifier(f)));
+                ("Record", children)
+            },
+            StatementKind::Import(ref path) => return tree_node(format!("Import {:?} {}", path, s.span()), vec![]),
+            StatementKind::Function(ref i, ref parameters, ref body) => {
+                let mut children = vec![self.visit_identifier(i)];
+                children.extend(parameters.iter().map(|p| self.visit_identifier(p)));
+                children.extend(body.iter().map(|s| self.visit_statement(s)));
+                ("Function", children)
+            },
+            StatementKind::Return(ref e) => ("Return", vec![self.visit_expression(e)]),
+            StatementKind::Try(ref body, ref i, ref handler) => {
+                let mut children: Vec<_> = body.iter().map(|s| self.visit_statement(s)).collect();
+                children.push(self.visit_identifier(i));
+                children.extend(handler.iter().map(|s| self.visit_statement(s)));
+                ("Try", children)
+            },
+            StatementKind::Throw(ref e) => ("Throw", vec![self.visit_expression(e)]),
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => {
+                let mut children = vec![self.visit_expression(condition)];
+                children.push(tree_node(String::from("Then"), then_branch.iter().map(|s| self.visit_statement(s)).collect()));
+
+                if !else_branch.is_empty() {
+                    children.push(tree_node(String::from("Else"), else_branch.iter().map(|s| self.visit_statement(s)).collect()));
+                }
+
+                ("If", children)
+            },
+            StatementKind::While(ref condition, ref body) => {
+                let mut children = vec![self.visit_expression(condition)];
+                children.extend(body.iter().map(|s| self.visit_statement(s)));
+                ("While", children)
+            },
+            StatementKind::ForRange(ref i, ref start, ref end, ref body) => {
+                let mut children = vec![self.visit_identifier(i), self.visit_expression(start), self.visit_expression(end)];
+                children.extend(body.iter().map(|s| self.visit_statement(s)));
+                ("ForRange", children)
+            },
+            StatementKind::ForEach(ref i, ref array, ref body) => {
+                let mut children = vec![self.visit_identifier(i), self.visit_expression(array)];
+                children.extend(body.iter().map(|s| self.visit_statement(s)));
+                ("ForEach", children)
+            },
+            StatementKind::Break => ("Break", vec![]),
+            StatementKind::Continue => ("Continue", vec![]),
+            StatementKind::Expression(ref e) =>
+                ("Expression", vec![self.visit_expression(e)]),
+        };
+
+        tree_node(format!("{} {}", label, s.span()), children)
+    }
+
+    fn visit_expression(&mut self, e: &Expression) -> String {
+        let (label, children) = match *e.kind() {
+            ExpressionKind::Nil => (String::from("Nil"), vec![]),
+            ExpressionKind::Integer(v) => (format!("Integer {}", v), vec![]),
+            ExpressionKind::Real(v) => (format!("Real {:?}", v), vec![]),
+            ExpressionKind::String(ref v) => (format!("String {:?}", v), vec![]),
+            ExpressionKind::InterpolatedString(ref parts) =>
+                (String::from("InterpolatedString"), parts.iter().map(|p| self.visit_expression(p)).collect()),
+            ExpressionKind::Character(v) => (format!("Character {:?}", v), vec![]),
+            ExpressionKind::Boolean(v) => (format!("Boolean {}", v), vec![]),
+            ExpressionKind::Identifier(ref i) =>
+                (String::from("Reference"), vec![self.visit_identifier(i)]),
+            ExpressionKind::FunctionCall(ref i, ref arguments) => {
+                let mut children = vec![self.visit_identifier(i)];
+                children.extend(arguments.iter().map(|a| self.visit_expression(a)));
+                (String::from("FunctionCall"), children)
+            },
+            ExpressionKind::Call(ref function, ref arguments) => {
+                let mut children = vec![self.visit_expression(function)];
+                children.extend(arguments.iter().map(|a| self.visit_expression(a)));
+                (String::from("Call"), children)
+            },
+            ExpressionKind::Function(ref parameters, ref body) => {
+                let mut children: Vec<String> = parameters.iter().map(|p| self.visit_identifier(p)).collect();
+                children.extend(body.iter().map(|s| self.visit_statement(s)));
+                (String::from("Function"), children)
+            },
+            ExpressionKind::Array(ref elements) =>
+                (String::from("Array"), elements.iter().map(|e| self.visit_expression(e)).collect()),
+            ExpressionKind::Index(ref array, ref index) =>
+                (String::from("Index"), vec![self.visit_expression(array), self.visit_expression(index)]),
+            ExpressionKind::RecordLiteral(ref i, ref fields) => {
+                let mut children = vec![self.visit_identifier(i)];
+
+                for (field, value) in fields {
+                    children.push(self.visit_identifier(field));
+                    children.push(self.visit_expression(value));
+                }
+
+                (String::from("RecordLiteral"), children)
+            },
+            ExpressionKind::Field(ref record, ref field) =>
+                (String::from("Field"), vec![self.visit_expression(record), self.visit_identifier(field)]),
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) =>
+                (format!("BinaryOperation {}", operator),
+                    vec![self.visit_expression(left), self.visit_expression(right)]),
+            ExpressionKind::UnaryOperation(operator, ref operand) =>
+                (format!("UnaryOperation {}", operator), vec![self.visit_expression(operand)]),
+            ExpressionKind::Match(ref value, ref arms) => {
+                let mut children = vec![self.visit_expression(value)];
+                children.extend(arms.iter().map(|(pattern, arm)| tree_node(
+                    format!("Arm {} {}", pattern_string(pattern), pattern.span()), vec![self.visit_expression(arm)])));
+                (String::from("Match"), children)
+            },
+        };
+
+        tree_node(format!("{} {}", label, e.span()), children)
+    }
+}
+
+/// Prints the AST as S-expressions, one line per statement, e.g. `(var x (+ 1 2))`.
+///
+/// Source positions are omitted so that the output only depends on the structure of the AST.
+#[derive(Default)]
+pub struct SExpressionPrinter;
+
+impl SExpressionPrinter {
+    pub fn new() -> SExpressionPrinter {
+        SExpressionPrinter
+    }
+
+    /// Renders the whole program.
+    pub fn print(&mut self, statements: &[Statement]) -> String {
+        let mut buffer = String::new();
+
+        for statement in statements {
+            buffer.push_str(&self.visit_statement(statement));
+            buffer.push('\n');
+        }
+
+        buffer
+    }
+}
+
+impl SExpressionPrinter {
+    // Renders the parameter list followed by the statements of the body, e.g. `(a b) (return a)`.
+    fn function(&mut self, parameters: &[Identifier], body: &[Statement]) -> String {
+        let parameters: Vec<String> = parameters.iter().map(|p| self.visit_identifier(p)).collect();
+        let mut buffer = format!("({})", parameters.join(" "));
+
+        for statement in body {
+            buffer.push(' ');
+            buffer.push_str(&self.visit_statement(statement));
+        }
+
+        buffer
+    }
+}
+
+impl SExpressionPrinter {
+    // Renders the statements separated by spaces.
+    fn block(&mut self, statements: &[Statement]) -> String {
+        let statements: Vec<String> = statements.iter().map(|s| self.visit_statement(s)).collect();
+        statements.join(" ")
+    }
+}
+
+impl Visitor<String> for SExpressionPrinter {
+    fn visit_identifier(&mut self, n: &Identifier) -> String {
+        n.name().to_string()
+    }
+
+    fn visit_statement(&mut self, s: &Statement) -> String {
+        match *s.kind() {
+            StatementKind::Assignment(ref i, ref e) =>
+                format!("(= {} {})", self.visit_identifier(i), self.visit_expression(e)),
+            StatementKind::IndexAssignment(ref a, ref i, operator, ref e) =>
+                format!("({}= (index {} {}) {})", compound_operator(operator),
+                    self.visit_expression(a), self.visit_expression(i), self.visit_expression(e)),
+            StatementKind::Constant(ref i, ref e) =>
+                format!("(const {} {})", self.visit_identifier(i), self.visit_expression(e)),
+            StatementKind::Variable(ref i, ref e) =>
+                format!("(var {} {})", self.visit_identifier(i), self.visit_expression(e)),
+            StatementKind::FieldAssignment(ref r, ref f, operator, ref e) =>
+                format!("({}= (field {} {}) {})", compound_operator(operator),
+                    self.visit_expression(r), self.visit_identifier(f), self.visit_expression(e)),
+            StatementKind::Record(ref i, ref fields) => {
+                let mut buffer = format!("(record {}", self.visit_identifier(i));
+
+                for field in fields {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_identifier(field));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+            StatementKind::Import(ref path) => format!("(import {:?})", path),
+            StatementKind::Function(ref i, ref parameters, ref body) =>
+                format!("(fun {} {})", self.visit_identifier(i), self.function(parameters, body)),
+            StatementKind::Return(ref e) => format!("(return {})", self.visit_expression(e)),
+            StatementKind::Try(ref body, ref i, ref handler) => {
+                let mut buffer = String::from("(try");
+
+                for statement in body {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_statement(statement));
+                }
+
+                buffer.push_str(&format!(" (catch {}", self.visit_identifier(i)));
+
+                for statement in handler {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_statement(statement));
+                }
+
+                buffer.push_str("))");
+                buffer
+            },
+            StatementKind::Throw(ref e) => format!("(throw {})", self.visit_expression(e)),
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => format!("(if {} ({}) ({}))",
+                self.visit_expression(condition), self.block(then_branch), self.block(else_branch)),
+            StatementKind::While(ref condition, ref body) =>
+                format!("(while {} {})", self.visit_expression(condition), self.block(body)),
+            StatementKind::ForRange(ref i, ref start, ref end, ref body) => format!("(for {} (range {} {}) {})",
+                self.visit_identifier(i), self.visit_expression(start), self.visit_expression(end), self.block(body)),
+            StatementKind::ForEach(ref i, ref array, ref body) => format!("(for {} {} {})",
+                self.visit_identifier(i), self.visit_expression(array), self.block(body)),
+            StatementKind::Break => String::from("(break)"),
+            StatementKind::Continue => String::from("(continue)"),
+            StatementKind::Expression(ref e) => self.visit_expression(e),
+        }
+    }
+
+    fn visit_expression(&mut self, e: &Expression) -> String {
+        match *e.kind() {
+            ExpressionKind::Nil => String::from("nil"),
+            ExpressionKind::Integer(v) => format!("{}", v),
+            ExpressionKind::Real(v) => format!("{:?}", v),
+            ExpressionKind::String(ref v) => format!("{:?}", v),
+            ExpressionKind::InterpolatedString(ref parts) => {
+                let mut buffer = String::from("(interpolate");
+
+                for part in parts {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_expression(part));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+            ExpressionKind::Character(v) => format!("{:?}", v),
+            ExpressionKind::Boolean(v) => format!("{}", v),
+            ExpressionKind::Identifier(ref i) => self.visit_identifier(i),
+            ExpressionKind::FunctionCall(ref i, ref arguments) => {
+                let mut buffer = format!("(call {}", self.visit_identifier(i));
+
+                for argument in arguments {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_expression(argument));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+            ExpressionKind::Call(ref function, ref arguments) => {
+                let mut buffer = format!("(call {}", self.visit_expression(function));
+
+                for argument in arguments {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_expression(argument));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+            ExpressionKind::Function(ref parameters, ref body) => format!("(fun {})", self.function(parameters, body)),
+            ExpressionKind::Array(ref elements) => {
+                let mut buffer = String::from("(array");
+
+                for element in elements {
+                    buffer.push(' ');
+                    buffer.push_str(&self.visit_expression(element));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+            ExpressionKind::Index(ref array, ref index) =>
+                format!("(index {} {})", self.visit_expression(array), self.visit_expression(index)),
+            ExpressionKind::RecordLiteral(ref i, ref fields) => {
+                let mut buffer = format!("(new {}", self.visit_identifier(i));
+
+                for (field, value) in fields {
+                    buffer.push_str(&format!(" ({} {})", self.visit_identifier(field), self.visit_expression(value)));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+            ExpressionKind::Field(ref record, ref field) =>
+                format!("(field {} {})", self.visit_expression(record), self.visit_identifier(field)),
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) =>
+                format!("({} {} {})", operator, self.visit_expression(left), self.visit_expression(right)),
+            ExpressionKind::UnaryOperation(operator, ref operand) =>
+                format!("({} {})", operator, self.visit_expression(operand)),
+            ExpressionKind::Match(ref value, ref arms) => {
+                let mut buffer = format!("(match {}", self.visit_expression(value));
+
+                for (pattern, arm) in arms {
+                    buffer.push_str(&format!(" ({} {})", pattern_string(pattern), self.visit_expression(arm)));
+                }
+
+                buffer.push(')');
+                buffer
+            },
+        }
+    }
+}
+
+// Renders a pattern like an S-expression, e.g. `(Point (x 0) (y y))`.
+fn pattern_string(pattern: &Pattern) -> String {
+    match *pattern.kind() {
+        PatternKind::Wildcard => String::from("_"),
+        PatternKind::Binding(ref i) => i.name().to_string(),
+        PatternKind::Integer(v) => format!("{}", v),
+        PatternKind::String(ref v) => format!("{:?}", v),
+        PatternKind::Boolean(v) => format!("{}", v),
+        PatternKind::Record(ref i, ref fields) => {
+            let mut buffer = format!("({}", i.name());
+
+            for (field, pattern) in fields {
+                buffer.push_str(&format!(" ({} {})", field.name(), pattern_string(pattern)));
+            }
+
+            buffer.push(')');
+            buffer
+        },
+    }
+}
+
+/// Prints the AST as JSON array with one object per statement.
+///
+/// Every node object has the properties `node` (kind of node) and `span`.
+#[derive(Default)]
+pub struct JsonPrinter;
+
+impl JsonPrinter {
+    pub fn new() -> JsonPrinter {
+        JsonPrinter
+    }
+
+    /// Renders the whole program.
+    pub fn print(&mut self, statements: &[Statement]) -> String {
+        let statements: Vec<String> = statements.iter()
+            .map(|s| self.visit_statement(s))
+            .collect();
+        format!("[{}]\n", statements.join(","))
+    }
+}
+
+fn json_span(span: Span) -> String {
+    format!(
+        "{{\"start\":{{\"line\":{},\"column\":{},\"offset\":{}}},\"end\":{{\"line\":{},\"column\":{},\"offset\":{}}}}}",
+        span.start().line(), span.start().column(), span.start_offset(),
+        span.end().line(), span.end().column(), span.end_offset())
+}
+
+// The operator of a compound assignment or `null` for a plain assignment.
+fn json_operator(operator: Option<BinaryOperator>) -> String {
+    operator.map_or(String::from("null"), |operator| json_string(&operator.to_string()))
+}
+
+fn json_string(value: &str) -> String {
+    let mut buffer = String::from("\"");
+
+    for ch in value.chars() {
+        match ch {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            c if (c as u32) < 0x20 => buffer.push_str(&format!("\\u{:04x}", c as u32)),
+            c => buffer.push(c),
+        }
+    }
+
+    buffer.push('"');
+    buffer
+}
+
+fn json_node(node: &str, span: Span, properties: Vec<(&str, String)>) -> String {
+    let mut buffer = format!("{{\"node\":{},\"span\":{}", json_string(node), json_span(span));
+
+    for (name, value) in properties {
+        buffer.push_str(&format!(",{}:{}", json_string(name), value));
+    }
+
+    buffer.push('}');
+    buffer
+}
+
+impl JsonPrinter {
+    fn function(&mut self, parameters: &[Identifier], body: &[Statement]) -> Vec<(&'static str, String)> {
+        let parameters: Vec<String> = parameters.iter()
+            .map(|p| self.visit_identifier(p))
+            .collect();
+        let body: Vec<String> = body.iter()
+            .map(|s| self.visit_statement(s))
+            .collect();
+        vec![("parameters", format!("[{}]", parameters.join(","))), ("body", format!("[{}]", body.join(",")))]
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> String {
+        let statements: Vec<String> = statements.iter().map(|s| self.visit_statement(s)).collect();
+        format!("[{}]", statements.join(","))
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> String {
+        let (node, properties) = match *pattern.kind() {
+            PatternKind::Wildcard => ("WildcardPattern", vec![]),
+            PatternKind::Binding(ref i) => ("BindingPattern", vec![("identifier", self.visit_identifier(i))]),
+            PatternKind::Integer(v) => ("IntegerPattern", vec![("value", format!("{}", v))]),
+            PatternKind::String(ref v) => ("StringPattern", vec![("value", json_string(v))]),
+            PatternKind::Boolean(v) => ("BooleanPattern", vec![("value", format!("{}", v))]),
+            PatternKind::Record(ref i, ref fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|(field, pattern)| format!(
+                        "{{\"field\":{},\"pattern\":{}}}", self.visit_identifier(field), self.pattern(pattern)))
+                    .collect();
+                ("RecordPattern", vec![("identifier", self.visit_identifier(i)), ("fields", format!("[{}]", fields.join(",")))])
+            },
+        };
+
+        json_node(node, pattern.span(), properties)
+    }
+}
+
+impl Visitor<String> for JsonPrinter {
+    fn visit_identifier(&mut self, n: &Identifier) -> String {
+        json_node("Identifier", n.span(), vec![("name", json_string(n.name()))])
+    }
+
+    fn visit_statement(&mut self, s: &Statement) -> String {
+        let (node, properties) = match *s.kind() {
+            StatementKind::Assignment(ref i, ref e) =>
+                ("Assignment", vec![("identifier", self.visit_identifier(i)), ("value", self.visit_expression(e))]),
+            StatementKind::IndexAssignment(ref a, ref i, operator, ref e) =>
+                ("IndexAssignment", vec![
+                    ("array", self.visit_expression(a)),
+                    ("index", self.visit_expression(i)),
+                    ("operator", json_operator(operator)),
+                    ("value", self.visit_expression(e))]),
+            StatementKind::Constant(ref i, ref e) =>
+                ("Constant", vec![("identifier", self.visit_identifier(i)), ("value", self.visit_expression(e))]),
+            StatementKind::Variable(ref i, ref e) =>
+                ("Variable", vec![("identifier", self.visit_identifier(i)), ("value", self.visit_expression(e))]),
+            StatementKind::FieldAssignment(ref r, ref f, operator, ref e) =>
+                ("FieldAssignment", vec![
+                    ("record", self.visit_expression(r)),
+                    ("field", self.visit_identifier(f)),
+                    ("operator", json_operator(operator)),
+                    ("value", self.visit_expression(e))]),
+            StatementKind::Record(ref i, ref fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|f| self.visit_identifier(f))
+                    .collect();
+                ("Record", vec![("identifier", self.visit_identifier(i)), ("fields", format!("[{}]", fields.join(",")))])
+            },
+            StatementKind::Import(ref path) => ("Import", vec![("path", json_string(path))]),
+            StatementKind::Function(ref i, ref parameters, ref body) => {
+                let mut properties = vec![("identifier", self.visit_identifier(i))];
+                properties.extend(self.function(parameters, body));
+                ("Function", properties)
+            },
+            StatementKind::Return(ref e) => ("Return", vec![("value", self.visit_expression(e))]),
+            StatementKind::Try(ref body, ref i, ref handler) => {
+                let body: Vec<String> = body.iter().map(|s| self.visit_statement(s)).collect();
+                let handler: Vec<String> = handler.iter().map(|s| self.visit_statement(s)).collect();
+                ("Try", vec![
+                    ("body", format!("[{}]", body.join(","))),
+                    ("identifier", self.visit_identifier(i)),
+                    ("handler", format!("[{}]", handler.join(",")))])
+            },
+            StatementKind::Throw(ref e) => ("Throw", vec![("value", self.visit_expression(e))]),
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => ("If", vec![
+                ("condition", self.visit_expression(condition)),
+                ("then", self.statements(then_branch)),
+                ("else", self.statements(else_branch))]),
+            StatementKind::While(ref condition, ref body) =>
+                ("While", vec![("condition", self.visit_expression(condition)), ("body", self.statements(body))]),
+            StatementKind::ForRange(ref i, ref start, ref end, ref body) => ("ForRange", vec![
+                ("identifier", self.visit_identifier(i)),
+                ("start", self.visit_expression(start)),
+                ("end", self.visit_expression(end)),
+                ("body", self.statements(body))]),
+            StatementKind::ForEach(ref i, ref array, ref body) => ("ForEach", vec![
+                ("identifier", self.visit_identifier(i)),
+                ("array", self.visit_expression(array)),
+                ("body", self.statements(body))]),
+            StatementKind::Break => ("Break", vec![]),
+            StatementKind::Continue => ("Continue", vec![]),
+            StatementKind::Expression(ref e) =>
+                ("Expression", vec![("expression", self.visit_expression(e))]),
+        };
+
+        json_node(node, s.span(), properties)
+    }
+
+    fn visit_expression(&mut self, e: &Expression) -> String {
+        let (node, properties) = match *e.kind() {
+            ExpressionKind::Nil => ("Nil", vec![]),
+            ExpressionKind::Integer(v) => ("Integer", vec![("value", format!("{}", v))]),
+            ExpressionKind::Real(v) => ("Real", vec![("value", format!("{:?}", v))]),
+            ExpressionKind::String(ref v) => ("String", vec![("value", json_string(v))]),
+            ExpressionKind::InterpolatedString(ref parts) => {
+                let parts: Vec<String> = parts.iter()
+                    .map(|p| self.visit_expression(p))
+                    .collect();
+                ("InterpolatedString", vec![("parts", format!("[{}]", parts.join(",")))])
+            },
+            ExpressionKind::Character(v) => ("Character", vec![("value", json_string(&v.to_string()))]),
+            ExpressionKind::Boolean(v) => ("Boolean", vec![("value", format!("{}", v))]),
+            ExpressionKind::Identifier(ref i) => ("Reference", vec![("identifier", self.visit_identifier(i))]),
+            ExpressionKind::FunctionCall(ref i, ref arguments) => {
+                let arguments: Vec<String> = arguments.iter()
+                    .map(|a| self.visit_expression(a))
+                    .collect();
+                ("FunctionCall", vec![
+                    ("identifier", self.visit_identifier(i)),
+                    ("arguments", format!("[{}]", arguments.join(",")))])
+            },
+            ExpressionKind::Call(ref function, ref arguments) => {
+                let arguments: Vec<String> = arguments.iter()
+                    .map(|a| self.visit_expression(a))
+                    .collect();
+                ("Call", vec![
+                    ("function", self.visit_expression(function)),
+                    ("arguments", format!("[{}]", arguments.join(",")))])
+            },
+            ExpressionKind::Function(ref parameters, ref body) => ("Function", self.function(parameters, body)),
+            ExpressionKind::Array(ref elements) => {
+                let elements: Vec<String> = elements.iter()
+                    .map(|e| self.visit_expression(e))
+                    .collect();
+                ("Array", vec![("elements", format!("[{}]", elements.join(",")))])
+            },
+            ExpressionKind::Index(ref array, ref index) =>
+                ("Index", vec![("array", self.visit_expression(array)), ("index", self.visit_expression(index))]),
+            ExpressionKind::RecordLiteral(ref i, ref fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|(field, value)| format!(
+                        "{{\"field\":{},\"value\":{}}}", self.visit_identifier(field), self.visit_expression(value)))
+                    .collect();
+                ("RecordLiteral", vec![("identifier", self.visit_identifier(i)), ("fields", format!("[{}]", fields.join(",")))])
+            },
+            ExpressionKind::Field(ref record, ref field) =>
+                ("Field", vec![("record", self.visit_expression(record)), ("field", self.visit_identifier(field))]),
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) =>
+                ("BinaryOperation", vec![
+                    ("operator", json_string(&operator.to_string())),
+                    ("left", self.visit_expression(left)),
+                    ("right", self.visit_expression(right))]),
+            ExpressionKind::UnaryOperation(operator, ref operand) =>
+                ("UnaryOperation", vec![
+                    ("operator", json_string(&operator.to_string())),
+                    ("operand", self.visit_expression(operand))]),
+            ExpressionKind::Match(ref value, ref arms) => {
+                let arms: Vec<String> = arms.iter()
+                    .map(|(pattern, arm)| format!(
+                        "{{\"pattern\":{},\"value\":{}}}", self.pattern(pattern), self.visit_expression(arm)))
+                    .collect();
+                ("Match", vec![("value", self.visit_expression(value)), ("arms", format!("[{}]", arms.join(",")))])
+            },
+        };
+
+        json_node(node, e.span(), properties)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use test_support::parse;
+
+    #[test]
+    fn format_from_str() {
+        assert_that!("tree".parse::<Format>(), is(equal_to(Ok(Format::Tree))));
+        assert_that!("sexp".parse::<Format>(), is(equal_to(Ok(Format::SExpression))));
+        assert_that!("json".parse::<Format>(), is(equal_to(Ok(Format::Json))));
+        assert_that!("xml".parse::<Format>(), is(equal_to(Err(String::from("Unknown AST format 'xml'!")))));
+    }
+
+    #[test]
+    fn print_tree() {
+        let statements = parse("var x = 1 + 2\nprintln(x)\n").unwrap();
+
+        assert_that!(&print(&statements, Format::Tree), is(equal_to(
+r#"Program
++-- Variable [1, 1]-[1, 14]
+|   +-- Identifier x [1, 5]-[1, 6]
+|   +-- BinaryOperation + [1, 9]-[1, 14]
+|       +-- Integer 1 [1, 9]-[1, 10]
+|       +-- Integer 2 [1, 13]-[1, 14]
++-- Expression [2, 1]-[2, 11]
+    +-- FunctionCall [2, 1]-[2, 11]
+        +-- Identifier println [2, 1]-[2, 8]
+        +-- Reference [2, 9]-[2, 10]
+            +-- Identifier x [2, 9]-[2, 10]
+"#)));
+    }
+
+    #[test]
+    fn print_s_expression() {
+        let statements = parse(
+            "var s = \"Hello\"\nconst c = 42\nx = not true or 1 * 2.5 >= 3\nprintln(s, x)\nvar y\n").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+r#"(var s "Hello")
+(const c 42)
+(= x (or (not true) (>= (* 1 2.5) 3)))
+(call println s x)
+(var y nil)
+"#)));
+    }
+
+    #[test]
+    fn print_try() {
+        let statements = parse("try {\n  throw \"x\"\n} catch e {\n  println(e)\n}\n").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to("(try (throw \"x\") (catch e (call println e)))\n")));
+    }
+
+    #[test]
+    fn print_match() {
+        let statements = parse("match x {\n  1 => \"one\"\n  P { x: 0, y } => y\n  _ => nil\n}\n").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+            "(match x (1 \"one\") ((P (x 0) (y y)) y) (_ nil))\n")));
+    }
+
+    #[test]
+    fn print_loops() {
+        let statements = parse("for i in 0..n {\n  if i > 1 { break } else { continue }\n}\nwhile true {}\nfor x in a {}\n").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+r#"(for i (range 0 n) (if (> i 1) ((break)) ((continue))))
+(while true )
+(for x a )
+"#)));
+    }
+
+    #[test]
+    fn print_arrays() {
+        let statements = parse("var a = [1, [2]]\na[0] += a[1][0]\n").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+r#"(var a (array 1 (array 2)))
+(+= (index a 0) (index (index a 1) 0))
+"#)));
+        assert_that!(&print(&statements[1..], Format::Tree), is(equal_to(
+r#"Program
++-- IndexAssignment += [2, 1]-[2, 16]
+    +-- Reference [2, 1]-[2, 2]
+    |   +-- Identifier a [2, 1]-[2, 2]
+    +-- Integer 0 [2, 3]-[2, 4]
+    +-- Index [2, 9]-[2, 16]
+        +-- Index [2, 9]-[2, 13]
+        |   +-- Reference [2, 9]-[2, 10]
+        |   |   +-- Identifier a [2, 9]-[2, 10]
+        |   +-- Integer 1 [2, 11]-[2, 12]
+        +-- Integer 0 [2, 14]-[2, 15]
+"#)));
+    }
+
+    #[test]
+    fn print_interpolated_string() {
+        let statements = parse("println(\"x = ${x}, y = ${f(y) + 1}!\")").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+r#"(call println (interpolate "x = " x ", y = " (+ (call f y) 1) "!"))
+"#)));
+    }
+
+    #[test]
+    fn print_records() {
+        let statements = parse("record P { x, y }\nvar p = P { y: 2, x: 1 }\np.x += p.y").unwrap();
+
+        assert_that!(&print(&statements, Format::SExpression), is(equal_to(
+r#"(record P x y)
+(var p (new P (y 2) (x 1)))
+(+= (field p x) (field p y))
+"#)));
+    }
+
+    #[test]
+    fn print_json() {
+        let statements = parse("x = \"ab\"").unwrap();
+
+        assert_that!(&print(&statements, Format::Json), is(equal_to(concat!(
+            r#"[{"node":"Assignment","span":{"start":{"line":1,"column":1,"offset":0},"end":{"line":1,"column":9,"offset":8}},"#,
+            r#""identifier":{"node":"Identifier","span":{"start":{"line":1,"column":1,"offset":0},"end":{"line":1,"column":2,"offset":1}},"name":"x"},"#,
+            r#""value":{"node":"String","span":{"start":{"line":1,"column":5,"offset":4},"end":{"line":1,"column":9,"offset":8}},"value":"ab"}}]"#,
+            "\n"))));
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_that!(&json_string("a\"b\\c\nd\u{1}"), is(equal_to(r#""a\"b\\c\nd\u0001""#)));
+    }
+}
@@ -0,0 +1,1154 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+
+use frontend::{Span, SyntaxError};
+use intermediate::ast::*;
+
+/// Static type of an expression.
+///
+/// The language is gradually typed: The type checker infers the types as far as possible and
+/// rejects operations which can never succeed. Everything it can't infer is `Any` and checked by
+/// the virtual machine at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    /// Statically unknown type.
+    Any,
+    /// Type of the nil literal.
+    Nil,
+    Integer,
+    Real,
+    Boolean,
+    Character,
+    String,
+    /// Array with the type of its elements.
+    Array(Box<Type>),
+    /// Record with the name of its declaration. The fields are untyped.
+    Record(String),
+    /// Function or closure. Parameters and result are untyped.
+    Function,
+}
+
+impl Type {
+    /// Determines if values of the given type may be assigned to a variable of this type.
+    pub fn is_assignable_from(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Array(element), Type::Array(other)) => element.is_assignable_from(other),
+            _ => self == other,
+        }
+    }
+
+    /// Get the common type of both types, which is `Any` if they differ.
+    pub fn join(&self, other: &Type) -> Type {
+        match (self, other) {
+            (Type::Array(element), Type::Array(other)) => Type::Array(Box::new(element.join(other))),
+            _ if self == other => self.clone(),
+            _ => Type::Any,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(*self, Type::Any | Type::Integer | Type::Real)
+    }
+
+    // Numbers are ordered among each other, characters among each other and strings
+    // lexicographically.
+    fn is_ordered_with(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Character, Type::Character) | (Type::String, Type::String) => true,
+            _ => self.is_numeric() && other.is_numeric(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Array(ref element) => write!(f, "Array<{}>", element),
+            Type::Record(ref name) => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Functions which are provided by the language itself.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Builtin {
+    /// Prints its argument.
+    Print,
+    /// Prints its argument followed by a new line.
+    PrintLn,
+    /// Get the number of elements of an array.
+    Length,
+}
+
+impl Builtin {
+    /// Get the builtin function with the given name.
+    pub fn for_name(name: &str) -> Option<Builtin> {
+        match name {
+            "print" => Some(Builtin::Print),
+            "println" => Some(Builtin::PrintLn),
+            "len" => Some(Builtin::Length),
+            _ => None,
+        }
+    }
+}
+
+/// Result of the semantic analysis.
+#[derive(Debug)]
+pub struct Analysis {
+    types: HashMap<Span, Type>,
+    variables: Vec<Variable>,
+    globals: HashMap<String, usize>,
+    references: HashMap<Span, usize>,
+    captures: HashMap<Span, Vec<usize>>,
+    records: HashMap<String, Vec<String>>,
+    warnings: Vec<SyntaxError>,
+}
+
+impl Analysis {
+    /// Get the inferred type of the given expression.
+    pub fn type_of(&self, expression: &Expression) -> &Type {
+        self.types.get(&expression.span()).unwrap_or(&Type::Any)
+    }
+
+    /// Get the type of the top level variable or constant with the given name.
+    pub fn variable_type(&self, name: &str) -> &Type {
+        self.globals.get(name).map_or(&Type::Any, |&id| &self.variables[id].value_type)
+    }
+
+    /// Get the variable the given identifier declares or refers to.
+    ///
+    /// Returns `None` if the identifier is no variable, e.g. the name of a called builtin function.
+    pub fn variable(&self, identifier: &Identifier) -> Option<&Variable> {
+        self.references.get(&identifier.span()).map(|&id| &self.variables[id])
+    }
+
+    /// Get the ids of the variables of enclosing functions which the function with the given span
+    /// captures. The order is the order of the upvalues of its closures.
+    pub fn captures(&self, function: Span) -> &[usize] {
+        self.captures.get(&function).map_or(&[], |captures| &captures[..])
+    }
+
+    /// Get the field names of the record with the given name in declaration order.
+    pub fn record_fields(&self, name: &str) -> Option<&[String]> {
+        self.records.get(name).map(|fields| &fields[..])
+    }
+
+    /// Get the problems which don't prevent the compilation, like unreachable match arms, in
+    /// source order.
+    pub fn warnings(&self) -> &[SyntaxError] {
+        &self.warnings
+    }
+}
+
+/// A declared variable, constant or parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    id: usize,
+    declaration: Span,
+    value_type: Type,
+    constant: bool,
+    captured: bool,
+}
+
+impl Variable {
+    /// Get the unique id of the variable. Variables with the same name in different functions
+    /// have different ids.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Get the span of the identifier which declares the variable.
+    pub fn declaration(&self) -> Span {
+        self.declaration
+    }
+
+    /// Get the type of the values the variable may hold.
+    pub fn value_type(&self) -> &Type {
+        &self.value_type
+    }
+
+    /// Determines if the variable is captured by a function declared in its scope.
+    pub fn is_captured(&self) -> bool {
+        self.captured
+    }
+}
+
+/// The variables declared at top level, in the body of a function or in a block.
+#[derive(Debug)]
+struct Scope {
+    symbols: HashMap<String, usize>,
+    // Variables of enclosing scopes referenced by the function or by functions nested in it.
+    captures: Vec<usize>,
+    // Blocks share the storage of the enclosing function, so their variables are not captured.
+    function: bool,
+}
+
+impl Scope {
+    fn new(function: bool) -> Scope {
+        Scope { symbols: HashMap::new(), captures: Vec::new(), function }
+    }
+}
+
+/// Checks the semantic of a program and infers the types of all expressions.
+///
+/// Rules:
+///
+/// * Variables and constants must be declared before they are referenced. Assigning an undeclared
+///   variable declares it.
+/// * A variable gets the type of its initial value, or `Any` if it has none. Only values of that
+///   type may be assigned later.
+/// * Constants may not be assigned.
+/// * Arithmetic operators require numbers. Mixing integers and reals results in a real.
+/// * There is no implicit truthiness: `and`, `or` and `not` require booleans. Comparisons result in
+///   booleans. Only numbers and characters are ordered, but all values may be compared for equality.
+/// * Only arrays may be indexed and only by integers. The type of an array literal is the common
+///   type of its elements.
+/// * Records must be declared before they are constructed. A record literal initializes each
+///   declared field exactly once. Fields are untyped and accessed by name: Unknown fields are
+///   rejected for values of a known record type, for all other values at runtime.
+/// * Each function has its own scope for its parameters and variables. Variables of enclosing
+///   scopes are visible and captured by the function. `return` is only allowed in functions.
+/// * `try` and `catch` blocks have their own scopes. The caught exception may be of any type.
+/// * Conditions of `if` and `while` must be booleans. Their blocks and the bodies of loops have
+///   their own scopes. A `for` loop declares its variable in the scope of the body: Integer for a
+///   range of integers, the element type for an array. `break` and `continue` are only allowed in
+///   loops of the current function.
+/// * Each arm of a `match` has its own scope for the variables bound by its pattern. Literal and
+///   record patterns must be able to match the type of the value. The type of the match is the
+///   common type of its arms. Unreachable arms and matches which don't cover all values are
+///   reported as warnings.
+/// * A called name is a builtin function unless a variable with this name is visible. Calls of
+///   values which are no functions are rejected if their type is known.
+pub struct TypeChecker {
+    variables: Vec<Variable>,
+    scopes: Vec<Scope>,
+    references: HashMap<Span, usize>,
+    captures: HashMap<Span, Vec<usize>>,
+    types: HashMap<Span, Type>,
+    records: HashMap<String, Vec<String>>,
+    warnings: Vec<SyntaxError>,
+    // Number of loops around the statement in the current function.
+    loops: usize,
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        TypeChecker {
+            variables: Vec::new(),
+            scopes: vec![Scope::new(false)],
+            references: HashMap::new(),
+            captures: HashMap::new(),
+            types: HashMap::new(),
+            records: HashMap::new(),
+            warnings: Vec::new(),
+            loops: 0,
+        }
+    }
+
+    /// Checks the given program.
+    ///
+    /// Returns an error for the first violated rule.
+    pub fn check(mut self, statements: &[Statement]) -> Result<Analysis, SyntaxError> {
+        for statement in statements {
+            self.check_statement(statement)?;
+        }
+
+        let globals = self.scopes.pop().expect("Top level scope!").symbols;
+
+        Ok(Analysis {
+            types: self.types,
+            variables: self.variables,
+            globals,
+            references: self.references,
+            captures: self.captures,
+            records: self.records,
+            warnings: self.warnings,
+        })
+    }
+
+    fn check_statement(&mut self, statement: &Statement) -> Result<(), SyntaxError> {
+        match *statement.kind() {
+            StatementKind::Variable(ref identifier, ref value) => self.declare(identifier, value, false),
+            StatementKind::Constant(ref identifier, ref value) => self.declare(identifier, value, true),
+            StatementKind::Assignment(ref identifier, ref value) => self.assign(identifier, value),
+            StatementKind::IndexAssignment(ref array, ref index, operator, ref value) => {
+                let element_type = self.check_index(array, index)?;
+                let value_type = self.check_expression(value)?;
+                // The result of a compound assignment is assigned, e.g. `a[i] + 1` for `a[i] += 1`.
+                let (value_type, span) = match operator {
+                    Some(operator) => (
+                        binary_type(operator, element_type.clone(), value_type, statement.span())?,
+                        statement.span()),
+                    None => (value_type, value.span()),
+                };
+
+                if !element_type.is_assignable_from(&value_type) {
+                    return Err(error(
+                        format!("Cannot assign {} to element of type {}!", value_type, element_type),
+                        span));
+                }
+
+                Ok(())
+            },
+            StatementKind::FieldAssignment(ref record, ref field, operator, ref value) => {
+                let field_type = self.check_field(record, field)?;
+                let value_type = self.check_expression(value)?;
+
+                if let Some(operator) = operator {
+                    binary_type(operator, field_type, value_type, statement.span())?;
+                }
+
+                Ok(())
+            },
+            StatementKind::Record(ref identifier, ref fields) => self.declare_record(identifier, fields),
+            StatementKind::Import(ref path) =>
+                Err(error(format!("Import of '{}' must be resolved by linking the modules!", path), statement.span())),
+            StatementKind::Function(ref identifier, ref parameters, ref body) => {
+                // Declared up front to allow recursive calls.
+                self.declare_variable(identifier, Type::Function, true)?;
+                self.check_function(parameters, body, statement.span())
+            },
+            StatementKind::Return(ref value) => {
+                if !self.scopes.iter().any(|scope| scope.function) {
+                    return Err(error(String::from("Return outside of function!"), statement.span()));
+                }
+
+                self.check_expression(value).map(|_| ())
+            },
+            StatementKind::Try(ref body, ref identifier, ref handler) => {
+                self.check_block(body, None)?;
+                self.check_block(handler, Some((identifier, Type::Any)))
+            },
+            StatementKind::Throw(ref value) => self.check_expression(value).map(|_| ()),
+            StatementKind::If(ref condition, ref then_branch, ref else_branch) => {
+                self.check_condition(condition)?;
+                self.check_block(then_branch, None)?;
+                self.check_block(else_branch, None)
+            },
+            StatementKind::While(ref condition, ref body) => {
+                self.check_condition(condition)?;
+                self.check_loop(body, None)
+            },
+            StatementKind::ForRange(ref identifier, ref start, ref end, ref body) => {
+                for bound in &[start, end] {
+                    let bound_type = self.check_expression(bound)?;
+
+                    if !Type::Integer.is_assignable_from(&bound_type) {
+                        return Err(error(format!("Range bound must be Integer but is {}!", bound_type), bound.span()));
+                    }
+                }
+
+                self.check_loop(body, Some((identifier, Type::Integer)))
+            },
+            StatementKind::ForEach(ref identifier, ref array, ref body) => {
+                let element_type = match self.check_expression(array)? {
+                    Type::Any => Type::Any,
+                    Type::Array(element_type) => *element_type,
+                    array_type => return Err(error(
+                        format!("Cannot iterate over value of type {}!", array_type), array.span())),
+                };
+
+                self.check_loop(body, Some((identifier, element_type)))
+            },
+            StatementKind::Break | StatementKind::Continue => {
+                if 0 == self.loops {
+                    let keyword = if StatementKind::Break == *statement.kind() { "Break" } else { "Continue" };
+                    return Err(error(format!("{} outside of loop!", keyword), statement.span()));
+                }
+
+                Ok(())
+            },
+            StatementKind::Expression(ref expression) => self.check_expression(expression).map(|_| ()),
+        }
+    }
+
+    // The block is checked in a new scope, optionally with a variable of the given type declared.
+    fn check_block(&mut self, statements: &[Statement], variable: Option<(&Identifier, Type)>)
+        -> Result<(), SyntaxError> {
+        self.scopes.push(Scope::new(false));
+
+        if let Some((identifier, variable_type)) = variable {
+            self.declare_variable(identifier, variable_type, false)?;
+        }
+
+        for statement in statements {
+            self.check_statement(statement)?;
+        }
+
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn check_loop(&mut self, body: &[Statement], variable: Option<(&Identifier, Type)>) -> Result<(), SyntaxError> {
+        self.loops += 1;
+        let result = self.check_block(body, variable);
+        self.loops -= 1;
+        result
+    }
+
+    fn check_condition(&mut self, condition: &Expression) -> Result<(), SyntaxError> {
+        let condition_type = self.check_expression(condition)?;
+
+        if !Type::Boolean.is_assignable_from(&condition_type) {
+            return Err(error(format!("Condition must be Boolean but is {}!", condition_type), condition.span()));
+        }
+
+        Ok(())
+    }
+
+    fn declare_record(&mut self, identifier: &Identifier, fields: &[Identifier]) -> Result<(), SyntaxError> {
+        if self.records.contains_key(identifier.name()) {
+            return Err(error(format!("Record '{}' is already declared!", identifier.name()), identifier.span()));
+        }
+
+        let mut names: Vec<String> = Vec::new();
+
+        for field in fields {
+            if names.iter().any(|name| name == field.name()) {
+                return Err(error(
+                    format!("Duplicate field '{}' in record '{}'!", field.name(), identifier.name()),
+                    field.span()));
+            }
+
+            names.push(field.name().to_string());
+        }
+
+        self.records.insert(identifier.name().to_string(), names);
+        Ok(())
+    }
+
+    // The function is checked in a new scope with the parameters declared.
+    fn check_function(&mut self, parameters: &[Identifier], body: &[Statement], span: Span)
+        -> Result<(), SyntaxError> {
+        self.scopes.push(Scope::new(true));
+        // Loops around the declaration can't be left from the body.
+        let loops = mem::replace(&mut self.loops, 0);
+        let result = self.check_function_body(parameters, body);
+        self.loops = loops;
+        result?;
+
+        let scope = self.scopes.pop().expect("Function scope!");
+        self.captures.insert(span, scope.captures);
+        Ok(())
+    }
+
+    fn check_function_body(&mut self, parameters: &[Identifier], body: &[Statement]) -> Result<(), SyntaxError> {
+        for parameter in parameters {
+            if self.current_scope().symbols.contains_key(parameter.name()) {
+                return Err(error(format!("Duplicate parameter '{}'!", parameter.name()), parameter.span()));
+            }
+
+            self.declare_variable(parameter, Type::Any, false)?;
+        }
+
+        for statement in body {
+            self.check_statement(statement)?;
+        }
+
+        Ok(())
+    }
+
+    fn declare(&mut self, identifier: &Identifier, value: &Expression, constant: bool) -> Result<(), SyntaxError> {
+        let value_type = self.check_expression(value)?;
+        self.declare_variable(identifier, variable_type(value_type), constant)
+    }
+
+    // Declares the variable in the current scope.
+    fn declare_variable(&mut self, identifier: &Identifier, value_type: Type, constant: bool)
+        -> Result<(), SyntaxError> {
+        if self.current_scope().symbols.contains_key(identifier.name()) {
+            return Err(error(format!("Variable '{}' is already declared!", identifier.name()), identifier.span()));
+        }
+
+        let id = self.variables.len();
+        self.variables.push(Variable { id, declaration: identifier.span(), value_type, constant, captured: false });
+        self.current_scope_mut().symbols.insert(identifier.name().to_string(), id);
+        self.references.insert(identifier.span(), id);
+        Ok(())
+    }
+
+    // Looks up the variable in the current and the enclosing scopes. A variable of an enclosing
+    // function is captured by the current function and all functions in between.
+    fn resolve(&mut self, identifier: &Identifier) -> Option<usize> {
+        let depth = self.scopes.iter().rposition(|scope| scope.symbols.contains_key(identifier.name()))?;
+        let id = self.scopes[depth].symbols[identifier.name()];
+
+        for scope in self.scopes[depth + 1..].iter_mut().filter(|scope| scope.function) {
+            self.variables[id].captured = true;
+
+            if !scope.captures.contains(&id) {
+                scope.captures.push(id);
+            }
+        }
+
+        self.references.insert(identifier.span(), id);
+        Some(id)
+    }
+
+    fn current_scope(&self) -> &Scope {
+        self.scopes.last().expect("Top level scope!")
+    }
+
+    fn current_scope_mut(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("Top level scope!")
+    }
+
+    fn assign(&mut self, identifier: &Identifier, value: &Expression) -> Result<(), SyntaxError> {
+        let value_type = self.check_expression(value)?;
+
+        let symbol = match self.resolve(identifier) {
+            Some(id) => &self.variables[id],
+            None => return self.declare_variable(identifier, variable_type(value_type), false),
+        };
+
+        if symbol.constant {
+            return Err(error(format!("Cannot assign to constant '{}'!", identifier.name()), identifier.span()));
+        }
+
+        if !symbol.value_type.is_assignable_from(&value_type) {
+            return Err(error(
+                format!("Cannot assign {} to variable '{}' of type {}!", value_type, identifier.name(), symbol.value_type),
+                value.span()));
+        }
+
+        Ok(())
+    }
+
+    fn check_expression(&mut self, expression: &Expression) -> Result<Type, SyntaxError> {
+        let expression_type = match *expression.kind() {
+            ExpressionKind::Nil => Type::Nil,
+            ExpressionKind::Integer(_) => Type::Integer,
+            ExpressionKind::Real(_) => Type::Real,
+            ExpressionKind::String(_) => Type::String,
+            ExpressionKind::InterpolatedString(ref parts) => {
+                for part in parts {
+                    self.check_expression(part)?;
+                }
+
+                Type::String
+            },
+            ExpressionKind::Character(_) => Type::Character,
+            ExpressionKind::Boolean(_) => Type::Boolean,
+            ExpressionKind::Identifier(ref identifier) => match self.resolve(identifier) {
+                Some(id) => self.variables[id].value_type.clone(),
+                None => return Err(error(
+                    format!("Undeclared variable '{}'!", identifier.name()), identifier.span())),
+            },
+            ExpressionKind::FunctionCall(ref identifier, ref arguments) => match self.resolve(identifier) {
+                Some(id) => {
+                    let function_type = self.variables[id].value_type.clone();
+                    self.check_call(&function_type, arguments, identifier.span())?
+                },
+                None => self.check_function_call(identifier, arguments)?,
+            },
+            ExpressionKind::Call(ref function, ref arguments) => {
+                let function_type = self.check_expression(function)?;
+                self.check_call(&function_type, arguments, function.span())?
+            },
+            ExpressionKind::Function(ref parameters, ref body) => {
+                self.check_function(parameters, body, expression.span())?;
+                Type::Function
+            },
+            ExpressionKind::Array(ref elements) => {
+                let mut element_type = None;
+
+                for element in elements {
+                    let current = self.check_expression(element)?;
+                    element_type = Some(match element_type {
+                        Some(previous) => current.join(&previous),
+                        None => current,
+                    });
+                }
+
+                Type::Array(Box::new(element_type.unwrap_or(Type::Any)))
+            },
+            ExpressionKind::Index(ref array, ref index) => self.check_index(array, index)?,
+            ExpressionKind::RecordLiteral(ref identifier, ref fields) =>
+                self.check_record_literal(identifier, fields, expression.span())?,
+            ExpressionKind::Field(ref record, ref field) => self.check_field(record, field)?,
+            ExpressionKind::BinaryOperation(operator, ref left, ref right) =>
+                self.check_binary_operation(operator, left, right, expression.span())?,
+            ExpressionKind::UnaryOperation(operator, ref operand) => {
+                let operand_type = self.check_expression(operand)?;
+
+                match operator {
+                    UnaryOperator::Negate | UnaryOperator::Plus if operand_type.is_numeric() => operand_type,
+                    UnaryOperator::Not if Type::Boolean.is_assignable_from(&operand_type) => Type::Boolean,
+                    _ => return Err(error(
+                        format!("Operator '{}' can not be applied to {}!", operator, operand_type),
+                        expression.span())),
+                }
+            },
+            ExpressionKind::Match(ref value, ref arms) => self.check_match(value, arms, expression.span())?,
+        };
+
+        self.types.insert(expression.span(), expression_type.clone());
+        Ok(expression_type)
+    }
+
+    fn check_function_call(&mut self, identifier: &Identifier, arguments: &[Expression]) -> Result<Type, SyntaxError> {
+        let builtin = match Builtin::for_name(identifier.name()) {
+            Some(builtin) => builtin,
+            None => return Err(error(format!("Unknown function '{}'!", identifier.name()), identifier.span())),
+        };
+
+        if 1 != arguments.len() {
+            return Err(error(
+                format!("Function '{}' expects 1 argument but got {}!", identifier.name(), arguments.len()),
+                identifier.span()));
+        }
+
+        let argument_type = self.check_expression(&arguments[0])?;
+
+        match builtin {
+            Builtin::Print | Builtin::PrintLn => Ok(Type::Nil),
+            Builtin::Length => match argument_type {
+                Type::Any | Type::Array(_) => Ok(Type::Integer),
+                _ => Err(error(
+                    format!("Function 'len' expects an Array but got {}!", argument_type),
+                    arguments[0].span())),
+            },
+        }
+    }
+
+    // The result of a call of a function value is statically unknown.
+    fn check_call(&mut self, function_type: &Type, arguments: &[Expression], span: Span) -> Result<Type, SyntaxError> {
+        if !Type::Function.is_assignable_from(function_type) {
+            return Err(error(format!("Cannot call value of type {}!", function_type), span));
+        }
+
+        for argument in arguments {
+            self.check_expression(argument)?;
+        }
+
+        Ok(Type::Any)
+    }
+
+    fn check_record_literal(&mut self, identifier: &Identifier, fields: &[(Identifier, Expression)], span: Span)
+        -> Result<Type, SyntaxError> {
+        let declared = match self.records.get(identifier.name()) {
+            Some(declared) => declared.clone(),
+            None => return Err(error(format!("Unknown record '{}'!", identifier.name()), identifier.span())),
+        };
+
+        for (index, (field, value)) in fields.iter().enumerate() {
+            if !declared.iter().any(|name| name == field.name()) {
+                return Err(error(
+                    format!("Record '{}' has no field '{}'!", identifier.name(), field.name()), field.span()));
+            }
+
+            if fields[..index].iter().any(|(other, _)| other.name() == field.name()) {
+                return Err(error(format!("Field '{}' is already initialized!", field.name()), field.span()));
+            }
+
+            self.check_expression(value)?;
+        }
+
+        if let Some(missing) = declared.iter().find(|name| !fields.iter().any(|(f, _)| f.name() == name.as_str())) {
+            return Err(error(format!("Missing field '{}' of record '{}'!", missing, identifier.name()), span));
+        }
+
+        Ok(Type::Record(identifier.name().to_string()))
+    }
+
+    fn check_match(&mut self, value: &Expression, arms: &[(Pattern, Expression)], span: Span)
+        -> Result<Type, SyntaxError> {
+        let value_type = self.check_expression(value)?;
+        let mut result_type: Option<Type> = None;
+        let mut exhaustive = false;
+        // Keys of the patterns of the previous arms.
+        let mut seen = HashSet::new();
+
+        for (pattern, arm) in arms {
+            self.scopes.push(Scope::new(false));
+            self.check_pattern(pattern, &value_type)?;
+            let arm_type = self.check_expression(arm)?;
+            self.scopes.pop();
+
+            if !seen.insert(pattern_key(pattern)) || exhaustive {
+                self.warnings.push(error(String::from("Unreachable match arm!"), pattern.span()));
+            }
+
+            // Both booleans together cover all values of type Boolean.
+            exhaustive = exhaustive || covers(pattern, &value_type) || match (pattern.kind(), &value_type) {
+                (PatternKind::Boolean(b), Type::Boolean) => seen.contains(&(!b).to_string()),
+                _ => false,
+            };
+            result_type = Some(match result_type {
+                Some(previous) => arm_type.join(&previous),
+                None => arm_type,
+            });
+        }
+
+        if !exhaustive {
+            self.warnings.push(error(String::from("Match is not exhaustive!"), span));
+        }
+
+        Ok(result_type.unwrap_or(Type::Nil))
+    }
+
+    // Declares the variables bound by the pattern in the current scope.
+    fn check_pattern(&mut self, pattern: &Pattern, value_type: &Type) -> Result<(), SyntaxError> {
+        let pattern_type = match *pattern.kind() {
+            PatternKind::Wildcard => return Ok(()),
+            PatternKind::Binding(ref identifier) =>
+                return self.declare_variable(identifier, value_type.clone(), false),
+            PatternKind::Integer(_) => Type::Integer,
+            PatternKind::String(_) => Type::String,
+            PatternKind::Boolean(_) => Type::Boolean,
+            PatternKind::Record(ref identifier, ref fields) => {
+                let declared = match self.records.get(identifier.name()) {
+                    Some(declared) => declared.clone(),
+                    None => return Err(error(format!("Unknown record '{}'!", identifier.name()), identifier.span())),
+                };
+
+                for (index, (field, field_pattern)) in fields.iter().enumerate() {
+                    if !declared.iter().any(|name| name == field.name()) {
+                        return Err(error(
+                            format!("Record '{}' has no field '{}'!", identifier.name(), field.name()), field.span()));
+                    }
+
+                    if fields[..index].iter().any(|(other, _)| other.name() == field.name()) {
+                        return Err(error(format!("Field '{}' is already matched!", field.name()), field.span()));
+                    }
+
+                    self.check_pattern(field_pattern, &Type::Any)?;
+                }
+
+                Type::Record(identifier.name().to_string())
+            },
+        };
+
+        if !value_type.is_assignable_from(&pattern_type) {
+            return Err(error(
+                format!("Pattern of type {} can not match value of type {}!", pattern_type, value_type),
+                pattern.span()));
+        }
+
+        Ok(())
+    }
+
+    // Returns the type of the field which is always `Any` because fields are untyped.
+    fn check_field(&mut self, record: &Expression, field: &Identifier) -> Result<Type, SyntaxError> {
+        match self.check_expression(record)? {
+            Type::Any => Ok(Type::Any),
+            Type::Record(ref name) => {
+                if !self.records[name].iter().any(|declared| declared == field.name()) {
+                    return Err(error(format!("Record '{}' has no field '{}'!", name, field.name()), field.span()));
+                }
+
+                Ok(Type::Any)
+            },
+            record_type => Err(error(
+                format!("Cannot access field '{}' of value of type {}!", field.name(), record_type), field.span())),
+        }
+    }
+
+    // Returns the type of the element.
+    fn check_index(&mut self, array: &Expression, index: &Expression) -> Result<Type, SyntaxError> {
+        let array_type = self.check_expression(array)?;
+        let index_type = self.check_expression(index)?;
+
+        if !Type::Integer.is_assignable_from(&index_type) {
+            return Err(error(format!("Array index must be Integer but is {}!", index_type), index.span()));
+        }
+
+        match array_type {
+            Type::Any => Ok(Type::Any),
+            Type::Array(element) => Ok(*element),
+            _ => Err(error(format!("Cannot index value of type {}!", array_type), array.span())),
+        }
+    }
+
+    fn check_binary_operation(&mut self, operator: BinaryOperator, left: &Expression, right: &Expression, span: Span)
+        -> Result<Type, SyntaxError> {
+        let left_type = self.check_expression(left)?;
+        let right_type = self.check_expression(right)?;
+        binary_type(operator, left_type, right_type, span)
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> TypeChecker {
+        TypeChecker::new()
+    }
+}
+
+// Determines the type of the result of the operator applied to operands of the given types.
+fn binary_type(operator: BinaryOperator, left_type: Type, right_type: Type, span: Span) -> Result<Type, SyntaxError> {
+    match operator {
+        // A string joined with a statically unknown value can only succeed if it is a string.
+        BinaryOperator::Add if Type::String.is_assignable_from(&left_type)
+            && Type::String.is_assignable_from(&right_type)
+            && (Type::String == left_type || Type::String == right_type) => Ok(Type::String),
+        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply
+            | BinaryOperator::Divide | BinaryOperator::Modulo => {
+            if !left_type.is_numeric() || !right_type.is_numeric() {
+                return Err(error(
+                    format!("Operator '{}' can not be applied to {} and {}!", operator, left_type, right_type),
+                    span));
+            }
+
+            Ok(match (left_type, right_type) {
+                (Type::Integer, Type::Integer) => Type::Integer,
+                (Type::Any, _) | (_, Type::Any) => Type::Any,
+                _ => Type::Real,
+            })
+        },
+        BinaryOperator::And | BinaryOperator::Or => {
+            if !Type::Boolean.is_assignable_from(&left_type) || !Type::Boolean.is_assignable_from(&right_type) {
+                return Err(error(
+                    format!("Operator '{}' can not be applied to {} and {}!", operator, left_type, right_type),
+                    span));
+            }
+
+            Ok(Type::Boolean)
+        },
+        BinaryOperator::LessThan | BinaryOperator::LessThanEqual
+            | BinaryOperator::GreaterThan | BinaryOperator::GreaterThanEqual => {
+            if !left_type.is_ordered_with(&right_type) {
+                return Err(error(
+                    format!("Operator '{}' can not be applied to {} and {}!", operator, left_type, right_type),
+                    span));
+            }
+
+            Ok(Type::Boolean)
+        },
+        BinaryOperator::Equal | BinaryOperator::NotEqual => Ok(Type::Boolean),
+        BinaryOperator::Assign => Ok(right_type),
+    }
+}
+
+// Determines if the pattern matches all values of the given type.
+fn covers(pattern: &Pattern, value_type: &Type) -> bool {
+    match (pattern.kind(), value_type) {
+        (PatternKind::Record(identifier, fields), Type::Record(name)) =>
+            identifier.name() == name && fields.iter().all(|(_, pattern)| pattern.is_irrefutable()),
+        _ => pattern.is_irrefutable(),
+    }
+}
+
+// Describes the values matched by the pattern regardless of its source position, the names of
+// its bindings and the order of the fields, so patterns with equal keys match the same values.
+fn pattern_key(pattern: &Pattern) -> String {
+    match *pattern.kind() {
+        PatternKind::Wildcard | PatternKind::Binding(_) => String::from("_"),
+        PatternKind::Integer(value) => value.to_string(),
+        PatternKind::String(ref value) => format!("{:?}", value),
+        PatternKind::Boolean(value) => value.to_string(),
+        PatternKind::Record(ref identifier, ref fields) => {
+            let mut fields: Vec<String> = fields.iter()
+                .filter(|(_, pattern)| !pattern.is_irrefutable())
+                .map(|(field, pattern)| format!("{}: {}", field.name(), pattern_key(pattern)))
+                .collect();
+            fields.sort();
+            format!("{} {{ {} }}", identifier.name(), fields.join(", "))
+        },
+    }
+}
+
+// Variables without initial value may hold values of any type.
+fn variable_type(value_type: Type) -> Type {
+    match value_type {
+        Type::Nil => Type::Any,
+        _ => value_type,
+    }
+}
+
+fn error(message: String, span: Span) -> SyntaxError {
+    SyntaxError::new(message, span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hamcrest::prelude::*;
+    use test_support::parse;
+
+    fn check(src: &str) -> Result<Analysis, SyntaxError> {
+        TypeChecker::new().check(&parse(src).unwrap())
+    }
+
+    fn error_message(src: &str) -> String {
+        check(src).unwrap_err().message().to_string()
+    }
+
+    fn expression_type(src: &str) -> Type {
+        let statements = parse(src).unwrap();
+        let analysis = TypeChecker::new().check(&statements).unwrap();
+
+        match *statements.last().unwrap().kind() {
+            StatementKind::Expression(ref expression) => analysis.type_of(expression).clone(),
+            _ => panic!("Expected expression statement!"),
+        }
+    }
+
+    #[test]
+    fn type_is_assignable_from() {
+        assert_that!(Type::Integer.is_assignable_from(&Type::Integer), is(true));
+        assert_that!(Type::Integer.is_assignable_from(&Type::Any), is(true));
+        assert_that!(Type::Any.is_assignable_from(&Type::Real), is(true));
+        assert_that!(Type::Array(Box::new(Type::Integer)).is_assignable_from(&Type::Array(Box::new(Type::Any))), is(true));
+
+        assert_that!(Type::Integer.is_assignable_from(&Type::Real), is(false));
+        assert_that!(Type::Integer.is_assignable_from(&Type::Nil), is(false));
+        assert_that!(Type::Array(Box::new(Type::Integer)).is_assignable_from(&Type::Array(Box::new(Type::Real))), is(false));
+    }
+
+    #[test]
+    fn type_join() {
+        assert_that!(Type::Integer.join(&Type::Integer), is(equal_to(Type::Integer)));
+        assert_that!(Type::Integer.join(&Type::Real), is(equal_to(Type::Any)));
+        assert_that!(Type::Array(Box::new(Type::Integer)).join(&Type::Array(Box::new(Type::Real))),
+            is(equal_to(Type::Array(Box::new(Type::Any)))));
+    }
+
+    #[test]
+    fn type_fmt() {
+        assert_that!(&Type::Integer.to_string(), is(equal_to("Integer")));
+        assert_that!(&Type::Array(Box::new(Type::Array(Box::new(Type::Real)))).to_string(),
+            is(equal_to("Array<Array<Real>>")));
+    }
+
+    #[test]
+    fn infer_arithmetic() {
+        assert_that!(expression_type("1 + 2 * 3"), is(equal_to(Type::Integer)));
+        assert_that!(expression_type("1 + 2.0"), is(equal_to(Type::Real)));
+        assert_that!(expression_type("var x\nx * 2"), is(equal_to(Type::Any)));
+        assert_that!(expression_type("-1.5"), is(equal_to(Type::Real)));
+    }
+
+    #[test]
+    fn infer_booleans() {
+        assert_that!(expression_type("true and not false"), is(equal_to(Type::Boolean)));
+        assert_that!(expression_type("1 < 2.5 or 'a' >= 'b'"), is(equal_to(Type::Boolean)));
+        assert_that!(expression_type("[1] != 1"), is(equal_to(Type::Boolean)));
+        assert_that!(expression_type("var x\nx or x"), is(equal_to(Type::Boolean)));
+    }
+
+    #[test]
+    fn infer_arrays() {
+        assert_that!(expression_type("[1, 2]"), is(equal_to(Type::Array(Box::new(Type::Integer)))));
+        assert_that!(expression_type("[1, 'a']"), is(equal_to(Type::Array(Box::new(Type::Any)))));
+        assert_that!(expression_type("[]"), is(equal_to(Type::Array(Box::new(Type::Any)))));
+        assert_that!(expression_type("var a = [[1.5]]\na[0][0]"), is(equal_to(Type::Real)));
+        assert_that!(expression_type("len([])"), is(equal_to(Type::Integer)));
+    }
+
+    #[test]
+    fn infer_strings() {
+        assert_that!(expression_type("\"a\" + \"b\""), is(equal_to(Type::String)));
+        assert_that!(expression_type("var x\n\"a\" + x"), is(equal_to(Type::String)));
+        assert_that!(expression_type("var x = 1\n\"x = ${x + 1}\""), is(equal_to(Type::String)));
+        assert_that!(expression_type("\"a\" < \"b\""), is(equal_to(Type::Boolean)));
+    }
+
+    #[test]
+    fn infer_variables() {
+        assert_that!(expression_type("var x = 'a'\nx"), is(equal_to(Type::Character)));
+        assert_that!(expression_type("const x = 1.5\nx"), is(equal_to(Type::Real)));
+        assert_that!(expression_type("x = [1]\nx"), is(equal_to(Type::Array(Box::new(Type::Integer)))));
+        assert_that!(expression_type("var x\nx = 1\nx"), is(equal_to(Type::Any)));
+    }
+
+    #[test]
+    fn analysis_variable_type() {
+        let analysis = check("var x = 1\nvar y\nconst z = [1.5]").unwrap();
+
+        assert_that!(analysis.variable_type("x"), is(equal_to(&Type::Integer)));
+        assert_that!(analysis.variable_type("y"), is(equal_to(&Type::Any)));
+        let expected = Type::Array(Box::new(Type::Real));
+        assert_that!(analysis.variable_type("z"), is(equal_to(&expected)));
+    }
+
+    #[test]
+    fn check_valid_program() {
+        assert_that!(check("var a = [1, 2]\na[0] += 1\nvar x\nx = 'a'\nx = 1\nprintln(len(a))").is_ok(), is(true));
+    }
+
+    #[test]
+    fn check_undeclared_variable() {
+        let error = check("var x = y").unwrap_err();
+
+        assert_that!(error.message(), is(equal_to("Undeclared variable 'y'!")));
+        assert_that!(error.span().start().column(), is(equal_to(9)));
+    }
+
+    #[test]
+    fn check_declarations() {
+        assert_that!(&error_message("var x = 1\nvar x = 2"), is(equal_to("Variable 'x' is already declared!")));
+        assert_that!(&error_message("const x = 1\nx = 2"), is(equal_to("Cannot assign to constant 'x'!")));
+        assert_that!(&error_message("var x = 1\nx = 2.5"), is(equal_to("Cannot assign Real to variable 'x' of type Integer!")));
+        assert_that!(&error_message("var x = 1\nx = [1]"), is(equal_to("Cannot assign Array<Integer> to variable 'x' of type Integer!")));
+    }
+
+    #[test]
+    fn check_operators() {
+        assert_that!(&error_message("1 + 'a'"), is(equal_to("Operator '+' can not be applied to Integer and Character!")));
+        assert_that!(&error_message("\"a\" + 1"), is(equal_to("Operator '+' can not be applied to String and Integer!")));
+        assert_that!(&error_message("\"a\" - \"b\""), is(equal_to("Operator '-' can not be applied to String and String!")));
+        assert_that!(&error_message("\"${y}\""), is(equal_to("Undeclared variable 'y'!")));
+        assert_that!(&error_message("-[1]"), is(equal_to("Operator '-' can not be applied to Array<Integer>!")));
+    }
+
+    #[test]
+    fn check_truthiness() {
+        assert_that!(&error_message("1 and true"), is(equal_to("Operator 'and' can not be applied to Integer and Boolean!")));
+        assert_that!(&error_message("true or [1]"), is(equal_to("Operator 'or' can not be applied to Boolean and Array<Integer>!")));
+        assert_that!(&error_message("not 0"), is(equal_to("Operator 'not' can not be applied to Integer!")));
+        assert_that!(&error_message("true < false"), is(equal_to("Operator '<' can not be applied to Boolean and Boolean!")));
+        assert_that!(&error_message("'a' > 1"), is(equal_to("Operator '>' can not be applied to Character and Integer!")));
+    }
+
+    #[test]
+    fn check_arrays() {
+        assert_that!(&error_message("var x = 1\nx[0]"), is(equal_to("Cannot index value of type Integer!")));
+        assert_that!(&error_message("[1][1.5]"), is(equal_to("Array index must be Integer but is Real!")));
+        assert_that!(&error_message("var a = [1]\na[0] = 'a'"), is(equal_to("Cannot assign Character to element of type Integer!")));
+    }
+
+    #[test]
+    fn check_records() {
+        assert_that!(check("record P { x, y }\nvar p = P { y: 1, x: 'a' }\np.x = p.y\nvar q\nq.z = 1").is_ok(), is(true));
+        assert_that!(expression_type("record P { x }\nP { x: 1 }"), is(equal_to(Type::Record(String::from("P")))));
+        assert_that!(expression_type("record P { x }\nvar p = P { x: 1 }\np.x"), is(equal_to(Type::Any)));
+
+        assert_that!(&error_message("record P { x }\nrecord P { y }"), is(equal_to("Record 'P' is already declared!")));
+        assert_that!(&error_message("record P { x, x }"), is(equal_to("Duplicate field 'x' in record 'P'!")));
+        assert_that!(&error_message("Q { x: 1 }"), is(equal_to("Unknown record 'Q'!")));
+        assert_that!(&error_message("record P { x }\nP { y: 1 }"), is(equal_to("Record 'P' has no field 'y'!")));
+        assert_that!(&error_message("record P { x }\nP { x: 1, x: 2 }"), is(equal_to("Field 'x' is already initialized!")));
+        assert_that!(&error_message("record P { x, y }\nP { x: 1 }"), is(equal_to("Missing field 'y' of record 'P'!")));
+        assert_that!(&error_message("record P { x }\nP { x: 1 }.y"), is(equal_to("Record 'P' has no field 'y'!")));
+        assert_that!(&error_message("var a = [1]\na.x = 1"), is(equal_to("Cannot access field 'x' of value of type Array<Integer>!")));
+        assert_that!(&error_message("record P { x }\nvar p = P { x: 1 }\np = 1"), is(equal_to("Cannot assign Integer to variable 'p' of type P!")));
+    }
+
+    #[test]
+    fn check_function_calls() {
+        assert_that!(&error_message("foo(1)"), is(equal_to("Unknown function 'foo'!")));
+        assert_that!(&error_message("println(1, 2)"), is(equal_to("Function 'println' expects 1 argument but got 2!")));
+        assert_that!(&error_message("len(1)"), is(equal_to("Function 'len' expects an Array but got Integer!")));
+    }
+
+    #[test]
+    fn check_functions() {
+        assert_that!(check("var x = 1\nfun f(x) {\n  return f(x)\n}\nf(1)(2)").is_ok(), is(true));
+        assert_that!(expression_type("fun() {}"), is(equal_to(Type::Function)));
+        assert_that!(expression_type("fun f() {}\nf()"), is(equal_to(Type::Any)));
+
+        assert_that!(&error_message("return 1"), is(equal_to("Return outside of function!")));
+        assert_that!(&error_message("fun f(a, a) {}"), is(equal_to("Duplicate parameter 'a'!")));
+        assert_that!(&error_message("var x = 1\nx()"), is(equal_to("Cannot call value of type Integer!")));
+        assert_that!(&error_message("fun f() {}\nf = 1"), is(equal_to("Cannot assign to constant 'f'!")));
+        assert_that!(&error_message("fun f() {\n  var y\n}\ny"), is(equal_to("Undeclared variable 'y'!")));
+    }
+
+    #[test]
+    fn check_exceptions() {
+        assert_that!(check("try {\n  throw 1\n} catch e {\n  e = 2\n}\ntry {} catch e { throw e }").is_ok(), is(true));
+
+        assert_that!(&error_message("try {} catch e {}\nprintln(e)"), is(equal_to("Undeclared variable 'e'!")));
+        assert_that!(&error_message("try { var x = 1 } catch e {}\nx"), is(equal_to("Undeclared variable 'x'!")));
+        assert_that!(&error_message("try {} catch e { var e }"), is(equal_to("Variable 'e' is already declared!")));
+        assert_that!(&error_message("throw y"), is(equal_to("Undeclared variable 'y'!")));
+        assert_that!(&error_message("try { return } catch e {}"), is(equal_to("Return outside of function!")));
+    }
+
+    #[test]
+    fn check_match() {
+        assert_that!(expression_type("match 1 { 1 => \"one\", n => \"${n}\" }"), is(equal_to(Type::String)));
+        assert_that!(expression_type("match 1 { 1 => \"one\", _ => 2 }"), is(equal_to(Type::Any)));
+        assert_that!(expression_type("var x\nmatch x { 1 => 1, \"a\" => 2, true => 3, _ => 4 }"), is(equal_to(Type::Integer)));
+        assert_that!(check("record P { x, y }\nvar p = P { x: 1, y: 2 }\nmatch p { P { x: 1, y } => y, _ => 0 }").is_ok(), is(true));
+
+        assert_that!(&error_message("match 1 { \"a\" => 1 }"), is(equal_to("Pattern of type String can not match value of type Integer!")));
+        assert_that!(&error_message("match 1 { Q { x } => 1 }"), is(equal_to("Unknown record 'Q'!")));
+        assert_that!(&error_message("record P { x }\nmatch 1 { P { x } => 1 }"),
+            is(equal_to("Pattern of type P can not match value of type Integer!")));
+        assert_that!(&error_message("record P { x }\nvar p\nmatch p { P { y } => 1 }"), is(equal_to("Record 'P' has no field 'y'!")));
+        assert_that!(&error_message("record P { x }\nvar p\nmatch p { P { x, x } => 1 }"), is(equal_to("Field 'x' is already matched!")));
+        assert_that!(&error_message("match 1 { n => 1 }\nn"), is(equal_to("Undeclared variable 'n'!")));
+    }
+
+    #[test]
+    fn check_loops() {
+        assert_that!(check(concat!(
+            "var a = [1, 2]\nvar s = 0\nfor i in 0..len(a) {\n  s += i\n}\nfor e in a {\n  s += e\n}\n",
+            "while s > 0 {\n  if s == 2 { break } else { s -= 1 }\n  continue\n}")).is_ok(), is(true));
+
+        assert_that!(&error_message("if 1 {}"), is(equal_to("Condition must be Boolean but is Integer!")));
+        assert_that!(&error_message("while \"a\" {}"), is(equal_to("Condition must be Boolean but is String!")));
+        assert_that!(&error_message("for i in 0..1.5 {}"), is(equal_to("Range bound must be Integer but is Real!")));
+        assert_that!(&error_message("for i in 1 {}"), is(equal_to("Cannot iterate over value of type Integer!")));
+        assert_that!(&error_message("for i in [\"a\"] { i = 1 }"), is(equal_to("Cannot assign Integer to variable 'i' of type String!")));
+        assert_that!(&error_message("for i in 0..1 {}\ni"), is(equal_to("Undeclared variable 'i'!")));
+        assert_that!(&error_message("if true { var x = 1 }\nx"), is(equal_to("Undeclared variable 'x'!")));
+        assert_that!(&error_message("break"), is(equal_to("Break outside of loop!")));
+        assert_that!(&error_message("while true {\n  fun f() { continue }\n}"), is(equal_to("Continue outside of loop!")));
+    }
+
+    #[test]
+    fn check_match_warnings() {
+        let warnings = |src: &str| -> Vec<String> {
+            check(src).unwrap().warnings().iter().map(|warning| warning.message().to_string()).collect()
+        };
+
+        assert_that!(warnings("match 1 { 1 => 1, _ => 2 }").is_empty(), is(true));
+        assert_that!(warnings("match true { true => 1, false => 2 }").is_empty(), is(true));
+        assert_that!(warnings("record P { x }\nvar p = P { x: 1 }\nmatch p { P { x } => x }").is_empty(), is(true));
+        assert_that!(warnings("match 1 { 1 => 1, 2 => 2 }"), is(equal_to(vec![String::from("Match is not exhaustive!")])));
+        assert_that!(warnings("var b\nmatch b { true => 1, false => 2 }"), is(equal_to(vec![String::from("Match is not exhaustive!")])));
+        assert_that!(warnings("match 1 { 1 => 1, 1 => 2, n => n, _ => 3 }"), is(equal_to(vec![
+            String::from("Unreachable match arm!"), String::from("Unreachable match arm!")])));
+        assert_that!(warnings(concat!(
+            "record P { x, y }\nvar p = P { x: 1, y: 2 }\n",
+            "match p { P { x: 1, y: a } => a, P { y: b, x: 1 } => b, P { x: 2, y } => y, _ => 0 }")),
+            is(equal_to(vec![String::from("Unreachable match arm!")])));
+    }
+
+    #[test]
+    fn analyse_variables_of_blocks_are_not_captured() {
+        let statements = parse("fun f() {\n  var x = 1\n  try { x = 2 } catch e {}\n}").unwrap();
+        let analysis = TypeChecker::new().check(&statements).unwrap();
+
+        assert_that!(analysis.captures(statements[0].span()).is_empty(), is(true));
+        match *statements[0].kind() {
+            StatementKind::Function(_, _, ref body) => match *body[0].kind() {
+                StatementKind::Variable(ref identifier, _) =>
+                    assert_that!(analysis.variable(identifier).unwrap().is_captured(), is(false)),
+                _ => panic!("Expected variable declaration!"),
+            },
+            _ => panic!("Expected function declaration!"),
+        }
+    }
+
+    #[test]
+    fn analyse_captures() {
+        let statements = parse("var a = 1\nvar b = 2\nfun f(x) {\n  return fun() { return a + x }\n}").unwrap();
+        let analysis = TypeChecker::new().check(&statements).unwrap();
+        let declaration = |index: usize| match *statements[index].kind() {
+            StatementKind::Variable(ref identifier, _) => analysis.variable(identifier).unwrap(),
+            _ => panic!("Expected variable declaration!"),
+        };
+        let (a, b) = (declaration(0), declaration(1));
+        let inner = match *statements[2].kind() {
+            StatementKind::Function(_, _, ref body) => match *body[0].kind() {
+                StatementKind::Return(ref function) => function.span(),
+                _ => panic!("Expected return statement!"),
+            },
+            _ => panic!("Expected function declaration!"),
+        };
+
+        assert_that!(a.is_captured(), is(true));
+        assert_that!(b.is_captured(), is(false));
+        let expected = [a.id()];
+        assert_that!(analysis.captures(statements[2].span()), is(equal_to(&expected[..])));
+        let expected = [a.id(), a.id() + 3];
+        assert_that!(analysis.captures(inner), is(equal_to(&expected[..])));
+    }
+}
@@ -13,11 +13,18 @@ pub mod backend;
 pub mod commands;
 pub mod frontend;
 pub mod intermediate;
+#[cfg(test)]
+mod test_support;
 
 /// Prints custom error message to STDERR.
 pub fn error(msg: &str) {
     eprintln!("ERROR: {}", msg);
 }
 
+/// Prints custom warning message to STDERR.
+pub fn warning(msg: &str) {
+    eprintln!("WARNING: {}", msg);
+}
+
 #[cfg(test)]
 mod tests {}
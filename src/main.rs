@@ -29,7 +29,25 @@ fn main() {
             .arg(Arg::with_name("ast")
                 .short("a")
                 .long("ast")
-                .help("Prints the parsed abstract syntax tree.")))
+                .help("Prints the parsed abstract syntax tree."))
+            .arg(Arg::with_name("ast-format")
+                .long("ast-format")
+                .takes_value(true)
+                .possible_values(&["tree", "sexp", "json"])
+                .default_value("tree")
+                .help("The format to print the abstract syntax tree.")))
+        .subcommand(SubCommand::with_name("fmt")
+            .about("Formats the given source code file in the canonical style.")
+            .arg(Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .takes_value(true)
+                .help("The file to format.")
+                .required(true))
+            .arg(Arg::with_name("check")
+                .short("c")
+                .long("check")
+                .help("Only checks if the file is formatted and exits with non-zero if not.")))
         .subcommand(SubCommand::with_name("compile")
             .about("Compiles the given source code file to byte code.")
             .arg(Arg::with_name("file")
@@ -37,6 +55,25 @@ fn main() {
                 .long("file")
                 .takes_value(true)
                 .help("The file to parse.")
+                .required(true))
+            .arg(Arg::with_name("optimization")
+                .short("O")
+                .takes_value(true)
+                .possible_values(&["0", "1"])
+                .default_value("0")
+                .help("The optimization level: 0 compiles as written, 1 folds constants, removes dead code and applies the peephole optimizer."))
+            .arg(Arg::with_name("emit")
+                .long("emit")
+                .takes_value(true)
+                .possible_values(&["ir"])
+                .help("Prints the given intermediate representation instead of writing the byte code file.")))
+        .subcommand(SubCommand::with_name("interpret")
+            .about("Executes the given source code file without compiling it.")
+            .arg(Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .takes_value(true)
+                .help("The file to execute.")
                 .required(true)))
         .subcommand(SubCommand::with_name("asm")
             .about("Transforms the given Minivm assembler into byte code.")
@@ -45,7 +82,13 @@ fn main() {
                 .long("file")
                 .takes_value(true)
                 .help("The file with assembly code.")
-                .required(true)))
+                .required(true))
+            .arg(Arg::with_name("optimization")
+                .short("O")
+                .takes_value(true)
+                .possible_values(&["0", "1"])
+                .default_value("0")
+                .help("The optimization level: 0 assembles as written, 1 applies the peephole optimizer.")))
         .subcommand(SubCommand::with_name("disasm")
             .about("Prints human readable assembler style form of the givne byte code.")
             .arg(Arg::with_name("file")
@@ -61,7 +104,29 @@ fn main() {
                 .long("file")
                 .takes_value(true)
                 .help("The byte code file to execute.")
-                .required(true)))
+                .required(true))
+            .arg(Arg::with_name("registers")
+                .short("r")
+                .long("registers")
+                .help("Translates the byte code and executes it on the register machine.")))
+        .subcommand(SubCommand::with_name("bench")
+            .about("Runs a compiled byte code file several times and reports the instructions per second.")
+            .arg(Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .takes_value(true)
+                .help("The byte code file to execute.")
+                .required(true))
+            .arg(Arg::with_name("runs")
+                .short("n")
+                .long("runs")
+                .takes_value(true)
+                .default_value("10")
+                .validator(|runs| match runs.parse::<usize>() {
+                    Ok(runs) if runs > 0 => Ok(()),
+                    _ => Err(String::from("The number of runs must be a positive integer.")),
+                })
+                .help("How often the byte code is executed.")))
         .get_matches();
 
     if let Some(matches) = matches.subcommand_matches("parse") {
@@ -69,20 +134,34 @@ fn main() {
         ParserCommand::new(
             file.to_string(),
             matches.is_present("tokens"),
-            matches.is_present("ast"))
+            matches.is_present("ast"),
+            matches.value_of("ast-format").unwrap().parse().unwrap())
             .execute();
+    } else if let Some(matches) = matches.subcommand_matches("fmt") {
+        let file = matches.value_of("file").unwrap();
+        FormatCommand::new(file.to_string(), matches.is_present("check")).execute();
     } else if let Some(matches) = matches.subcommand_matches("compile") {
         let file = matches.value_of("file").unwrap();
-        CompileCommand::new(file.to_string()).execute();
+        CompileCommand::new(
+            file.to_string(),
+            matches.value_of("optimization").unwrap().parse().unwrap(),
+            matches.value_of("emit") == Some("ir"))
+            .execute();
+    } else if let Some(matches) = matches.subcommand_matches("interpret") {
+        let file = matches.value_of("file").unwrap();
+        InterpretCommand::new(file.to_string()).execute();
     } else if let Some(matches) = matches.subcommand_matches("asm") {
         let file = matches.value_of("file").unwrap();
-        AssembleCommand::new(file.to_string()).execute();
+        AssembleCommand::new(file.to_string(), matches.value_of("optimization").unwrap().parse().unwrap()).execute();
     } else if let Some(matches) = matches.subcommand_matches("disasm") {
         let file = matches.value_of("file").unwrap();
         DisassembleCommand::new(file.to_string()).execute();
     } else if let Some(matches) = matches.subcommand_matches("run") {
         let file = matches.value_of("file").unwrap();
-        RunCommand::new(file.to_string()).execute();
+        RunCommand::new(file.to_string(), matches.is_present("registers")).execute();
+    } else if let Some(matches) = matches.subcommand_matches("bench") {
+        let file = matches.value_of("file").unwrap();
+        BenchCommand::new(file.to_string(), matches.value_of("runs").unwrap().parse().unwrap()).execute();
     } else {
         error("No subcommand given!");
         return;
@@ -0,0 +1,59 @@
+//! Helpers shared by the unit tests.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use backend::compiler::Compiler;
+use backend::image::Image;
+use frontend::SyntaxError;
+use frontend::character_stream::CharacterStream;
+use frontend::lexer::Lexer;
+use frontend::parser::Parser;
+use intermediate::ast::Statement;
+use intermediate::ir::Program;
+use intermediate::lowering;
+use intermediate::semantic::TypeChecker;
+
+/// Parses the source code.
+pub fn parse(src: &str) -> Result<Vec<Statement>, SyntaxError> {
+    let lexer = Lexer::new(CharacterStream::new(String::from(src)));
+    Parser::new(lexer).parse()
+}
+
+/// Compiles the valid source code without optimizations.
+pub fn compile(src: &str) -> Result<Image, SyntaxError> {
+    Compiler::new().compile(&parse(src).unwrap())
+}
+
+/// Type checks the valid source code and lowers it into the intermediate representation.
+pub fn lower(src: &str) -> Program {
+    let statements = parse(src).unwrap();
+    let analysis = TypeChecker::new().check(&statements).unwrap();
+    lowering::lower(&statements, &analysis).unwrap()
+}
+
+/// Output which may be inspected after an engine took ownership.
+#[derive(Clone)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    pub fn new() -> SharedOutput {
+        SharedOutput(Rc::new(RefCell::new(Vec::new())))
+    }
+
+    /// Get everything written so far.
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}